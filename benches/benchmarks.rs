@@ -165,8 +165,10 @@ fn bench_multipart(c: &mut Criterion) {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(async {
             let mut buffer = Vec::new();
-            let mut writer = multipart::Writer::new(&mut buffer);
-            writer.set_boundary("test-boundary".to_string()).unwrap();
+            let mut writer = multipart::Writer::builder()
+                .boundary("test-boundary")
+                .build(&mut buffer)
+                .unwrap();
 
             for i in 0..5 {
                 writer.write_field(&format!("field{}", i), "test data").await.unwrap();
@@ -193,6 +195,81 @@ fn bench_multipart(c: &mut Criterion) {
         });
     });
 
+    // Benchmark reading many small parts, with and without header interning
+    let many_parts = {
+        let mut data = Vec::new();
+        for i in 0..1000 {
+            data.extend_from_slice(b"--test-boundary\r\n");
+            data.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"field{}\"\r\n", i % 10).as_bytes(),
+            );
+            data.extend_from_slice(b"Content-Type: text/plain\r\n\r\nvalue\r\n");
+        }
+        data.extend_from_slice(b"--test-boundary--\r\n");
+        data
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let interner = multipart::Interner::new();
+    for interned in [false, true] {
+        let name = if interned { "read_many_parts_interned" } else { "read_many_parts" };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                rt.block_on(async {
+                    let options = multipart::ReaderOptions {
+                        interner: interned.then(|| interner.clone()),
+                        ..Default::default()
+                    };
+                    let mut reader = multipart::Reader::with_options(
+                        black_box(&many_parts[..]),
+                        "test-boundary",
+                        options,
+                    );
+                    let mut count = 0;
+                    while reader.next_part().await.unwrap().is_some() {
+                        count += 1;
+                    }
+                    count
+                })
+            });
+        });
+    }
+
+    group.finish();
+}
+
+// Benchmark multipart parsing throughput on large bodies (parts are capped at 32 MB)
+fn bench_multipart_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multipart_throughput");
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    for size_mb in [1, 8, 24].iter() {
+        let size = size_mb * 1024 * 1024;
+        // Text-like content with frequent newlines and dashes
+        let line = b"--some-content-that-looks-almost-like-a-boundary-line\r\n";
+        let body: Vec<u8> = line.iter().copied().cycle().take(size).collect();
+
+        let mut data = b"--test-boundary\r\nContent-Type: text/plain\r\n\r\n".to_vec();
+        data.extend_from_slice(&body);
+        data.extend_from_slice(b"\r\n--test-boundary--\r\n");
+
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.sample_size(10);
+        group.bench_with_input(BenchmarkId::new("read", format!("{}MB", size_mb)), &data, |b, data| {
+            b.iter(|| {
+                rt.block_on(async {
+                    let mut reader = multipart::Reader::new(black_box(&data[..]), "test-boundary");
+                    let mut total = 0;
+                    while let Some(mut part) = reader.next_part().await.unwrap() {
+                        let mut body = Vec::new();
+                        part.read_to_end(&mut body).await.unwrap();
+                        total += body.len();
+                    }
+                    total
+                })
+            });
+        });
+    }
+
     group.finish();
 }
 
@@ -203,7 +280,8 @@ criterion_group!(
     bench_encoded_word,
     bench_mime_type_lookup,
     bench_quoted_printable,
-    bench_multipart
+    bench_multipart,
+    bench_multipart_throughput
 );
 
 criterion_main!(benches);
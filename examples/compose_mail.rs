@@ -0,0 +1,103 @@
+//! Composing an email with an inline image and an attachment.
+//!
+//! The message is `multipart/mixed`: first a `multipart/related` body
+//! holding the HTML text and the image it shows through a `cid:` URL, then
+//! the attachment. Binary parts are base64 encoded, in lines of 76
+//! characters. The message is parsed back to check its structure.
+
+use base64::Engine;
+use tokio::io::AsyncWriteExt;
+use yamime::multipart::{MimeHeader, RelatedReader, Writer};
+
+fn headers(entries: &[(&str, &str)]) -> MimeHeader {
+    entries.iter().copied().collect()
+}
+
+/// Base64 encodes `data` in lines of 76 characters (RFC 2045 section 6.8).
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let lines: Vec<_> = encoded.as_bytes().chunks(76).map(|l| std::str::from_utf8(l).unwrap()).collect();
+    lines.join("\r\n")
+}
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let logo = b"\x89PNG\r\n\x1a\n pretend this is an image";
+    let report = "Quarterly numbers: up and to the right.\r\n".repeat(20);
+
+    // The HTML body and the image it references
+    let mut related = Vec::new();
+    let mut writer = Writer::builder().boundary("related-boundary").build(&mut related)?;
+    writer
+        .create_part(headers(&[("Content-Type", "text/html; charset=utf-8")]))
+        .await?
+        .write_all(b"<p>Hello!</p><img src=\"cid:logo@example.com\">")
+        .await?;
+    writer
+        .create_part(headers(&[
+            ("Content-Type", "image/png"),
+            ("Content-ID", "<logo@example.com>"),
+            ("Content-Disposition", "inline"),
+            ("Content-Transfer-Encoding", "base64"),
+        ]))
+        .await?
+        .write_all(base64_lines(logo).as_bytes())
+        .await?;
+    writer.close().await?;
+
+    // The message: the related body, then the attachment
+    let mut message = b"From: alice@example.com\r\n\
+To: bob@example.com\r\n\
+Subject: Report\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"mixed-boundary\"\r\n\
+\r\n"
+        .to_vec();
+    let mut writer = Writer::builder().boundary("mixed-boundary").build(&mut message)?;
+    writer
+        .create_part(headers(&[(
+            "Content-Type",
+            "multipart/related; boundary=\"related-boundary\"; type=\"text/html\"",
+        )]))
+        .await?
+        .write_all(&related)
+        .await?;
+    writer
+        .create_part(headers(&[
+            ("Content-Type", "text/plain"),
+            ("Content-Disposition", "attachment; filename=\"report.txt\""),
+            ("Content-Transfer-Encoding", "base64"),
+        ]))
+        .await?
+        .write_all(base64_lines(report.as_bytes()).as_bytes())
+        .await?;
+    writer.close().await?;
+    println!("{}", String::from_utf8_lossy(&message));
+
+    // Read it back: skip the mail header, then walk the parts
+    let body_start = message.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let mut reader = yamime::multipart::Reader::new(&message[body_start..], "mixed-boundary");
+
+    let first = reader.next_part().await?.unwrap();
+    let content_type = first.header.get("content-type").unwrap().to_string();
+    let mut related = RelatedReader::new(first, &content_type)?;
+    let mut html = related.next_part().await?.unwrap();
+    assert!(String::from_utf8(html.bytes().await?.to_vec())?.contains("cid:logo@example.com"));
+    let mut image = related.next_part().await?.unwrap();
+    assert_eq!(related.resolve("cid:logo@example.com"), Some(1));
+    let encoded = image.bytes().await?;
+    let compact: Vec<u8> = encoded.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect();
+    assert_eq!(base64::engine::general_purpose::STANDARD.decode(compact)?, logo);
+    assert!(related.next_part().await?.is_none());
+
+    let mut attachment = reader.next_part().await?.unwrap();
+    assert_eq!(attachment.file_name().as_deref(), Some("report.txt"));
+    let mut decoded = Vec::new();
+    {
+        use tokio::io::AsyncReadExt;
+        yamime::base64::Decoder::new(&mut attachment).read_to_end(&mut decoded).await?;
+    }
+    assert_eq!(decoded, report.as_bytes());
+    assert!(reader.next_part().await?.is_none());
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Consuming an MJPEG stream (`multipart/x-mixed-replace`).
+//!
+//! Cameras and streaming servers send video as an endless multipart body,
+//! one JPEG image per part. Each part is available as soon as its closing
+//! delimiter arrives, so frames can be shown while the stream goes on. A
+//! task stands in for the camera here, writing into an in-memory pipe.
+
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use yamime::multipart::{MimeHeader, Reader, Writer};
+
+/// A fake JPEG image: start and end of image markers around some bytes.
+fn jpeg(frame: u8) -> Vec<u8> {
+    let mut image = vec![0xFF, 0xD8];
+    image.extend(std::iter::repeat(frame).take(2000 + frame as usize));
+    image.extend([0xFF, 0xD9]);
+    image
+}
+
+async fn camera(pipe: tokio::io::DuplexStream, frames: u8) -> yamime::Result<()> {
+    let mut writer = Writer::builder().boundary("frame").build(pipe)?;
+    for frame in 0..frames {
+        let image = jpeg(frame);
+        let mut header = MimeHeader::new();
+        header.insert("Content-Type", "image/jpeg");
+        header.insert("Content-Length", image.len().to_string());
+        writer.create_part(header).await?.write_all(&image).await?;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    writer.close().await
+}
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (camera_end, viewer_end) = tokio::io::duplex(4096);
+    let camera = tokio::spawn(camera(camera_end, 10));
+
+    // The server's Content-Type: multipart/x-mixed-replace; boundary=frame
+    let mut reader = Reader::new(viewer_end, "frame");
+    let mut frames = 0;
+    while let Some(mut part) = reader.next_part().await? {
+        assert_eq!(part.content_type().map(|(t, _)| t), Some("image/jpeg"));
+        let length: usize = part.header.get("content-length").unwrap().parse()?;
+        let image = part.bytes().await?;
+        assert_eq!(image.len(), length);
+        assert!(image.starts_with(&[0xFF, 0xD8]) && image.ends_with(&[0xFF, 0xD9]));
+        println!("frame {}: {} bytes", frames, image.len());
+        frames += 1;
+    }
+    camera.await??;
+    assert_eq!(frames, 10);
+    assert!(reader.is_complete());
+    Ok(())
+}
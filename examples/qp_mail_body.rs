@@ -0,0 +1,58 @@
+//! Decoding quoted-printable mail bodies.
+//!
+//! Mail text is usually sent quoted-printable: 8-bit characters become
+//! `=XX` escapes and long lines are wrapped with soft line breaks (`=` at
+//! the end of a line). [`Reader::next_part`] decodes such parts on its own;
+//! a body outside a multipart message is decoded with
+//! [`quotedprintable::Reader`].
+
+use tokio::io::AsyncReadExt;
+use yamime::multipart::Reader;
+use yamime::quotedprintable;
+
+const ALTERNATIVE: &[u8] = b"--alt\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Gr=C3=BC=C3=9Fe aus M=C3=BCnchen! This line is long enough that it was wrapp=\r\n\
+ed with a soft line break.\r\n\
+--alt\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+Content-Transfer-Encoding: QUOTED-PRINTABLE\r\n\
+\r\n\
+<p style=3D\"color: red\">Gr=C3=BC=C3=9Fe</p>\r\n\
+--alt--\r\n";
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Parts of a multipart/alternative body come out decoded
+    let mut reader = Reader::new(ALTERNATIVE, "alt");
+    let mut text = String::new();
+    let mut plain = reader.next_part().await?.unwrap();
+    plain.read_to_string(&mut text).await?;
+    println!("{}", text);
+    assert_eq!(
+        text,
+        "Grüße aus München! This line is long enough that it was wrapped with a soft line break."
+    );
+    // The encoding header is gone, since the body is no longer encoded
+    assert!(plain.header.get("content-transfer-encoding").is_none());
+
+    let mut html = String::new();
+    reader.next_part().await?.unwrap().read_to_string(&mut html).await?;
+    assert_eq!(html, "<p style=\"color: red\">Grüße</p>");
+
+    // Raw parts keep the encoding, to be decoded separately
+    let mut reader = Reader::new(ALTERNATIVE, "alt");
+    let mut raw = reader.next_raw_part().await?.unwrap();
+    assert_eq!(raw.header.get("content-transfer-encoding"), Some("quoted-printable"));
+    let decoded = quotedprintable::decode_to_string(&mut raw).await?;
+    assert_eq!(decoded, text);
+
+    // A single-part message body
+    let body = b"Caf=C3=A9 at 10=3A00?";
+    let mut decoded = String::new();
+    quotedprintable::Reader::new(&body[..]).read_to_string(&mut decoded).await?;
+    assert_eq!(decoded, "Café at 10:00?");
+    Ok(())
+}
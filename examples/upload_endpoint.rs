@@ -0,0 +1,92 @@
+//! Handling a `multipart/form-data` upload in an HTTP endpoint.
+//!
+//! HTTP frameworks hand request bodies over as streams of `Bytes` chunks;
+//! [`Reader::from_stream`] parses them as they arrive and `read_form`
+//! collects the fields and files. The handler below is independent of any
+//! framework. With axum it would be wired up as:
+//!
+//! ```ignore
+//! async fn upload(headers: HeaderMap, body: axum::body::Body) -> Result<String, StatusCode> {
+//!     let content_type = headers[CONTENT_TYPE].to_str().map_err(|_| StatusCode::BAD_REQUEST)?;
+//!     let body = body
+//!         .into_data_stream()
+//!         .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
+//!     handle_upload(content_type, body).await.map_err(|_| StatusCode::BAD_REQUEST)
+//! }
+//!
+//! let app = Router::new().route("/upload", post(upload));
+//! ```
+
+use bytes::Bytes;
+use futures::Stream;
+use std::io;
+use tokio::io::AsyncReadExt;
+use yamime::multipart::{Reader, Writer};
+
+/// Form data kept in memory; larger files go to temporary files.
+const MAX_MEMORY: usize = 1 << 20;
+
+/// Parses an upload and describes what it contained.
+pub async fn handle_upload<S>(content_type: &str, body: S) -> yamime::Result<String>
+where
+    S: Stream<Item = io::Result<Bytes>> + Unpin,
+{
+    let (media_type, params) = yamime::parse_media_type(content_type)?;
+    let boundary = match params.get("boundary") {
+        Some(boundary) if media_type == "multipart/form-data" => boundary,
+        _ => return Err(yamime::Error::Multipart("expected multipart/form-data".to_string())),
+    };
+
+    let mut reader = Reader::from_stream(body, boundary);
+    let mut form = reader.read_form(MAX_MEMORY).await?;
+
+    let mut summary = Vec::new();
+    let mut names: Vec<_> = form.value.keys().collect();
+    names.sort();
+    for name in names {
+        summary.push(format!("{} = {}", name, form.value[name].join(", ")));
+    }
+    for (name, files) in &form.file {
+        for file in files {
+            let mut content = Vec::new();
+            file.open().await?.read_to_end(&mut content).await?;
+            summary.push(format!("{}: {} ({} bytes)", name, file.filename, content.len()));
+        }
+    }
+    form.remove_all().await?;
+    Ok(summary.join("\n"))
+}
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // What a browser would send
+    let mut body = Vec::new();
+    let mut writer = Writer::new(&mut body);
+    let content_type = writer.form_data_content_type();
+    writer.write_field("title", "Holiday").await?;
+    writer.write_field("tags", "beach").await?;
+    writer.write_field("tags", "sun").await?;
+    {
+        use tokio::io::AsyncWriteExt;
+        let mut file = writer.create_form_file("photo", "beach.jpg").await?;
+        file.write_all(&[0xAB; 5000]).await?;
+    }
+    writer.close().await?;
+
+    // Delivered in network-sized chunks
+    let chunks: Vec<io::Result<Bytes>> = body
+        .chunks(1400)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect();
+    let summary = handle_upload(&content_type, futures::stream::iter(chunks)).await?;
+    println!("{}", summary);
+    assert_eq!(
+        summary,
+        "tags = beach, sun\ntitle = Holiday\nphoto: beach.jpg (5000 bytes)"
+    );
+
+    // Anything else is rejected
+    let empty = futures::stream::iter(Vec::<io::Result<Bytes>>::new());
+    assert!(handle_upload("application/json", empty).await.is_err());
+    Ok(())
+}
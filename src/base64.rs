@@ -0,0 +1,336 @@
+//! Streaming base64 transfer encoding (RFC 2045 section 6.8).
+
+use pin_project::pin_project;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use base64::alphabet;
+use base64::engine::general_purpose::STANDARD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+
+/// Engine accepting input with or without padding.
+const LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Input bytes per encoded line, giving the 76 characters RFC 2045 allows.
+const LINE_INPUT_LEN: usize = 57;
+
+/// A streaming base64 decoder.
+///
+/// Implements `AsyncRead` to decode base64 data on the fly. Line breaks and
+/// other whitespace in the input are ignored, as they are in MIME bodies.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::base64::Decoder;
+/// use tokio::io::AsyncReadExt;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = b"SGVsbG8s\r\nIFdvcmxk\r\n";
+/// let mut decoded = String::new();
+/// Decoder::new(&data[..]).read_to_string(&mut decoded).await?;
+/// assert_eq!(decoded, "Hello, World");
+/// # Ok(())
+/// # }
+/// ```
+#[pin_project]
+pub struct Decoder<R> {
+    #[pin]
+    inner: R,
+    /// Encoded characters not yet decoded (fewer than a full quantum).
+    pending: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    eof: bool,
+}
+
+impl<R: AsyncRead> Decoder<R> {
+    /// Creates a new base64 decoder.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for Decoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+
+        loop {
+            if *this.output_pos < this.output.len() {
+                let available = &this.output[*this.output_pos..];
+                let n = available.len().min(buf.remaining());
+                buf.put_slice(&available[..n]);
+                *this.output_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+
+            if *this.eof {
+                if this.pending.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                // Trailing quantum, possibly without padding
+                let rest = std::mem::take(this.pending);
+                *this.output = decode(&rest)?;
+                *this.output_pos = 0;
+                continue;
+            }
+
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let mut read_buf = ReadBuf::new(&mut chunk);
+            ready!(this.inner.as_mut().poll_read(cx, &mut read_buf))?;
+            let filled = read_buf.filled();
+            if filled.is_empty() {
+                *this.eof = true;
+                continue;
+            }
+
+            this.pending
+                .extend(filled.iter().copied().filter(|b| !b.is_ascii_whitespace()));
+            let complete = this.pending.len() / 4 * 4;
+            *this.output = decode(&this.pending[..complete])?;
+            *this.output_pos = 0;
+            this.pending.drain(..complete);
+        }
+    }
+}
+
+/// A streaming base64 encoder.
+///
+/// Implements `AsyncWrite` to encode data to base64 on the fly, in lines of
+/// 76 characters separated by CRLF. The last line is not terminated, and
+/// the final quantum is only written on shutdown, so
+/// [`close`](Self::close) must be called.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::base64::Encoder;
+/// use tokio::io::AsyncWriteExt;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut output = Vec::new();
+/// let mut encoder = Encoder::new(&mut output);
+/// encoder.write_all(b"Hello, World").await?;
+/// encoder.close().await?;
+/// assert_eq!(output, b"SGVsbG8sIFdvcmxk");
+/// # Ok(())
+/// # }
+/// ```
+#[pin_project]
+pub struct Encoder<W> {
+    #[pin]
+    inner: W,
+    state: EncoderState,
+}
+
+impl<W: AsyncWrite> Encoder<W> {
+    /// Creates a new base64 encoder.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            state: EncoderState::new(b"\r\n"),
+        }
+    }
+
+    /// Closes the encoder, writing the final quantum and shutting down the
+    /// inner writer.
+    pub async fn close(self) -> io::Result<()> {
+        let mut pinned = Box::pin(self);
+        futures::future::poll_fn(|cx| pinned.as_mut().poll_shutdown(cx)).await
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for Encoder<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        ready!(this.state.poll_drain(this.inner.as_mut(), cx))?;
+        this.state.encode(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        ready!(this.state.poll_drain(this.inner.as_mut(), cx))?;
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        this.state.finish();
+        ready!(this.state.poll_drain(this.inner.as_mut(), cx))?;
+        this.inner.poll_shutdown(cx)
+    }
+}
+
+/// The state of an [`Encoder`], independent of its inner writer, so other
+/// writers can encode into their own output.
+#[derive(Debug)]
+pub(crate) struct EncoderState {
+    /// Input of the line being collected.
+    pending: Vec<u8>,
+    /// Whether a line was written, so the next one needs a line break.
+    started: bool,
+    /// Encoded output waiting for the inner writer, from `out_pos` on.
+    out: Vec<u8>,
+    out_pos: usize,
+    nl: &'static [u8],
+}
+
+impl EncoderState {
+    pub(crate) fn new(nl: &'static [u8]) -> Self {
+        Self {
+            pending: Vec::with_capacity(LINE_INPUT_LEN),
+            started: false,
+            out: Vec::new(),
+            out_pos: 0,
+            nl,
+        }
+    }
+
+    pub(crate) fn encode(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            let n = (LINE_INPUT_LEN - self.pending.len()).min(input.len());
+            self.pending.extend_from_slice(&input[..n]);
+            input = &input[n..];
+            if self.pending.len() == LINE_INPUT_LEN {
+                self.write_line();
+            }
+        }
+    }
+
+    /// Ends the input, encoding the last, possibly partial, line.
+    pub(crate) fn finish(&mut self) {
+        if !self.pending.is_empty() {
+            self.write_line();
+        }
+    }
+
+    fn write_line(&mut self) {
+        if self.started {
+            self.out.extend_from_slice(self.nl);
+        }
+        self.out
+            .extend_from_slice(STANDARD.encode(&self.pending).as_bytes());
+        self.pending.clear();
+        self.started = true;
+    }
+
+    /// Writes the buffered output to `inner`.
+    pub(crate) fn poll_drain<W: AsyncWrite>(
+        &mut self,
+        inner: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        poll_drain_output(&mut self.out, &mut self.out_pos, inner, cx)
+    }
+}
+
+/// Writes `out[*pos..]` to `inner`, then clears `out`, for encoders that
+/// buffer their output.
+pub(crate) fn poll_drain_output<W: AsyncWrite>(
+    out: &mut Vec<u8>,
+    pos: &mut usize,
+    mut inner: Pin<&mut W>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>> {
+    while *pos < out.len() {
+        let n = ready!(inner.as_mut().poll_write(cx, &out[*pos..]))?;
+        if n == 0 {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "failed to write encoded output",
+            )));
+        }
+        *pos += n;
+    }
+    out.clear();
+    *pos = 0;
+    Poll::Ready(Ok(()))
+}
+
+fn decode(input: &[u8]) -> io::Result<Vec<u8>> {
+    LENIENT
+        .decode(input)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_encode_lines() {
+        let input: Vec<u8> = (0..=255).collect();
+        let mut output = Vec::new();
+        let mut encoder = Encoder::new(&mut output);
+        // Writes not aligned with quanta or lines
+        for chunk in input.chunks(10) {
+            encoder.write_all(chunk).await.unwrap();
+        }
+        encoder.close().await.unwrap();
+
+        let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[..4].iter().all(|l| l.len() == 77 && l.ends_with(b"\r")));
+        assert!(lines[4].ends_with(b"=="));
+
+        let mut decoded = Vec::new();
+        Decoder::new(&output[..]).read_to_end(&mut decoded).await.unwrap();
+        assert_eq!(decoded, input);
+
+        // Exactly one line: no trailing line break
+        let mut output = Vec::new();
+        let mut encoder = Encoder::new(&mut output);
+        encoder.write_all(&[0; LINE_INPUT_LEN]).await.unwrap();
+        encoder.close().await.unwrap();
+        assert_eq!(output, vec![b'A'; 76]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_split_input() {
+        // Quanta split across reads and line breaks
+        let (mut tx, rx) = tokio::io::duplex(3);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            tx.write_all(b"AAEC\r\nAwQF\r\nBgc=\r\n").await.unwrap();
+        });
+
+        let mut decoded = Vec::new();
+        Decoder::new(rx).read_to_end(&mut decoded).await.unwrap();
+        assert_eq!(decoded, vec![0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_unpadded_and_invalid() {
+        let mut decoded = String::new();
+        Decoder::new(&b"SGk"[..]).read_to_string(&mut decoded).await.unwrap();
+        assert_eq!(decoded, "Hi");
+
+        let mut decoded = Vec::new();
+        let err = Decoder::new(&b"S*Gk"[..]).read_to_end(&mut decoded).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
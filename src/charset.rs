@@ -0,0 +1,359 @@
+//! Writing text in legacy charsets.
+//!
+//! Some recipients cannot handle UTF-8 bodies. An [`EncodingWriter`]
+//! accepts Rust strings and writes them in the part's charset, so a text
+//! part can be composed in e.g. ISO-8859-1 or ISO-2022-JP, and
+//! [`set_charset`] labels the part's Content-Type to match.
+//!
+//! UTF-8, US-ASCII and ISO-8859-1 are built in. With the `encoding_rs`
+//! feature, so are the other charsets `encoding_rs` can encode into, such
+//! as ISO-2022-JP, Shift_JIS and windows-1251. Any other charset needs a
+//! [`CharsetEncoder`], mirroring
+//! [`WordDecoder::charset_reader`](crate::WordDecoder::charset_reader) on
+//! the decoding side.
+
+use crate::error::{Error, Result};
+use crate::media_type::{format_media_type, parse_media_type};
+use crate::multipart::MimeHeader;
+use std::collections::HashMap;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// A custom charset conversion function: `(charset, text) -> encoded`.
+///
+/// It should fail with [`Error::Encoding`] for characters the charset
+/// cannot represent. Each call receives a complete string, so stateful
+/// encodings such as ISO-2022-JP must end it in their initial state.
+pub type CharsetEncoder = Box<dyn Fn(&str, &str) -> Result<Vec<u8>> + Send + Sync>;
+
+/// A writer encoding text into a charset before writing it to the inner
+/// writer, usually a [`PartWriter`](crate::multipart::writer::PartWriter).
+///
+/// # Examples
+///
+/// ```
+/// use yamime::charset::{self, EncodingWriter};
+/// use yamime::multipart::{MimeHeader, Writer};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut output = Vec::new();
+/// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+///
+/// let mut header = MimeHeader::new();
+/// charset::set_charset(&mut header, "iso-8859-1")?;
+/// let part = writer.create_part(header.clone()).await?;
+///
+/// let mut text = EncodingWriter::from_header(part, &header)?;
+/// text.write_str("Café").await?;
+/// drop(text);
+/// writer.close().await?;
+///
+/// let expected = b"Content-Type: text/plain; charset=iso-8859-1\r\n\r\nCaf\xe9\r\n";
+/// assert!(output.windows(expected.len()).any(|w| w == expected));
+/// # Ok(())
+/// # }
+/// ```
+pub struct EncodingWriter<W> {
+    inner: W,
+    charset: String,
+    encoder: Option<CharsetEncoder>,
+}
+
+impl<W> std::fmt::Debug for EncodingWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncodingWriter")
+            .field("charset", &self.charset)
+            .field("encoder", &self.encoder.as_ref().map(|_| "<function>"))
+            .finish()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> EncodingWriter<W> {
+    /// Creates a writer for a builtin charset: UTF-8, US-ASCII or
+    /// ISO-8859-1, and with the `encoding_rs` feature the charsets it
+    /// supports.
+    ///
+    /// Fails for any other charset; use [`EncodingWriter::with_encoder`]
+    /// for those.
+    pub fn new(inner: W, charset: &str) -> Result<Self> {
+        if builtin(charset).is_none() && whatwg_encoding(charset).is_none() {
+            return Err(Error::Encoding(format!("unhandled charset: {}", charset)));
+        }
+        Ok(Self {
+            inner,
+            charset: charset.to_lowercase(),
+            encoder: None,
+        })
+    }
+
+    /// Creates a writer for any charset, encoding with `encoder`.
+    pub fn with_encoder(inner: W, charset: &str, encoder: CharsetEncoder) -> Self {
+        Self {
+            inner,
+            charset: charset.to_lowercase(),
+            encoder: Some(encoder),
+        }
+    }
+
+    /// Creates a writer for the charset named by the Content-Type of
+    /// `header`, or US-ASCII if it names none (RFC 2045 section 5.2).
+    ///
+    /// Fails if the Content-Type is malformed or the charset is not builtin.
+    pub fn from_header(inner: W, header: &MimeHeader) -> Result<Self> {
+        let charset = header_charset(header)?;
+        Self::new(inner, &charset)
+    }
+
+    /// Returns the charset written, in lowercase.
+    pub fn charset(&self) -> &str {
+        &self.charset
+    }
+
+    /// Checks that the Content-Type of `header` names this writer's
+    /// charset, so the part is labelled with the bytes it carries.
+    pub fn check_header(&self, header: &MimeHeader) -> Result<()> {
+        let labelled = header_charset(header)?;
+        if same_charset(&labelled, &self.charset) {
+            Ok(())
+        } else {
+            Err(Error::Encoding(format!(
+                "part is labelled {} but written as {}",
+                labelled, self.charset
+            )))
+        }
+    }
+
+    /// Encodes `s` and writes it.
+    ///
+    /// Fails with [`Error::Encoding`] if `s` has characters the charset
+    /// cannot represent; nothing is written then.
+    pub async fn write_str(&mut self, s: &str) -> Result<()> {
+        let encoded = match &self.encoder {
+            Some(encoder) => encoder(&self.charset, s)?,
+            None => encode_default(&self.charset, s)?,
+        };
+        self.inner.write_all(&encoded).await?;
+        Ok(())
+    }
+
+    /// Flushes the inner writer.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.inner.flush().await?;
+        Ok(())
+    }
+
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Sets the `charset` parameter of the Content-Type in `header`, keeping
+/// its media type and other parameters.
+///
+/// A header without a Content-Type gets `text/plain`.
+pub fn set_charset(header: &mut MimeHeader, charset: &str) -> Result<()> {
+    let (media_type, mut params) = match header.get("content-type") {
+        Some(value) => parse_media_type(value)?,
+        None => ("text/plain".to_string(), HashMap::new()),
+    };
+    params.insert("charset".to_string(), charset.to_lowercase());
+    let value = format_media_type(&media_type, &params);
+    if value.is_empty() {
+        return Err(Error::Encoding(format!("invalid charset: {}", charset)));
+    }
+    header.insert("Content-Type", value);
+    Ok(())
+}
+
+/// Returns the charset named by the Content-Type of `header`, in lowercase.
+fn header_charset(header: &MimeHeader) -> Result<String> {
+    match header.get("content-type") {
+        Some(value) => {
+            let (_, params) = parse_media_type(value)?;
+            Ok(params
+                .get("charset")
+                .map(|c| c.to_lowercase())
+                .unwrap_or_else(|| "us-ascii".to_string()))
+        }
+        None => Ok("us-ascii".to_string()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Builtin {
+    Utf8,
+    UsAscii,
+    Latin1,
+}
+
+fn builtin(charset: &str) -> Option<Builtin> {
+    match charset.to_lowercase().as_str() {
+        "utf-8" | "utf8" => Some(Builtin::Utf8),
+        "us-ascii" | "ascii" => Some(Builtin::UsAscii),
+        "iso-8859-1" | "latin1" | "iso_8859-1" => Some(Builtin::Latin1),
+        _ => None,
+    }
+}
+
+fn same_charset(a: &str, b: &str) -> bool {
+    match (builtin(a), builtin(b)) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => {
+            a.eq_ignore_ascii_case(b)
+                || whatwg_encoding(a).is_some_and(|encoding| whatwg_encoding(b) == Some(encoding))
+        }
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+/// Returns the `encoding_rs` encoding for a charset label, if the feature
+/// is enabled and the encoding can be encoded into.
+#[cfg(feature = "encoding_rs")]
+fn whatwg_encoding(charset: &str) -> Option<&'static encoding_rs::Encoding> {
+    // UTF-16 and the replacement encoding encode as UTF-8
+    encoding_rs::Encoding::for_label(charset.as_bytes())
+        .filter(|encoding| encoding.output_encoding() == *encoding)
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn whatwg_encoding(_charset: &str) -> Option<std::convert::Infallible> {
+    None
+}
+
+/// Encodes with the builtin conversions, then with `encoding_rs` if the
+/// feature is enabled.
+pub(crate) fn encode_default(charset: &str, s: &str) -> Result<Vec<u8>> {
+    let max = match builtin(charset) {
+        Some(Builtin::Utf8) => return Ok(s.as_bytes().to_vec()),
+        Some(Builtin::UsAscii) => 0x7F,
+        Some(Builtin::Latin1) => 0xFF,
+        None => return encode_whatwg(charset, s),
+    };
+    s.chars()
+        .map(|c| {
+            if (c as u32) <= max {
+                Ok(c as u8)
+            } else {
+                Err(Error::Encoding(format!(
+                    "character {:?} cannot be encoded in {}",
+                    c, charset
+                )))
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "encoding_rs")]
+fn encode_whatwg(charset: &str, s: &str) -> Result<Vec<u8>> {
+    let encoding = whatwg_encoding(charset)
+        .ok_or_else(|| Error::Encoding(format!("unhandled charset: {}", charset)))?;
+    // Unmappable characters come out as numeric character references
+    let (encoded, _, had_errors) = encoding.encode(s);
+    if had_errors {
+        return Err(Error::Encoding(format!(
+            "text cannot be encoded in {}",
+            charset
+        )));
+    }
+    Ok(encoded.into_owned())
+}
+
+#[cfg(not(feature = "encoding_rs"))]
+fn encode_whatwg(charset: &str, _s: &str) -> Result<Vec<u8>> {
+    Err(Error::Encoding(format!("unhandled charset: {}", charset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builtin_charsets() {
+        let mut output = Vec::new();
+        let mut writer = EncodingWriter::new(&mut output, "ISO-8859-1").unwrap();
+        assert_eq!(writer.charset(), "iso-8859-1");
+        writer.write_str("Grüße").await.unwrap();
+        // Unmappable text fails without writing anything
+        assert!(matches!(writer.write_str("1 €").await, Err(Error::Encoding(_))));
+        drop(writer);
+        assert_eq!(output, b"Gr\xfc\xdfe");
+
+        let mut output = Vec::new();
+        let mut writer = EncodingWriter::new(&mut output, "us-ascii").unwrap();
+        assert!(writer.write_str("é").await.is_err());
+        writer.write_str("plain").await.unwrap();
+        assert_eq!(writer.into_inner(), b"plain");
+
+        if cfg!(not(feature = "encoding_rs")) {
+            assert!(EncodingWriter::new(Vec::new(), "iso-2022-jp").is_err());
+        }
+        assert!(EncodingWriter::new(Vec::new(), "x-unknown").is_err());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[tokio::test]
+    async fn test_encoding_rs_charsets() {
+        let mut writer = EncodingWriter::new(Vec::new(), "ISO-2022-JP").unwrap();
+        writer.write_str("日本語 text").await.unwrap();
+        // Each string ends back in ASCII
+        writer.write_str("語").await.unwrap();
+        assert_eq!(writer.into_inner(), b"\x1b$BF|K\\8l\x1b(B text\x1b$B8l\x1b(B");
+
+        let header: MimeHeader = [("Content-Type", "text/plain; charset=windows-1251")]
+            .into_iter()
+            .collect();
+        let mut writer = EncodingWriter::from_header(Vec::new(), &header).unwrap();
+        writer.write_str("Привет").await.unwrap();
+        // Unmappable text fails rather than writing character references
+        assert!(matches!(writer.write_str("日").await, Err(Error::Encoding(_))));
+        let aliased: MimeHeader = [("Content-Type", "text/plain; charset=cp1251")]
+            .into_iter()
+            .collect();
+        assert!(writer.check_header(&aliased).is_ok());
+        assert_eq!(writer.into_inner(), b"\xcf\xf0\xe8\xe2\xe5\xf2");
+
+        // UTF-16 would be written as UTF-8, so it is not offered
+        assert!(EncodingWriter::new(Vec::new(), "utf-16le").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_encoder() {
+        // A stand-in for a real transcoder: uppercases ASCII, rejects the rest
+        let encoder: CharsetEncoder = Box::new(|charset, text| {
+            assert_eq!(charset, "x-upper");
+            if text.is_ascii() {
+                Ok(text.to_ascii_uppercase().into_bytes())
+            } else {
+                Err(Error::Encoding("not ascii".to_string()))
+            }
+        });
+        let mut writer = EncodingWriter::with_encoder(Vec::new(), "X-Upper", encoder);
+        writer.write_str("shout").await.unwrap();
+        assert!(writer.write_str("ça").await.is_err());
+        assert_eq!(writer.into_inner(), b"SHOUT");
+    }
+
+    #[test]
+    fn test_set_and_check_charset() {
+        let mut header = MimeHeader::new();
+        set_charset(&mut header, "UTF-8").unwrap();
+        assert_eq!(header.get("content-type"), Some("text/plain; charset=utf-8"));
+
+        header.insert("Content-Type", "text/html; format=flowed; charset=utf-8");
+        set_charset(&mut header, "iso-8859-1").unwrap();
+        assert_eq!(
+            header.get("content-type"),
+            Some("text/html; charset=iso-8859-1; format=flowed")
+        );
+
+        let writer = EncodingWriter::from_header(Vec::new(), &header).unwrap();
+        assert_eq!(writer.charset(), "iso-8859-1");
+        assert!(writer.check_header(&header).is_ok());
+
+        // No charset parameter means US-ASCII
+        let header: MimeHeader = [("Content-Type", "text/plain")].into_iter().collect();
+        assert!(writer.check_header(&header).is_err());
+        let writer = EncodingWriter::from_header(Vec::new(), &header).unwrap();
+        assert_eq!(writer.charset(), "us-ascii");
+    }
+}
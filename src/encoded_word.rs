@@ -2,12 +2,13 @@
 //!
 //! This module implements MIME encoded-word processing as defined in RFC 2047.
 
-use crate::error::{Error, Result};
+use crate::charset::{self, CharsetEncoder};
+use crate::error::{Error, LimitExceeded, LimitKind, Result};
+use crate::limits::Limits;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
 const UPPER_HEX: &[u8] = b"0123456789ABCDEF";
 const MAX_ENCODED_WORD_LEN: usize = 75;
-const MAX_CONTENT_LEN: usize = MAX_ENCODED_WORD_LEN - "=?UTF-8?q?".len() - "?=".len();
 
 /// An RFC 2047 encoded-word encoder.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -18,18 +19,116 @@ pub enum WordEncoder {
     QEncoding,
 }
 
+/// Where in a header an encoded-word appears (RFC 2047 section 5).
+///
+/// Each context restricts the characters a Q-encoded word may carry
+/// unencoded, so that the word cannot be mistaken for the surrounding
+/// syntax, and where a decoder recognizes encoded-words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EncodeContext {
+    /// Unstructured text, such as a Subject: any printable ASCII except
+    /// `=`, `?` and `_`. Words must be separated from adjacent text by
+    /// whitespace.
+    #[default]
+    Text,
+    /// A word in a phrase, such as the display name of an address: only
+    /// letters, digits and `!*+-/`. Words must not appear within a
+    /// quoted-string.
+    Phrase,
+    /// The content of a comment: as for text, but not `(`, `)`, `"` or
+    /// `\`. Words may also be delimited by the parentheses of nested
+    /// comments.
+    Comment,
+}
+
+impl EncodeContext {
+    /// Reports whether `b` may appear unencoded in a Q-encoded word.
+    fn is_q_safe(self, b: u8) -> bool {
+        let text_safe = (b'!'..=b'~').contains(&b) && !matches!(b, b'=' | b'?' | b'_');
+        match self {
+            EncodeContext::Text => text_safe,
+            EncodeContext::Phrase => {
+                b.is_ascii_alphanumeric() || matches!(b, b'!' | b'*' | b'+' | b'-' | b'/')
+            }
+            EncodeContext::Comment => text_safe && !matches!(b, b'(' | b')' | b'"' | b'\\'),
+        }
+    }
+
+    /// Reports whether the encoded-word at `start..end` of `header`, with
+    /// the given encoding and encoded text, is placed as this context
+    /// allows.
+    fn allows(self, header: &str, start: usize, end: usize, encoding: u8, text: &str) -> bool {
+        let is_delimiter = |c: Option<char>, paren: char| {
+            c.map_or(true, |c| {
+                c.is_ascii_whitespace() || (self == EncodeContext::Comment && c == paren)
+            })
+        };
+        if !is_delimiter(header[..start].chars().next_back(), '(')
+            || !is_delimiter(header[end..].chars().next(), ')')
+        {
+            return false;
+        }
+        let is_q = matches!(encoding, b'Q' | b'q');
+        match self {
+            EncodeContext::Text => true,
+            EncodeContext::Phrase => {
+                !in_quoted_string(&header[..start])
+                    && (!is_q || text.bytes().all(|b| self.is_q_safe(b) || b == b'=' || b == b'_'))
+            }
+            EncodeContext::Comment => !text.contains(['(', ')', '"', '\\']),
+        }
+    }
+}
+
+/// A custom charset conversion function: `(charset, content) -> decoded`.
+pub type CharsetReader = Box<dyn Fn(&str, &[u8]) -> Result<String> + Send + Sync>;
+
+/// How a [`WordDecoder`] handles an encoded-word whose bytes cannot be
+/// converted from its charset.
+///
+/// The policy applies the same way to every charset: bytes invalid in
+/// UTF-8 or US-ASCII, a [`CharsetReader`] failing, or a charset with no
+/// conversion at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    /// Fail with [`Error::Encoding`].
+    #[default]
+    Strict,
+    /// Replace each invalid sequence with U+FFFD. A word that a charset
+    /// reader fails to convert, or in an unhandled charset, becomes a
+    /// single U+FFFD.
+    Replace,
+    /// Leave the whole encoded-word undecoded, as for malformed words.
+    SkipWord,
+}
+
 /// An RFC 2047 encoded-word decoder.
 #[derive(Default)]
 pub struct WordDecoder {
     /// Custom charset reader function (optional).
     /// For charsets other than UTF-8, ISO-8859-1, and US-ASCII.
-    pub charset_reader: Option<Box<dyn Fn(&str, &[u8]) -> Result<String> + Send + Sync>>,
+    ///
+    /// Readers should fail on malformed input rather than replace it, so
+    /// that [`replacement`](Self::replacement) decides.
+    pub charset_reader: Option<CharsetReader>,
+
+    /// How words that cannot be converted are handled.
+    pub replacement: ReplacementPolicy,
+
+    /// Maximum decoded size in bytes of one encoded-word.
+    ///
+    /// Larger words fail with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::DecodedSize`], whatever the replacement policy.
+    /// Unlimited by default.
+    pub max_decoded_word: Option<usize>,
 }
 
 impl std::fmt::Debug for WordDecoder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("WordDecoder")
             .field("charset_reader", &self.charset_reader.as_ref().map(|_| "<function>"))
+            .field("replacement", &self.replacement)
+            .field("max_decoded_word", &self.max_decoded_word)
             .finish()
     }
 }
@@ -40,6 +139,11 @@ impl WordEncoder {
     /// If s is ASCII without special characters, it is returned unchanged.
     /// The provided charset is the IANA charset name of s (case insensitive).
     ///
+    /// Long text is split into several words for UTF-8 and single-byte
+    /// charsets. Other charsets cannot be split safely without knowing
+    /// their byte sequences and stay in one word; use
+    /// [`encode_charset`](Self::encode_charset) for those.
+    ///
     /// # Examples
     ///
     /// ```
@@ -50,20 +154,178 @@ impl WordEncoder {
     /// assert!(encoded.starts_with("=?UTF-8?q?"));
     /// ```
     pub fn encode(&self, charset: &str, s: &str) -> String {
+        self.encode_in(charset, s, EncodeContext::Text)
+    }
+
+    /// Returns the encoded-word form of s for use in the given header
+    /// context.
+    ///
+    /// Like [`encode`](Self::encode), but Q-encoding escapes every
+    /// character the context does not allow unencoded, e.g. the commas and
+    /// quotes of a display name. B-encoding is the same in all contexts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::{EncodeContext, WordEncoder};
+    ///
+    /// let name = WordEncoder::QEncoding.encode_in("UTF-8", "Müller, Jörg", EncodeContext::Phrase);
+    /// assert_eq!(name, "=?UTF-8?q?M=C3=BCller=2C_J=C3=B6rg?=");
+    /// ```
+    pub fn encode_in(&self, charset: &str, s: &str, context: EncodeContext) -> String {
         if !needs_encoding(s) {
             return s.to_string();
         }
-        self.encode_word(charset, s)
+        let mut units = s.char_indices().map(|(i, ch)| (i, &s[i..i + ch.len_utf8()]));
+        self.encode_word(charset, s, &mut units, context)
+    }
+
+    /// Returns the encoded-word form of s converted into `charset`.
+    ///
+    /// Unlike [`encode`](Self::encode), which writes the UTF-8 bytes of s
+    /// under the given label, this converts s with `encoder`, or if `None`
+    /// the builtin UTF-8, US-ASCII and ISO-8859-1 conversions and, with the
+    /// `encoding_rs` feature, those of `encoding_rs` (see [`charset`]).
+    /// Long text is split into several words of at most 75 characters, each
+    /// converted on its own, so no word ends inside a multi-byte sequence or
+    /// a stateful escape sequence.
+    ///
+    /// Fails with [`Error::Encoding`] if the charset cannot be converted
+    /// to, or if a single character does not fit in a word.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::WordEncoder;
+    ///
+    /// let subject = "é".repeat(60);
+    /// let encoded = WordEncoder::BEncoding.encode_charset("ISO-8859-1", &subject, None)?;
+    /// assert!(encoded.split(' ').all(|word| word.len() <= 75));
+    /// assert!(WordEncoder::BEncoding.encode_charset("Shift_JIS", &subject, None).is_err());
+    /// # Ok::<(), yamime::Error>(())
+    /// ```
+    pub fn encode_charset(
+        &self,
+        charset: &str,
+        s: &str,
+        encoder: Option<&CharsetEncoder>,
+    ) -> Result<String> {
+        if !needs_encoding(s) {
+            return Ok(s.to_string());
+        }
+        let max_content = max_content_len(charset);
+        if max_content < 4 {
+            return Err(Error::Encoding(format!(
+                "charset name too long for an encoded-word: {}",
+                charset
+            )));
+        }
+        let convert = |text: &str| match encoder {
+            Some(encoder) => encoder(charset, text),
+            None => charset::encode_default(charset, text),
+        };
+        let encoded_len = |bytes: &[u8]| match self {
+            WordEncoder::BEncoding => (bytes.len() + 2) / 3 * 4,
+            WordEncoder::QEncoding => q_len(bytes, EncodeContext::Text),
+        };
+
+        // Grow each word a character at a time, converting its text anew so
+        // stateful charsets end every word in their initial state
+        let mut words = Vec::new();
+        let mut start = 0;
+        let mut current = Vec::new();
+        for (i, ch) in s.char_indices() {
+            let end = i + ch.len_utf8();
+            let mut candidate = convert(&s[start..end])?;
+            if encoded_len(&candidate) > max_content && start < i {
+                words.push(std::mem::take(&mut current));
+                start = i;
+                candidate = convert(&s[start..end])?;
+            }
+            current = candidate;
+            if encoded_len(&current) > max_content {
+                return Err(Error::Encoding(format!(
+                    "character {:?} does not fit in an encoded-word in {}",
+                    ch, charset
+                )));
+            }
+        }
+        words.push(current);
+
+        let mut buf = String::new();
+        for (i, word) in words.iter().enumerate() {
+            if i > 0 {
+                buf.push(' ');
+            }
+            self.open_word(&mut buf, charset);
+            match self {
+                WordEncoder::BEncoding => buf.push_str(&BASE64.encode(word)),
+                WordEncoder::QEncoding => write_q_string(&mut buf, word, EncodeContext::Text),
+            }
+            close_word(&mut buf);
+        }
+        Ok(buf)
+    }
+
+    /// Returns the encoded-word form of s, splitting only between grapheme
+    /// clusters.
+    ///
+    /// Like [`encode`](Self::encode), but when s needs several encoded-words
+    /// no word ends in the middle of a user-perceived character, e.g. between
+    /// a letter and its combining accent. Strict renderers decode each word
+    /// on its own and would otherwise show mojibake. A cluster too long for
+    /// one word is still split between characters.
+    ///
+    /// Requires the `grapheme` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::WordEncoder;
+    ///
+    /// let subject = "e\u{301}".repeat(20);
+    /// let encoded = WordEncoder::QEncoding.encode_graphemes("UTF-8", &subject);
+    /// for word in encoded.split(' ') {
+    ///     assert!(word.starts_with("=?UTF-8?q?e"));
+    /// }
+    /// ```
+    #[cfg(feature = "grapheme")]
+    pub fn encode_graphemes(&self, charset: &str, s: &str) -> String {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        if !needs_encoding(s) {
+            return s.to_string();
+        }
+        let mut units = s.grapheme_indices(true).flat_map(|(i, cluster)| {
+            if cluster.len() * 3 <= max_content_len(charset) {
+                vec![(i, cluster)]
+            } else {
+                cluster
+                    .char_indices()
+                    .map(|(j, ch)| (i + j, &cluster[j..j + ch.len_utf8()]))
+                    .collect()
+            }
+        });
+        self.encode_word(charset, s, &mut units, EncodeContext::Text)
     }
 
     /// Encodes a string into an encoded-word.
-    fn encode_word(&self, charset: &str, s: &str) -> String {
+    ///
+    /// `units` yields the byte offset and text of each piece of s that must
+    /// not be split across words.
+    fn encode_word<'a>(
+        &self,
+        charset: &str,
+        s: &'a str,
+        units: &mut dyn Iterator<Item = (usize, &'a str)>,
+        context: EncodeContext,
+    ) -> String {
         let mut buf = String::with_capacity(48);
 
         self.open_word(&mut buf, charset);
         match self {
-            WordEncoder::BEncoding => self.b_encode(&mut buf, charset, s),
-            WordEncoder::QEncoding => self.q_encode(&mut buf, charset, s),
+            WordEncoder::BEncoding => self.b_encode(&mut buf, charset, s, units),
+            WordEncoder::QEncoding => self.q_encode(&mut buf, charset, s, units, context),
         }
         close_word(&mut buf);
 
@@ -71,69 +333,74 @@ impl WordEncoder {
     }
 
     /// Base64 encoding.
-    fn b_encode(&self, buf: &mut String, charset: &str, s: &str) {
+    fn b_encode<'a>(
+        &self,
+        buf: &mut String,
+        charset: &str,
+        s: &'a str,
+        units: &mut dyn Iterator<Item = (usize, &'a str)>,
+    ) {
         let encoded = BASE64.encode(s.as_bytes());
+        let max_content = max_content_len(charset);
 
         // If short enough, write it all
-        if !is_utf8(charset) || encoded.len() <= MAX_CONTENT_LEN {
+        if !can_split(charset) || max_content < 4 || encoded.len() <= max_content {
             buf.push_str(&encoded);
             return;
         }
 
         // Need to split for UTF-8 content
-        let max_decoded = BASE64.decode(&vec![b'A'; MAX_CONTENT_LEN]).unwrap().len();
+        // Decoded length of max_content base64 characters
+        let max_decoded = max_content / 4 * 3;
         let mut last = 0;
         let mut current_len = 0;
 
-        for (i, ch) in s.char_indices() {
-            let char_len = ch.len_utf8();
-            if current_len + char_len <= max_decoded {
-                current_len += char_len;
+        for (i, unit) in units {
+            let unit_len = unit.len();
+            if current_len + unit_len <= max_decoded {
+                current_len += unit_len;
             } else {
                 // Split here
                 let chunk = &s[last..i];
                 buf.push_str(&BASE64.encode(chunk.as_bytes()));
                 self.split_word(buf, charset);
                 last = i;
-                current_len = char_len;
+                current_len = unit_len;
             }
         }
 
         // Write remaining
         if last < s.len() {
-            buf.push_str(&BASE64.encode(s[last..].as_bytes()));
+            buf.push_str(&BASE64.encode(&s.as_bytes()[last..]));
         }
     }
 
     /// Q encoding.
-    fn q_encode(&self, buf: &mut String, charset: &str, s: &str) {
-        if !is_utf8(charset) {
-            write_q_string(buf, s);
+    fn q_encode<'a>(
+        &self,
+        buf: &mut String,
+        charset: &str,
+        s: &'a str,
+        units: &mut dyn Iterator<Item = (usize, &'a str)>,
+        context: EncodeContext,
+    ) {
+        if !can_split(charset) {
+            write_q_string(buf, s.as_bytes(), context);
             return;
         }
 
+        let max_content = max_content_len(charset);
         let mut current_len = 0;
 
-        for (i, ch) in s.char_indices() {
-            let b = s.as_bytes()[i];
-            let (char_len, enc_len) = if ch.is_ascii()
-                && b >= b' '
-                && b <= b'~'
-                && b != b'='
-                && b != b'?'
-                && b != b'_'
-            {
-                (ch.len_utf8(), 1)
-            } else {
-                (ch.len_utf8(), 3 * ch.len_utf8())
-            };
+        for (_, unit) in units {
+            let enc_len = q_len(unit.as_bytes(), context);
 
-            if current_len + enc_len > MAX_CONTENT_LEN {
+            if current_len > 0 && current_len + enc_len > max_content {
                 self.split_word(buf, charset);
                 current_len = 0;
             }
 
-            write_q_string(buf, &s[i..i + char_len]);
+            write_q_string(buf, unit.as_bytes(), context);
             current_len += enc_len;
         }
     }
@@ -162,6 +429,14 @@ impl WordDecoder {
         Self::default()
     }
 
+    /// Applies `max_decoded_word` from `limits`, if set.
+    pub fn limits(mut self, limits: &Limits) -> Self {
+        if let Some(limit) = limits.max_decoded_word {
+            self.max_decoded_word = Some(limit);
+        }
+        self
+    }
+
     /// Decodes an RFC 2047 encoded-word.
     ///
     /// # Examples
@@ -183,6 +458,7 @@ impl WordDecoder {
             return Err(Error::Encoding("invalid RFC 2047 encoded-word".to_string()));
         }
 
+        let original = word;
         let word = &word[2..word.len() - 2];
 
         // Split into charset, encoding, text
@@ -196,7 +472,10 @@ impl WordDecoder {
         let text = parts[2];
 
         let content = decode_content(encoding, text)?;
-        self.convert(charset, &content)
+        self.check_word_size(&content)?;
+        Ok(self
+            .convert_word(charset, &content)?
+            .unwrap_or_else(|| original.to_string()))
     }
 
     /// Decodes all encoded-words in the given string.
@@ -211,6 +490,38 @@ impl WordDecoder {
     /// assert_eq!(decoded, "Subject: Hello");
     /// ```
     pub fn decode_header(&self, header: &str) -> Result<String> {
+        self.decode_words(header, None)
+    }
+
+    /// Decodes the encoded-words of a header value that RFC 2047 section 5
+    /// allows in the given context.
+    ///
+    /// Unlike [`decode_header`](Self::decode_header), which decodes
+    /// encoded-words wherever they appear, words the context does not allow
+    /// are left as they are, as the RFC requires: words not separated from
+    /// adjacent text by whitespace, words within a quoted-string of a
+    /// phrase, and Q-encoded words carrying characters the context forbids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::{EncodeContext, WordDecoder};
+    ///
+    /// let decoder = WordDecoder::new();
+    /// let name = decoder.decode_header_in("=?UTF-8?q?J=C3=B6rg?= Smith", EncodeContext::Phrase).unwrap();
+    /// assert_eq!(name, "Jörg Smith");
+    ///
+    /// // Not a separate word, so not an encoded-word
+    /// let text = decoder.decode_header_in("x=?UTF-8?q?J=C3=B6rg?=", EncodeContext::Text).unwrap();
+    /// assert_eq!(text, "x=?UTF-8?q?J=C3=B6rg?=");
+    /// ```
+    pub fn decode_header_in(&self, header: &str, context: EncodeContext) -> Result<String> {
+        self.decode_words(header, Some(context))
+    }
+
+    /// Decodes the encoded-words of a header value, only where `context`
+    /// allows them if given.
+    fn decode_words(&self, header: &str, context: Option<EncodeContext>) -> Result<String> {
         // Quick check if there's anything to decode
         if !header.contains("=?") {
             return Ok(header.to_string());
@@ -254,21 +565,33 @@ impl WordDecoder {
             let text = &remaining[cur..end_pos];
             let end = end_pos + 2;
 
+            let offset = header.len() - remaining.len();
+            let placed = context.map_or(true, |context| {
+                context.allows(header, offset + start, offset + end, encoding, text)
+            });
+
             // Try to decode
-            match decode_content(encoding, text) {
-                Ok(content) => {
+            let decoded = match decode_content(encoding, text) {
+                Ok(content) if placed => {
+                    self.check_word_size(&content)?;
+                    self.convert_word(charset, &content)?
+                }
+                _ => None,
+            };
+            match decoded {
+                Some(decoded) => {
                     // Add text before encoded-word (but skip whitespace between encoded-words)
                     if start > 0 && (!between_words || has_non_whitespace(&remaining[..start])) {
                         result.push_str(&remaining[..start]);
                     }
 
                     // Add decoded content
-                    result.push_str(&self.convert(charset, &content)?);
+                    result.push_str(&decoded);
                     remaining = &remaining[end..];
                     between_words = true;
                     continue;
                 }
-                Err(_) => {
+                None => {
                     // Failed to decode, skip this and continue
                     result.push_str(&remaining[..start + 2]);
                     remaining = &remaining[start + 2..];
@@ -286,6 +609,29 @@ impl WordDecoder {
         Ok(result)
     }
 
+    fn check_word_size(&self, content: &[u8]) -> Result<()> {
+        match self.max_decoded_word {
+            Some(limit) if content.len() > limit => Err(Error::MessageTooLarge(
+                LimitExceeded::new(LimitKind::DecodedSize, limit as u64, content.len() as u64),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Converts the content of a word, applying the replacement policy.
+    ///
+    /// Returns `None` if the word is to be left undecoded.
+    fn convert_word(&self, charset: &str, content: &[u8]) -> Result<Option<String>> {
+        match self.convert(charset, content) {
+            Ok(text) => Ok(Some(text)),
+            Err(err) => match self.replacement {
+                ReplacementPolicy::Strict => Err(err),
+                ReplacementPolicy::Replace => Ok(Some(convert_lossy(charset, content))),
+                ReplacementPolicy::SkipWord => Ok(None),
+            },
+        }
+    }
+
     /// Converts content from the given charset to UTF-8.
     fn convert(&self, charset: &str, content: &[u8]) -> Result<String> {
         if charset.eq_ignore_ascii_case("utf-8") {
@@ -299,11 +645,13 @@ impl WordDecoder {
         }
 
         if charset.eq_ignore_ascii_case("us-ascii") {
-            // US-ASCII - replace non-ASCII with replacement char
-            return Ok(content
-                .iter()
-                .map(|&b| if b < 128 { b as char } else { '\u{FFFD}' })
-                .collect());
+            return match content.iter().position(|b| !b.is_ascii()) {
+                Some(pos) => Err(Error::Encoding(format!(
+                    "non-ASCII byte 0x{:02x} in US-ASCII",
+                    content[pos]
+                ))),
+                None => Ok(content.iter().map(|&b| b as char).collect()),
+            };
         }
 
         // Try custom charset reader
@@ -315,10 +663,56 @@ impl WordDecoder {
     }
 }
 
+/// Decodes an RFC 2047 encoded-word with the default charset handling.
+///
+/// UTF-8, ISO-8859-1 and US-ASCII are supported; use a [`WordDecoder`] with a
+/// charset reader for other charsets.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::encoded_word;
+///
+/// assert_eq!(encoded_word::decode("=?UTF-8?q?caf=C3=A9?=").unwrap(), "café");
+/// ```
+pub fn decode(word: &str) -> Result<String> {
+    WordDecoder::new().decode(word)
+}
+
+/// Decodes all encoded-words in a header value with the default charset
+/// handling.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::encoded_word;
+///
+/// let decoded = encoded_word::decode_header("Re: =?ISO-8859-1?q?Caf=E9?=").unwrap();
+/// assert_eq!(decoded, "Re: Café");
+/// ```
+pub fn decode_header(header: &str) -> Result<String> {
+    WordDecoder::new().decode_header(header)
+}
+
+/// Converts content that failed strict conversion, replacing what cannot
+/// be converted with U+FFFD.
+fn convert_lossy(charset: &str, content: &[u8]) -> String {
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8_lossy(content).into_owned()
+    } else if charset.eq_ignore_ascii_case("us-ascii") {
+        content
+            .iter()
+            .map(|&b| if b.is_ascii() { b as char } else { '\u{FFFD}' })
+            .collect()
+    } else {
+        '\u{FFFD}'.to_string()
+    }
+}
+
 /// Checks if a string needs encoding.
 fn needs_encoding(s: &str) -> bool {
     s.chars()
-        .any(|ch| (ch < ' ' || ch > '~') && ch != '\t')
+        .any(|ch| !(' '..='~').contains(&ch) && ch != '\t')
 }
 
 /// Writes the closing marker of an encoded-word.
@@ -331,12 +725,43 @@ fn is_utf8(charset: &str) -> bool {
     charset.eq_ignore_ascii_case("UTF-8")
 }
 
-/// Encodes a string using Q encoding.
-fn write_q_string(buf: &mut String, s: &str) {
-    for &b in s.as_bytes() {
+/// Checks if every byte of charset is a character on its own, so text may
+/// be split between any two bytes.
+fn is_single_byte(charset: &str) -> bool {
+    let charset = charset.to_ascii_lowercase();
+    matches!(charset.as_str(), "us-ascii" | "ascii" | "latin1" | "cp437" | "cp850")
+        || ["iso-8859-", "iso_8859-", "windows-125", "cp125", "koi8-"]
+            .iter()
+            .any(|prefix| charset.starts_with(prefix))
+}
+
+/// Checks if the bytes of a string labelled charset may be split between
+/// its characters. For other charsets the string is kept in one word.
+fn can_split(charset: &str) -> bool {
+    is_utf8(charset) || is_single_byte(charset)
+}
+
+/// Returns the longest encoded text fitting in an encoded-word of charset.
+fn max_content_len(charset: &str) -> usize {
+    let overhead = "=?".len() + charset.len() + "?q?".len() + "?=".len();
+    MAX_ENCODED_WORD_LEN.saturating_sub(overhead)
+}
+
+/// Returns the length of bytes once Q-encoded for `context`.
+fn q_len(bytes: &[u8], context: EncodeContext) -> usize {
+    bytes
+        .iter()
+        .map(|&b| if b == b' ' || context.is_q_safe(b) { 1 } else { 3 })
+        .sum()
+}
+
+/// Encodes bytes using Q encoding, leaving the characters `context`
+/// allows unencoded.
+fn write_q_string(buf: &mut String, bytes: &[u8], context: EncodeContext) {
+    for &b in bytes {
         match b {
             b' ' => buf.push('_'),
-            b'!' ..= b'~' if b != b'=' && b != b'?' && b != b'_' => buf.push(b as char),
+            _ if context.is_q_safe(b) => buf.push(b as char),
             _ => {
                 buf.push('=');
                 buf.push(UPPER_HEX[(b >> 4) as usize] as char);
@@ -404,6 +829,21 @@ fn from_hex(b: u8) -> Result<u8> {
     }
 }
 
+/// Reports whether the end of `s` lies within a quoted-string.
+fn in_quoted_string(s: &str) -> bool {
+    let mut quoted = false;
+    let mut escaped = false;
+    for b in s.bytes() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            _ => {}
+        }
+    }
+    quoted
+}
+
 /// Checks if a string contains non-whitespace characters.
 fn has_non_whitespace(s: &str) -> bool {
     s.bytes().any(|b| !matches!(b, b' ' | b'\t' | b'\n' | b'\r'))
@@ -664,6 +1104,180 @@ mod tests {
         assert_eq!(encoded, "");
     }
 
+    #[test]
+    fn test_encode_contexts() {
+        let q = WordEncoder::QEncoding;
+        let value = "é (a,b) \"c\" d*e";
+        assert_eq!(
+            q.encode_in("UTF-8", value, EncodeContext::Text),
+            "=?UTF-8?q?=C3=A9_(a,b)_\"c\"_d*e?="
+        );
+        assert_eq!(
+            q.encode_in("UTF-8", value, EncodeContext::Comment),
+            "=?UTF-8?q?=C3=A9_=28a,b=29_=22c=22_d*e?="
+        );
+        assert_eq!(
+            q.encode_in("UTF-8", value, EncodeContext::Phrase),
+            "=?UTF-8?q?=C3=A9_=28a=2Cb=29_=22c=22_d*e?="
+        );
+        assert_eq!(q.encode("UTF-8", value), q.encode_in("UTF-8", value, EncodeContext::Text));
+        // B-encoding and plain ASCII do not depend on the context
+        let b = WordEncoder::BEncoding;
+        assert_eq!(b.encode_in("UTF-8", value, EncodeContext::Phrase), b.encode("UTF-8", value));
+        assert_eq!(q.encode_in("UTF-8", "a, b", EncodeContext::Phrase), "a, b");
+
+        // Long phrases still split into words within the length limit
+        let long = "ü,".repeat(30);
+        let encoded = q.encode_in("UTF-8", &long, EncodeContext::Phrase);
+        assert!(encoded.split(' ').all(|w| w.len() <= MAX_ENCODED_WORD_LEN));
+        let decoder = WordDecoder::new();
+        assert_eq!(decoder.decode_header_in(&encoded, EncodeContext::Phrase).unwrap(), long);
+    }
+
+    #[test]
+    fn test_decode_contexts() {
+        let d = WordDecoder::new();
+        let word = "=?UTF-8?q?caf=C3=A9?=";
+
+        // Whitespace-separated words are decoded in every context
+        for context in [EncodeContext::Text, EncodeContext::Phrase, EncodeContext::Comment] {
+            assert_eq!(d.decode_header_in(&format!("a {} b", word), context).unwrap(), "a café b");
+        }
+
+        // Words run into adjacent text are not encoded-words
+        let joined = format!("a{}", word);
+        assert_eq!(d.decode_header_in(&joined, EncodeContext::Text).unwrap(), joined);
+        assert_eq!(d.decode_header(&joined).unwrap(), "acafé");
+        let adjacent = format!("{}{}", word, word);
+        assert_eq!(d.decode_header_in(&adjacent, EncodeContext::Text).unwrap(), adjacent);
+
+        // Comments may delimit words with parentheses
+        let comment = format!("({})", word);
+        assert_eq!(d.decode_header_in(&comment, EncodeContext::Comment).unwrap(), "(café)");
+        assert_eq!(d.decode_header_in(&comment, EncodeContext::Text).unwrap(), comment);
+        let unsafe_comment = "=?UTF-8?q?a(b?=";
+        assert_eq!(d.decode_header_in(unsafe_comment, EncodeContext::Comment).unwrap(), unsafe_comment);
+
+        // Phrases forbid quoted words and Q text outside the phrase set
+        let quoted = format!("\"{}\" <a@example.com>", word);
+        assert_eq!(d.decode_header_in(&quoted, EncodeContext::Phrase).unwrap(), quoted);
+        let unsafe_phrase = "=?UTF-8?q?a,b?=";
+        assert_eq!(d.decode_header_in(unsafe_phrase, EncodeContext::Phrase).unwrap(), unsafe_phrase);
+        assert_eq!(d.decode_header_in(unsafe_phrase, EncodeContext::Text).unwrap(), "a,b");
+        assert_eq!(d.decode_header_in("=?UTF-8?b?YSxi?=", EncodeContext::Phrase).unwrap(), "a,b");
+    }
+
+    #[test]
+    fn test_encode_long_single_byte_charset() {
+        // Labelled single-byte text splits at any character
+        let s = "ß".repeat(80);
+        for encoder in [WordEncoder::BEncoding, WordEncoder::QEncoding] {
+            let encoded = encoder.encode("ISO-8859-15", &s);
+            assert!(encoded.split(' ').count() > 1);
+            assert!(encoded.split(' ').all(|w| w.len() <= MAX_ENCODED_WORD_LEN));
+        }
+    }
+
+    #[test]
+    fn test_encode_charset() {
+        let decoder = WordDecoder::new();
+        let s = "Grüße aus Köln, ".repeat(8);
+        for encoder in [WordEncoder::BEncoding, WordEncoder::QEncoding] {
+            let encoded = encoder.encode_charset("iso-8859-1", &s, None).unwrap();
+            assert!(encoded.split(' ').all(|w| w.len() <= MAX_ENCODED_WORD_LEN));
+            assert_eq!(decoder.decode_header(&encoded).unwrap(), s);
+        }
+        assert!(matches!(
+            WordEncoder::BEncoding.encode_charset("us-ascii", "é", None),
+            Err(Error::Encoding(_))
+        ));
+        assert!(WordEncoder::BEncoding.encode_charset("x-unknown", "é", None).is_err());
+
+        // A stand-in for a stateful charset: each call wraps its text in
+        // escape sequences, which must never be split across words
+        let encoder: CharsetEncoder = Box::new(|_, text| {
+            let mut out = b"\x1b$B".to_vec();
+            for ch in text.chars() {
+                out.extend_from_slice(&(ch as u32 as u16).to_be_bytes());
+            }
+            out.extend_from_slice(b"\x1b(B");
+            Ok(out)
+        });
+        let s = "日本語".repeat(20);
+        let encoded = WordEncoder::BEncoding
+            .encode_charset("ISO-2022-JP", &s, Some(&encoder))
+            .unwrap();
+        for word in encoded.split(' ') {
+            assert!(word.len() <= MAX_ENCODED_WORD_LEN);
+            let content = &word["=?ISO-2022-JP?b?".len()..word.len() - 2];
+            let bytes = BASE64.decode(content).unwrap();
+            assert!(bytes.starts_with(b"\x1b$B") && bytes.ends_with(b"\x1b(B"));
+        }
+
+        // A character that cannot fit in any word
+        let huge: CharsetEncoder = Box::new(|_, text| Ok(text.repeat(40).into_bytes()));
+        assert!(WordEncoder::QEncoding.encode_charset("x-big", "é", Some(&huge)).is_err());
+    }
+
+    #[cfg(feature = "encoding_rs")]
+    #[test]
+    fn test_encode_charset_with_encoding_rs() {
+        assert_eq!(
+            WordEncoder::BEncoding.encode_charset("ISO-2022-JP", "日本語", None).unwrap(),
+            "=?ISO-2022-JP?b?GyRCRnxLXDhsGyhC?="
+        );
+        let encoded = WordEncoder::BEncoding
+            .encode_charset("ISO-2022-JP", &"日本語".repeat(20), None)
+            .unwrap();
+        assert!(encoded.split(' ').count() > 1);
+        for word in encoded.split(' ') {
+            let content = &word["=?ISO-2022-JP?b?".len()..word.len() - 2];
+            assert!(BASE64.decode(content).unwrap().ends_with(b"\x1b(B"));
+        }
+        assert_eq!(
+            WordEncoder::QEncoding.encode_charset("windows-1251", "Мир", None).unwrap(),
+            "=?windows-1251?q?=CC=E8=F0?="
+        );
+    }
+
+    #[test]
+    fn test_replacement_policy() {
+        let header = "=?UTF-8?q?a=FFb?= =?US-ASCII?q?caf=E9?= =?X-Unknown?q?z?=";
+        let mut decoder = WordDecoder::new();
+        assert!(matches!(decoder.decode_header(header), Err(Error::Encoding(_))));
+        assert!(decoder.decode("=?us-ascii?q?=E9?=").is_err());
+
+        decoder.replacement = ReplacementPolicy::Replace;
+        assert_eq!(decoder.decode_header(header).unwrap(), "a\u{FFFD}bcaf\u{FFFD}\u{FFFD}");
+
+        decoder.replacement = ReplacementPolicy::SkipWord;
+        assert_eq!(decoder.decode_header(header).unwrap(), header);
+        assert_eq!(decoder.decode("=?UTF-8?b?/w==?=").unwrap(), "=?UTF-8?b?/w==?=");
+        assert_eq!(
+            decoder.decode_header("=?utf-8?q?ok?= =?utf-8?q?=FF?=").unwrap(),
+            "ok =?utf-8?q?=FF?="
+        );
+
+        // A failing charset reader is subject to the same policy
+        decoder.charset_reader = Some(Box::new(|_, _| Err(Error::Encoding("bad".to_string()))));
+        assert_eq!(decoder.decode("=?koi8-r?q?x?=").unwrap(), "=?koi8-r?q?x?=");
+        decoder.replacement = ReplacementPolicy::Replace;
+        assert_eq!(decoder.decode("=?koi8-r?q?x?=").unwrap(), "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_max_decoded_word() {
+        let limits = Limits {
+            max_decoded_word: Some(3),
+            ..Default::default()
+        };
+        let decoder = WordDecoder::new().limits(&limits);
+        assert_eq!(decoder.decode_header("=?utf-8?q?abc?= x").unwrap(), "abc x");
+        let err = decoder.decode_header("=?utf-8?b?YWJjZA==?=").unwrap_err();
+        assert_eq!(err.limit_exceeded().map(|l| (l.limit, l.observed)), Some((3, 4)));
+        assert!(decoder.decode("=?utf-8?q?abcd?=").is_err());
+    }
+
     #[test]
     fn test_charset_us_ascii() {
         let decoder = WordDecoder::new();
@@ -671,4 +1285,30 @@ mod tests {
         let decoded = decoder.decode("=?US-ASCII?q?Hello?=").unwrap();
         assert_eq!(decoded, "Hello");
     }
+
+    #[cfg(feature = "grapheme")]
+    #[test]
+    fn test_encode_graphemes_keeps_combining_sequences() {
+        // "e" + COMBINING ACUTE ACCENT, offset so a word fills up mid-cluster
+        let s = format!("a{}", "e\u{301}".repeat(30));
+        let words = |encoded: &str| -> Vec<String> {
+            let decoder = WordDecoder::new();
+            encoded.split(' ').map(|w| decoder.decode(w).unwrap()).collect()
+        };
+
+        for encoder in [WordEncoder::QEncoding, WordEncoder::BEncoding] {
+            let by_char = encoder.encode("UTF-8", &s);
+            let by_grapheme = encoder.encode_graphemes("UTF-8", &s);
+            assert_eq!(WordDecoder::new().decode_header(&by_grapheme).unwrap(), s);
+
+            let by_grapheme = words(&by_grapheme);
+            assert!(by_grapheme.len() > 1);
+            assert!(by_grapheme.iter().all(|w| !w.starts_with('\u{301}')));
+            assert!(by_grapheme.iter().all(|w| w.len() <= 63));
+
+            if encoder == WordEncoder::QEncoding {
+                assert!(words(&by_char).iter().any(|w| w.starts_with('\u{301}')));
+            }
+        }
+    }
 }
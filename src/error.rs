@@ -1,5 +1,7 @@
 //! Error types for the mime crate.
 
+use crate::multipart::schema::FormViolation;
+use std::fmt;
 use std::io;
 use thiserror::Error;
 
@@ -7,8 +9,11 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum Error {
     /// IO error
+    ///
+    /// I/O errors carrying one of this crate's errors, as returned by part
+    /// readers, convert to that error rather than to `Io`.
     #[error("IO error: {0}")]
-    Io(#[from] io::Error),
+    Io(#[source] io::Error),
 
     /// MIME type error
     #[error("MIME type error: {0}")]
@@ -31,8 +36,212 @@ pub enum Error {
     InvalidParameter(String),
 
     /// Message too large
-    #[error("Message too large")]
-    MessageTooLarge,
+    #[error("Message too large: {0}")]
+    MessageTooLarge(LimitExceeded),
+
+    /// Invalid UTF-8 in decoded data, at the given offset of the decoded output
+    #[error("Invalid UTF-8 at byte offset {offset}")]
+    InvalidUtf8 {
+        /// Offset of the first invalid byte in the decoded output.
+        offset: usize,
+    },
+
+    /// Nested multipart bodies exceed the configured depth limit
+    #[error("Multipart nesting depth {depth} exceeds limit of {limit}")]
+    NestingTooDeep {
+        /// Depth of the multipart body that was rejected.
+        depth: usize,
+        /// The configured maximum depth.
+        limit: usize,
+    },
+
+    /// No delimiter line was found within the configured preamble size
+    #[error("Boundary {expected:?} not found in the first {bytes_scanned} bytes")]
+    BoundaryNotFound {
+        /// The boundary that was looked for.
+        expected: String,
+        /// Number of bytes read before giving up.
+        bytes_scanned: u64,
+    },
+
+    /// A form does not match its schema, for each of the listed reasons
+    #[error("Invalid form: {}", join_violations(.0))]
+    InvalidForm(Vec<FormViolation>),
+
+    /// Operation cancelled
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// The input was too slow to arrive
+    #[error("Timed out: {0} elapsed")]
+    Timeout(TimeoutKind),
+}
+
+impl Error {
+    /// Returns the kind of the underlying I/O error, if any.
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Error::Io(err) => Some(err.kind()),
+            _ => None,
+        }
+    }
+
+    /// Reports whether the input ended before the message was complete.
+    pub fn is_unexpected_eof(&self) -> bool {
+        self.io_kind() == Some(io::ErrorKind::UnexpectedEof)
+    }
+
+    /// Reports whether the operation was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self, Error::Cancelled)
+    }
+
+    /// Reports whether the input was too slow to arrive, whether from this
+    /// crate's timeouts or the underlying transport's.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::Timeout(_)) || self.io_kind() == Some(io::ErrorKind::TimedOut)
+    }
+
+    /// Returns the details of an exceeded limit.
+    pub fn limit_exceeded(&self) -> Option<&LimitExceeded> {
+        match self {
+            Error::MessageTooLarge(limit) => Some(limit),
+            _ => None,
+        }
+    }
+
+    /// Reports whether the input itself is invalid, as opposed to an I/O
+    /// failure, cancellation, timeout or exceeded limit.
+    pub fn is_malformed(&self) -> bool {
+        match self {
+            Error::MimeType(_)
+            | Error::MediaType(_)
+            | Error::Encoding(_)
+            | Error::Multipart(_)
+            | Error::InvalidParameter(_)
+            | Error::InvalidUtf8 { .. }
+            | Error::NestingTooDeep { .. }
+            | Error::BoundaryNotFound { .. }
+            | Error::InvalidForm(_) => true,
+            Error::Io(err) => err.kind() == io::ErrorKind::InvalidData,
+            Error::MessageTooLarge(_) | Error::Cancelled | Error::Timeout(_) => false,
+        }
+    }
+}
+
+fn join_violations(violations: &[FormViolation]) -> String {
+    let violations: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    violations.join("; ")
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        if !err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            return Error::Io(err);
+        }
+        match err.into_inner().map(|inner| inner.downcast::<Error>()) {
+            Some(Ok(inner)) => *inner,
+            _ => unreachable!("checked to hold an Error"),
+        }
+    }
+}
+
+/// The kind of timeout reported by [`Error::Timeout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// No input arrived for the idle timeout.
+    Idle,
+    /// Parsing did not finish before the deadline.
+    Deadline,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeoutKind::Idle => "idle timeout",
+            TimeoutKind::Deadline => "parse deadline",
+        })
+    }
+}
+
+/// The kind of limit reported by [`Error::MessageTooLarge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    /// Total size in bytes of a part's header block.
+    HeaderSize,
+    /// Number of headers in a part.
+    HeaderCount,
+    /// Length in bytes of an unfolded header line.
+    HeaderLineLength,
+    /// Size in bytes of a part body.
+    PartSize,
+    /// Number of parts in a form.
+    PartCount,
+    /// Total size in bytes of a multipart message.
+    MessageSize,
+    /// Size in bytes of a decoded part body.
+    DecodedSize,
+    /// Length in bytes of a line of encoded body text.
+    LineLength,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LimitKind::HeaderSize => "header size",
+            LimitKind::HeaderCount => "header count",
+            LimitKind::HeaderLineLength => "header line length",
+            LimitKind::PartSize => "part size",
+            LimitKind::PartCount => "part count",
+            LimitKind::MessageSize => "message size",
+            LimitKind::DecodedSize => "decoded size",
+            LimitKind::LineLength => "line length",
+        })
+    }
+}
+
+/// Details of an exceeded limit.
+///
+/// Parsing stops as soon as a limit is crossed, so `observed` is the value at
+/// that point: a lower bound of the actual size for size limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// Which limit was exceeded.
+    pub kind: LimitKind,
+    /// The configured limit.
+    pub limit: u64,
+    /// The value that exceeded it.
+    pub observed: u64,
+    /// Zero-based index of the offending part, if known.
+    pub part_index: Option<usize>,
+}
+
+impl LimitExceeded {
+    /// Creates limit details without a part index.
+    pub fn new(kind: LimitKind, limit: u64, observed: u64) -> Self {
+        Self {
+            kind,
+            limit,
+            observed,
+            part_index: None,
+        }
+    }
+
+    /// Sets the index of the offending part.
+    pub fn with_part_index(mut self, index: usize) -> Self {
+        self.part_index = Some(index);
+        self
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} exceeds limit of {}", self.kind, self.observed, self.limit)?;
+        if let Some(index) = self.part_index {
+            write!(f, " (part {})", index)?;
+        }
+        Ok(())
+    }
 }
 
 /// Specialized Result type for mime operations.
@@ -77,8 +286,27 @@ mod tests {
         assert_eq!(err.to_string(), "Invalid parameter: invalid param");
 
         // Test MessageTooLarge error
-        let err = Error::MessageTooLarge;
-        assert_eq!(err.to_string(), "Message too large");
+        let err = Error::MessageTooLarge(
+            LimitExceeded::new(LimitKind::PartSize, 8 << 20, 9 << 20).with_part_index(2),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Message too large: part size 9437184 exceeds limit of 8388608 (part 2)"
+        );
+        let err = Error::MessageTooLarge(LimitExceeded::new(LimitKind::HeaderCount, 10, 11));
+        assert_eq!(err.to_string(), "Message too large: header count 11 exceeds limit of 10");
+
+        // Test InvalidUtf8 error
+        let err = Error::InvalidUtf8 { offset: 7 };
+        assert_eq!(err.to_string(), "Invalid UTF-8 at byte offset 7");
+
+        // Test Cancelled error
+        let err = Error::Cancelled;
+        assert_eq!(err.to_string(), "Operation cancelled");
+
+        // Test Timeout error
+        let err = Error::Timeout(TimeoutKind::Idle);
+        assert_eq!(err.to_string(), "Timed out: idle timeout elapsed");
     }
 
     #[test]
@@ -90,6 +318,42 @@ mod tests {
         assert!(err.to_string().contains("file not found"));
     }
 
+    #[test]
+    fn test_io_error_unwraps_crate_errors() {
+        let err: Error = io::Error::new(io::ErrorKind::Other, Error::Cancelled).into();
+        assert!(err.is_cancelled());
+
+        let err: Error =
+            io::Error::new(io::ErrorKind::TimedOut, Error::Timeout(TimeoutKind::Idle)).into();
+        assert!(matches!(err, Error::Timeout(TimeoutKind::Idle)));
+
+        // Other I/O errors stay I/O errors, with their source
+        let err: Error = io::Error::new(io::ErrorKind::InvalidData, "bad byte").into();
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "bad byte");
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let eof: Error = io::Error::from(io::ErrorKind::UnexpectedEof).into();
+        assert!(eof.is_unexpected_eof());
+        assert!(!eof.is_malformed());
+        assert_eq!(eof.io_kind(), Some(io::ErrorKind::UnexpectedEof));
+
+        let timed_out: Error = io::Error::from(io::ErrorKind::TimedOut).into();
+        assert!(timed_out.is_timeout());
+        assert!(Error::Timeout(TimeoutKind::Deadline).is_timeout());
+
+        let err = Error::MessageTooLarge(LimitExceeded::new(LimitKind::PartCount, 1, 2));
+        assert_eq!(err.limit_exceeded().map(|l| l.kind), Some(LimitKind::PartCount));
+        assert!(!err.is_malformed());
+
+        assert!(Error::Multipart("bad".to_string()).is_malformed());
+        assert!(Error::InvalidUtf8 { offset: 0 }.is_malformed());
+        assert!(!Error::Cancelled.is_malformed());
+        assert_eq!(Error::Cancelled.io_kind(), None);
+    }
+
     #[test]
     fn test_invalid_media_parameter_conversion() {
         // Test InvalidMediaParameter conversion
@@ -108,6 +372,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_literal_unwrap)]
     fn test_result_type() {
         // Test Result type alias
         let ok_result: Result<i32> = Ok(42);
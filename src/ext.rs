@@ -0,0 +1,62 @@
+//! Extension trait chaining the crate's decoders onto any `AsyncRead`.
+
+use crate::{base64, multipart, quotedprintable};
+use tokio::io::AsyncRead;
+
+/// Adds decoding combinators to every [`AsyncRead`].
+///
+/// # Examples
+///
+/// ```
+/// use yamime::MimeReadExt;
+/// use tokio::io::AsyncReadExt;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let body = b"--b\r\n\r\nSGVsbG8=\r\n--b--\r\n";
+/// let mut reader = (&body[..]).multipart("b");
+///
+/// let part = reader.next_part().await?.unwrap();
+/// let mut decoded = String::new();
+/// part.base64_decode().read_to_string(&mut decoded).await?;
+/// assert_eq!(decoded, "Hello");
+/// # Ok(())
+/// # }
+/// ```
+pub trait MimeReadExt: AsyncRead + Sized {
+    /// Decodes quoted-printable data.
+    fn quoted_printable_decode(self) -> quotedprintable::Reader<Self> {
+        quotedprintable::Reader::new(self)
+    }
+
+    /// Decodes base64 data, ignoring line breaks.
+    fn base64_decode(self) -> base64::Decoder<Self> {
+        base64::Decoder::new(self)
+    }
+
+    /// Parses multipart data with the given boundary.
+    fn multipart(self, boundary: impl AsRef<[u8]>) -> multipart::Reader<Self>
+    where
+        Self: Unpin,
+    {
+        multipart::Reader::new(self, boundary)
+    }
+}
+
+impl<R: AsyncRead> MimeReadExt for R {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_quoted_printable_decode() {
+        let mut decoded = String::new();
+        (&b"caf=C3=A9"[..])
+            .quoted_printable_decode()
+            .read_to_string(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, "café");
+    }
+}
@@ -0,0 +1,174 @@
+//! Go-shaped entry points, for code ported from Go's `mime` and
+//! `mime/multipart` packages.
+//!
+//! Each function is a thin wrapper named after its Go counterpart and
+//! returning the same shape, so a port can be checked against Go output
+//! call by call before moving to the crate's native API:
+//!
+//! | Go | Here | Native |
+//! |----|------|--------|
+//! | `mime.TypeByExtension` | [`type_by_extension`] | [`crate::type_by_extension`] |
+//! | `mime.ExtensionsByType` | [`extensions_by_type`] | same |
+//! | `mime.AddExtensionType` | [`add_extension_type`] | same |
+//! | `mime.ParseMediaType` | [`parse_media_type`] | [`crate::parse_media_type`] |
+//! | `mime.FormatMediaType` | [`format_media_type`] | same |
+//! | `multipart.NewReader` | [`new_reader`] | [`Reader::new`] |
+//! | `multipart.NewWriter` | [`new_writer`] | [`Writer::new`] |
+//! | `(*Writer).FormDataContentType` | [`form_data_content_type`] | [`Writer::form_data_content_type`] |
+//! | `(*Writer).CreateFormFile` | [`create_form_file`] | [`Writer::create_form_file`] |
+//! | `(*Writer).CreateFormField` | [`create_form_field`] | [`Writer::create_form_field`] |
+//! | `(*Writer).WriteField` | [`write_field`] | [`Writer::write_field`] |
+//!
+//! The form helpers write headers exactly as Go does: quotes and
+//! backslashes escaped, other characters as given, and
+//! `application/octet-stream` for every file. The native methods detect
+//! the Content-Type from the filename and encode non-ASCII names.
+//!
+//! `NextPart` returning `io.EOF` maps to [`Reader::next_part`] returning
+//! `None`.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::go_compat;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut body = Vec::new();
+//! let mut w = yamime::multipart::Writer::builder().boundary("b").build(&mut body)?;
+//! go_compat::write_field(&mut w, "name", "value").await?;
+//! assert_eq!(go_compat::form_data_content_type(&w), "multipart/form-data; boundary=b");
+//! w.close().await?;
+//!
+//! let (media_type, params, err) = go_compat::parse_media_type("text/html; charset=utf-8");
+//! assert_eq!((media_type.as_str(), err.is_none()), ("text/html", true));
+//! assert_eq!(params["charset"], "utf-8");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{Error, Result};
+use crate::multipart::writer::{escape_quotes, PartWriter};
+use crate::multipart::{MimeHeader, Reader, Writer};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+pub use crate::media_type::format_media_type;
+pub use crate::mime_type::{add_extension_type, extensions_by_type};
+
+/// Returns the MIME type of the extension, or an empty string if it is
+/// unknown, like Go's `mime.TypeByExtension`.
+pub fn type_by_extension(ext: &str) -> String {
+    crate::type_by_extension(ext).unwrap_or_default()
+}
+
+/// Parses a media type value, like Go's `mime.ParseMediaType`.
+///
+/// Returns the lowercase media type, its parameters and the error, if
+/// any. As in Go, the media type and parameters are empty on error.
+pub fn parse_media_type(v: &str) -> (String, HashMap<String, String>, Option<Error>) {
+    match crate::parse_media_type(v) {
+        Ok((media_type, params)) => (media_type, params, None),
+        Err(err) => (String::new(), HashMap::new(), Some(err)),
+    }
+}
+
+/// Creates a reader for a multipart body, like Go's `multipart.NewReader`.
+pub fn new_reader<R: AsyncRead + Unpin>(r: R, boundary: &str) -> Reader<R> {
+    Reader::new(r, boundary)
+}
+
+/// Creates a writer with a random boundary, like Go's
+/// `multipart.NewWriter`.
+pub fn new_writer<W: AsyncWrite + Unpin>(w: W) -> Writer<W> {
+    Writer::new(w)
+}
+
+/// Returns the Content-Type of a form-data body, like Go's
+/// `(*Writer).FormDataContentType`.
+pub fn form_data_content_type<W: AsyncWrite + Unpin>(w: &Writer<W>) -> String {
+    w.form_data_content_type()
+}
+
+/// Creates a form file part, like Go's `(*Writer).CreateFormFile`.
+///
+/// The Content-Type is always `application/octet-stream`.
+pub async fn create_form_file<'a, W: AsyncWrite + Unpin>(
+    w: &'a mut Writer<W>,
+    fieldname: &str,
+    filename: &str,
+) -> Result<PartWriter<'a, W>> {
+    let mut header = MimeHeader::new();
+    header.insert(
+        "Content-Disposition",
+        format!(
+            "form-data; name=\"{}\"; filename=\"{}\"",
+            escape_quotes(fieldname),
+            escape_quotes(filename)
+        ),
+    );
+    header.insert("Content-Type", "application/octet-stream");
+    w.create_part(header).await
+}
+
+/// Creates a form field part, like Go's `(*Writer).CreateFormField`.
+pub async fn create_form_field<'a, W: AsyncWrite + Unpin>(
+    w: &'a mut Writer<W>,
+    fieldname: &str,
+) -> Result<PartWriter<'a, W>> {
+    let mut header = MimeHeader::new();
+    header.insert(
+        "Content-Disposition",
+        format!("form-data; name=\"{}\"", escape_quotes(fieldname)),
+    );
+    w.create_part(header).await
+}
+
+/// Writes a complete form field, like Go's `(*Writer).WriteField`.
+pub async fn write_field<W: AsyncWrite + Unpin>(
+    w: &mut Writer<W>,
+    fieldname: &str,
+    value: &str,
+) -> Result<()> {
+    let mut part = create_form_field(w, fieldname).await?;
+    part.write_all(value.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_matches_go_output() {
+        let mut body = Vec::new();
+        let mut w = new_writer(&mut body);
+        w.set_boundary("b".to_string()).unwrap();
+        write_field(&mut w, "a\"b", "1").await.unwrap();
+        let mut part = create_form_file(&mut w, "file", "café.png").await.unwrap();
+        part.write_all(b"x").await.unwrap();
+        w.close().await.unwrap();
+
+        // As written by Go's mime/multipart for the same calls
+        let expected = "--b\r\nContent-Disposition: form-data; name=\"a\\\"b\"\r\n\r\n1\r\n\
+--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"café.png\"\r\n\
+Content-Type: application/octet-stream\r\n\r\nx\r\n--b--\r\n";
+        assert_eq!(String::from_utf8(body).unwrap(), expected);
+
+        let mut r = new_reader(expected.as_bytes(), "b");
+        r.next_part().await.unwrap().unwrap();
+        let mut part = r.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name().as_deref(), Some("café.png"));
+        drop(part);
+        assert!(r.next_part().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_go_return_shapes() {
+        assert_eq!(type_by_extension(".no-such-extension"), "");
+
+        let (media_type, params, err) = parse_media_type("not a media type");
+        assert!(media_type.is_empty() && params.is_empty());
+        assert!(matches!(err, Some(Error::MediaType(_))));
+    }
+}
@@ -5,18 +5,30 @@
 //! - Media type parsing and formatting (RFC 2045, RFC 2616, RFC 2231)
 //! - RFC 2047 encoded-word encoding and decoding
 //! - Multipart MIME parsing and writing (RFC 2046, RFC 2388)
-//! - Quoted-printable encoding (RFC 2045)
+//! - Quoted-printable and base64 transfer encodings (RFC 2045)
 //!
 //! All I/O operations are async-first using tokio.
 
+#![forbid(unsafe_code)]
+
+pub mod base64;
+pub mod charset;
 pub mod error;
+pub mod ext;
+pub mod go_compat;
 pub mod grammar;
+pub mod limits;
 pub mod mime_type;
+pub mod mime_resolver;
+pub mod mime_source;
 pub mod media_type;
 pub mod encoded_word;
 pub mod multipart;
 pub mod quotedprintable;
 
+#[cfg(feature = "mhtml")]
+pub mod mhtml;
+
 #[cfg(unix)]
 pub mod platform;
 
@@ -24,7 +36,12 @@ pub mod platform;
 pub mod platform;
 
 // Re-export commonly used types
-pub use error::{Error, Result};
-pub use mime_type::{type_by_extension, extensions_by_type, add_extension_type};
-pub use media_type::{parse_media_type, format_media_type};
-pub use encoded_word::{WordEncoder, WordDecoder};
+pub use error::{Error, LimitExceeded, LimitKind, Result, TimeoutKind};
+pub use limits::Limits;
+pub use mime_type::{
+    type_by_extension, extensions_by_type, add_extension_type, add_extension_types_from_stream,
+    extensions_for_file, preferred_extension,
+};
+pub use media_type::{parse_media_type, format_media_type, MediaType};
+pub use encoded_word::{EncodeContext, ReplacementPolicy, WordEncoder, WordDecoder};
+pub use ext::MimeReadExt;
@@ -0,0 +1,75 @@
+//! One set of safety limits for every parser in the crate.
+//!
+//! Each parser has its own options, but an application usually wants a
+//! single policy: the same header caps for a multipart part and for a
+//! message read by `MhtmlArchive`, the same line length
+//! for headers and quoted-printable bodies. A [`Limits`] value states it
+//! once and is handed to each of them:
+//!
+//! - [`ReaderOptions::limits`](crate::multipart::ReaderOptions::limits), for
+//!   multipart readers and so [`Reader::read_form`](crate::multipart::Reader::read_form)
+//! - [`quotedprintable::Reader::with_limits`](crate::quotedprintable::Reader::with_limits)
+//! - [`WordDecoder::limits`](crate::WordDecoder::limits)
+//! - `MhtmlArchive::read_with_limits`, with the `mhtml` feature
+//!
+//! Every limit is unset by default, leaving each parser's own default in
+//! place, and each parser uses only the limits that apply to it.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::multipart::{Reader, ReaderOptions};
+//! use yamime::{quotedprintable, Limits, WordDecoder};
+//!
+//! let limits = Limits {
+//!     max_header_bytes: Some(16 * 1024),
+//!     max_parts: Some(100),
+//!     max_total: Some(8 << 20),
+//!     max_decoded_size: Some(8 << 20),
+//!     max_line_len: Some(998),
+//!     ..Default::default()
+//! };
+//!
+//! let options = ReaderOptions::default().limits(&limits);
+//! let reader = Reader::with_options(&b""[..], "b", options);
+//! let body = quotedprintable::Reader::with_limits(&b""[..], &limits);
+//! let decoder = WordDecoder::new().limits(&limits);
+//! # let _ = (reader, body, decoder);
+//! ```
+
+/// Safety limits shared by the crate's parsers.
+///
+/// Limits exceeded fail with [`Error::MessageTooLarge`](crate::Error::MessageTooLarge)
+/// carrying the [`LimitKind`](crate::LimitKind) named for each field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size in bytes of a header block
+    /// ([`HeaderSize`](crate::LimitKind::HeaderSize)).
+    pub max_header_bytes: Option<usize>,
+    /// Maximum number of headers in a header block
+    /// ([`HeaderCount`](crate::LimitKind::HeaderCount)).
+    pub max_headers: Option<usize>,
+    /// Maximum size in bytes of a part body
+    /// ([`PartSize`](crate::LimitKind::PartSize)).
+    pub max_part_size: Option<u64>,
+    /// Maximum number of parts in a message
+    /// ([`PartCount`](crate::LimitKind::PartCount)).
+    pub max_parts: Option<usize>,
+    /// Maximum size in bytes of a whole multipart message
+    /// ([`MessageSize`](crate::LimitKind::MessageSize)).
+    pub max_total: Option<u64>,
+    /// Maximum size in bytes of the decoded output of a quoted-printable
+    /// body ([`DecodedSize`](crate::LimitKind::DecodedSize)).
+    pub max_decoded_size: Option<u64>,
+    /// Maximum depth of nested multipart bodies; exceeding it fails with
+    /// [`Error::NestingTooDeep`](crate::Error::NestingTooDeep).
+    pub max_nesting: Option<usize>,
+    /// Maximum length in bytes of a line, excluding its line ending: an
+    /// unfolded header line, or a line of quoted-printable text
+    /// ([`HeaderLineLength`](crate::LimitKind::HeaderLineLength) and
+    /// [`LineLength`](crate::LimitKind::LineLength)).
+    pub max_line_len: Option<usize>,
+    /// Maximum decoded size in bytes of one RFC 2047 encoded-word
+    /// ([`DecodedSize`](crate::LimitKind::DecodedSize)).
+    pub max_decoded_word: Option<usize>,
+}
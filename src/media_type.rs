@@ -65,6 +65,101 @@ pub fn parse_media_type(v: &str) -> Result<(String, HashMap<String, String>)> {
     Ok((mediatype, params))
 }
 
+/// A parsed media type with its parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    /// The lowercase media type, e.g. "text/html".
+    pub media_type: String,
+    /// The parameters, keyed by lowercase name.
+    pub params: HashMap<String, String>,
+}
+
+impl MediaType {
+    /// Parses a single media type value, see [`parse_media_type`].
+    pub fn parse(v: &str) -> Result<Self> {
+        let (media_type, params) = parse_media_type(v)?;
+        Ok(Self { media_type, params })
+    }
+}
+
+/// Parses a comma-separated list of media types, as found in headers like Accept.
+///
+/// Commas inside quoted strings and comments do not split elements, and
+/// comments are removed. Empty or malformed elements are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::media_type::parse_list;
+///
+/// let list = parse_list("text/html, application/x-foo; name=\"a,b\" (legacy, v1), */*;q=0.1");
+/// assert_eq!(list.len(), 3);
+/// assert_eq!(list[1].media_type, "application/x-foo");
+/// assert_eq!(list[1].params.get("name"), Some(&"a,b".to_string()));
+/// assert_eq!(list[2].params.get("q"), Some(&"0.1".to_string()));
+/// ```
+pub fn parse_list(v: &str) -> Vec<MediaType> {
+    split_list(v)
+        .iter()
+        .filter_map(|element| MediaType::parse(element).ok())
+        .collect()
+}
+
+/// Splits a header list on commas outside quoted strings and comments,
+/// dropping comments and empty elements.
+fn split_list(v: &str) -> Vec<String> {
+    let mut elements = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut comment_depth = 0usize;
+    let mut chars = v.chars();
+
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            current.push(ch);
+            match ch {
+                '\\' => {
+                    if let Some(escaped) = chars.next() {
+                        current.push(escaped);
+                    }
+                }
+                '"' => in_quotes = false,
+                _ => {}
+            }
+        } else if comment_depth > 0 {
+            match ch {
+                '\\' => {
+                    chars.next();
+                }
+                '(' => comment_depth += 1,
+                ')' => comment_depth -= 1,
+                _ => {}
+            }
+        } else {
+            match ch {
+                '"' => {
+                    in_quotes = true;
+                    current.push(ch);
+                }
+                '(' => comment_depth = 1,
+                ',' => {
+                    if !current.trim().is_empty() {
+                        elements.push(current.trim().to_string());
+                    }
+                    current.clear();
+                }
+                _ => current.push(ch),
+            }
+        }
+    }
+
+    if !current.trim().is_empty() {
+        elements.push(current.trim().to_string());
+    }
+
+    elements
+}
+
 /// Serializes a media type and parameters as a media type conforming to RFC 2045 and RFC 2616.
 ///
 /// The type and parameter names are written in lower-case.
@@ -81,6 +176,55 @@ pub fn parse_media_type(v: &str) -> Result<(String, HashMap<String, String>)> {
 /// assert_eq!(formatted, "text/html; charset=utf-8");
 /// ```
 pub fn format_media_type(t: &str, params: &HashMap<String, String>) -> String {
+    format_media_type_with(t, params, &FormatOptions::default())
+}
+
+/// Options for [`format_media_type_with`].
+///
+/// The defaults format like [`format_media_type`]: values as given, and
+/// parameters in alphabetical order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Write well-known case-insensitive values in lowercase: `charset`,
+    /// and the RFC 3676 `format` and `delsp`. Other values, notably
+    /// `boundary`, are case-sensitive and kept as given.
+    pub normalize_values: bool,
+
+    /// Order parameters canonically: `charset` first, `boundary` last, the
+    /// rest alphabetically between them.
+    pub canonical_order: bool,
+}
+
+/// Like [`format_media_type`], with normalization set by `options`, so the
+/// same media type is written identically whatever casing or parameter
+/// set it was built from.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::media_type::{format_media_type_with, FormatOptions};
+/// use std::collections::HashMap;
+///
+/// let params: HashMap<String, String> = [
+///     ("Boundary".to_string(), "AbC".to_string()),
+///     ("CHARSET".to_string(), "UTF-8".to_string()),
+///     ("format".to_string(), "Flowed".to_string()),
+/// ]
+/// .into();
+/// let options = FormatOptions {
+///     normalize_values: true,
+///     canonical_order: true,
+/// };
+/// assert_eq!(
+///     format_media_type_with("Multipart/Mixed", &params, &options),
+///     "multipart/mixed; charset=utf-8; format=flowed; boundary=AbC"
+/// );
+/// ```
+pub fn format_media_type_with(
+    t: &str,
+    params: &HashMap<String, String>,
+    options: &FormatOptions,
+) -> String {
     let mut result = String::new();
 
     // Validate and format the media type
@@ -100,10 +244,31 @@ pub fn format_media_type(t: &str, params: &HashMap<String, String>) -> String {
 
     // Sort parameters for consistent output
     let mut keys: Vec<_> = params.keys().collect();
-    keys.sort();
+    if options.canonical_order {
+        keys.sort_by_cached_key(|key| {
+            let lower = key.to_lowercase();
+            let rank = match lower.as_str() {
+                "charset" => 0,
+                "boundary" => 2,
+                _ => 1,
+            };
+            (rank, lower)
+        });
+    } else {
+        keys.sort();
+    }
 
     for key in keys {
         let value = &params[key];
+        let normalized;
+        let value = if options.normalize_values
+            && matches!(key.to_lowercase().as_str(), "charset" | "format" | "delsp")
+        {
+            normalized = value.to_lowercase();
+            &normalized
+        } else {
+            value
+        };
 
         if !is_token(key) {
             return String::new();
@@ -116,17 +281,8 @@ pub fn format_media_type(t: &str, params: &HashMap<String, String>) -> String {
         let needs_encoding = needs_encoding(value);
 
         if needs_encoding {
-            // RFC 2231 encoding
-            result.push_str("*=utf-8''");
-            for &b in value.as_bytes() {
-                if b <= b' ' || b >= 0x7F || b == b'*' || b == b'\'' || b == b'%' || is_tspecial(b as char) {
-                    result.push('%');
-                    result.push(UPPER_HEX[(b >> 4) as usize] as char);
-                    result.push(UPPER_HEX[(b & 0x0F) as usize] as char);
-                } else {
-                    result.push(b as char);
-                }
-            }
+            result.push_str("*=");
+            result.push_str(&encode_rfc2231_value(value));
         } else if is_token(value) {
             result.push('=');
             result.push_str(value);
@@ -146,10 +302,71 @@ pub fn format_media_type(t: &str, params: &HashMap<String, String>) -> String {
     result
 }
 
+/// Encodes an RFC 2231 extended parameter value as `utf-8''` followed by
+/// the percent-encoded value.
+pub(crate) fn encode_rfc2231_value(value: &str) -> String {
+    let mut result = String::from("utf-8''");
+    for &b in value.as_bytes() {
+        if b <= b' ' || b >= 0x7F || b == b'*' || b == b'\'' || b == b'%' || is_tspecial(b as char) {
+            result.push('%');
+            result.push(UPPER_HEX[(b >> 4) as usize] as char);
+            result.push(UPPER_HEX[(b & 0x0F) as usize] as char);
+        } else {
+            result.push(b as char);
+        }
+    }
+    result
+}
+
+/// Decodes an RFC 2231 extended parameter value (`charset'language'value`).
+///
+/// Supports the UTF-8, ISO-8859-1 and US-ASCII charsets; returns `None` for
+/// other charsets or malformed values.
+pub(crate) fn decode_rfc2231_value(v: &str) -> Option<String> {
+    let mut fields = v.splitn(3, '\'');
+    let charset = fields.next()?;
+    let _language = fields.next()?;
+    let encoded = fields.next()?;
+
+    let bytes = percent_decode(encoded)?;
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&b| b as char).collect())
+    } else if charset.eq_ignore_ascii_case("us-ascii") && bytes.is_ascii() {
+        String::from_utf8(bytes).ok()
+    } else {
+        None
+    }
+}
+
+/// Decodes %XX escapes.
+pub(crate) fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            result.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Some(result)
+}
+
 /// Checks if a string needs encoding per RFC 2231.
 fn needs_encoding(s: &str) -> bool {
     for ch in s.chars() {
-        if (ch < ' ' || ch > '~') && ch != '\t' {
+        if !(' '..='~').contains(&ch) && ch != '\t' {
             return true;
         }
     }
@@ -181,6 +398,49 @@ mod tests {
         assert_eq!(params.get("charset"), Some(&"utf-8".to_string()));
     }
 
+    #[test]
+    fn test_parse_list() {
+        let list = parse_list("text/html;level=1, text/plain; charset=\"a,b\\\"c\", ,image/*");
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[0].media_type, "text/html");
+        assert_eq!(list[0].params.get("level"), Some(&"1".to_string()));
+        assert_eq!(list[1].media_type, "text/plain");
+        assert_eq!(list[1].params.get("charset"), Some(&"a,b\\\"c".to_string()));
+        assert_eq!(list[2].media_type, "image/*");
+    }
+
+    #[test]
+    fn test_parse_list_comments_and_invalid() {
+        let list = parse_list("text/html (with, (nested) comment), bogus, application/json");
+        assert_eq!(
+            list,
+            vec![
+                MediaType::parse("text/html").unwrap(),
+                MediaType::parse("application/json").unwrap(),
+            ]
+        );
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_decode_rfc2231_value() {
+        assert_eq!(
+            decode_rfc2231_value("UTF-8''%E2%82%AC%20rates.txt"),
+            Some("€ rates.txt".to_string())
+        );
+        assert_eq!(
+            decode_rfc2231_value("iso-8859-1'en'caf%E9"),
+            Some("café".to_string())
+        );
+        assert_eq!(decode_rfc2231_value("us-ascii''plain"), Some("plain".to_string()));
+
+        // Malformed or unsupported
+        assert_eq!(decode_rfc2231_value("no-quotes"), None);
+        assert_eq!(decode_rfc2231_value("utf-8''%E2%82"), None);
+        assert_eq!(decode_rfc2231_value("utf-8''%G1"), None);
+        assert_eq!(decode_rfc2231_value("koi8-r''%C1"), None);
+    }
+
     #[test]
     fn test_format_media_type_simple() {
         let params = HashMap::new();
@@ -196,6 +456,51 @@ mod tests {
         assert_eq!(formatted, "text/html; charset=utf-8");
     }
 
+    #[test]
+    fn test_format_options() {
+        let params: HashMap<String, String> = [
+            ("name", "Report.TXT"),
+            ("boundary", "Q-X"),
+            ("charset", "ISO-8859-1"),
+            ("delsp", "Yes"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        assert_eq!(
+            format_media_type("text/plain", &params),
+            "text/plain; boundary=Q-X; charset=ISO-8859-1; delsp=Yes; name=Report.TXT"
+        );
+
+        let normalized = FormatOptions {
+            normalize_values: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_media_type_with("text/plain", &params, &normalized),
+            "text/plain; boundary=Q-X; charset=iso-8859-1; delsp=yes; name=Report.TXT"
+        );
+
+        let ordered = FormatOptions {
+            canonical_order: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_media_type_with("text/plain", &params, &ordered),
+            "text/plain; charset=ISO-8859-1; delsp=Yes; name=Report.TXT; boundary=Q-X"
+        );
+
+        // Normalization keeps values that need RFC 2231 encoding encoded
+        let params: HashMap<String, String> =
+            [("charset".to_string(), "UTF-8".to_string()), ("title".to_string(), "Ünï".to_string())]
+                .into();
+        assert_eq!(
+            format_media_type_with("text/plain", &params, &normalized),
+            "text/plain; charset=utf-8; title*=utf-8''%C3%9Cn%C3%AF"
+        );
+    }
+
     #[test]
     fn test_format_media_type_quoted() {
         // Test with a value that needs quoting (contains spaces)
@@ -0,0 +1,320 @@
+//! MHTML web archives (RFC 2557).
+//!
+//! Available with the `mhtml` feature. An MHTML (`.mht`) file is a
+//! `multipart/related` message whose parts are the resources of a web page,
+//! each identified by its Content-Location URL. The first part is the root
+//! document.
+
+use crate::encoded_word::{WordDecoder, WordEncoder};
+use crate::error::{Error, Result};
+use crate::limits::Limits;
+use crate::media_type::{format_media_type, parse_media_type};
+use crate::multipart::reader::read_mime_header;
+use crate::multipart::writer::generate_boundary;
+use crate::multipart::{MimeHeader, ReaderOptions, RelatedReader, Writer};
+use crate::quotedprintable;
+use base64::Engine;
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// A resource stored in an MHTML archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    /// The resource URL (Content-Location), or `cid:` URL for parts only
+    /// identified by a Content-ID.
+    pub location: String,
+    /// The full Content-Type value, e.g. `text/html; charset=utf-8`.
+    pub content_type: String,
+    /// The decoded resource content.
+    pub data: Vec<u8>,
+}
+
+impl Resource {
+    /// Creates a resource.
+    pub fn new(
+        location: impl Into<String>,
+        content_type: impl Into<String>,
+        data: impl Into<Vec<u8>>,
+    ) -> Self {
+        Self {
+            location: location.into(),
+            content_type: content_type.into(),
+            data: data.into(),
+        }
+    }
+}
+
+/// An MHTML web archive.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::mhtml::{MhtmlArchive, Resource};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut archive = MhtmlArchive::new();
+/// archive.resources.push(Resource::new(
+///     "http://example.com/",
+///     "text/html; charset=utf-8",
+///     "<img src=\"logo.png\">",
+/// ));
+/// archive.resources.push(Resource::new(
+///     "http://example.com/logo.png",
+///     "image/png",
+///     vec![0x89, b'P', b'N', b'G'],
+/// ));
+///
+/// let mut file = Vec::new();
+/// archive.write(&mut file).await?;
+///
+/// let read = MhtmlArchive::read(&file[..]).await?;
+/// assert_eq!(read.root().unwrap().location, "http://example.com/");
+/// assert_eq!(read.get("http://example.com/logo.png").unwrap().data, b"\x89PNG");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MhtmlArchive {
+    /// The archive's Subject, usually the page title.
+    pub subject: Option<String>,
+    /// The resources, root document first.
+    pub resources: Vec<Resource>,
+}
+
+impl MhtmlArchive {
+    /// Creates an empty archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the root document, i.e. the first resource.
+    pub fn root(&self) -> Option<&Resource> {
+        self.resources.first()
+    }
+
+    /// Returns the resource with the given URL.
+    pub fn get(&self, location: &str) -> Option<&Resource> {
+        self.resources.iter().find(|r| r.location == location)
+    }
+
+    /// Returns the resources keyed by URL.
+    ///
+    /// If several resources share a URL, the first one wins.
+    pub fn by_location(&self) -> HashMap<&str, &Resource> {
+        let mut map = HashMap::new();
+        for resource in &self.resources {
+            map.entry(resource.location.as_str()).or_insert(resource);
+        }
+        map
+    }
+
+    /// Reads an archive from an `.mht` file.
+    ///
+    /// The root document is the part named by the `start` parameter, or the
+    /// first part. Parts are decoded according to their Content-Transfer-Encoding.
+    /// Parts with neither a Content-Location nor a Content-ID are skipped.
+    pub async fn read<R: AsyncRead + Unpin>(reader: R) -> Result<Self> {
+        Self::read_with_limits(reader, &Limits::default()).await
+    }
+
+    /// Reads an archive like [`read`](Self::read), applying `limits` to the
+    /// message header, the parts and the encoded-words of the subject.
+    pub async fn read_with_limits<R: AsyncRead + Unpin>(reader: R, limits: &Limits) -> Result<Self> {
+        let options = ReaderOptions::default().limits(limits);
+        let mut input = BufReader::new(reader);
+        let header = read_mime_header(&mut input, &options).await?;
+
+        let content_type = header
+            .get("content-type")
+            .ok_or_else(|| Error::Multipart("MHTML file has no Content-Type".to_string()))?;
+        let mut parts = RelatedReader::with_options(input, content_type, options)?;
+
+        let decoder = WordDecoder::new().limits(limits);
+        let subject = header
+            .get("subject")
+            .map(|s| decoder.decode_header(s).unwrap_or_else(|_| s.to_string()));
+
+        let mut resources = Vec::new();
+        while let Some(mut part) = parts.next_part().await? {
+            let location = match part.content_location() {
+                Some(location) => location,
+                None => match part.content_id() {
+                    Some(id) => format!("cid:{}", id),
+                    None => continue,
+                },
+            };
+            let content_type = part
+                .header
+                .get("content-type")
+                .unwrap_or("text/plain")
+                .to_string();
+            let encoding = part
+                .header
+                .get("content-transfer-encoding")
+                .map(|e| e.trim().to_ascii_lowercase());
+
+            // Quoted-printable bodies are decoded by the reader
+            let body = part.bytes().await?.to_vec();
+            let data = match encoding.as_deref() {
+                Some("base64") => {
+                    let compact: Vec<u8> = body
+                        .into_iter()
+                        .filter(|b| !b.is_ascii_whitespace())
+                        .collect();
+                    base64::engine::general_purpose::STANDARD
+                        .decode(compact)
+                        .map_err(|e| {
+                            Error::Encoding(format!("invalid base64 in {}: {}", location, e))
+                        })?
+                }
+                _ => body,
+            };
+
+            resources.push(Resource {
+                location,
+                content_type,
+                data,
+            });
+        }
+
+        Ok(Self { subject, resources })
+    }
+
+    /// Writes the archive as an `.mht` file.
+    ///
+    /// Text resources are quoted-printable encoded and everything else is
+    /// base64 encoded, so the output is 7-bit clean.
+    pub async fn write<W: AsyncWrite + Unpin>(&self, mut writer: W) -> Result<()> {
+        let boundary = generate_boundary();
+        let root_type = self
+            .root()
+            .and_then(|r| parse_media_type(&r.content_type).ok())
+            .map(|(t, _)| t)
+            .unwrap_or_else(|| "text/html".to_string());
+
+        let mut params = HashMap::new();
+        params.insert("type".to_string(), root_type);
+        params.insert("boundary".to_string(), boundary.clone());
+
+        let mut header = String::new();
+        if let Some(subject) = &self.subject {
+            header.push_str(&format!(
+                "Subject: {}\r\n",
+                WordEncoder::QEncoding.encode("utf-8", subject)
+            ));
+        }
+        header.push_str("MIME-Version: 1.0\r\n");
+        header.push_str(&format!(
+            "Content-Type: {}\r\n\r\n",
+            format_media_type("multipart/related", &params)
+        ));
+        writer.write_all(header.as_bytes()).await?;
+
+        let mut parts = Writer::builder().boundary(boundary).build(&mut writer)?;
+        for resource in &self.resources {
+            let is_text = resource.content_type.trim_start().to_ascii_lowercase().starts_with("text/");
+            let encoding = if is_text { "quoted-printable" } else { "base64" };
+
+            let mut header = MimeHeader::new();
+            header.insert("Content-Type", resource.content_type.as_str());
+            header.insert("Content-Transfer-Encoding", encoding);
+            header.insert("Content-Location", resource.location.as_str());
+
+            let body = if is_text {
+                let mut encoded = Vec::new();
+                let mut qp = quotedprintable::Writer::new(&mut encoded);
+                qp.write_all(&resource.data).await?;
+                qp.close().await?;
+                encoded
+            } else {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&resource.data);
+                let lines: Vec<&[u8]> = encoded.as_bytes().chunks(76).collect();
+                lines.join(&b"\r\n"[..])
+            };
+
+            let mut part = parts.create_part(header).await?;
+            part.write_all(&body).await?;
+        }
+        parts.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_browser_archive() {
+        let data = b"From: <Saved by Blink>\r\n\
+Subject: =?utf-8?q?Caf=C3=A9?=\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/related;\r\n\
+\ttype=\"text/html\";\r\n\
+\tboundary=\"----MultipartBoundary--abc----\"\r\n\
+\r\n\
+------MultipartBoundary--abc----\r\n\
+Content-Type: text/html\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+Content-Location: https://example.com/\r\n\
+\r\n\
+<p class=3D\"x\">caf=C3=A9</p>=\r\n\
+<img src=3D\"cid:logo\">\r\n\
+------MultipartBoundary--abc----\r\n\
+Content-Type: image/gif\r\n\
+Content-Transfer-Encoding: base64\r\n\
+Content-ID: <logo>\r\n\
+\r\n\
+R0lG\r\n\
+ODlh\r\n\
+------MultipartBoundary--abc----\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+no location\r\n\
+------MultipartBoundary--abc------\r\n";
+
+        let archive = MhtmlArchive::read(&data[..]).await.unwrap();
+        assert_eq!(archive.subject.as_deref(), Some("Café"));
+        assert_eq!(archive.resources.len(), 2);
+
+        let root = archive.root().unwrap();
+        assert_eq!(root.location, "https://example.com/");
+        assert_eq!(
+            String::from_utf8(root.data.clone()).unwrap(),
+            "<p class=\"x\">café</p><img src=\"cid:logo\">"
+        );
+        assert_eq!(archive.by_location()["cid:logo"].data, b"GIF89a");
+    }
+
+    #[tokio::test]
+    async fn test_write_round_trip() {
+        let mut archive = MhtmlArchive::new();
+        archive.subject = Some("Übersicht".to_string());
+        archive.resources.push(Resource::new(
+            "http://example.com/index.html",
+            "text/html; charset=utf-8",
+            "<h1>Übersicht</h1>\r\n".repeat(10),
+        ));
+        archive.resources.push(Resource::new(
+            "http://example.com/data.bin",
+            "application/octet-stream",
+            (0..=255u8).collect::<Vec<_>>(),
+        ));
+
+        let mut file = Vec::new();
+        archive.write(&mut file).await.unwrap();
+        assert!(file.is_ascii());
+        let text = String::from_utf8_lossy(&file);
+        assert!(text.starts_with("Subject: =?utf-8?q?=C3=9Cbersicht?=\r\nMIME-Version: 1.0\r\n"));
+        assert!(text.contains("multipart/related; boundary="));
+        assert!(text.contains("type=\"text/html\""));
+
+        let read = MhtmlArchive::read(&file[..]).await.unwrap();
+        assert_eq!(read, archive);
+    }
+
+    #[tokio::test]
+    async fn test_read_rejects_non_related() {
+        let data = b"Content-Type: multipart/mixed; boundary=b\r\n\r\n--b--\r\n";
+        assert!(MhtmlArchive::read(&data[..]).await.is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! MIME type lookup through an explicit chain of sources.
+//!
+//! [`type_by_extension`](crate::type_by_extension) consults one global
+//! table with a fixed precedence: builtin types, then the platform's
+//! databases, then [`add_extension_type`](crate::add_extension_type)
+//! additions. A [`MimeResolver`] instead tries the stages it is built with,
+//! in the order they are added, so an application decides whether its own
+//! mappings, the system's or the crate's win, and whether file contents are
+//! consulted at all.
+
+use crate::mime_source::{MimeSource, SystemSource};
+use crate::mime_type;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A content-based type detector, returning `None` if it does not
+/// recognize the data.
+pub type Sniffer = Arc<dyn Fn(&[u8]) -> Option<String> + Send + Sync>;
+
+/// What to resolve a MIME type for.
+#[derive(Debug, Clone, Copy)]
+pub enum Subject<'a> {
+    /// A file name or path, resolved by its extension.
+    Path(&'a Path),
+    /// File contents, or a prefix of them, resolved by sniffing.
+    Bytes(&'a [u8]),
+    /// A path with its contents: extension stages use the path, sniffing
+    /// the contents.
+    File(&'a Path, &'a [u8]),
+}
+
+impl<'a> From<&'a Path> for Subject<'a> {
+    fn from(path: &'a Path) -> Self {
+        Subject::Path(path)
+    }
+}
+
+impl<'a> From<&'a str> for Subject<'a> {
+    fn from(path: &'a str) -> Self {
+        Subject::Path(Path::new(path))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Subject<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Subject::Bytes(data)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for Subject<'a> {
+    fn from(data: &'a [u8; N]) -> Self {
+        Subject::Bytes(data)
+    }
+}
+
+#[derive(Clone)]
+enum Stage {
+    Custom(Table),
+    System(Table),
+    Builtin,
+    Sniff(Sniffer),
+}
+
+impl Stage {
+    fn is_same_kind(&self, other: &Stage) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Extension mappings loaded from a [`MimeSource`], first mapping winning.
+#[derive(Debug, Clone, Default)]
+struct Table {
+    exact: HashMap<String, String>,
+    lower: HashMap<String, String>,
+}
+
+impl Table {
+    fn load(source: &dyn MimeSource) -> Self {
+        let mut table = Table::default();
+        // Like the global table, unreadable sources contribute nothing
+        let _ = source.load(&mut |ext, mime| {
+            table
+                .exact
+                .entry(ext.to_string())
+                .or_insert_with(|| mime.to_string());
+            table
+                .lower
+                .entry(ext.to_lowercase())
+                .or_insert_with(|| mime.to_string());
+        });
+        table
+    }
+
+    fn get(&self, ext: &str) -> Option<&str> {
+        self.exact
+            .get(ext)
+            .or_else(|| self.lower.get(&ext.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+/// Resolves MIME types through a configurable chain of stages.
+///
+/// Each `with_*` method appends a stage, or moves it to the end if already
+/// present; `with_system(false)` and `with_builtin(false)` remove theirs.
+/// [`resolve`](Self::resolve) returns the answer of the first stage that
+/// has one. A new resolver has no stages and resolves nothing.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use yamime::mime_resolver::{self, MimeResolver};
+///
+/// let mut custom = HashMap::new();
+/// custom.insert(".log".to_string(), "text/x-app-log".to_string());
+///
+/// let resolver = MimeResolver::new()
+///     .with_custom(custom)
+///     .with_builtin(true)
+///     .with_sniffing(mime_resolver::sniff);
+///
+/// assert_eq!(resolver.resolve("server.log").as_deref(), Some("text/x-app-log"));
+/// assert_eq!(resolver.resolve("photo.png").as_deref(), Some("image/png"));
+/// assert_eq!(resolver.resolve(b"%PDF-1.7").as_deref(), Some("application/pdf"));
+/// assert_eq!(resolver.resolve("README").as_deref(), None);
+/// ```
+#[derive(Clone, Default)]
+pub struct MimeResolver {
+    stages: Vec<Stage>,
+}
+
+impl fmt::Debug for MimeResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages: Vec<&str> = self
+            .stages
+            .iter()
+            .map(|stage| match stage {
+                Stage::Custom(_) => "custom",
+                Stage::System(_) => "system",
+                Stage::Builtin => "builtin",
+                Stage::Sniff(_) => "sniff",
+            })
+            .collect();
+        f.debug_struct("MimeResolver").field("stages", &stages).finish()
+    }
+}
+
+impl MimeResolver {
+    /// Creates a resolver with no stages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stage looking extensions up in mappings from `source`, read
+    /// now.
+    ///
+    /// Unlike the other stages, several custom stages may be added; each is
+    /// consulted in turn.
+    pub fn with_custom(mut self, source: impl MimeSource) -> Self {
+        self.stages.push(Stage::Custom(Table::load(&source)));
+        self
+    }
+
+    /// Adds or removes a stage looking extensions up in the platform's
+    /// databases, read now.
+    pub fn with_system(self, enabled: bool) -> Self {
+        let stage = enabled.then(|| Stage::System(Table::load(&SystemSource)));
+        self.set_stage(Stage::System(Table::default()), stage)
+    }
+
+    /// Adds or removes a stage looking extensions up in the crate's
+    /// builtin table.
+    ///
+    /// Unlike [`type_by_extension`](crate::type_by_extension), this ignores
+    /// [`add_extension_type`](crate::add_extension_type) and
+    /// [`reload_mime_types`](crate::mime_type::reload_mime_types).
+    pub fn with_builtin(self, enabled: bool) -> Self {
+        self.set_stage(Stage::Builtin, enabled.then_some(Stage::Builtin))
+    }
+
+    /// Adds or replaces the stage detecting types from contents, such as
+    /// [`sniff`].
+    pub fn with_sniffing<F>(self, detector: F) -> Self
+    where
+        F: Fn(&[u8]) -> Option<String> + Send + Sync + 'static,
+    {
+        let stage = Stage::Sniff(Arc::new(detector));
+        self.set_stage(stage.clone(), Some(stage))
+    }
+
+    /// Removes any stage of the same kind as `kind`, then appends `stage`.
+    fn set_stage(mut self, kind: Stage, stage: Option<Stage>) -> Self {
+        self.stages.retain(|s| !s.is_same_kind(&kind));
+        self.stages.extend(stage);
+        self
+    }
+
+    /// Returns the MIME type of `subject` from the first stage that knows
+    /// it.
+    ///
+    /// Extension stages are skipped for bytes and for paths without an
+    /// extension; sniffing is skipped for paths alone.
+    pub fn resolve<'a>(&self, subject: impl Into<Subject<'a>>) -> Option<String> {
+        let (path, data) = match subject.into() {
+            Subject::Path(path) => (Some(path), None),
+            Subject::Bytes(data) => (None, Some(data)),
+            Subject::File(path, data) => (Some(path), Some(data)),
+        };
+        let ext = path
+            .and_then(Path::extension)
+            .map(|ext| format!(".{}", ext.to_string_lossy()));
+
+        self.stages.iter().find_map(|stage| match (stage, &ext, data) {
+            (Stage::Custom(table) | Stage::System(table), Some(ext), _) => {
+                table.get(ext).map(str::to_string)
+            }
+            (Stage::Builtin, Some(ext), _) => mime_type::builtin_type(ext).map(str::to_string),
+            (Stage::Sniff(detector), _, Some(data)) => detector(data),
+            _ => None,
+        })
+    }
+}
+
+/// Detects common formats from their leading magic bytes.
+///
+/// Recognizes PNG, JPEG, GIF, WebP, PDF, ZIP, gzip, 7z, WebAssembly and
+/// documents starting with `<?xml` or an HTML doctype or tag.
+pub fn sniff(data: &[u8]) -> Option<String> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b\x08", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\0asm", "application/wasm"),
+    ];
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| data.starts_with(sig)) {
+        return Some(mime.to_string());
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("image/webp".to_string());
+    }
+
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let text = &data[start..];
+    let starts_with_ignore_case = |prefix: &[u8]| {
+        text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    if starts_with_ignore_case(b"<?xml") {
+        Some("text/xml; charset=utf-8".to_string())
+    } else if starts_with_ignore_case(b"<!doctype html") || starts_with_ignore_case(b"<html") {
+        Some("text/html; charset=utf-8".to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(e, m)| (e.to_string(), m.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let overrides = custom(&[(".html", "application/xhtml+xml")]);
+
+        let resolver = MimeResolver::new()
+            .with_custom(overrides.clone())
+            .with_builtin(true);
+        assert_eq!(
+            resolver.resolve("index.HTML").as_deref(),
+            Some("application/xhtml+xml")
+        );
+
+        // Trusting the builtin table first
+        let resolver = MimeResolver::new().with_builtin(true).with_custom(overrides);
+        assert_eq!(
+            resolver.resolve("index.html").as_deref(),
+            Some("text/html; charset=utf-8")
+        );
+
+        // Re-adding moves a stage to the end; disabling removes it
+        let resolver = resolver.with_builtin(true);
+        assert_eq!(
+            resolver.resolve("index.html").as_deref(),
+            Some("application/xhtml+xml")
+        );
+        let resolver = resolver.with_builtin(false);
+        assert_eq!(resolver.resolve("a.png"), None);
+        assert_eq!(format!("{:?}", resolver), "MimeResolver { stages: [\"custom\"] }");
+
+        assert_eq!(MimeResolver::new().resolve("a.png"), None);
+        // The platform tables vary; only check the stage is consulted
+        let _ = MimeResolver::new().with_system(true).resolve("a.txt");
+    }
+
+    #[test]
+    fn test_sniffing() {
+        let resolver = MimeResolver::new()
+            .with_builtin(true)
+            .with_sniffing(sniff);
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+
+        // The extension wins when its stage comes first
+        let subject = Subject::File(Path::new("upload.jpg"), png);
+        assert_eq!(resolver.resolve(subject).as_deref(), Some("image/jpeg"));
+        let subject = Subject::File(Path::new("upload"), png);
+        assert_eq!(resolver.resolve(subject).as_deref(), Some("image/png"));
+        assert_eq!(resolver.resolve("upload"), None);
+
+        let sniff_first = MimeResolver::new().with_sniffing(sniff).with_builtin(true);
+        let subject = Subject::File(Path::new("upload.jpg"), png);
+        assert_eq!(sniff_first.resolve(subject).as_deref(), Some("image/png"));
+
+        // A custom detector replaces the previous one
+        let resolver = resolver.with_sniffing(|data: &[u8]| {
+            data.starts_with(b"BEGIN:VCARD").then(|| "text/vcard".to_string())
+        });
+        assert_eq!(resolver.resolve(b"BEGIN:VCARD\r\n").as_deref(), Some("text/vcard"));
+        assert_eq!(resolver.resolve(&png[..]), None);
+    }
+
+    #[test]
+    fn test_sniff_signatures() {
+        assert_eq!(sniff(b"GIF89a...").as_deref(), Some("image/gif"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 ").as_deref(), Some("image/webp"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt ").as_deref(), None);
+        assert_eq!(
+            sniff(b"\n  <!DOCTYPE HTML><html>").as_deref(),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(
+            sniff(b"<?xml version=\"1.0\"?>").as_deref(),
+            Some("text/xml; charset=utf-8")
+        );
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(b"plain text"), None);
+    }
+}
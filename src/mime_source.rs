@@ -0,0 +1,262 @@
+//! Sources of extension to MIME type mappings.
+//!
+//! The MIME type table starts from the builtin types and is then augmented
+//! from a [`MimeSource`]. By default that is [`SystemSource`], which reads the
+//! platform's databases; [`FileSource`] reads the same file formats from
+//! arbitrary paths, so the loading logic can be exercised on any OS.
+
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// A source of extension to MIME type mappings.
+///
+/// Sources report mappings in priority order: when an extension is reported
+/// more than once, or is already a builtin type, the first mapping wins.
+pub trait MimeSource: Send + Sync {
+    /// Calls `add` with each `(extension, mime_type)` mapping, where the
+    /// extension includes its leading dot.
+    fn load(&self, add: &mut dyn FnMut(&str, &str)) -> Result<()>;
+}
+
+/// The platform's MIME databases.
+///
+/// On Unix these are the FreeDesktop globs2 files and mime.types files
+/// listed in [`mime_type`](crate::mime_type); on Windows, the registry.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemSource;
+
+impl MimeSource for SystemSource {
+    fn load(&self, add: &mut dyn FnMut(&str, &str)) -> Result<()> {
+        #[cfg(any(unix, windows))]
+        {
+            crate::platform::load(add)
+        }
+
+        #[cfg(not(any(unix, windows)))]
+        {
+            // Unsupported platform, use builtin types only
+            let _ = add;
+            Ok(())
+        }
+    }
+}
+
+/// In-memory mappings from extension, with its leading dot, to MIME type.
+impl MimeSource for HashMap<String, String> {
+    fn load(&self, add: &mut dyn FnMut(&str, &str)) -> Result<()> {
+        for (ext, mime) in self {
+            add(ext, mime);
+        }
+        Ok(())
+    }
+}
+
+/// MIME databases read from the given files, in the Unix formats.
+///
+/// Like the Unix system loader, the globs2 files are tried in order and
+/// the first readable one is used exclusively; only if none can be read are
+/// all the mime.types files loaded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yamime::mime_source::FileSource;
+///
+/// let source = FileSource::new().mime_types("config/mime.types");
+/// yamime::mime_type::reload_mime_types(&source).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileSource {
+    globs: Vec<PathBuf>,
+    mime_types: Vec<PathBuf>,
+}
+
+impl FileSource {
+    /// Creates a source with no files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a FreeDesktop globs2 file (`weight:mimetype:glob` lines).
+    pub fn globs(mut self, path: impl Into<PathBuf>) -> Self {
+        self.globs.push(path.into());
+        self
+    }
+
+    /// Adds a mime.types file (`mimetype ext1 ext2 ...` lines).
+    pub fn mime_types(mut self, path: impl Into<PathBuf>) -> Self {
+        self.mime_types.push(path.into());
+        self
+    }
+}
+
+impl MimeSource for FileSource {
+    fn load(&self, add: &mut dyn FnMut(&str, &str)) -> Result<()> {
+        load_files(&self.globs, &self.mime_types, add);
+        Ok(())
+    }
+}
+
+/// Loads globs2 files, falling back to mime.types files; unreadable files
+/// are skipped.
+pub(crate) fn load_files<P: AsRef<Path>>(
+    globs: &[P],
+    mime_types: &[P],
+    add: &mut dyn FnMut(&str, &str),
+) {
+    for filename in globs {
+        if load_globs_file(filename.as_ref(), add).is_ok() {
+            // Stop checking more files if mimetype database is found
+            return;
+        }
+    }
+
+    for filename in mime_types {
+        let _ = load_mime_types_file(filename.as_ref(), add);
+    }
+}
+
+/// Load MIME types from a globs2 file.
+///
+/// Format: `weight:mimetype:glob[:morefields...]`
+/// Example: `50:text/plain:*.txt`
+///
+/// The file is sorted by descending weight, so reporting entries in file
+/// order lets the highest weight win.
+///
+/// See https://specifications.freedesktop.org/shared-mime-info-spec/shared-mime-info-spec-0.21.html
+fn load_globs_file(filename: &Path, add: &mut dyn FnMut(&str, &str)) -> Result<()> {
+    let file = File::open(filename)?;
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line?;
+
+        // Each line should be of format: weight:mimetype:glob[:morefields...]
+        let fields: Vec<&str> = line.split(':').collect();
+
+        // Need at least 3 fields, and valid weight/glob
+        if fields.len() < 3 || fields[0].is_empty() || fields[2].len() < 3 {
+            continue;
+        }
+
+        // Skip comments
+        if fields[0].starts_with('#') {
+            continue;
+        }
+
+        // Only process simple extensions (*.ext)
+        if !fields[2].starts_with("*.") {
+            continue;
+        }
+
+        let extension = &fields[2][1..]; // Remove leading *
+
+        // Skip globs with wildcards (we only handle simple extensions)
+        if extension.contains(&['?', '*', '['][..]) {
+            continue;
+        }
+
+        add(extension, fields[1]);
+    }
+
+    Ok(())
+}
+
+/// Load MIME types from a mime.types file.
+///
+/// Format: `mimetype ext1 ext2 ext3 ...`
+/// Example: `text/plain txt text`
+fn load_mime_types_file(filename: &Path, add: &mut dyn FnMut(&str, &str)) -> Result<()> {
+    let file = File::open(filename)?;
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        // Need at least type and one extension
+        if fields.len() <= 1 {
+            continue;
+        }
+
+        // Skip comments
+        if fields[0].starts_with('#') {
+            continue;
+        }
+
+        let mime_type = fields[0];
+
+        // Process all extensions
+        for ext in &fields[1..] {
+            // Stop at comments
+            if ext.starts_with('#') {
+                break;
+            }
+
+            // Add dot prefix if missing
+            let extension = if ext.starts_with('.') {
+                ext.to_string()
+            } else {
+                format!(".{}", ext)
+            };
+
+            add(&extension, mime_type);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/mime")
+            .join(name)
+    }
+
+    fn collect(source: &dyn MimeSource) -> Vec<(String, String)> {
+        let mut mappings = Vec::new();
+        source
+            .load(&mut |ext, mime| mappings.push((ext.to_string(), mime.to_string())))
+            .unwrap();
+        mappings
+    }
+
+    #[test]
+    fn test_globs_file() {
+        let mappings = collect(&FileSource::new().globs(fixture("globs2")));
+        assert_eq!(
+            mappings,
+            [
+                (".foo", "application/x-foo"),
+                (".foo", "text/x-foo"),
+                (".bar", "application/x-bar"),
+            ]
+            .map(|(e, m)| (e.to_string(), m.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_globs_take_precedence_over_mime_types() {
+        let source = FileSource::new()
+            .globs(fixture("missing-globs2"))
+            .globs(fixture("globs2"))
+            .mime_types(fixture("mime.types"));
+        assert!(collect(&source).iter().all(|(ext, _)| ext != ".baz"));
+
+        let source = FileSource::new()
+            .globs(fixture("missing-globs2"))
+            .mime_types(fixture("mime.types"));
+        let mappings = collect(&source);
+        assert!(mappings.contains(&(".baz".to_string(), "application/x-baz".to_string())));
+        assert!(mappings.contains(&(".qux".to_string(), "application/x-baz".to_string())));
+        assert!(!mappings.iter().any(|(ext, _)| ext == ".ignored"));
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Provides functions to map file extensions to MIME types and vice versa.
 //!
-//! The built-in table is small but on Unix it is augmented by the local system's
+//! The built-in table covers common web, document, archive and media formats;
+//! on Unix it is augmented by the local system's
 //! MIME-info database or mime.types file(s) if available under one or more of these names:
 //! - /usr/local/share/mime/globs2
 //! - /usr/share/mime/globs2
@@ -12,51 +13,179 @@
 //!
 //! On Windows, MIME types are extracted from the registry.
 //!
+//! [`reload_mime_types`] replaces the platform databases with any other
+//! [`MimeSource`].
+//!
 //! Text types have the charset parameter set to "utf-8" by default.
 
 use crate::error::{Error, Result};
 use crate::media_type::{format_media_type, parse_media_type};
+use crate::mime_source::{MimeSource, SystemSource};
+use futures::{Stream, StreamExt};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+// Loom models the table locks when built with `--cfg yamime_loom`
+#[cfg(yamime_loom)]
+use loom::sync::RwLock;
+#[cfg(not(yamime_loom))]
 use std::sync::RwLock;
 
-/// Built-in MIME type mappings (all lowercase extensions).
+/// Built-in MIME type mappings (all lowercase extensions, sorted).
+///
+/// Covers common web, document, archive and media formats. Additions must
+/// also be made to the list in `test_builtin_types_exact_set`.
 static BUILTIN_TYPES_LOWER: &[(&str, &str)] = &[
+    (".7z", "application/x-7z-compressed"),
+    (".aac", "audio/aac"),
+    (".apk", "application/vnd.android.package-archive"),
     (".avif", "image/avif"),
+    (".bmp", "image/bmp"),
+    (".bz2", "application/x-bzip2"),
     (".css", "text/css; charset=utf-8"),
+    (".csv", "text/csv; charset=utf-8"),
+    (".dmg", "application/x-apple-diskimage"),
+    (".doc", "application/msword"),
+    (".docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    (".epub", "application/epub+zip"),
+    (".flac", "audio/flac"),
     (".gif", "image/gif"),
+    (".gz", "application/gzip"),
     (".htm", "text/html; charset=utf-8"),
     (".html", "text/html; charset=utf-8"),
+    (".ico", "image/vnd.microsoft.icon"),
+    (".ics", "text/calendar; charset=utf-8"),
+    (".jar", "application/java-archive"),
     (".jpeg", "image/jpeg"),
     (".jpg", "image/jpeg"),
     (".js", "text/javascript; charset=utf-8"),
     (".json", "application/json"),
+    (".m4a", "audio/mp4"),
+    (".md", "text/markdown; charset=utf-8"),
     (".mjs", "text/javascript; charset=utf-8"),
+    (".mkv", "video/x-matroska"),
+    (".mov", "video/quicktime"),
+    (".mp3", "audio/mpeg"),
+    (".mp4", "video/mp4"),
+    (".ogg", "audio/ogg"),
+    (".ogv", "video/ogg"),
+    (".otf", "font/otf"),
     (".pdf", "application/pdf"),
     (".png", "image/png"),
+    (".ppt", "application/vnd.ms-powerpoint"),
+    (".pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    (".rar", "application/vnd.rar"),
+    (".rtf", "application/rtf"),
     (".svg", "image/svg+xml"),
+    (".tar", "application/x-tar"),
+    (".tgz", "application/gzip"),
+    (".tif", "image/tiff"),
+    (".tiff", "image/tiff"),
+    (".toml", "application/toml"),
+    (".tsv", "text/tab-separated-values; charset=utf-8"),
+    (".ttf", "font/ttf"),
+    (".txt", "text/plain; charset=utf-8"),
     (".wasm", "application/wasm"),
+    (".wav", "audio/wav"),
+    (".webm", "video/webm"),
     (".webp", "image/webp"),
+    (".woff", "font/woff"),
+    (".woff2", "font/woff2"),
+    (".xls", "application/vnd.ms-excel"),
+    (".xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
     (".xml", "text/xml; charset=utf-8"),
+    (".yaml", "application/yaml"),
+    (".yml", "application/yaml"),
+    (".zip", "application/zip"),
 ];
 
-/// Maps file extensions to MIME types (case-sensitive).
-/// Example: ".Z" => "application/x-compress"
-static MIME_TYPES: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| {
-    RwLock::new(HashMap::new())
-});
+/// The extension preferred for builtin types with several.
+static PREFERRED_EXTENSIONS: &[(&str, &str)] = &[
+    ("application/gzip", ".gz"),
+    ("application/yaml", ".yaml"),
+    ("image/jpeg", ".jpg"),
+    ("image/tiff", ".tiff"),
+    ("text/html", ".html"),
+    ("text/javascript", ".js"),
+];
 
-/// Maps lowercase file extensions to MIME types (case-insensitive).
-/// Example: ".z" => "application/x-compress"
-static MIME_TYPES_LOWER: Lazy<RwLock<HashMap<String, String>>> = Lazy::new(|| {
-    RwLock::new(HashMap::new())
-});
+/// Bytes read from a file to sniff its type.
+const SNIFF_LEN: usize = 512;
+
+/// The global lookup maps.
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+/// The locked lookup maps, always locked in field order.
+struct Registry {
+    /// Maps file extensions to MIME types (case-sensitive).
+    /// Example: ".Z" => "application/x-compress"
+    mime_types: RwLock<HashMap<String, String>>,
+    /// Maps lowercase file extensions to MIME types (case-insensitive).
+    /// Example: ".z" => "application/x-compress"
+    mime_types_lower: RwLock<HashMap<String, String>>,
+    /// Maps MIME types to lists of file extensions.
+    /// Example: "image/jpeg" => [".jpg", ".jpeg"]
+    extensions: RwLock<HashMap<String, Vec<String>>>,
+}
 
-/// Maps MIME types to lists of file extensions.
-/// Example: "image/jpeg" => [".jpg", ".jpeg"]
-static EXTENSIONS: Lazy<RwLock<HashMap<String, Vec<String>>>> = Lazy::new(|| {
-    RwLock::new(HashMap::new())
-});
+impl Registry {
+    fn new() -> Self {
+        Self {
+            mime_types: RwLock::new(HashMap::new()),
+            mime_types_lower: RwLock::new(HashMap::new()),
+            extensions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the maps with `tables`, holding all three write locks so no
+    /// lookup sees a mix of old and new maps.
+    fn install(&self, tables: Tables) {
+        let mut mime_types = self.mime_types.write().unwrap();
+        let mut mime_types_lower = self.mime_types_lower.write().unwrap();
+        let mut extensions = self.extensions.write().unwrap();
+        *mime_types = tables.mime_types;
+        *mime_types_lower = tables.mime_types_lower;
+        *extensions = tables.extensions;
+    }
+
+    /// Looks up an extension case-sensitively, then case-insensitively.
+    fn type_by_extension(&self, ext: &str) -> Option<String> {
+        // Case-sensitive lookup
+        {
+            let mime_types = self.mime_types.read().unwrap();
+            if let Some(mime) = mime_types.get(ext) {
+                return Some(mime.clone());
+            }
+        }
+
+        // Case-insensitive lookup
+        // Optimistically assume a short ASCII extension and be allocation-free in that case
+        let lower = if ext.is_ascii() {
+            // Fast path: use stack buffer for ASCII
+            ext.to_ascii_lowercase()
+        } else {
+            // Slow path: handle UTF-8
+            ext.to_lowercase()
+        };
+
+        let mime_types_lower = self.mime_types_lower.read().unwrap();
+        mime_types_lower.get(&lower).cloned()
+    }
+
+    /// Returns the sorted extensions of a type without parameters.
+    fn extensions_by_type(&self, just_type: &str) -> Vec<String> {
+        let extensions = self.extensions.read().unwrap();
+        if let Some(exts) = extensions.get(just_type) {
+            let mut ret = exts.clone();
+            ret.sort();
+            ret
+        } else {
+            Vec::new()
+        }
+    }
+}
 
 /// Ensures MIME types are initialized exactly once.
 static INIT: Lazy<()> = Lazy::new(|| {
@@ -65,44 +194,89 @@ static INIT: Lazy<()> = Lazy::new(|| {
 
 /// Initializes the MIME type maps with builtin types and platform-specific types.
 fn init_mime() {
-    // Set builtin types
-    set_mime_types_internal(BUILTIN_TYPES_LOWER, BUILTIN_TYPES_LOWER);
-
     // Load platform-specific types (errors are ignored)
-    #[cfg(any(unix, windows))]
-    let _ = crate::platform::init_mime();
+    let _ = init_mime_from(&SystemSource);
 }
 
-/// Internal function to set MIME type mappings.
-fn set_mime_types_internal(lower_ext: &[(&str, &str)], mix_ext: &[(&str, &str)]) {
-    let mut mime_types = MIME_TYPES.write().unwrap();
-    let mut mime_types_lower = MIME_TYPES_LOWER.write().unwrap();
-    let mut extensions = EXTENSIONS.write().unwrap();
+/// Replaces the maps with the builtin types plus the source's types, not
+/// overriding existing ones.
+fn init_mime_from(source: &dyn MimeSource) -> Result<()> {
+    let mut tables = Tables::builtin();
+    let result = source.load(&mut |ext, mime| {
+        let _ = tables.add_if_absent(ext, mime);
+    });
+    REGISTRY.install(tables);
+    result
+}
 
-    // Clear existing mappings
-    mime_types.clear();
-    mime_types_lower.clear();
-    extensions.clear();
+/// Replaces all loaded MIME types with the builtin types plus those from
+/// `source`.
+///
+/// Types added with [`add_extension_type`] are discarded. As during normal
+/// initialization, builtin types and earlier mappings from the source take
+/// precedence over later ones for the same extension.
+///
+/// The new tables are built before any lookup sees them and replace the old
+/// ones at once, so concurrent lookups see all of the old types or all of
+/// the new ones.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yamime::mime_source::FileSource;
+/// use yamime::mime_type::reload_mime_types;
+///
+/// reload_mime_types(&FileSource::new().mime_types("/srv/app/mime.types")).unwrap();
+/// ```
+pub fn reload_mime_types(source: &dyn MimeSource) -> Result<()> {
+    // Make sure the default initialization cannot run afterwards
+    Lazy::force(&INIT);
+    init_mime_from(source)
+}
 
-    // Set lowercase mappings
-    for (ext, mime) in lower_ext {
-        mime_types_lower.insert(ext.to_string(), mime.to_string());
-    }
+/// The lookup maps, built off-lock by reloads and swapped in together by
+/// [`Registry::install`].
+struct Tables {
+    mime_types: HashMap<String, String>,
+    mime_types_lower: HashMap<String, String>,
+    extensions: HashMap<String, Vec<String>>,
+}
 
-    // Set case-sensitive mappings
-    for (ext, mime) in mix_ext {
-        mime_types.insert(ext.to_string(), mime.to_string());
+impl Tables {
+    /// Returns maps holding only the builtin types.
+    fn builtin() -> Self {
+        let mut tables = Self {
+            mime_types: HashMap::new(),
+            mime_types_lower: HashMap::new(),
+            extensions: HashMap::new(),
+        };
+        for (ext, mime) in BUILTIN_TYPES_LOWER {
+            tables.mime_types_lower.insert(ext.to_string(), mime.to_string());
+            tables.mime_types.insert(ext.to_string(), mime.to_string());
+            // Parse media type to get just the type without parameters
+            if let Ok((just_type, _)) = parse_media_type(mime) {
+                tables
+                    .extensions
+                    .entry(just_type)
+                    .or_default()
+                    .push(ext.to_string());
+            }
+        }
+        tables
     }
 
-    // Build reverse mapping (MIME type -> extensions)
-    for (ext, mime) in lower_ext {
-        // Parse media type to get just the type without parameters
-        if let Ok((just_type, _)) = parse_media_type(mime) {
-            extensions
-                .entry(just_type)
-                .or_insert_with(Vec::new)
-                .push(ext.to_string());
+    /// Adds a mapping unless the extension already has a type.
+    fn add_if_absent(&mut self, extension: &str, mime_type: &str) -> Result<()> {
+        let ext_lower = extension.to_lowercase();
+        if self.mime_types_lower.contains_key(&ext_lower) {
+            return Ok(());
         }
+
+        let (just_type, final_mime_type) = normalize_mime_type(mime_type)?;
+        self.mime_types.insert(extension.to_string(), final_mime_type.clone());
+        self.mime_types_lower.insert(ext_lower.clone(), final_mime_type);
+        add_reverse_mapping(&mut self.extensions, just_type, ext_lower);
+        Ok(())
     }
 }
 
@@ -127,26 +301,16 @@ pub fn type_by_extension(ext: &str) -> Option<String> {
     // Ensure initialization
     Lazy::force(&INIT);
 
-    // Case-sensitive lookup
-    {
-        let mime_types = MIME_TYPES.read().unwrap();
-        if let Some(mime) = mime_types.get(ext) {
-            return Some(mime.clone());
-        }
-    }
-
-    // Case-insensitive lookup
-    // Optimistically assume a short ASCII extension and be allocation-free in that case
-    let lower = if ext.is_ascii() {
-        // Fast path: use stack buffer for ASCII
-        ext.to_ascii_lowercase()
-    } else {
-        // Slow path: handle UTF-8
-        ext.to_lowercase()
-    };
+    REGISTRY.type_by_extension(ext)
+}
 
-    let mime_types_lower = MIME_TYPES_LOWER.read().unwrap();
-    mime_types_lower.get(&lower).cloned()
+/// Looks up an extension in the builtin table only, case-insensitively.
+pub(crate) fn builtin_type(ext: &str) -> Option<&'static str> {
+    let lower = ext.to_lowercase();
+    BUILTIN_TYPES_LOWER
+        .binary_search_by(|(e, _)| (*e).cmp(lower.as_str()))
+        .ok()
+        .map(|i| BUILTIN_TYPES_LOWER[i].1)
 }
 
 /// Returns the extensions known to be associated with the MIME type typ.
@@ -170,13 +334,90 @@ pub fn extensions_by_type(mime_type: &str) -> Result<Vec<String>> {
     // Ensure initialization
     Lazy::force(&INIT);
 
-    let extensions = EXTENSIONS.read().unwrap();
-    if let Some(exts) = extensions.get(&just_type) {
-        let mut ret = exts.clone();
-        ret.sort();
-        Ok(ret)
-    } else {
-        Ok(Vec::new())
+    Ok(REGISTRY.extensions_by_type(&just_type))
+}
+
+/// Returns the extension to give files of the MIME type typ.
+///
+/// For builtin types with several extensions, the most common one is
+/// chosen; otherwise the first of [`extensions_by_type`]. Returns `None`
+/// if the type has no known extension.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::preferred_extension;
+///
+/// assert_eq!(preferred_extension("image/jpeg").unwrap().as_deref(), Some(".jpg"));
+/// assert_eq!(preferred_extension("text/html; charset=utf-8").unwrap().as_deref(), Some(".html"));
+/// ```
+pub fn preferred_extension(mime_type: &str) -> Result<Option<String>> {
+    let (just_type, _) = parse_media_type(mime_type)?;
+    if let Some((_, ext)) = PREFERRED_EXTENSIONS.iter().find(|(t, _)| *t == just_type) {
+        return Ok(Some(ext.to_string()));
+    }
+    Ok(extensions_by_type(&just_type)?.into_iter().next())
+}
+
+/// Returns the path a file should be stored under so that its extension
+/// matches its type.
+///
+/// The type is `declared` if given, else sniffed from the start of the
+/// file with [`sniff`](crate::mime_resolver::sniff). If the file's
+/// extension already maps to that type it is kept; otherwise the
+/// [`preferred_extension`] is appended, so `photo.png` holding a JPEG
+/// becomes `photo.png.jpg`. The path is returned unchanged if the type is
+/// unknown or has no extension.
+///
+/// # Examples
+///
+/// ```no_run
+/// use yamime::extensions_for_file;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let stored = extensions_for_file("/uploads/scan", Some("application/pdf")).await?;
+/// assert_eq!(stored, std::path::Path::new("/uploads/scan.pdf"));
+/// # Ok(())
+/// # }
+/// ```
+pub async fn extensions_for_file(path: impl AsRef<Path>, declared: Option<&str>) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let detected = match declared {
+        Some(declared) => Some(declared.to_string()),
+        None => {
+            let mut prefix = Vec::with_capacity(SNIFF_LEN);
+            tokio::fs::File::open(path)
+                .await?
+                .take(SNIFF_LEN as u64)
+                .read_to_end(&mut prefix)
+                .await?;
+            crate::mime_resolver::sniff(&prefix)
+        }
+    };
+    let Some(detected) = detected else {
+        return Ok(path.to_path_buf());
+    };
+    let (just_type, _) = parse_media_type(&detected)?;
+
+    let current = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()));
+    if let Some(current) = &current {
+        let matches = type_by_extension(current)
+            .and_then(|mime| parse_media_type(&mime).ok())
+            .is_some_and(|(current_type, _)| current_type == just_type);
+        if matches || extensions_by_type(&just_type)?.contains(&current.to_lowercase()) {
+            return Ok(path.to_path_buf());
+        }
+    }
+
+    match preferred_extension(&just_type)? {
+        Some(ext) => {
+            let mut name = path.as_os_str().to_owned();
+            name.push(ext);
+            Ok(PathBuf::from(name))
+        }
+        None => Ok(path.to_path_buf()),
     }
 }
 
@@ -223,45 +464,158 @@ fn set_extension_type_internal(extension: &str, mime_type: &str, skip_if_exists:
 
     // Check if extension already exists (for platform loading)
     if skip_if_exists {
-        let mime_types_lower = MIME_TYPES_LOWER.read().unwrap();
+        let mime_types_lower = REGISTRY.mime_types_lower.read().unwrap();
         if mime_types_lower.contains_key(&ext_lower) {
             return Ok(());
         }
     }
 
-    // Parse media type
+    let (just_type, final_mime_type) = normalize_mime_type(mime_type)?;
+
+    // Update MIME type mappings
+    {
+        let mut mime_types = REGISTRY.mime_types.write().unwrap();
+        mime_types.insert(extension.to_string(), final_mime_type.clone());
+    }
+    {
+        let mut mime_types_lower = REGISTRY.mime_types_lower.write().unwrap();
+        mime_types_lower.insert(ext_lower.clone(), final_mime_type);
+    }
+
+    // Update reverse mapping (extensions)
+    {
+        let mut extensions = REGISTRY.extensions.write().unwrap();
+        add_reverse_mapping(&mut extensions, just_type, ext_lower);
+    }
+
+    Ok(())
+}
+
+/// Parses a MIME type, returning it without parameters and as stored:
+/// text types get charset=utf-8 if they name no charset.
+fn normalize_mime_type(mime_type: &str) -> Result<(String, String)> {
     let (just_type, mut params) = parse_media_type(mime_type)?;
 
-    // Add charset=utf-8 for text/* types if not present
     let final_mime_type = if mime_type.starts_with("text/") && !params.contains_key("charset") {
         params.insert("charset".to_string(), "utf-8".to_string());
         format_media_type(&just_type, &params)
     } else {
         mime_type.to_string()
     };
+    Ok((just_type, final_mime_type))
+}
 
-    // Update MIME type mappings
-    {
-        let mut mime_types = MIME_TYPES.write().unwrap();
-        mime_types.insert(extension.to_string(), final_mime_type.clone());
-    }
-    {
-        let mut mime_types_lower = MIME_TYPES_LOWER.write().unwrap();
-        mime_types_lower.insert(ext_lower.clone(), final_mime_type.clone());
+fn add_reverse_mapping(extensions: &mut HashMap<String, Vec<String>>, just_type: String, ext_lower: String) {
+    let exts = extensions.entry(just_type).or_default();
+
+    // Only add if not already present
+    if !exts.contains(&ext_lower) {
+        exts.push(ext_lower);
     }
+}
 
-    // Update reverse mapping (extensions)
-    {
-        let mut extensions = EXTENSIONS.write().unwrap();
-        let exts = extensions.entry(just_type).or_insert_with(Vec::new);
+/// An entry rejected by [`add_extension_types_from_stream`].
+#[derive(Debug)]
+pub struct RejectedEntry {
+    /// The extension, as received.
+    pub ext: String,
+    /// The MIME type, as received.
+    pub mime_type: String,
+    /// Why the entry was rejected.
+    pub error: Error,
+}
+
+/// The outcome of [`add_extension_types_from_stream`].
+#[derive(Debug, Default)]
+pub struct BulkReport {
+    /// The number of distinct extensions added.
+    pub added: usize,
+    /// The entries not added, in stream order.
+    pub rejected: Vec<RejectedEntry>,
+}
 
-        // Only add if not already present
-        if !exts.contains(&ext_lower) {
-            exts.push(ext_lower);
+/// Validates `(extension, MIME type)` pairs from a stream and adds the
+/// valid ones in one step.
+///
+/// Up to `concurrency` entries are validated at once on tokio's blocking
+/// thread pool while the stream is polled for more, e.g. from a remote
+/// configuration service, so this must be called within a tokio runtime.
+/// Nothing is added until the stream ends; the valid entries are then
+/// added at once, so lookups see either none or all of them. Entries
+/// rejected by
+/// [`add_extension_type`], for a missing leading dot or a malformed type,
+/// are reported instead. If an extension appears more than once, the last
+/// valid entry wins.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::mime_type::add_extension_types_from_stream;
+///
+/// # async fn example() {
+/// let entries = futures::stream::iter([
+///     (".wgt", "application/widget"),
+///     ("nodot", "application/x-nodot"),
+/// ]);
+/// let report = add_extension_types_from_stream(entries, 8).await;
+/// assert_eq!(report.added, 1);
+/// assert_eq!(report.rejected[0].ext, "nodot");
+/// # }
+/// ```
+pub async fn add_extension_types_from_stream<S, E, T>(entries: S, concurrency: usize) -> BulkReport
+where
+    S: Stream<Item = (E, T)>,
+    E: Into<String>,
+    T: Into<String>,
+{
+    let results: Vec<_> = entries
+        .map(|(ext, mime_type)| {
+            let (ext, mime_type) = (ext.into(), mime_type.into());
+            async move {
+                let validated = if ext.starts_with('.') {
+                    let input = mime_type.clone();
+                    tokio::task::spawn_blocking(move || normalize_mime_type(&input))
+                        .await
+                        .unwrap_or_else(|e| {
+                            Err(Error::MimeType(format!("validation of {:?} failed: {}", ext, e)))
+                        })
+                } else {
+                    Err(Error::MimeType(format!("extension {:?} missing leading dot", ext)))
+                };
+                (ext, mime_type, validated)
+            }
+        })
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut report = BulkReport::default();
+    let mut valid = Vec::new();
+    for (ext, mime_type, validated) in results {
+        match validated {
+            Ok(normalized) => valid.push((ext, normalized)),
+            Err(error) => report.rejected.push(RejectedEntry { ext, mime_type, error }),
         }
     }
+    // Keep only the last valid entry for each extension
+    let mut seen = HashSet::new();
+    valid.reverse();
+    valid.retain(|(ext, _)| seen.insert(ext.clone()));
+    valid.reverse();
 
-    Ok(())
+    Lazy::force(&INIT);
+    // Locked in the same order as Registry::install
+    let mut mime_types = REGISTRY.mime_types.write().unwrap();
+    let mut mime_types_lower = REGISTRY.mime_types_lower.write().unwrap();
+    let mut extensions = REGISTRY.extensions.write().unwrap();
+    for (ext, (just_type, final_mime_type)) in valid {
+        let ext_lower = ext.to_lowercase();
+        mime_types_lower.insert(ext_lower.clone(), final_mime_type.clone());
+        mime_types.insert(ext, final_mime_type);
+        add_reverse_mapping(&mut extensions, just_type, ext_lower);
+    }
+    report.added = seen.len();
+    report
 }
 
 #[cfg(test)]
@@ -294,8 +648,13 @@ mod tests {
         assert!(exts.len() >= 2);
     }
 
+    /// Serializes tests that replace or extend the global tables.
+    static TABLE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
     #[test]
     fn test_add_extension_type() {
+        let _guard = TABLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
         // Test error case
         let result = add_extension_type("foo", "application/foo");
         assert!(result.is_err());
@@ -307,4 +666,220 @@ mod tests {
             Some("application/test".to_string())
         );
     }
+
+    #[test]
+    fn test_add_extension_types_from_stream() {
+        let _guard = TABLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let entries = futures::stream::iter(vec![
+            (".bulk1", "application/x-bulk1"),
+            ("bulk2", "application/x-bulk2"),
+            (".bulk3", "text/x-bulk3"),
+            (".bulk4", "not a type"),
+            (".Bulk1", "application/x-bulk1-v2"),
+            (".bulk5", "application/x-bulk5"),
+            (".bulk5", "application/x-bulk5-v2"),
+        ]);
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let report = runtime.block_on(add_extension_types_from_stream(entries, 2));
+        // Repeated extensions count once
+        assert_eq!(report.added, 4);
+        let rejected: Vec<_> = report.rejected.iter().map(|r| r.ext.as_str()).collect();
+        assert_eq!(rejected, ["bulk2", ".bulk4"]);
+        assert!(matches!(report.rejected[0].error, Error::MimeType(_)));
+        assert_eq!(report.rejected[1].mime_type, "not a type");
+
+        assert_eq!(type_by_extension(".bulk1").as_deref(), Some("application/x-bulk1"));
+        // The later entry for the same extension wins case-insensitively
+        assert_eq!(type_by_extension(".BULK1").as_deref(), Some("application/x-bulk1-v2"));
+        assert_eq!(type_by_extension(".bulk3").as_deref(), Some("text/x-bulk3; charset=utf-8"));
+        assert_eq!(extensions_by_type("text/x-bulk3").unwrap(), [".bulk3"]);
+        assert_eq!(type_by_extension(".bulk4"), None);
+        // Only the last entry for an extension is added, in both directions
+        assert_eq!(type_by_extension(".bulk5").as_deref(), Some("application/x-bulk5-v2"));
+        assert!(extensions_by_type("application/x-bulk5").unwrap().is_empty());
+
+        reload_mime_types(&SystemSource).unwrap();
+    }
+
+    #[test]
+    fn test_preferred_extension() {
+        assert_eq!(preferred_extension("image/JPEG").unwrap().as_deref(), Some(".jpg"));
+        assert_eq!(preferred_extension("application/pdf").unwrap().as_deref(), Some(".pdf"));
+        assert_eq!(preferred_extension("application/x-unknown-type").unwrap(), None);
+        assert!(preferred_extension("not a type").is_err());
+        for (mime, ext) in PREFERRED_EXTENSIONS {
+            assert_eq!(builtin_type(ext).map(|t| parse_media_type(t).unwrap().0).as_deref(), Some(*mime));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extensions_for_file() {
+        let dir = std::env::temp_dir().join(format!("yamime-ext-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        // Declared types are not checked against the contents
+        let upload = dir.join("scan");
+        assert_eq!(
+            extensions_for_file(&upload, Some("application/pdf")).await.unwrap(),
+            dir.join("scan.pdf")
+        );
+        let jpeg = dir.join("Photo.JPEG");
+        assert_eq!(extensions_for_file(&jpeg, Some("image/jpeg")).await.unwrap(), jpeg);
+
+        // Sniffed from the contents otherwise
+        let mislabelled = dir.join("photo.png");
+        tokio::fs::write(&mislabelled, b"\xff\xd8\xff\xe0 jfif").await.unwrap();
+        assert_eq!(
+            extensions_for_file(&mislabelled, None).await.unwrap(),
+            dir.join("photo.png.jpg")
+        );
+        let page = dir.join("index.htm");
+        tokio::fs::write(&page, b"<!DOCTYPE html><p>hi").await.unwrap();
+        assert_eq!(extensions_for_file(&page, None).await.unwrap(), page);
+        let unknown = dir.join("data.bin");
+        tokio::fs::write(&unknown, b"opaque").await.unwrap();
+        assert_eq!(extensions_for_file(&unknown, None).await.unwrap(), unknown);
+
+        assert!(extensions_for_file(dir.join("missing"), None).await.is_err());
+        assert!(extensions_for_file(&upload, Some("bad type")).await.is_err());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn test_builtin_types_exact_set() {
+        // Growing the builtin table is deliberate: update this list with it
+        let expected = [
+            ".7z", ".aac", ".apk", ".avif", ".bmp", ".bz2", ".css", ".csv", ".dmg", ".doc",
+            ".docx", ".epub", ".flac", ".gif", ".gz", ".htm", ".html", ".ico", ".ics", ".jar",
+            ".jpeg", ".jpg", ".js", ".json", ".m4a", ".md", ".mjs", ".mkv", ".mov", ".mp3", ".mp4",
+            ".ogg", ".ogv", ".otf", ".pdf", ".png", ".ppt", ".pptx", ".rar", ".rtf", ".svg",
+            ".tar", ".tgz", ".tif", ".tiff", ".toml", ".tsv", ".ttf", ".txt", ".wasm", ".wav",
+            ".webm", ".webp", ".woff", ".woff2", ".xls", ".xlsx", ".xml", ".yaml", ".yml", ".zip",
+        ];
+        let actual: Vec<&str> = BUILTIN_TYPES_LOWER.iter().map(|(ext, _)| *ext).collect();
+        assert_eq!(actual, expected);
+
+        for (ext, mime) in BUILTIN_TYPES_LOWER {
+            assert_eq!(*ext, ext.to_ascii_lowercase());
+            parse_media_type(mime).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_builtin_archive_and_media_types() {
+        assert_eq!(type_by_extension(".zip"), Some("application/zip".to_string()));
+        assert_eq!(type_by_extension(".WOFF2"), Some("font/woff2".to_string()));
+        assert!(extensions_by_type("application/yaml").unwrap().contains(&".yml".to_string()));
+    }
+
+    #[test]
+    fn test_reload_from_fixture_source() {
+        use crate::mime_source::FileSource;
+        use std::path::Path;
+
+        let _guard = TABLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mime");
+        let source = FileSource::new()
+            .globs(fixtures.join("globs2"))
+            .mime_types(fixtures.join("mime.types"));
+        reload_mime_types(&source).unwrap();
+
+        // The higher weight entry, listed first, wins
+        assert_eq!(type_by_extension(".foo"), Some("application/x-foo".to_string()));
+        assert_eq!(type_by_extension(".bar"), Some("application/x-bar".to_string()));
+        // mime.types files are ignored when a globs2 file was loaded
+        assert_eq!(type_by_extension(".baz"), None);
+        // Builtins are never overridden
+        assert_eq!(type_by_extension(".png"), Some("image/png".to_string()));
+
+        reload_mime_types(&SystemSource).unwrap();
+        assert_eq!(type_by_extension(".foo"), None);
+    }
+
+    #[test]
+    fn test_concurrent_reloads() {
+        use std::collections::HashMap;
+        use std::sync::Arc;
+
+        let _guard = TABLE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Kept small so the test also runs under Miri
+        let rounds = if cfg!(miri) { 3 } else { 200 };
+        let custom: Arc<HashMap<String, String>> = Arc::new(
+            [(".swap".to_string(), "application/x-swap".to_string())].into(),
+        );
+        reload_mime_types(&*custom).unwrap();
+
+        let reloaders: Vec<_> = (0..2)
+            .map(|_| {
+                let custom = custom.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..rounds {
+                        reload_mime_types(&*custom).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    for _ in 0..rounds {
+                        // Each reload replaces the tables as a whole, so
+                        // neither builtin nor custom types ever go missing
+                        assert_eq!(type_by_extension(".PNG").as_deref(), Some("image/png"));
+                        assert!(extensions_by_type("image/png").unwrap().contains(&".png".to_string()));
+                        assert_eq!(type_by_extension(".swap").as_deref(), Some("application/x-swap"));
+                    }
+                })
+            })
+            .collect();
+        for thread in reloaders.into_iter().chain(readers) {
+            thread.join().unwrap();
+        }
+        assert_eq!(type_by_extension(".swap").as_deref(), Some("application/x-swap"));
+
+        reload_mime_types(&SystemSource).unwrap();
+    }
+}
+
+/// Loom models of the table locks, run with
+/// `RUSTFLAGS="--cfg yamime_loom" cargo test --lib loom`.
+#[cfg(all(test, yamime_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::sync::Arc;
+
+    /// Returns the builtin tables plus `ext`.
+    fn tables_with(ext: &str) -> Tables {
+        let mut tables = Tables::builtin();
+        tables.add_if_absent(ext, "application/x-swap").unwrap();
+        tables
+    }
+
+    #[test]
+    fn loom_reload_against_lookup() {
+        loom::model(|| {
+            let registry = Arc::new(Registry::new());
+            registry.install(tables_with(".old"));
+
+            let reloader = {
+                let registry = registry.clone();
+                loom::thread::spawn(move || registry.install(tables_with(".new")))
+            };
+            // Builtin types never go missing, and once a lookup sees the new
+            // tables, later ones do too
+            assert_eq!(registry.type_by_extension(".PNG").as_deref(), Some("image/png"));
+            let reloaded = registry.type_by_extension(".new").is_some();
+            let exts = registry.extensions_by_type("application/x-swap");
+            if reloaded {
+                assert_eq!(exts, [".new"]);
+            } else {
+                assert!(exts == [".old"] || exts == [".new"], "{:?}", exts);
+            }
+            reloader.join().unwrap();
+
+            assert_eq!(registry.type_by_extension(".old"), None);
+            assert_eq!(registry.type_by_extension(".new").as_deref(), Some("application/x-swap"));
+        });
+    }
 }
@@ -0,0 +1,525 @@
+//! Multipart bodies produced as a stream of chunks.
+//!
+//! A [`Writer`] pushes a message into an `AsyncWrite`. HTTP clients such as
+//! hyper and reqwest instead pull request bodies from a
+//! `Stream<Item = Result<Bytes>>`. A [`BodyBuilder`] describes the parts up
+//! front and [`into_stream`](BodyBuilder::into_stream) produces the message
+//! as it is polled, reading files and readers only when their turn comes,
+//! so an upload is never materialized in memory.
+//!
+//! To send a Content-Length rather than chunked encoding, a
+//! [`LengthPlanner`] computes the exact size of a message from its headers
+//! and body sizes before anything is written.
+
+use super::header::MimeHeader;
+use super::writer::{
+    content_type_for, form_data_content_type, form_data_disposition, form_file_header,
+    generate_boundary, validate_boundary, LineEnding, Writer,
+};
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use futures::Stream;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Bytes read from a file or reader per chunk.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+type BoxedReader = Pin<Box<dyn AsyncRead + Send>>;
+
+enum Source {
+    Bytes(Bytes),
+    File(PathBuf),
+    Reader(BoxedReader),
+}
+
+/// Describes a multipart/form-data body to stream.
+///
+/// # Examples
+///
+/// ```
+/// use futures::TryStreamExt;
+/// use yamime::multipart::BodyBuilder;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let body = BodyBuilder::new()
+///     .field("title", "Holiday")
+///     .reader("photo", "beach.jpg", &b"\xff\xd8\xff..."[..]);
+/// // Set as the request's Content-Type
+/// let content_type = body.content_type();
+///
+/// let chunks: Vec<_> = body.into_stream().try_collect().await?;
+/// assert!(chunks.concat().ends_with(b"--\r\n"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct BodyBuilder {
+    boundary: String,
+    parts: VecDeque<(MimeHeader, Source)>,
+}
+
+impl fmt::Debug for BodyBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyBuilder")
+            .field("boundary", &self.boundary)
+            .field("parts", &self.parts.len())
+            .finish()
+    }
+}
+
+impl Default for BodyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BodyBuilder {
+    /// Creates an empty body with a random boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: generate_boundary(),
+            parts: VecDeque::new(),
+        }
+    }
+
+    /// Sets the boundary, failing if RFC 2046 does not allow it.
+    pub fn with_boundary(mut self, boundary: impl Into<String>) -> Result<Self> {
+        let boundary = boundary.into();
+        validate_boundary(boundary.as_bytes())?;
+        self.boundary = boundary;
+        Ok(self)
+    }
+
+    /// Returns the boundary.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the Content-Type of the body, with its boundary.
+    pub fn content_type(&self) -> String {
+        form_data_content_type(&self.boundary)
+    }
+
+    /// Adds a text field.
+    pub fn field(self, name: &str, value: impl Into<String>) -> Self {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Disposition", form_data_disposition(name, None));
+        self.part(header, value.into())
+    }
+
+    /// Adds a file part read from `path` when the stream reaches it, with
+    /// filename and Content-Type as in [`Writer::write_file`].
+    pub fn file(mut self, name: &str, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .ok_or_else(|| {
+                Error::Multipart(format!("path has no file name: {}", path.display()))
+            })?;
+        let header = form_file_header(name, &filename, &content_type_for(&filename));
+        self.parts
+            .push_back((header, Source::File(path.to_path_buf())));
+        Ok(self)
+    }
+
+    /// Adds a file part whose body is read from `reader` when the stream
+    /// reaches it, with a Content-Type detected from `filename`.
+    pub fn reader<R>(mut self, name: &str, filename: &str, reader: R) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let header = form_file_header(name, filename, &content_type_for(filename));
+        self.parts
+            .push_back((header, Source::Reader(Box::pin(reader))));
+        self
+    }
+
+    /// Adds a part with the given header and body.
+    pub fn part(mut self, header: MimeHeader, body: impl Into<Bytes>) -> Self {
+        self.parts.push_back((header, Source::Bytes(body.into())));
+        self
+    }
+
+    /// Returns the length of the message [`into_stream`](Self::into_stream)
+    /// produces, or `None` if it has a reader part, whose size is unknown.
+    ///
+    /// File sizes are taken from the file system now, so the length is
+    /// only exact if the files do not change before they are streamed.
+    pub async fn content_length(&self) -> Result<Option<u64>> {
+        let mut planner = LengthPlanner::new(&self.boundary);
+        for (header, source) in &self.parts {
+            let size = match source {
+                Source::Bytes(body) => body.len() as u64,
+                Source::File(path) => tokio::fs::metadata(path).await?.len(),
+                Source::Reader(_) => return Ok(None),
+            };
+            planner.part(header, size);
+        }
+        Ok(Some(planner.content_length()))
+    }
+
+    /// Returns the message as a stream of chunks.
+    ///
+    /// Files and readers are read 16 KiB at a time, each read becoming a
+    /// chunk; bodies given as bytes are yielded whole with their headers.
+    /// After an error, such as a file failing to open, the stream ends.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes>> + Send {
+        let buf = SharedBuf::default();
+        let writer = Writer::builder()
+            .boundary(self.boundary)
+            .build(buf.clone())
+            .expect("boundary checked by with_boundary");
+        let state = StreamState {
+            writer: Some(writer),
+            buf,
+            parts: self.parts,
+            current: None,
+            chunk: vec![0; CHUNK_SIZE],
+        };
+        futures::stream::unfold(state, |mut state| async move {
+            let chunk = state.next_chunk().await?;
+            Some((chunk, state))
+        })
+    }
+}
+
+/// Computes the exact length of a multipart message before it is written.
+///
+/// Parts are described by their headers and body sizes; form fields and
+/// files get the headers [`Writer::write_field`] and
+/// [`Writer::create_form_file`] would write. The length matches a
+/// [`Writer`] with the same boundary and line ending writing the parts
+/// without transfer encodings and then closing.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::LengthPlanner;
+///
+/// let mut planner = LengthPlanner::new("b");
+/// planner.field("title", "Holiday").file("photo", "beach.jpg", 1_048_576);
+/// let content_length = planner.content_length();
+/// assert!(content_length > 1_048_576);
+/// ```
+#[derive(Debug, Clone)]
+pub struct LengthPlanner {
+    boundary: String,
+    line_ending: LineEnding,
+    /// The length of the parts so far, without the closing delimiter.
+    length: u64,
+}
+
+impl LengthPlanner {
+    /// Creates a planner for a message with the given boundary and CRLF
+    /// line endings.
+    pub fn new(boundary: &str) -> Self {
+        Self {
+            boundary: boundary.to_string(),
+            line_ending: LineEnding::Crlf,
+            length: 0,
+        }
+    }
+
+    /// Sets the line ending, as configured with
+    /// [`WriterBuilder::line_ending`](super::WriterBuilder::line_ending).
+    pub fn line_ending(&mut self, line_ending: LineEnding) -> &mut Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Adds a text field.
+    pub fn field(&mut self, name: &str, value: &str) -> &mut Self {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Disposition", form_data_disposition(name, None));
+        self.part(&header, value.len() as u64)
+    }
+
+    /// Adds a file of `size` bytes, with a Content-Type detected from
+    /// `filename`.
+    pub fn file(&mut self, name: &str, filename: &str, size: u64) -> &mut Self {
+        let header = form_file_header(name, filename, &content_type_for(filename));
+        self.part(&header, size)
+    }
+
+    /// Adds a part with the given header and a body of `size` bytes.
+    pub fn part(&mut self, header: &MimeHeader, size: u64) -> &mut Self {
+        let nl = self.line_ending.as_str().len() as u64;
+        // Delimiter line, header lines, blank line, body, line break
+        let mut length = 2 + self.boundary.len() as u64 + nl;
+        for (key, value) in header.iter() {
+            length += (key.len() + 2 + value.len()) as u64 + nl;
+        }
+        self.length += length + nl + size + nl;
+        self
+    }
+
+    /// Returns the length of the message, including the closing delimiter.
+    pub fn content_length(&self) -> u64 {
+        let nl = self.line_ending.as_str().len() as u64;
+        self.length + 4 + self.boundary.len() as u64 + nl
+    }
+}
+
+struct StreamState {
+    /// The writer, until the message is complete or failed.
+    writer: Option<Writer<SharedBuf>>,
+    buf: SharedBuf,
+    parts: VecDeque<(MimeHeader, Source)>,
+    /// The source of the part being written, if read incrementally.
+    current: Option<BoxedReader>,
+    chunk: Vec<u8>,
+}
+
+impl StreamState {
+    async fn next_chunk(&mut self) -> Option<Result<Bytes>> {
+        loop {
+            if let Some(chunk) = self.buf.take() {
+                return Some(Ok(chunk));
+            }
+            self.writer.as_ref()?;
+            if let Err(err) = self.advance().await {
+                self.writer = None;
+                self.current = None;
+                self.buf.take();
+                return Some(Err(err));
+            }
+        }
+    }
+
+    /// Writes the next piece of the message into the buffer.
+    async fn advance(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+
+        if let Some(reader) = &mut self.current {
+            let n = reader.read(&mut self.chunk).await?;
+            if n == 0 {
+                self.current = None;
+            } else {
+                writer.resume_part().write_all(&self.chunk[..n]).await?;
+            }
+            return Ok(());
+        }
+
+        match self.parts.pop_front() {
+            Some((header, source)) => {
+                let mut part = writer.create_part_with_header(&header).await?;
+                match source {
+                    Source::Bytes(body) => part.write_all(&body).await?,
+                    Source::File(path) => {
+                        self.current = Some(Box::pin(tokio::fs::File::open(path).await?));
+                    }
+                    Source::Reader(reader) => self.current = Some(reader),
+                }
+            }
+            None => {
+                if let Some(writer) = self.writer.take() {
+                    writer.close().await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An in-memory output shared with the stream, which takes what the
+/// writer wrote after each step.
+#[derive(Debug, Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuf {
+    fn take(&self) -> Option<Bytes> {
+        let mut buf = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if buf.is_empty() {
+            None
+        } else {
+            Some(Bytes::from(std::mem::take(&mut *buf)))
+        }
+    }
+}
+
+impl AsyncWrite for SharedBuf {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Reader;
+    use super::*;
+    use futures::TryStreamExt;
+
+    async fn collect(body: BodyBuilder) -> Result<Vec<Bytes>> {
+        body.into_stream().try_collect().await
+    }
+
+    #[tokio::test]
+    async fn test_body_round_trip() {
+        let large = vec![b'x'; CHUNK_SIZE * 2 + 10];
+        let mut header = MimeHeader::new();
+        header.insert("X-Kind", "raw");
+        let body = BodyBuilder::new()
+            .with_boundary("b")
+            .unwrap()
+            .field("title", "Holiday")
+            .reader("photo", "beach.jpg", std::io::Cursor::new(large.clone()))
+            .part(header, "raw body");
+        assert_eq!(body.content_type(), "multipart/form-data; boundary=b");
+
+        let chunks = collect(body).await.unwrap();
+        // The reader's body arrives in pieces, not all at once
+        assert!(chunks.len() > 3);
+        assert!(chunks.iter().all(|c| !c.is_empty()));
+        let message = chunks.concat();
+
+        let mut reader = Reader::new(&message[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("title"));
+        assert_eq!(part.bytes().await.unwrap(), &b"Holiday"[..]);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("beach.jpg".to_string()));
+        assert_eq!(part.header.get("content-type"), Some("image/jpeg"));
+        assert_eq!(part.bytes().await.unwrap(), &large[..]);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("x-kind"), Some("raw"));
+        assert_eq!(part.bytes().await.unwrap(), &b"raw body"[..]);
+        assert!(reader.next_part().await.unwrap().is_none());
+
+        assert!(BodyBuilder::new().with_boundary("bad\\").is_err());
+        let empty = collect(BodyBuilder::new().with_boundary("b").unwrap()).await.unwrap();
+        assert_eq!(empty.concat(), b"--b--\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_length_planner_matches_writer() {
+        for line_ending in [LineEnding::Crlf, LineEnding::Lf] {
+            let mut planner = LengthPlanner::new("a boundary");
+            planner.line_ending(line_ending);
+            assert_eq!(planner.content_length(), 14 + line_ending.as_str().len() as u64);
+
+            let mut output = Vec::new();
+            let mut writer = Writer::builder()
+                .boundary("a boundary")
+                .line_ending(line_ending)
+                .build(&mut output)
+                .unwrap();
+            let mut header = MimeHeader::new();
+            header.insert("X-Custom", "yes");
+            header.append("X-Custom", "twice");
+            write_and_plan(&mut writer, &mut planner, header, b"").await;
+            writer.write_field("title", "Holiday").await.unwrap();
+            planner.field("title", "Holiday");
+            // Non-ASCII names are encoded, growing the header
+            writer.write_field("café", "crème").await.unwrap();
+            planner.field("café", "crème");
+            let photo = vec![0u8; 5000];
+            writer
+                .create_form_file("photo", "été.jpg")
+                .await
+                .unwrap()
+                .write_all(&photo)
+                .await
+                .unwrap();
+            planner.file("photo", "été.jpg", photo.len() as u64);
+            writer.close().await.unwrap();
+
+            assert_eq!(planner.content_length(), output.len() as u64);
+        }
+    }
+
+    async fn write_and_plan(
+        writer: &mut Writer<&mut Vec<u8>>,
+        planner: &mut LengthPlanner,
+        header: MimeHeader,
+        body: &[u8],
+    ) {
+        planner.part(&header, body.len() as u64);
+        writer
+            .create_part(header)
+            .await
+            .unwrap()
+            .write_all(body)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_body_content_length() {
+        let dir = std::env::temp_dir().join(format!("yamime-length-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("data.bin");
+        tokio::fs::write(&path, vec![7u8; 40_000]).await.unwrap();
+
+        let body = BodyBuilder::new().field("a", "1").file("data", &path).unwrap();
+        let length = body.content_length().await.unwrap().unwrap();
+        let message = collect(body).await.unwrap().concat();
+        assert_eq!(length, message.len() as u64);
+
+        let body = BodyBuilder::new().reader("r", "r.txt", &b"unknown"[..]);
+        assert_eq!(body.content_length().await.unwrap(), None);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_body_from_file() {
+        let dir = std::env::temp_dir().join(format!("yamime-body-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("notes.txt");
+        tokio::fs::write(&path, "file contents").await.unwrap();
+
+        let body = BodyBuilder::new()
+            .with_boundary("b")
+            .unwrap()
+            .file("doc", &path)
+            .unwrap();
+        // The file is only read when the stream reaches it
+        tokio::fs::write(&path, "changed contents").await.unwrap();
+        let message = collect(body).await.unwrap().concat();
+        let mut reader = Reader::new(&message[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("notes.txt".to_string()));
+        assert_eq!(part.header.get("content-type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(part.bytes().await.unwrap(), &b"changed contents"[..]);
+
+        // A missing file fails the stream, which then ends
+        let body = BodyBuilder::new()
+            .field("a", "1")
+            .file("doc", dir.join("missing.txt"))
+            .unwrap();
+        let mut stream = Box::pin(body.into_stream());
+        let mut results = Vec::new();
+        while let Some(result) = futures::StreamExt::next(&mut stream).await {
+            results.push(result);
+        }
+        assert!(matches!(results.last(), Some(Err(Error::Io(_)))));
+        assert!(results[..results.len() - 1].iter().all(|r| r.is_ok()));
+
+        assert!(BodyBuilder::new().file("doc", "/").is_err());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
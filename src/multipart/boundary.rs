@@ -0,0 +1,61 @@
+//! Delimiter patterns derived from a multipart boundary.
+
+use memchr::memmem;
+
+/// The patterns a parser looks for, derived once from a boundary.
+///
+/// All patterns are slices of a single `\r\n--boundary--` buffer, and the
+/// substring searcher for delimiters inside a body is built here too, so a
+/// parser allocates for its boundary once rather than per pattern or per
+/// read.
+#[derive(Debug, Clone)]
+pub(crate) struct BoundaryPatterns {
+    /// `\r\n--boundary--`
+    buf: Box<[u8]>,
+    /// Searches for `\n--boundary`, which precedes every delimiter inside a
+    /// body.
+    finder: memmem::Finder<'static>,
+}
+
+impl BoundaryPatterns {
+    pub(crate) fn new(boundary: &[u8]) -> Self {
+        let buf: Box<[u8]> = [b"\r\n--", boundary, b"--"].concat().into();
+        let finder = memmem::Finder::new(&buf[1..buf.len() - 2]).into_owned();
+        Self { buf, finder }
+    }
+
+    /// `--boundary`, starting every delimiter line.
+    pub(crate) fn dash_boundary(&self) -> &[u8] {
+        &self.buf[2..self.buf.len() - 2]
+    }
+
+    /// `--boundary--`, starting the closing delimiter line.
+    pub(crate) fn dash_boundary_dash(&self) -> &[u8] {
+        &self.buf[2..]
+    }
+
+    /// `\n--boundary`, the pattern searched by [`find_nl_dash_boundary`](Self::find_nl_dash_boundary).
+    pub(crate) fn nl_dash_boundary(&self) -> &[u8] {
+        &self.buf[1..self.buf.len() - 2]
+    }
+
+    /// Returns the position of the first `\n--boundary` in `haystack`.
+    pub(crate) fn find_nl_dash_boundary(&self, haystack: &[u8]) -> Option<usize> {
+        self.finder.find(haystack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns() {
+        let patterns = BoundaryPatterns::new(b"b:1");
+        assert_eq!(patterns.dash_boundary(), b"--b:1");
+        assert_eq!(patterns.dash_boundary_dash(), b"--b:1--");
+        assert_eq!(patterns.nl_dash_boundary(), b"\n--b:1");
+        assert_eq!(patterns.find_nl_dash_boundary(b"ab\r\n--b:1--"), Some(3));
+        assert_eq!(patterns.clone().find_nl_dash_boundary(b"--b:1"), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! `multipart/byteranges` responses (RFC 9110 section 14.6).
+//!
+//! A server answering a multi-range request sends each range as a part with
+//! a Content-Range header. [`assemble`] writes every part at its offset in an
+//! output file, reconstructing the requested portions of the resource.
+
+use super::reader::Reader;
+use crate::error::{Error, Result};
+use std::io::SeekFrom;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// A parsed `Content-Range: bytes start-end/total` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    /// Offset of the first byte.
+    pub start: u64,
+    /// Offset of the last byte (inclusive).
+    pub end: u64,
+    /// Complete length of the resource, if known (`*` otherwise).
+    pub total: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parses a Content-Range header value.
+    ///
+    /// Only satisfied byte ranges are accepted; unsatisfied ranges
+    /// (`bytes */1234`) and other units return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::byteranges::ContentRange;
+    ///
+    /// let range = ContentRange::parse("bytes 500-999/8000").unwrap();
+    /// assert_eq!((range.start, range.end, range.total), (500, 999, Some(8000)));
+    /// assert_eq!(range.len(), 500);
+    /// ```
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || Error::InvalidParameter(format!("invalid Content-Range: {:?}", value));
+
+        let value = value.trim();
+        let (unit, rest) = value.split_once(' ').ok_or_else(invalid)?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return Err(invalid());
+        }
+        let (range, total) = rest.trim().split_once('/').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+
+        let parse = |s: &str| s.parse::<u64>().map_err(|_| invalid());
+        let start = parse(start)?;
+        let end = parse(end)?;
+        let total = match total {
+            "*" => None,
+            total => Some(parse(total)?),
+        };
+
+        if start > end || total.is_some_and(|t| end >= t) {
+            return Err(invalid());
+        }
+
+        Ok(Self { start, end, total })
+    }
+
+    /// Returns the number of bytes in the range.
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Always `false`: a satisfied range holds at least one byte.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Writes every part of a `multipart/byteranges` body at its offset in `out`.
+///
+/// Returns the ranges written, in the order received. Each part must carry a
+/// valid Content-Range whose length matches its body.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Cursor;
+/// use yamime::multipart::{byteranges, Reader};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let body = b"--r\r\nContent-Range: bytes 4-5/6\r\n\r\nef\r\n\
+/// --r\r\nContent-Range: bytes 0-1/6\r\n\r\nab\r\n--r--\r\n";
+/// let mut out = Cursor::new(vec![b'.'; 6]);
+/// let ranges = byteranges::assemble(&mut Reader::new(&body[..], "r"), &mut out).await?;
+/// assert_eq!(ranges.len(), 2);
+/// assert_eq!(out.into_inner(), b"ab..ef");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn assemble<R, W>(reader: &mut Reader<R>, mut out: W) -> Result<Vec<ContentRange>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + AsyncSeek + Unpin,
+{
+    let mut ranges = Vec::new();
+
+    while let Some(mut part) = reader.next_part().await? {
+        let range = part
+            .content_range()
+            .ok_or_else(|| Error::Multipart("byteranges part has no Content-Range".to_string()))??;
+
+        let body = part.bytes().await?;
+        let expected = range.len();
+        if body.len() as u64 != expected {
+            return Err(Error::Multipart(format!(
+                "byteranges part has {} bytes, Content-Range {}-{} expects {}",
+                body.len(),
+                range.start,
+                range.end,
+                expected
+            )));
+        }
+
+        out.seek(SeekFrom::Start(range.start)).await?;
+        out.write_all(&body).await?;
+        ranges.push(range);
+    }
+
+    out.flush().await?;
+    Ok(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            ContentRange::parse("bytes 0-0/1").unwrap(),
+            ContentRange { start: 0, end: 0, total: Some(1) }
+        );
+        assert_eq!(ContentRange::parse("Bytes 10-19/*").unwrap().total, None);
+
+        for invalid in [
+            "bytes */100",
+            "items 0-1/2",
+            "bytes 5-4/10",
+            "bytes 0-10/10",
+            "bytes 0-/10",
+            "bytes=0-1/2",
+        ] {
+            assert!(ContentRange::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assemble_rejects_length_mismatch() {
+        let body = b"--r\r\nContent-Range: bytes 0-9/10\r\n\r\nshort\r\n--r--\r\n";
+        let mut out = Cursor::new(Vec::new());
+        let result = assemble(&mut Reader::new(&body[..], "r"), &mut out).await;
+        assert!(matches!(result, Err(Error::Multipart(_))));
+
+        let body = b"--r\r\n\r\nno range\r\n--r--\r\n";
+        let result = assemble(&mut Reader::new(&body[..], "r"), &mut out).await;
+        assert!(matches!(result, Err(Error::Multipart(_))));
+    }
+}
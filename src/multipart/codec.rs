@@ -0,0 +1,373 @@
+//! Incremental multipart parsing as a [`tokio_util::codec::Decoder`].
+//!
+//! [`MultipartDecoder`] turns a byte stream into [`MultipartFrame`] events,
+//! for transports already built on [`FramedRead`](tokio_util::codec::FramedRead)
+//! and consumers that do not need the [`Reader`](super::Reader) and
+//! [`Part`](super::Part) object model. Bodies are passed through as received,
+//! as by [`Reader::next_raw_part`](super::Reader::next_raw_part), and are
+//! never buffered whole.
+
+use super::boundary::BoundaryPatterns;
+use super::header::MimeHeader;
+use super::reader::{HeaderParser, MAX_MIME_HEADER_SIZE};
+use crate::error::{Error, LimitExceeded, LimitKind, Result};
+use bytes::{Buf, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::Decoder;
+
+/// An event produced by a [`MultipartDecoder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartFrame {
+    /// The header of a new part.
+    PartHeaders(MimeHeader),
+    /// A piece of the current part's body.
+    BodyChunk(Bytes),
+    /// The end of the current part's body.
+    PartEnd,
+    /// The closing delimiter; nothing follows but the epilogue, which is
+    /// discarded.
+    Finished,
+}
+
+/// A decoder of multipart bodies into [`MultipartFrame`]s.
+///
+/// Each part produces a `PartHeaders` frame, any number of `BodyChunk`
+/// frames and a `PartEnd` frame; the message ends with `Finished`. The
+/// preamble is skipped, and, as for the `Reader`, part bodies exclude the
+/// line break before the next delimiter. Input ending before the closing
+/// delimiter fails with an unexpected EOF error.
+///
+/// # Examples
+///
+/// ```
+/// use futures::StreamExt;
+/// use tokio_util::codec::FramedRead;
+/// use yamime::multipart::{MultipartDecoder, MultipartFrame};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = b"--b\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b--\r\n";
+/// let mut frames = FramedRead::new(&data[..], MultipartDecoder::new("b"));
+///
+/// while let Some(frame) = frames.next().await {
+///     match frame? {
+///         MultipartFrame::PartHeaders(header) => println!("{:?}", header.get("content-type")),
+///         MultipartFrame::BodyChunk(chunk) => println!("{} bytes", chunk.len()),
+///         MultipartFrame::PartEnd => println!("end of part"),
+///         MultipartFrame::Finished => break,
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MultipartDecoder {
+    patterns: BoundaryPatterns,
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Before the first delimiter; `skip_line` is set inside a line that is
+    /// not one.
+    Preamble { skip_line: bool },
+    Headers(HeaderParser),
+    /// In a body; `at_start` until a delimiter right at its start is ruled
+    /// out.
+    Body { at_start: bool },
+    /// The closing delimiter was read, `Finished` is yet to be returned.
+    Closing,
+    Done,
+}
+
+/// What follows `--boundary` at the start of a line.
+pub(crate) enum Delimiter {
+    /// A delimiter line of the given length, after the boundary.
+    Part(usize),
+    /// The closing delimiter.
+    Closing,
+    /// Not a delimiter.
+    None,
+    /// Too little input to tell.
+    Incomplete,
+}
+
+impl MultipartDecoder {
+    /// Creates a decoder for a body with the given boundary.
+    pub fn new(boundary: &str) -> Self {
+        Self {
+            patterns: BoundaryPatterns::new(boundary.as_bytes()),
+            state: State::Preamble { skip_line: false },
+        }
+    }
+
+    /// Reports whether the closing delimiter has been decoded.
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, State::Closing | State::Done)
+    }
+
+    fn decode_preamble(&mut self, src: &mut BytesMut) -> Result<Option<MultipartFrame>> {
+        loop {
+            let State::Preamble { skip_line } = &mut self.state else {
+                return self.decode(src);
+            };
+            if *skip_line {
+                match memchr::memchr(b'\n', src) {
+                    Some(pos) => {
+                        src.advance(pos + 1);
+                        *skip_line = false;
+                    }
+                    None => {
+                        src.clear();
+                        return Ok(None);
+                    }
+                }
+                continue;
+            }
+
+            let n = src.len().min(self.patterns.dash_boundary().len());
+            if src[..n] != self.patterns.dash_boundary()[..n] {
+                *skip_line = true;
+                continue;
+            }
+            if n < self.patterns.dash_boundary().len() {
+                return Ok(None);
+            }
+            match delimiter(&src[n..]) {
+                Delimiter::Part(len) => {
+                    src.advance(n + len);
+                    self.state = State::Headers(HeaderParser::default());
+                }
+                Delimiter::Closing => self.state = State::Closing,
+                Delimiter::None => *skip_line = true,
+                Delimiter::Incomplete => return Ok(None),
+            }
+        }
+    }
+
+    fn decode_headers(&mut self, src: &mut BytesMut) -> Result<Option<MultipartFrame>> {
+        let State::Headers(parser) = &mut self.state else {
+            unreachable!("not reading headers");
+        };
+        while let Some(pos) = memchr::memchr(b'\n', src) {
+            let line = src.split_to(pos + 1);
+            let line = std::str::from_utf8(&line).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+            })?;
+            if parser.feed(line, None)? {
+                let parser = std::mem::replace(&mut self.state, State::Body { at_start: true });
+                let State::Headers(parser) = parser else {
+                    unreachable!("not reading headers");
+                };
+                return Ok(Some(MultipartFrame::PartHeaders(parser.finish(None))));
+            }
+        }
+        if src.len() > MAX_MIME_HEADER_SIZE {
+            return Err(Error::MessageTooLarge(LimitExceeded::new(
+                LimitKind::HeaderSize,
+                MAX_MIME_HEADER_SIZE as u64,
+                src.len() as u64,
+            )));
+        }
+        Ok(None)
+    }
+
+    fn decode_body(&mut self, src: &mut BytesMut) -> Result<Option<MultipartFrame>> {
+        let State::Body { at_start } = &mut self.state else {
+            unreachable!("not reading a body");
+        };
+
+        // An empty body: the delimiter directly follows the header block
+        if *at_start {
+            let n = src.len().min(self.patterns.dash_boundary().len());
+            if src[..n] == self.patterns.dash_boundary()[..n] {
+                if n < self.patterns.dash_boundary().len() {
+                    return Ok(None);
+                }
+                match delimiter(&src[n..]) {
+                    Delimiter::Incomplete => return Ok(None),
+                    Delimiter::None => {}
+                    found => return Ok(Some(self.end_part(src, n, found))),
+                }
+            }
+            *at_start = false;
+        }
+
+        let mut from = 0;
+        while let Some(found) = self.patterns.find_nl_dash_boundary(&src[from..]) {
+            let nl = from + found;
+            let after = nl + self.patterns.nl_dash_boundary().len();
+            let line_break = if nl > 0 && src[nl - 1] == b'\r' { nl - 1 } else { nl };
+            match delimiter(&src[after..]) {
+                Delimiter::None => from = nl + 1,
+                Delimiter::Incomplete if line_break == 0 => return Ok(None),
+                Delimiter::Incomplete => return Ok(Some(body_chunk(src, line_break))),
+                _ if line_break > 0 => return Ok(Some(body_chunk(src, line_break))),
+                found => return Ok(Some(self.end_part(src, after, found))),
+            }
+        }
+
+        // Keep what could be the start of a delimiter's line break
+        let keep = self.patterns.nl_dash_boundary().len();
+        if src.len() > keep {
+            let len = src.len() - keep;
+            return Ok(Some(body_chunk(src, len)));
+        }
+        Ok(None)
+    }
+
+    /// Consumes the delimiter found after `boundary_end` bytes and ends the
+    /// part.
+    fn end_part(&mut self, src: &mut BytesMut, boundary_end: usize, found: Delimiter) -> MultipartFrame {
+        match found {
+            Delimiter::Part(len) => {
+                src.advance(boundary_end + len);
+                self.state = State::Headers(HeaderParser::default());
+            }
+            _ => {
+                src.clear();
+                self.state = State::Closing;
+            }
+        }
+        MultipartFrame::PartEnd
+    }
+}
+
+impl Decoder for MultipartDecoder {
+    type Item = MultipartFrame;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<MultipartFrame>> {
+        match self.state {
+            State::Preamble { .. } => self.decode_preamble(src),
+            State::Headers(_) => self.decode_headers(src),
+            State::Body { .. } => self.decode_body(src),
+            State::Closing => {
+                src.clear();
+                self.state = State::Done;
+                Ok(Some(MultipartFrame::Finished))
+            }
+            State::Done => {
+                // The epilogue
+                src.clear();
+                Ok(None)
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<MultipartFrame>> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if matches!(self.state, State::Done) => Ok(None),
+            None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected EOF").into()),
+        }
+    }
+}
+
+/// Splits off the first `len` bytes of `src` as a body chunk.
+fn body_chunk(src: &mut BytesMut, len: usize) -> MultipartFrame {
+    MultipartFrame::BodyChunk(src.split_to(len).freeze())
+}
+
+/// Classifies what follows `--boundary` at the start of a line: transport
+/// padding and a line break for a delimiter, or `--` for the closing one.
+pub(crate) fn delimiter(rest: &[u8]) -> Delimiter {
+    if rest.starts_with(b"--") {
+        return Delimiter::Closing;
+    }
+    if rest == b"-" {
+        return Delimiter::Incomplete;
+    }
+    let padding = rest.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+    match &rest[padding..] {
+        [] | [b'\r'] => Delimiter::Incomplete,
+        [b'\n', ..] => Delimiter::Part(padding + 1),
+        [b'\r', b'\n', ..] => Delimiter::Part(padding + 2),
+        _ => Delimiter::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes `data` in pieces of `chunk` bytes, joining adjacent body
+    /// chunks.
+    fn decode_all(data: &[u8], chunk: usize) -> Result<Vec<MultipartFrame>> {
+        let mut decoder = MultipartDecoder::new("b");
+        let mut src = BytesMut::new();
+        let mut frames: Vec<MultipartFrame> = Vec::new();
+        let push = |frame: MultipartFrame, frames: &mut Vec<MultipartFrame>| {
+            if let (MultipartFrame::BodyChunk(more), Some(MultipartFrame::BodyChunk(body))) =
+                (&frame, frames.last_mut())
+            {
+                *body = [&body[..], &more[..]].concat().into();
+                return;
+            }
+            frames.push(frame);
+        };
+        for piece in data.chunks(chunk) {
+            src.extend_from_slice(piece);
+            while let Some(frame) = decoder.decode(&mut src)? {
+                push(frame, &mut frames);
+            }
+        }
+        while let Some(frame) = decoder.decode_eof(&mut src)? {
+            push(frame, &mut frames);
+        }
+        Ok(frames)
+    }
+
+    fn headers(entries: &[(&str, &str)]) -> MultipartFrame {
+        MultipartFrame::PartHeaders(entries.iter().copied().collect())
+    }
+
+    fn chunk(body: &'static [u8]) -> MultipartFrame {
+        MultipartFrame::BodyChunk(Bytes::from_static(body))
+    }
+
+    #[test]
+    fn test_frames_at_every_split() {
+        let data: &[u8] = b"preamble\r\n--bX\r\n--b \t\r\nContent-Type: text/plain\r\n\r\n\
+line one\r\n--not\r\n\r\n--b\r\n\r\n--b\r\nX-Empty: 1\r\n\r\n\r\n--b--\r\nepilogue";
+        let expected = vec![
+            headers(&[("Content-Type", "text/plain")]),
+            chunk(b"line one\r\n--not\r\n"),
+            MultipartFrame::PartEnd,
+            headers(&[]),
+            MultipartFrame::PartEnd,
+            headers(&[("X-Empty", "1")]),
+            MultipartFrame::PartEnd,
+            MultipartFrame::Finished,
+        ];
+        for size in 1..=data.len() {
+            assert_eq!(decode_all(data, size).unwrap(), expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_matches_reader() {
+        let data: &[u8] = b"--b\n\nfirst\n--b\nA: 1\n\nsecond\nline\n--b--";
+        let parsed = crate::multipart::parse_multipart_to_vec(data, "b").unwrap();
+        let parts: Vec<_> = parsed
+            .into_iter()
+            .flat_map(|(header, body)| {
+                [
+                    MultipartFrame::PartHeaders(header),
+                    MultipartFrame::BodyChunk(body.into()),
+                    MultipartFrame::PartEnd,
+                ]
+            })
+            .chain([MultipartFrame::Finished])
+            .collect();
+        for size in [1, 3, 7, data.len()] {
+            assert_eq!(decode_all(data, size).unwrap(), parts);
+        }
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        for data in [&b"no delimiter"[..], b"--b\r\nA: 1\r\n", b"--b\r\n\r\nbody\r\n--b"] {
+            assert!(decode_all(data, 4).unwrap_err().is_unexpected_eof());
+        }
+    }
+}
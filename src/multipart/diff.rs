@@ -0,0 +1,266 @@
+//! Structural comparison of multipart messages.
+//!
+//! Useful for verifying that a rewriting proxy or a round trip preserved the
+//! content of a message, independently of the boundaries used.
+
+use super::header::MimeHeader;
+use super::reader::Reader;
+use crate::error::Result;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Length and SHA-256 digest of a part body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyDigest {
+    /// Body length in bytes.
+    pub len: usize,
+    /// SHA-256 digest of the body.
+    pub sha256: [u8; 32],
+}
+
+impl BodyDigest {
+    /// Computes the digest of a body.
+    pub fn of(body: &[u8]) -> Self {
+        Self {
+            len: body.len(),
+            sha256: Sha256::digest(body).into(),
+        }
+    }
+
+    /// Returns the SHA-256 digest as a lowercase hex string.
+    pub fn hex(&self) -> String {
+        self.sha256.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Summary of a part that exists on only one side of a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartSummary {
+    /// The part's headers.
+    pub header: MimeHeader,
+    /// The part's body digest.
+    pub body: BodyDigest,
+}
+
+/// A single difference between two multipart messages.
+///
+/// Parts are compared by position; `index` is the zero-based part index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartChange {
+    /// The part exists only in the second message.
+    Added {
+        /// Part index.
+        index: usize,
+        /// The added part.
+        part: PartSummary,
+    },
+    /// The part exists only in the first message.
+    Removed {
+        /// Part index.
+        index: usize,
+        /// The removed part.
+        part: PartSummary,
+    },
+    /// The values of a header differ (compared case-insensitively by name).
+    HeaderChanged {
+        /// Part index.
+        index: usize,
+        /// Header name, as spelled in the first message if present there.
+        name: String,
+        /// Values in the first message.
+        before: Vec<String>,
+        /// Values in the second message.
+        after: Vec<String>,
+    },
+    /// The part bodies differ.
+    BodyChanged {
+        /// Part index.
+        index: usize,
+        /// Body digest in the first message.
+        before: BodyDigest,
+        /// Body digest in the second message.
+        after: BodyDigest,
+    },
+}
+
+/// The structural difference between two multipart messages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultipartDiff {
+    /// The differences, ordered by part index.
+    pub changes: Vec<PartChange>,
+}
+
+impl MultipartDiff {
+    /// Reports whether the two messages are structurally identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Compares two multipart messages part by part.
+///
+/// Both messages are parsed with `next_raw_part`, so bodies are compared as
+/// transmitted. Preambles, epilogues and boundaries are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::diff;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let a = b"--a\r\nX-Id: 1\r\n\r\nbody\r\n--a--\r\n";
+/// let b = b"--b\r\nX-Id: 1\r\n\r\nbody\r\n--b--\r\n";
+/// let result = diff(&a[..], &b[..], "a", "b").await?;
+/// assert!(result.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn diff<A, B>(a: A, b: B, boundary_a: &str, boundary_b: &str) -> Result<MultipartDiff>
+where
+    A: AsyncRead + Unpin,
+    B: AsyncRead + Unpin,
+{
+    let parts_a = collect_parts(Reader::new(a, boundary_a)).await?;
+    let parts_b = collect_parts(Reader::new(b, boundary_b)).await?;
+
+    let mut changes = Vec::new();
+    let common = parts_a.len().min(parts_b.len());
+
+    for (index, (pa, pb)) in parts_a.iter().zip(&parts_b).enumerate() {
+        diff_headers(index, &pa.header, &pb.header, &mut changes);
+        if pa.body != pb.body {
+            changes.push(PartChange::BodyChanged {
+                index,
+                before: pa.body.clone(),
+                after: pb.body.clone(),
+            });
+        }
+    }
+
+    for (index, part) in parts_a.into_iter().enumerate().skip(common) {
+        changes.push(PartChange::Removed { index, part });
+    }
+    for (index, part) in parts_b.into_iter().enumerate().skip(common) {
+        changes.push(PartChange::Added { index, part });
+    }
+
+    Ok(MultipartDiff { changes })
+}
+
+async fn collect_parts<R: AsyncRead + Unpin>(mut reader: Reader<R>) -> Result<Vec<PartSummary>> {
+    let mut parts = Vec::new();
+    while let Some(mut part) = reader.next_raw_part().await? {
+        let mut body = Vec::new();
+        part.read_to_end(&mut body).await?;
+        parts.push(PartSummary {
+            header: part.header.clone(),
+            body: BodyDigest::of(&body),
+        });
+    }
+    Ok(parts)
+}
+
+fn diff_headers(index: usize, a: &MimeHeader, b: &MimeHeader, changes: &mut Vec<PartChange>) {
+    let mut names: Vec<&str> = Vec::new();
+    for (name, _) in a.iter().chain(b.iter()) {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name);
+        }
+    }
+
+    for name in names {
+        let before: Vec<String> = a.get_all(name).map(str::to_string).collect();
+        let after: Vec<String> = b.get_all(name).map(str::to_string).collect();
+        if before != after {
+            changes.push(PartChange::HeaderChanged {
+                index,
+                name: name.to_string(),
+                before,
+                after,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_diff_identical_with_different_boundaries() {
+        let a = b"--a\r\ncontent-type: text/plain\r\n\r\nhello\r\n--a--\r\n";
+        let b = b"preamble\r\n--bbb\r\nContent-Type: text/plain\r\n\r\nhello\r\n--bbb--\r\n";
+        let result = diff(&a[..], &b[..], "a", "bbb").await.unwrap();
+        assert!(result.is_empty(), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn test_diff_changes() {
+        let a = b"--a\r\n\
+X-Id: 1\r\n\
+X-Old: gone\r\n\
+\r\n\
+one\r\n\
+--a\r\n\
+X-Id: 2\r\n\
+\r\n\
+two\r\n\
+--a--\r\n";
+        let b = b"--b\r\n\
+X-Id: 1\r\n\
+X-New: here\r\n\
+\r\n\
+ONE\r\n\
+--b\r\n\
+X-Id: 2\r\n\
+\r\n\
+two\r\n\
+--b\r\n\
+X-Id: 3\r\n\
+\r\n\
+three\r\n\
+--b--\r\n";
+
+        let result = diff(&a[..], &b[..], "a", "b").await.unwrap();
+        assert_eq!(result.changes.len(), 4);
+
+        assert_eq!(
+            result.changes[0],
+            PartChange::HeaderChanged {
+                index: 0,
+                name: "X-Old".to_string(),
+                before: vec!["gone".to_string()],
+                after: vec![],
+            }
+        );
+        assert!(matches!(
+            &result.changes[1],
+            PartChange::HeaderChanged { index: 0, name, .. } if name == "X-New"
+        ));
+        match &result.changes[2] {
+            PartChange::BodyChanged { index, before, after } => {
+                assert_eq!(*index, 0);
+                assert_eq!(before, &BodyDigest::of(b"one"));
+                assert_eq!(after, &BodyDigest::of(b"ONE"));
+            }
+            other => panic!("unexpected change: {:?}", other),
+        }
+        match &result.changes[3] {
+            PartChange::Added { index, part } => {
+                assert_eq!(*index, 2);
+                assert_eq!(part.header.get("x-id"), Some("3"));
+            }
+            other => panic!("unexpected change: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_body_digest_hex() {
+        let digest = BodyDigest::of(b"");
+        assert_eq!(digest.len, 0);
+        assert_eq!(
+            digest.hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
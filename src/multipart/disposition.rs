@@ -0,0 +1,141 @@
+//! Typed Content-Disposition values (RFC 2183, RFC 7578).
+
+use crate::encoded_word;
+use std::collections::HashMap;
+
+/// A parsed Content-Disposition header value.
+///
+/// Parameters are looked up by lowercase name, as in the rest of the
+/// crate. The names as sent, in their original order, stay available
+/// through [`raw_params`](Self::raw_params) for checks that must see the
+/// header exactly as the peer wrote it, such as signature verification.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::ContentDisposition;
+///
+/// let disposition = ContentDisposition::parse("Form-Data; Name=\"a\"; FileName=\"x.txt\"");
+/// assert_eq!(disposition.disposition_type(), "form-data");
+/// assert_eq!(disposition.param("filename"), Some("x.txt"));
+/// assert_eq!(
+///     disposition.raw_params(),
+///     [("Name".to_string(), "a".to_string()), ("FileName".to_string(), "x.txt".to_string())]
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentDisposition {
+    disposition_type: String,
+    params: HashMap<String, String>,
+    raw_params: Vec<(String, String)>,
+}
+
+impl ContentDisposition {
+    /// Parses a header value of the form `type; name=value; ...`.
+    ///
+    /// Parsing is lenient: parameters without a value are skipped, and if a
+    /// name repeats the last value wins in [`params`](Self::params).
+    pub fn parse(value: &str) -> Self {
+        let (disposition_type, rest) = value.split_once(';').unwrap_or((value, ""));
+        let mut disposition = Self {
+            disposition_type: disposition_type.trim().to_lowercase(),
+            ..Default::default()
+        };
+
+        for param in rest.split(';') {
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+
+            if let Some((raw_key, val)) = param.split_once('=') {
+                let raw_key = raw_key.trim();
+                let key = raw_key.to_lowercase();
+                let val = val.trim();
+
+                // Remove quotes if present
+                let val = if val.starts_with('"') && val.ends_with('"') && val.len() >= 2 {
+                    &val[1..val.len() - 1]
+                } else {
+                    val
+                };
+                disposition
+                    .raw_params
+                    .push((raw_key.to_string(), val.to_string()));
+
+                // Names and filenames written as RFC 2047 encoded-words
+                let val = if val.starts_with("=?") && matches!(key.as_str(), "name" | "filename") {
+                    encoded_word::decode_header(val).unwrap_or_else(|_| val.to_string())
+                } else {
+                    val.to_string()
+                };
+                disposition.params.insert(key, val);
+            }
+        }
+
+        disposition
+    }
+
+    /// Returns the disposition type in lowercase, e.g. "form-data" or
+    /// "attachment".
+    pub fn disposition_type(&self) -> &str {
+        &self.disposition_type
+    }
+
+    /// Returns the value of a parameter, looking `name` up case-insensitively.
+    ///
+    /// RFC 2047 encoded-words in `name` and `filename` are decoded.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        match self.params.get(name) {
+            Some(value) => Some(value),
+            None => self.params.get(&name.to_lowercase()).map(String::as_str),
+        }
+    }
+
+    /// Returns the parameters, keyed by lowercase name.
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    /// Returns the parameters as sent: names in their original casing, in
+    /// order, including repeated names.
+    ///
+    /// Values are unquoted but not otherwise decoded.
+    pub fn raw_params(&self) -> &[(String, String)] {
+        &self.raw_params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized_and_raw_params() {
+        let disposition = ContentDisposition::parse(
+            " ATTACHMENT ; FileName=\"=?utf-8?q?caf=C3=A9.txt?=\"; size=10;flag; SIZE=\"12\"",
+        );
+        assert_eq!(disposition.disposition_type(), "attachment");
+        assert_eq!(disposition.param("FILENAME"), Some("café.txt"));
+        assert_eq!(disposition.param("size"), Some("12"));
+        assert_eq!(disposition.params().len(), 2);
+
+        let raw: Vec<_> = disposition
+            .raw_params()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            raw,
+            vec![
+                ("FileName", "=?utf-8?q?caf=C3=A9.txt?="),
+                ("size", "10"),
+                ("SIZE", "12")
+            ]
+        );
+
+        let empty = ContentDisposition::parse("");
+        assert_eq!(empty.disposition_type(), "");
+        assert!(empty.raw_params().is_empty());
+    }
+}
@@ -2,15 +2,14 @@
 //!
 //! Implements RFC 2388 multipart/form-data processing.
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::multipart::reader::MimeHeader;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
-use tokio::io::AsyncRead;
-
-#[cfg(test)]
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 #[allow(dead_code)]
 const MAX_MEMORY_DEFAULT: usize = 32 << 20; // 32 MB
@@ -36,6 +35,64 @@ impl Form {
         }
     }
 
+    /// Exports the form's values and file metadata as JSON, e.g. to log
+    /// or forward a parsed upload.
+    ///
+    /// Values map to a string, or an array of strings for repeated fields.
+    /// Files map to their metadata, or an array of it for repeated fields:
+    /// the filename, size, Content-Type (`null` if absent) and the SHA-256
+    /// digest of the content in hex. Files kept on disk are read to compute
+    /// the digest.
+    ///
+    /// Requires the `json` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\na\r\n\
+    /// --b\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\nb\r\n--b--\r\n";
+    /// let form = Reader::new(&data[..], "b").read_form(1 << 20).await?;
+    /// let json = form.to_json().await?;
+    /// assert_eq!(json["values"]["tag"], serde_json::json!(["a", "b"]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    pub async fn to_json(&self) -> Result<serde_json::Value> {
+        use serde_json::{Map, Value};
+
+        fn one_or_many(mut items: Vec<Value>) -> Value {
+            if items.len() == 1 {
+                items.pop().unwrap_or_default()
+            } else {
+                Value::Array(items)
+            }
+        }
+
+        let mut values = Map::new();
+        for (name, field) in &self.value {
+            let items = field.iter().map(|v| Value::String(v.clone())).collect();
+            values.insert(name.clone(), one_or_many(items));
+        }
+
+        let mut files = Map::new();
+        for (name, field) in &self.file {
+            let mut items = Vec::with_capacity(field.len());
+            for file in field {
+                items.push(file.to_json().await?);
+            }
+            files.insert(name.clone(), one_or_many(items));
+        }
+
+        let mut json = Map::new();
+        json.insert("values".to_string(), Value::Object(values));
+        json.insert("files".to_string(), Value::Object(files));
+        Ok(Value::Object(json))
+    }
+
     /// Removes all temporary files created during form parsing.
     pub async fn remove_all(&mut self) -> Result<()> {
         for files in self.file.values_mut() {
@@ -66,7 +123,9 @@ pub struct FileHeader {
     /// In-memory content (if file is small enough).
     content: Option<Vec<u8>>,
     /// Temporary file path (if file was written to disk).
-    tmpfile: Option<String>,
+    tmpfile: Option<PathBuf>,
+    /// Whether [`temp_path`](Self::temp_path) returns the path.
+    path_exposed: bool,
 }
 
 impl FileHeader {
@@ -79,20 +138,43 @@ impl FileHeader {
             header,
             content: Some(content),
             tmpfile: None,
+            path_exposed: false,
         }
     }
 
     /// Creates a new FileHeader with temporary file.
     pub fn from_file(filename: String, size: i64, tmpfile: String, header: MimeHeader) -> Self {
+        Self::from_temp_file(filename, size, tmpfile.into(), header, false)
+    }
+
+    /// Creates a FileHeader for a file [`Reader::read_form`](super::Reader::read_form)
+    /// kept on disk.
+    pub(crate) fn from_temp_file(
+        filename: String,
+        size: i64,
+        tmpfile: PathBuf,
+        header: MimeHeader,
+        path_exposed: bool,
+    ) -> Self {
         Self {
             filename,
             size,
             header,
             content: None,
             tmpfile: Some(tmpfile),
+            path_exposed,
         }
     }
 
+    /// Returns the path of the temporary file holding the content, if the
+    /// reader was created with [`ReaderOptions::expose_temp_paths`](super::ReaderOptions::expose_temp_paths).
+    ///
+    /// See that option for why the path is hidden by default. The file is
+    /// removed when the header is dropped, so rename it to keep it.
+    pub fn temp_path(&self) -> Option<&Path> {
+        self.tmpfile.as_deref().filter(|_| self.path_exposed)
+    }
+
     /// Opens the file for reading.
     ///
     /// Returns a reader that can be used to read the file contents.
@@ -110,6 +192,29 @@ impl FileHeader {
         }
     }
 
+    /// Returns the file's metadata as JSON; see [`Form::to_json`].
+    #[cfg(feature = "json")]
+    async fn to_json(&self) -> Result<serde_json::Value> {
+        let mut hasher = Sha256::new();
+        let mut reader = self.open().await?;
+        let mut buf = vec![0u8; 8192];
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        let digest = hex(&hasher.finalize());
+
+        Ok(serde_json::json!({
+            "filename": self.filename,
+            "size": self.size,
+            "content_type": self.header.get("content-type"),
+            "sha256": digest,
+        }))
+    }
+
     /// Removes the temporary file if it exists.
     async fn remove(&mut self) -> Result<()> {
         if let Some(path) = self.tmpfile.take() {
@@ -119,6 +224,93 @@ impl FileHeader {
     }
 }
 
+/// The outcome of [`assemble_upload`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembledUpload {
+    /// The total number of bytes written.
+    pub size: u64,
+    /// The SHA-256 digest of the assembled content, in lowercase hex.
+    pub sha256: String,
+}
+
+/// Streams the chunks of a resumable upload, in order, into `dest` as one
+/// object.
+///
+/// Chunked-upload backends receive a large file as several form uploads;
+/// once all have arrived, this concatenates them without holding the file
+/// in memory. Each chunk must hold the number of bytes its
+/// [`size`](FileHeader::size) declares, catching truncated temporary
+/// files, and if `expected_sha256` is given (in hex, any case) the digest
+/// of the whole must match it. `dest` is flushed at the end.
+///
+/// Chunks are written as they are read, so on error `dest` holds partial
+/// content: write to a staging location and move it into place only on
+/// success.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::{assemble_upload, FileHeader, MimeHeader};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let chunks = vec![
+///     FileHeader::new("part0".to_string(), b"ab".to_vec(), MimeHeader::new()),
+///     FileHeader::new("part1".to_string(), b"c".to_vec(), MimeHeader::new()),
+/// ];
+/// let mut object = Vec::new();
+/// let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+/// let assembled = assemble_upload(&chunks, &mut object, Some(digest)).await?;
+/// assert_eq!(object, b"abc");
+/// assert_eq!(assembled.size, 3);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn assemble_upload<W: AsyncWrite + Unpin>(
+    chunks: &[FileHeader],
+    dest: &mut W,
+    expected_sha256: Option<&str>,
+) -> Result<AssembledUpload> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 8192];
+    let mut size = 0u64;
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut reader = chunk.open().await?;
+        let mut chunk_size = 0u64;
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            dest.write_all(&buf[..n]).await?;
+            chunk_size += n as u64;
+        }
+        if i64::try_from(chunk_size).ok() != Some(chunk.size) {
+            return Err(Error::Multipart(format!(
+                "upload chunk {} ({}) has {} bytes, expected {}",
+                index, chunk.filename, chunk_size, chunk.size
+            )));
+        }
+        size += chunk_size;
+    }
+    dest.flush().await?;
+
+    let sha256 = hex(&hasher.finalize());
+    if let Some(expected) = expected_sha256 {
+        if !expected.eq_ignore_ascii_case(&sha256) {
+            return Err(Error::Multipart(format!(
+                "upload digest mismatch: expected {}, got {}",
+                expected, sha256
+            )));
+        }
+    }
+    Ok(AssembledUpload { size, sha256 })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 impl Drop for FileHeader {
     fn drop(&mut self) {
         // Note: We can't await in Drop, so temporary files are cleaned up via remove_all()
@@ -187,4 +379,85 @@ mod tests {
         // Clean up
         file_header.remove().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_assemble_upload() {
+        let path = std::env::temp_dir().join(format!("yamime-chunk-{}", std::process::id()));
+        tokio::fs::write(&path, b"def").await.unwrap();
+        let chunks = vec![
+            FileHeader::new("0".to_string(), b"abc".to_vec(), MimeHeader::new()),
+            FileHeader::from_file(
+                "1".to_string(),
+                3,
+                path.to_string_lossy().into_owned(),
+                MimeHeader::new(),
+            ),
+            FileHeader::new("2".to_string(), Vec::new(), MimeHeader::new()),
+        ];
+
+        let digest = "BEF57EC7F53A6D40BEB640A780A639C83BC29AC8A9816F1FC6C5C6DCD93C4721";
+        let mut object = Vec::new();
+        let assembled = assemble_upload(&chunks, &mut object, Some(digest)).await.unwrap();
+        assert_eq!(object, b"abcdef");
+        assert_eq!(assembled.size, 6);
+        assert_eq!(assembled.sha256, digest.to_lowercase());
+
+        let err = assemble_upload(&chunks, &mut Vec::new(), Some("00")).await;
+        assert!(matches!(err, Err(Error::Multipart(msg)) if msg.contains("digest mismatch")));
+
+        // A chunk file shorter than recorded
+        tokio::fs::write(&path, b"d").await.unwrap();
+        let err = assemble_upload(&chunks, &mut Vec::new(), None).await;
+        assert!(matches!(err, Err(Error::Multipart(msg)) if msg.contains("chunk 1")));
+
+        // Clean up
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn test_to_json() {
+        let mut form = Form::new();
+        form.value.insert("title".to_string(), vec!["Holiday".to_string()]);
+        form.value.insert("tag".to_string(), vec!["sea".to_string(), "sun".to_string()]);
+
+        let mut header = MimeHeader::new();
+        header.insert("Content-Type", "text/plain");
+        let file = FileHeader::new("a.txt".to_string(), b"abc".to_vec(), header);
+        form.file.insert("doc".to_string(), vec![file]);
+
+        let empty = FileHeader::new("b.bin".to_string(), Vec::new(), MimeHeader::new());
+        let other = FileHeader::new("c.bin".to_string(), Vec::new(), MimeHeader::new());
+        form.file.insert("blobs".to_string(), vec![empty, other]);
+
+        let json = form.to_json().await.unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "values": {"title": "Holiday", "tag": ["sea", "sun"]},
+                "files": {
+                    "doc": {
+                        "filename": "a.txt",
+                        "size": 3,
+                        "content_type": "text/plain",
+                        "sha256": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+                    },
+                    "blobs": [
+                        {
+                            "filename": "b.bin",
+                            "size": 0,
+                            "content_type": null,
+                            "sha256": "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                        },
+                        {
+                            "filename": "c.bin",
+                            "size": 0,
+                            "content_type": null,
+                            "sha256": "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                        },
+                    ],
+                },
+            })
+        );
+    }
 }
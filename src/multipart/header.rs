@@ -0,0 +1,348 @@
+//! MIME header storage.
+//!
+//! Headers keep the order and casing they were received or inserted with,
+//! while lookups by name are case-insensitive.
+
+use crate::encoded_word::{self, WordEncoder};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// MIME header type (similar to HTTP headers).
+///
+/// Entries are stored in insertion order with their original name casing.
+/// Multiple entries may share the same name. Names and values are shared
+/// strings, so cloning a header does not copy them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MimeHeader {
+    entries: Vec<(Arc<str>, Arc<str>)>,
+}
+
+impl MimeHeader {
+    /// Creates an empty header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the first value associated with the given name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::MimeHeader;
+    ///
+    /// let mut header = MimeHeader::new();
+    /// header.insert("Content-Type", "text/plain");
+    /// assert_eq!(header.get("content-type"), Some("text/plain"));
+    /// ```
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| &**v)
+    }
+
+    /// Returns all values associated with the given name, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| &**v)
+    }
+
+    /// Sets the value for the given name, replacing any existing values.
+    ///
+    /// The entry keeps the position of the first existing entry with that
+    /// name, or is appended if the name is new.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name: Arc<str> = name.into().into();
+        let value: Arc<str> = value.into().into();
+
+        match self.position(&name) {
+            Some(pos) => {
+                self.entries[pos] = (name, value);
+                let mut i = pos + 1;
+                while i < self.entries.len() {
+                    if self.entries[i].0.eq_ignore_ascii_case(&self.entries[pos].0) {
+                        self.entries.remove(i);
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            None => self.entries.push((name, value)),
+        }
+    }
+
+    /// Appends a value for the given name, keeping existing values.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.append_shared(name.into().into(), value.into().into());
+    }
+
+    /// Appends an entry whose strings are already shared, e.g. interned.
+    pub(crate) fn append_shared(&mut self, name: Arc<str>, value: Arc<str>) {
+        self.entries.push((name, value));
+    }
+
+    /// Removes all values associated with the given name.
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    /// Reports whether the header contains the given name.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Returns an iterator over all entries in order, with original casing.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (&**k, &**v))
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Reports whether the header has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the Content-Description, with RFC 2047 encoded words decoded.
+    ///
+    /// Values that fail to decode are returned as received.
+    pub fn content_description(&self) -> Option<String> {
+        let value = self.get("content-description")?;
+        Some(encoded_word::decode_header(value).unwrap_or_else(|_| value.to_string()))
+    }
+
+    /// Sets the Content-Description, Q-encoding it if it is not plain ASCII.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::MimeHeader;
+    ///
+    /// let mut header = MimeHeader::new();
+    /// header.set_content_description("Café menu");
+    /// assert_eq!(header.get("Content-Description"), Some("=?utf-8?q?Caf=C3=A9_menu?="));
+    /// assert_eq!(header.content_description().as_deref(), Some("Café menu"));
+    /// ```
+    pub fn set_content_description(&mut self, description: &str) {
+        self.insert(
+            "Content-Description",
+            WordEncoder::QEncoding.encode("utf-8", description),
+        );
+    }
+
+    /// Returns the Content-Location URI (RFC 2557).
+    ///
+    /// Long locations may be folded across lines; the whitespace left by
+    /// unfolding is not part of the URI and is removed.
+    pub fn content_location(&self) -> Option<String> {
+        let location: String = self
+            .get("content-location")?
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        if location.is_empty() {
+            None
+        } else {
+            Some(location)
+        }
+    }
+
+    /// Sets the Content-Location URI.
+    pub fn set_content_location(&mut self, location: &str) {
+        self.insert("Content-Location", location.trim());
+    }
+
+    /// Returns the Content-ID (RFC 2045 section 7), without its angle
+    /// brackets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::MimeHeader;
+    ///
+    /// let mut header = MimeHeader::new();
+    /// header.insert("Content-ID", " <logo@example.com>");
+    /// assert_eq!(header.content_id().as_deref(), Some("logo@example.com"));
+    /// ```
+    pub fn content_id(&self) -> Option<String> {
+        let id = self.get("content-id")?.trim();
+        let id = id
+            .strip_prefix('<')
+            .and_then(|id| id.strip_suffix('>'))
+            .unwrap_or(id)
+            .trim();
+        if id.is_empty() {
+            None
+        } else {
+            Some(id.to_string())
+        }
+    }
+
+    /// Sets the Content-ID, adding angle brackets.
+    pub fn set_content_id(&mut self, id: &str) {
+        let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+        self.insert("Content-ID", format!("<{}>", id));
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for MimeHeader {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut header = MimeHeader::new();
+        for (k, v) in iter {
+            header.append(k, v);
+        }
+        header
+    }
+}
+
+/// Builds a header from a map, sorting names for consistent output.
+impl From<HashMap<String, Vec<String>>> for MimeHeader {
+    fn from(map: HashMap<String, Vec<String>>) -> Self {
+        let mut names: Vec<_> = map.keys().collect();
+        names.sort();
+
+        let mut header = MimeHeader::new();
+        for name in names {
+            for value in &map[name] {
+                header.append(name.as_str(), value.as_str());
+            }
+        }
+        header
+    }
+}
+
+/// Converts into a map of names to values, losing the order of names.
+impl From<MimeHeader> for HashMap<String, Vec<String>> {
+    fn from(header: MimeHeader) -> Self {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, value) in header.entries {
+            map.entry(name.to_string()).or_default().push(value.to_string());
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_insensitive_lookup() {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Type", "text/plain");
+
+        assert_eq!(header.get("content-type"), Some("text/plain"));
+        assert_eq!(header.get("CONTENT-TYPE"), Some("text/plain"));
+        assert!(header.contains_key("Content-type"));
+        assert_eq!(header.get("Content-Length"), None);
+    }
+
+    #[test]
+    fn test_order_and_case_preserved() {
+        let header: MimeHeader = [
+            ("X-Second", "2"),
+            ("content-TYPE", "text/plain"),
+            ("X-First", "1"),
+        ]
+        .into_iter()
+        .collect();
+
+        let entries: Vec<_> = header.iter().collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("X-Second", "2"),
+                ("content-TYPE", "text/plain"),
+                ("X-First", "1")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let mut header = MimeHeader::new();
+        header.append("X-Tag", "a");
+        header.append("Other", "x");
+        header.append("x-tag", "b");
+
+        assert_eq!(header.get_all("X-TAG").collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(header.len(), 3);
+
+        // insert replaces every value but keeps the first position
+        header.insert("X-Tag", "c");
+        let entries: Vec<_> = header.iter().collect();
+        assert_eq!(entries, vec![("X-Tag", "c"), ("Other", "x")]);
+
+        header.remove("x-tag");
+        assert_eq!(header.len(), 1);
+        assert!(!header.contains_key("X-Tag"));
+    }
+
+    #[test]
+    fn test_content_description_and_location() {
+        let mut header = MimeHeader::new();
+        assert_eq!(header.content_description(), None);
+        assert_eq!(header.content_location(), None);
+
+        header.set_content_description("plain text");
+        assert_eq!(header.get("content-description"), Some("plain text"));
+
+        header.insert("Content-Description", "=?ISO-8859-1?q?Andr=E9?= notes");
+        assert_eq!(header.content_description().as_deref(), Some("André notes"));
+
+        // 8-bit bytes are invalid in US-ASCII, so the value is kept as received
+        header.insert("Content-Description", "=?us-ascii?q?caf=E9?=");
+        assert_eq!(header.content_description().as_deref(), Some("=?us-ascii?q?caf=E9?="));
+
+        // Folded location with leftover whitespace
+        header.insert("Content-Location", "http://example.com/a/ very/long\tpath.html");
+        assert_eq!(
+            header.content_location().as_deref(),
+            Some("http://example.com/a/very/longpath.html")
+        );
+
+        header.set_content_location(" cid-free.png ");
+        assert_eq!(header.get("content-location"), Some("cid-free.png"));
+
+        let map: HashMap<String, Vec<String>> = header.into();
+        assert_eq!(map["Content-Location"], vec!["cid-free.png".to_string()]);
+    }
+
+    #[test]
+    fn test_from_map_sorts_names() {
+        let mut map = HashMap::new();
+        map.insert("X-B".to_string(), vec!["1".to_string(), "2".to_string()]);
+        map.insert("X-A".to_string(), vec!["3".to_string()]);
+        let header = MimeHeader::from(map);
+        let entries: Vec<_> = header.iter().collect();
+        assert_eq!(entries, vec![("X-A", "3"), ("X-B", "1"), ("X-B", "2")]);
+    }
+
+    #[test]
+    fn test_content_id() {
+        let mut header = MimeHeader::new();
+        assert_eq!(header.content_id(), None);
+
+        header.insert("Content-ID", "<>");
+        assert_eq!(header.content_id(), None);
+
+        // Bare ids without brackets are accepted as is
+        header.insert("Content-ID", "part1.abc@example.com");
+        assert_eq!(header.content_id().as_deref(), Some("part1.abc@example.com"));
+
+        header.set_content_id("<image001>");
+        assert_eq!(header.get("content-id"), Some("<image001>"));
+        assert_eq!(header.content_id().as_deref(), Some("image001"));
+    }
+}
@@ -0,0 +1,183 @@
+//! Sharing repeated header strings between parts.
+//!
+//! Header names and many values ("Content-Disposition", "form-data",
+//! "text/plain") repeat across every part a service parses. An [`Interner`]
+//! set in [`ReaderOptions::interner`](super::ReaderOptions::interner) hands
+//! out one shared allocation per distinct string instead of a new one per
+//! occurrence.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Default capacity of an [`Interner`], in bytes of string data.
+const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// A shared, size-capped set of interned strings.
+///
+/// Clones share the same set, so one interner can serve many readers, in
+/// any number of tasks. A string that does not fit evicts the least
+/// recently used ones, down to half the capacity, so unique values such as
+/// filenames cannot crowd out the names and values that repeat. The
+/// capacity bounds the memory an adversary can pin by sending distinct
+/// header values; strings longer than it are never held.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::{Interner, Reader, ReaderOptions};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let interner = Interner::new();
+/// for _ in 0..2 {
+///     let data = b"--b\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b--\r\n";
+///     let options = ReaderOptions {
+///         interner: Some(interner.clone()),
+///         ..Default::default()
+///     };
+///     let mut reader = Reader::with_options(&data[..], "b", options);
+///     while reader.next_part().await?.is_some() {}
+/// }
+/// // "Content-Type" and "text/plain", each stored once
+/// assert_eq!(interner.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Interner {
+    table: Arc<Mutex<Table>>,
+}
+
+#[derive(Debug)]
+struct Table {
+    /// Each string held, with the tick it was last used at.
+    strings: HashMap<Arc<str>, u64>,
+    size: usize,
+    capacity: usize,
+    tick: u64,
+}
+
+impl Table {
+    /// Evicts the least recently used strings until at most `target` bytes
+    /// are held.
+    fn evict_to(&mut self, target: usize) {
+        let mut by_use: Vec<_> = self.strings.iter().map(|(s, &used)| (used, s.clone())).collect();
+        by_use.sort_unstable_by_key(|(used, _)| *used);
+        for (_, s) in by_use {
+            if self.size <= target {
+                break;
+            }
+            self.size -= s.len();
+            self.strings.remove(&s);
+        }
+    }
+}
+
+impl Interner {
+    /// Creates an interner holding up to 64 KiB of strings.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates an interner holding up to `capacity` bytes of strings.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: Arc::new(Mutex::new(Table {
+                strings: HashMap::new(),
+                size: 0,
+                capacity,
+                tick: 0,
+            })),
+        }
+    }
+
+    /// Returns the shared copy of `s`, adding it if it fits in the
+    /// capacity.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        let mut table = self.lock();
+        table.tick += 1;
+        let tick = table.tick;
+        if let Some((shared, _)) = table.strings.get_key_value(s) {
+            let shared = shared.clone();
+            table.strings.insert(shared.clone(), tick);
+            return shared;
+        }
+
+        let shared: Arc<str> = Arc::from(s);
+        if s.len() > table.capacity {
+            return shared;
+        }
+        if table.size + s.len() > table.capacity {
+            let target = (table.capacity / 2).min(table.capacity - s.len());
+            table.evict_to(target);
+        }
+        table.size += s.len();
+        table.strings.insert(shared.clone(), tick);
+        shared
+    }
+
+    /// Returns the number of strings held.
+    pub fn len(&self) -> usize {
+        self.lock().strings.len()
+    }
+
+    /// Reports whether no strings are held.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total length in bytes of the strings held.
+    pub fn size(&self) -> usize {
+        self.lock().size
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Table> {
+        self.table.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_and_respects_capacity() {
+        let interner = Interner::with_capacity(10);
+        let a = interner.intern("form-data");
+        let b = interner.intern("form-data");
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(interner.size(), 9);
+
+        // No room left: the least recently used string makes way
+        let c = interner.intern("text/plain");
+        let d = interner.intern("text/plain");
+        assert!(Arc::ptr_eq(&c, &d));
+        assert_eq!((interner.len(), interner.size()), (1, 10));
+        assert!(!Arc::ptr_eq(&interner.intern("form-data"), &a));
+
+        // Too long to ever hold
+        let e = interner.intern("multipart/form-data");
+        assert!(!Arc::ptr_eq(&e, &interner.intern("multipart/form-data")));
+        assert_eq!(interner.len(), 1);
+
+        // Clones share the table
+        assert!(Arc::ptr_eq(&interner.clone().intern("form-data"), &interner.intern("form-data")));
+    }
+
+    #[test]
+    fn test_intern_keeps_recently_used() {
+        let interner = Interner::with_capacity(12);
+        let name = interner.intern("name");
+        for i in 0..100 {
+            // A unique value each time, while the name keeps being used
+            interner.intern(&format!("v{:03}", i));
+            assert!(Arc::ptr_eq(&interner.intern("name"), &name));
+        }
+        assert!(interner.size() <= 12);
+    }
+}
@@ -0,0 +1,185 @@
+//! Tower middleware validating multipart requests.
+//!
+//! Available with the `tower` feature. [`MultipartLayer`] runs a
+//! [`RequestValidator`] on each request before the inner service sees it,
+//! so any tower-based stack (axum, hyper, tonic-web gateways) can adopt it
+//! with one layer. Requests that fail are answered right away, with 415 for
+//! a missing or unexpected Content-Type, 413 for a Content-Length over the
+//! limit and 400 for a bad boundary. Requests that pass carry a
+//! [`ValidatedRequest`](super::ValidatedRequest) in their extensions, from
+//! which the handler creates the [`Reader`](super::Reader) once it reads the
+//! body; nothing is parsed before then.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::multipart::{MultipartLayer, RequestValidator, ValidatedRequest};
+//!
+//! // In the service stack, e.g. with axum's `Router::layer`
+//! let layer = MultipartLayer::new(RequestValidator::new().max_content_length(10 << 20));
+//!
+//! // In the handler
+//! # async fn example(request: http::Request<&'static [u8]>) -> Result<(), Box<dyn std::error::Error>> {
+//! let validated = request.extensions().get::<ValidatedRequest>().cloned().unwrap();
+//! let mut reader = validated.reader(*request.body());
+//! while let Some(mut part) = reader.next_part().await? {
+//!     println!("{:?}", part.form_name());
+//! }
+//! # Ok(())
+//! # }
+//! # let _ = layer;
+//! ```
+
+use super::request::RequestValidator;
+use crate::error::Error;
+use futures::future::{ready, Either, Ready};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A layer checking the headers of multipart requests with a
+/// [`RequestValidator`].
+#[derive(Debug, Clone)]
+pub struct MultipartLayer {
+    validator: RequestValidator,
+}
+
+impl MultipartLayer {
+    /// Creates a layer checking requests with `validator`.
+    pub fn new(validator: RequestValidator) -> Self {
+        Self { validator }
+    }
+}
+
+impl<S> Layer<S> for MultipartLayer {
+    type Service = MultipartService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MultipartService {
+            inner,
+            validator: self.validator.clone(),
+        }
+    }
+}
+
+/// The service created by [`MultipartLayer`].
+///
+/// Rejected requests get a response with an empty body and the status
+/// for the error; accepted ones reach the inner service with a
+/// [`ValidatedRequest`](super::ValidatedRequest) extension.
+#[derive(Debug, Clone)]
+pub struct MultipartService<S> {
+    inner: S,
+    validator: RequestValidator,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for MultipartService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response<ResBody>, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let headers = request.headers();
+        let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok());
+        let content_length = headers
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok());
+
+        match self.validator.check(content_type, content_length) {
+            Ok(validated) => {
+                request.extensions_mut().insert(validated);
+                Either::Left(self.inner.call(request))
+            }
+            Err(err) => {
+                let mut response = Response::new(ResBody::default());
+                *response.status_mut() = rejection_status(&err);
+                Either::Right(ready(Ok(response)))
+            }
+        }
+    }
+}
+
+/// Returns the status a request rejected by [`RequestValidator::check`] is
+/// answered with.
+fn rejection_status(err: &Error) -> StatusCode {
+    match err {
+        Error::MediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        Error::MessageTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multipart::ValidatedRequest;
+    use futures::future::BoxFuture;
+    use std::convert::Infallible;
+
+    /// Answers with the names of the request's form fields.
+    #[derive(Clone)]
+    struct FieldNames;
+
+    impl Service<Request<&'static [u8]>> for FieldNames {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = BoxFuture<'static, Result<Response<String>, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<&'static [u8]>) -> Self::Future {
+            Box::pin(async move {
+                let validated = request.extensions().get::<ValidatedRequest>().cloned().unwrap();
+                let mut reader = validated.reader(*request.body());
+                let mut names = Vec::new();
+                while let Some(mut part) = reader.next_part().await.unwrap() {
+                    names.push(part.form_name().unwrap_or_default().to_string());
+                }
+                Ok(Response::new(names.join(",")))
+            })
+        }
+    }
+
+    async fn send(content_type: &str, content_length: Option<usize>) -> Response<String> {
+        let body = &b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b--\r\n"[..];
+        let mut builder = Request::post("/upload").header(CONTENT_TYPE, content_type);
+        if let Some(length) = content_length {
+            builder = builder.header(CONTENT_LENGTH, length);
+        }
+        let mut service = MultipartLayer::new(RequestValidator::new().max_content_length(100))
+            .layer(FieldNames);
+        futures::future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        service.call(builder.body(body).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_layer_validates_requests() {
+        let response = send("multipart/form-data; boundary=b", Some(50)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "a");
+
+        let rejections = [
+            ("text/plain", None, StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ("multipart/form-data", None, StatusCode::BAD_REQUEST),
+            ("multipart/form-data; boundary=b", Some(101), StatusCode::PAYLOAD_TOO_LARGE),
+        ];
+        for (content_type, content_length, status) in rejections {
+            let response = send(content_type, content_length).await;
+            assert_eq!(response.status(), status, "{}", content_type);
+            assert!(response.body().is_empty());
+        }
+    }
+}
@@ -1,9 +1,49 @@
 //! Multipart MIME parsing and writing.
 
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod body;
+mod boundary;
+pub mod byteranges;
+pub mod codec;
+pub mod diff;
+pub mod disposition;
+pub mod header;
+pub mod intern;
+#[cfg(feature = "tower")]
+pub mod layer;
+mod peek;
+pub mod preserve;
 pub mod reader;
+pub mod request;
+pub mod related;
+pub mod schema;
+mod spill;
+pub mod split;
+pub mod sync;
+pub mod tee;
+pub mod transform;
 pub mod writer;
 pub mod formdata;
 
-pub use reader::{Reader, Part};
-pub use writer::Writer;
-pub use formdata::{Form, FileHeader};
+pub use body::{BodyBuilder, LengthPlanner};
+pub use codec::{MultipartDecoder, MultipartFrame};
+pub use diff::{diff, MultipartDiff, PartChange};
+pub use disposition::ContentDisposition;
+pub use header::MimeHeader;
+pub use intern::Interner;
+#[cfg(feature = "tower")]
+pub use layer::{MultipartLayer, MultipartService};
+pub use reader::{
+    Diagnostics, InvalidUtf8, Part, Reader, ReaderOptions, SkipHook, Skipped, TruncatedHeader, Warning,
+};
+pub use preserve::PartFraming;
+pub use related::RelatedReader;
+pub use schema::{FieldKind, FieldRule, FormSchema, FormViolation};
+pub use request::{RequestValidator, ValidatedRequest};
+pub use split::SharedPart;
+pub use sync::parse_multipart_to_vec;
+pub use tee::Tee;
+pub use transform::{PartBody, PartTransform};
+pub use writer::{LineEnding, TransferEncoding, Writer, WriterBuilder};
+pub use formdata::{assemble_upload, AssembledUpload, FileHeader, Form};
@@ -0,0 +1,239 @@
+//! Buffered input for the multipart reader.
+//!
+//! Unlike [`tokio::io::BufReader`], [`PeekReader`] can be asked to buffer at
+//! least `n` bytes, which lets the boundary scanner look at a whole delimiter
+//! even when it straddles two reads.
+
+use super::tee::Tee;
+use crate::error::{Error, TimeoutKind};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// A buffered reader supporting look-ahead of a requested length.
+pub(crate) struct PeekReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// Total number of bytes consumed so far.
+    consumed: u64,
+    tee: Option<Tee>,
+    /// Consumed bytes not yet written to the tee.
+    tee_pending: Vec<u8>,
+    timeouts: Timeouts,
+}
+
+/// Time limits on waiting for the inner reader.
+///
+/// Timers are created on first use, so a reader can be built outside of a
+/// Tokio runtime.
+#[derive(Default)]
+struct Timeouts {
+    idle: Option<Duration>,
+    deadline: Option<Instant>,
+    idle_sleep: Option<Pin<Box<Sleep>>>,
+    /// The idle timer counts from the current wait rather than an earlier one.
+    idle_armed: bool,
+    deadline_sleep: Option<Pin<Box<Sleep>>>,
+    expired: Option<TimeoutKind>,
+}
+
+impl Timeouts {
+    /// Polls the timers while the inner reader is pending.
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<TimeoutKind> {
+        if let Some(idle) = self.idle {
+            let sleep = self
+                .idle_sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(idle)));
+            if !self.idle_armed {
+                sleep.as_mut().reset(Instant::now() + idle);
+                self.idle_armed = true;
+            }
+            if sleep.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(*self.expired.insert(TimeoutKind::Idle));
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            let sleep = self
+                .deadline_sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if sleep.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(*self.expired.insert(TimeoutKind::Deadline));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// The I/O error carrying a timeout, recovered by [`PeekReader::timed_out`].
+fn timeout_error(kind: TimeoutKind) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, Error::Timeout(kind))
+}
+
+impl<R: AsyncRead + Unpin> PeekReader<R> {
+    /// Creates a reader with the given initial buffer capacity.
+    pub(crate) fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; capacity.max(1)],
+            start: 0,
+            end: 0,
+            consumed: 0,
+            tee: None,
+            tee_pending: Vec::new(),
+            timeouts: Timeouts::default(),
+        }
+    }
+
+    /// Fails reads after waiting `idle` for input, or once `total` has
+    /// passed since now.
+    pub(crate) fn with_timeouts(mut self, idle: Option<Duration>, total: Option<Duration>) -> Self {
+        self.timeouts.idle = idle;
+        self.timeouts.deadline = total.map(|total| Instant::now() + total);
+        self
+    }
+
+    /// Returns the timeout that failed a read, if any.
+    ///
+    /// Once expired, every further read fails too.
+    pub(crate) fn timed_out(&self) -> Option<TimeoutKind> {
+        self.timeouts.expired
+    }
+
+    /// Copies every consumed byte to `tee`.
+    pub(crate) fn with_tee(mut self, tee: Option<Tee>) -> Self {
+        self.tee = tee;
+        self
+    }
+
+    /// Writes out consumed bytes still pending for the tee and flushes it.
+    pub(crate) async fn flush_tee(&mut self) -> io::Result<()> {
+        match &self.tee {
+            Some(tee) => std::future::poll_fn(|cx| tee.poll_flush(cx, &mut self.tee_pending)).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the total number of bytes consumed so far.
+    pub(crate) fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    /// Buffers at least `n` bytes unless EOF is reached first, and returns
+    /// the buffered data.
+    pub(crate) async fn fill_to(&mut self, n: usize) -> io::Result<&[u8]> {
+        while self.end - self.start < n {
+            let read = std::future::poll_fn(|cx| self.poll_read_more(cx)).await?;
+            if read == 0 {
+                break;
+            }
+        }
+        Ok(&self.buf[self.start..self.end])
+    }
+
+    /// Reads more data from the inner reader, after the buffered data.
+    fn poll_read_more(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        if let Some(kind) = self.timeouts.expired {
+            return Poll::Ready(Err(timeout_error(kind)));
+        }
+
+        // Keep the tee caught up before taking in more input
+        if let Some(tee) = &self.tee {
+            ready!(tee.poll_write_pending(cx, &mut self.tee_pending))?;
+        }
+
+        if self.end == self.buf.len() {
+            if self.start > 0 {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
+            } else {
+                let len = self.buf.len();
+                self.buf.resize(len * 2, 0);
+            }
+        }
+
+        let mut read_buf = ReadBuf::new(&mut self.buf[self.end..]);
+        if Pin::new(&mut self.inner).poll_read(cx, &mut read_buf)?.is_pending() {
+            let kind = ready!(self.timeouts.poll_expired(cx));
+            return Poll::Ready(Err(timeout_error(kind)));
+        }
+        let n = read_buf.filled().len();
+        self.end += n;
+        self.timeouts.idle_armed = false;
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for PeekReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self;
+        let available = ready!(this.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        this.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for PeekReader<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.start == this.end {
+            this.start = 0;
+            this.end = 0;
+            ready!(this.poll_read_more(cx))?;
+        }
+        Poll::Ready(Ok(&this.buf[this.start..this.end]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        let amt = amt.min(this.end - this.start);
+        if let Some(tee) = &this.tee {
+            tee.record(&this.buf[this.start..this.start + amt], &mut this.tee_pending);
+        }
+        this.start += amt;
+        this.consumed += amt as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    #[tokio::test]
+    async fn test_fill_to_across_reads() {
+        // A reader yielding one byte per read
+        let (mut tx, rx) = tokio::io::duplex(1);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            tx.write_all(b"hello world").await.unwrap();
+        });
+
+        let mut reader = PeekReader::with_capacity(4, rx);
+        assert_eq!(reader.fill_to(8).await.unwrap(), b"hello wo");
+
+        let mut line = Vec::new();
+        reader.read_until(b' ', &mut line).await.unwrap();
+        assert_eq!(line, b"hello ");
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "world");
+        assert_eq!(reader.consumed(), 11);
+
+        // At EOF, fill_to returns whatever is left
+        assert!(reader.fill_to(1).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+//! Byte-faithful round trips of multipart messages.
+//!
+//! With [`ReaderOptions::preserve`](super::ReaderOptions::preserve) set, a
+//! [`Reader`](super::Reader) records everything parsing normally discards:
+//! the preamble, each delimiter line as received, the raw header block, the
+//! line break before each delimiter, and the closing delimiter with the
+//! epilogue. Handing that back to a [`Writer`](super::Writer) reproduces the
+//! input byte for byte, so gateways can pass signed messages through while
+//! still looking at, or changing, individual parts.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::multipart::{Reader, ReaderOptions, Writer};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let input = b"preamble\r\n--b \r\nX-Folded: a\r\n  b\r\n\r\nbody\n--b--\r\nepilogue";
+//! let options = ReaderOptions {
+//!     preserve: true,
+//!     ..Default::default()
+//! };
+//! let mut reader = Reader::with_options(&input[..], "b", options);
+//!
+//! let mut output = Vec::new();
+//! let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+//! let mut next = reader.next_raw_part().await?;
+//! // The preamble is known once the first part has been found
+//! writer.write_preamble(reader.preamble().unwrap()).await?;
+//! while let Some(mut part) = next {
+//!     let body = part.bytes().await?;
+//!     writer
+//!         .write_preserved_part(&part.header, part.framing().unwrap(), &body)
+//!         .await?;
+//!     next = reader.next_raw_part().await?;
+//! }
+//! writer.close_preserved(reader.closing().unwrap()).await?;
+//! assert_eq!(output, input);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::header::MimeHeader;
+use bytes::Bytes;
+
+/// The framing of a part as received, recorded in preserve mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartFraming {
+    /// The delimiter line, with any transport padding and its line ending.
+    pub delimiter: Bytes,
+    /// The header block, including the blank line ending it.
+    pub header: Bytes,
+    /// The line break between the body and the next delimiter; empty if the
+    /// input ended first.
+    pub line_break: Bytes,
+    /// The header as parsed from `header`, to detect modifications.
+    pub(crate) received: MimeHeader,
+}
+
+impl PartFraming {
+    /// Reports whether `header` is the header this framing was recorded
+    /// with, so the raw header block still matches it.
+    pub fn header_matches(&self, header: &MimeHeader) -> bool {
+        self.received == *header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Reader, ReaderOptions, Writer};
+    use super::*;
+    use crate::error::LimitKind;
+
+    /// Reads `input` in preserve mode and writes it back, changing the
+    /// header of the part at `modify` if given.
+    async fn round_trip(input: &[u8], modify: Option<usize>) -> Vec<u8> {
+        let options = ReaderOptions {
+            preserve: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(input, "b", options);
+
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        let mut next = reader.next_raw_part().await.unwrap();
+        writer.write_preamble(reader.preamble().unwrap()).await.unwrap();
+        let mut index = 0;
+        while let Some(mut part) = next {
+            let body = part.bytes().await.unwrap();
+            let mut header = part.header.clone();
+            if modify == Some(index) {
+                header.insert("X-Modified", "yes");
+            }
+            writer
+                .write_preserved_part(&header, part.framing().unwrap(), &body)
+                .await
+                .unwrap();
+            index += 1;
+            next = reader.next_raw_part().await.unwrap();
+        }
+        writer.close_preserved(reader.closing().unwrap()).await.unwrap();
+        output
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_is_byte_faithful() {
+        let input: &[u8] = b"This is the preamble.\r\n\
+--b \t\r\nContent-Type: text/plain;\r\n  charset=us-ascii\r\nX-Empty:\r\n\r\nfirst\r\n\
+--b\r\n\r\nheaderless\r\n\
+--b\r\ncontent-transfer-encoding: quoted-printable\r\n\r\n=41 stays encoded\r\n\
+--b--  \r\nthe epilogue\r\n";
+        assert_eq!(round_trip(input, None).await, input);
+
+        let input: &[u8] = b"--b\nX-Lf: only\n\nfirst\n--b\n\nsecond\n--b--\n";
+        assert_eq!(round_trip(input, None).await, input);
+
+        // No preamble, no epilogue, truncated closing delimiter line
+        let input: &[u8] = b"--b\r\n\r\nonly\r\n--b--";
+        assert_eq!(round_trip(input, None).await, input);
+    }
+
+    #[tokio::test]
+    async fn test_modified_header_is_regenerated() {
+        let input: &[u8] = b"--b\r\nX-A:  one\r\n\r\nfirst\r\n--b \r\nX-B:\ttwo\r\n\r\nsecond\r\n--b--\r\n";
+        let output = round_trip(input, Some(1)).await;
+        assert_eq!(
+            output,
+            &b"--b\r\nX-A:  one\r\n\r\nfirst\r\n--b \r\nX-B: two\r\nX-Modified: yes\r\n\r\nsecond\r\n--b--\r\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_epilogue_round_trip() {
+        use tokio::io::AsyncWriteExt;
+
+        let input: &[u8] = b"--b\r\n\r\nbody\r\n--b--\r\nthe epilogue\r\n--b\r\n";
+        let options = ReaderOptions {
+            preserve: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(input, "b", options);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let body = part.bytes().await.unwrap();
+        drop(part);
+        assert!(reader.epilogue().is_none());
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert_eq!(reader.epilogue(), Some(&b"the epilogue\r\n--b\r\n"[..]));
+
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.create_part(MimeHeader::new()).await.unwrap().write_all(&body).await.unwrap();
+        writer.close_with_epilogue(reader.epilogue().unwrap()).await.unwrap();
+        assert_eq!(output, input);
+
+        // Nothing after the closing delimiter
+        let options = ReaderOptions {
+            preserve: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&b"--b\r\n\r\nx\r\n--b--"[..], "b", options);
+        while reader.next_part().await.unwrap().is_some() {}
+        assert_eq!(reader.epilogue(), Some(&b""[..]));
+
+        // The epilogue counts towards the message size
+        let mut input = b"--b\r\n\r\nx\r\n--b--\r\n".to_vec();
+        input.resize(1 << 20, b'x');
+        let options = ReaderOptions {
+            preserve: true,
+            max_message_size: Some(1024),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&input[..], "b", options);
+        assert!(reader.next_part().await.unwrap().is_some());
+        let err = reader.next_part().await.err().unwrap();
+        let limit = err.limit_exceeded().unwrap();
+        assert_eq!((limit.kind, limit.observed), (LimitKind::MessageSize, 1025));
+        assert!(reader.epilogue().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_framing_absent_without_preserve() {
+        let input: &[u8] = b"preamble\r\n--b\r\n\r\nbody\r\n--b--\r\n";
+        let mut reader = Reader::new(input, "b");
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.framing().is_none());
+        drop(part);
+        while reader.next_part().await.unwrap().is_some() {}
+        assert!(reader.preamble().is_none());
+        assert!(reader.closing().is_none());
+        assert!(reader.epilogue().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mixed_with_normal_parts() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        let framing = PartFraming {
+            delimiter: Bytes::from_static(b"--b  \n"),
+            header: Bytes::from_static(b"\n"),
+            line_break: Bytes::from_static(b"\n"),
+            received: MimeHeader::new(),
+        };
+        writer.create_part(Default::default()).await.unwrap();
+        writer
+            .write_preserved_part(&MimeHeader::new(), &framing, b"kept")
+            .await
+            .unwrap();
+        writer.create_part(Default::default()).await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(
+            output,
+            &b"--b\r\n\r\n\r\n--b  \n\nkept\n--b\r\n\r\n\r\n--b--\r\n"[..]
+        );
+
+        // Delimiters of a longer boundary, or closing ones, are replaced
+        for delimiter in [&b"--bc\r\n"[..], b"--b--\r\n", b"--b x\r\n"] {
+            let mut output = Vec::new();
+            let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+            let framing = PartFraming {
+                delimiter: Bytes::copy_from_slice(delimiter),
+                ..framing.clone()
+            };
+            writer
+                .write_preserved_part(&MimeHeader::new(), &framing, b"kept")
+                .await
+                .unwrap();
+            writer.close().await.unwrap();
+            assert_eq!(output, &b"--b\r\n\nkept\n--b--\r\n"[..]);
+        }
+
+        // A preamble must come first
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.create_part(Default::default()).await.unwrap();
+        assert!(writer.write_preamble(b"late\r\n").await.is_err());
+    }
+}
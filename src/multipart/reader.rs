@@ -2,35 +2,561 @@
 //!
 //! Implements RFC 2046 multipart parsing with async I/O.
 
-use crate::error::{Error, Result};
-use pin_project::pin_project;
+use crate::error::{Error, LimitExceeded, LimitKind, Result};
+use crate::limits::Limits;
+use crate::media_type::{decode_rfc2231_value, parse_media_type};
+use crate::quotedprintable;
+use super::boundary::BoundaryPatterns;
+use super::byteranges::ContentRange;
+use super::disposition::ContentDisposition;
+use super::intern::Interner;
+use super::peek::PeekReader;
+use super::preserve::PartFraming;
+use super::spill::{Spill, SpilledBody};
+use super::tee::Tee;
+use super::transform::{PartBody, PartTransform};
+use super::writer::validate_boundary;
+use bytes::Bytes;
+use once_cell::unsync::OnceCell;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, BufReader, ReadBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, ReadBuf};
+use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
 
-const PEEK_BUFFER_SIZE: usize = 4096;
-const MAX_MIME_HEADER_SIZE: usize = 10 << 20; // 10 MB
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+pub(crate) const MAX_MIME_HEADER_SIZE: usize = 10 << 20; // 10 MB
 const MAX_MIME_HEADERS: usize = 10000;
+const MAX_PART_SIZE: usize = 32 << 20; // 32 MB
+const MAX_NESTING_DEPTH: usize = 10;
+const SPILL_CHUNK_SIZE: usize = 64 * 1024;
 
-/// MIME header type (similar to HTTP headers).
-pub type MimeHeader = HashMap<String, Vec<String>>;
+pub use super::header::MimeHeader;
+
+/// Options controlling how a multipart [`Reader`] parses its input.
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    /// Token that aborts parsing when cancelled.
+    ///
+    /// Once triggered, `next_part` and reads from part bodies return
+    /// [`Error::Cancelled`] instead of waiting for more input.
+    pub cancellation: Option<CancellationToken>,
+
+    /// Tolerate common malformations instead of failing.
+    ///
+    /// In lenient mode, delimiter lines may end in either CRLF or a bare LF,
+    /// surrounding whitespace is trimmed from the boundary, boundaries RFC
+    /// 2046 does not allow are accepted, and input ending without the
+    /// closing delimiter ends the message like a final boundary.
+    /// Each tolerated problem is recorded in [`Diagnostics::warnings`].
+    pub lenient: bool,
+
+    /// Normalize header values after unfolding.
+    ///
+    /// Values are trimmed and each run of spaces and tabs is collapsed to a
+    /// single space, except inside quoted strings. The values as received
+    /// remain available through [`Part::original_header`].
+    pub normalize_header_values: bool,
+
+    /// Maximum number of multipart bodies nested inside the top-level one.
+    ///
+    /// [`Part::into_multipart`] fails with [`Error::NestingTooDeep`] beyond
+    /// this depth, guarding against maliciously deep nesting. Defaults to 10.
+    pub max_nesting_depth: usize,
+
+    /// Maximum number of bytes read from the input across all parts.
+    ///
+    /// Preamble, delimiters, headers and bodies all count. Once exceeded,
+    /// parsing fails with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::MessageSize`]. Unlimited by default; each part is still
+    /// capped at 32 MB.
+    pub max_message_size: Option<u64>,
+
+    /// Maximum number of parts in the message.
+    ///
+    /// Reading past the limit fails with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::PartCount`], stopping messages of millions of tiny parts.
+    /// Unlimited by default; [`Reader::read_form`] then stops at 1000
+    /// parts, like Go.
+    pub max_parts: Option<usize>,
+
+    /// Maximum size in bytes of a part's header block.
+    ///
+    /// Larger blocks fail with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::HeaderSize`]. Defaults to 10 MB when unset.
+    pub max_header_size: Option<usize>,
+
+    /// Maximum number of headers in a part.
+    ///
+    /// More headers fail with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::HeaderCount`]. Defaults to 10000 when unset.
+    pub max_headers: Option<usize>,
+
+    /// Maximum size in bytes of a part body.
+    ///
+    /// Larger bodies fail with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::PartSize`]. When unset, bodies kept in memory are capped
+    /// at 32 MB and [spilled](Self::spill_threshold) ones are unlimited; a
+    /// limit set here applies to both.
+    pub max_part_size: Option<u64>,
+
+    /// Maximum number of bytes skipped as preamble before the first
+    /// delimiter.
+    ///
+    /// A client sending the wrong boundary otherwise has its whole body
+    /// read as preamble before parsing fails at the end of the input. Past
+    /// the limit, parsing fails with [`Error::BoundaryNotFound`], which a
+    /// server can answer with a 400 right away. Unlimited by default.
+    pub max_preamble_size: Option<u64>,
+
+    /// Maximum size of a decoded part body, as a multiple of its encoded
+    /// size.
+    ///
+    /// Applies to bodies decoded by [`Reader::next_part`] or a
+    /// [`PartTransform`], such as quoted-printable or compressed bodies.
+    /// Reading past the limit fails with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::DecodedSize`], stopping decompression bombs. Unlimited
+    /// by default.
+    pub max_expansion_ratio: Option<u64>,
+
+    /// Maximum length in bytes of a header line, after unfolding.
+    ///
+    /// Longer lines fail with [`Error::MessageTooLarge`] of kind
+    /// [`LimitKind::HeaderLineLength`]. In [lenient](Self::lenient) mode the
+    /// value is truncated to the limit instead, recorded in
+    /// [`Diagnostics::truncated_headers`], and the part is still returned.
+    /// Unlimited by default; each header block is still capped at 10 MB.
+    pub max_header_line_length: Option<usize>,
+
+    /// How header lines that are not valid UTF-8 are handled.
+    ///
+    /// RFC 7578 requires form field names and filenames to be UTF-8. By
+    /// default a malformed header fails the read; see [`InvalidUtf8`].
+    pub invalid_header_utf8: InvalidUtf8,
+
+    /// Receives a copy of every byte consumed from the input.
+    ///
+    /// See [`Tee`]. Bytes after the closing delimiter are not consumed and
+    /// so not copied.
+    pub tee: Option<Tee>,
+
+    /// Size in bytes above which a part body is moved to a temporary file.
+    ///
+    /// Spilled parts read back from disk transparently, are not subject to
+    /// the 32 MB per-part limit, and delete their file when dropped. By
+    /// default every body is kept in memory.
+    pub spill_threshold: Option<usize>,
+
+    /// Prefix of the names of temporary files, both for spilled parts and
+    /// for files [`Reader::read_form`] keeps on disk.
+    ///
+    /// Defaults to "multipart-". The prefix is followed by a Unix
+    /// timestamp, the process id and a random UUID, so names never carry
+    /// anything from the message, such as the uploaded filename. Files are
+    /// created exclusively in the system temporary directory, readable only
+    /// by their owner on Unix. A prefix with a path separator or `..`
+    /// fails the read that would create a file.
+    pub temp_file_prefix: String,
+
+    /// Let [`FileHeader::temp_path`](super::FileHeader::temp_path) return
+    /// the paths of files [`Reader::read_form`] keeps on disk.
+    ///
+    /// Off by default: the path is only an address in a shared directory,
+    /// and code handed it tends to log it, return it to clients or reopen
+    /// it later, by which time it may have been removed or replaced. Read
+    /// the content through [`FileHeader::open`](super::FileHeader::open)
+    /// instead, and only opt in to move the file somewhere permanent.
+    pub expose_temp_paths: bool,
+
+    /// Shares repeated header names and values across parts.
+    ///
+    /// See [`Interner`]; one interner is typically shared by every reader
+    /// in a service, and nested readers from [`Part::into_multipart`] use
+    /// it too.
+    pub interner: Option<Interner>,
+
+    /// Longest wait for more input before failing.
+    ///
+    /// Reading fails with [`Error::Timeout`] of kind
+    /// [`Idle`](crate::TimeoutKind::Idle) once the input has produced nothing
+    /// for this long, e.g. a client that stalls mid-part. Time spent by the
+    /// caller between reads does not count. Unlimited by default.
+    pub idle_timeout: Option<Duration>,
+
+    /// Time allowed for parsing the whole message, from the creation of the
+    /// reader.
+    ///
+    /// Unlike `idle_timeout`, this also stops clients that trickle input
+    /// slowly: reading fails with [`Error::Timeout`] of kind
+    /// [`Deadline`](crate::TimeoutKind::Deadline) once it has passed.
+    /// Unlimited by default.
+    pub parse_timeout: Option<Duration>,
+
+    /// Record the framing of the message so it can be written back byte for
+    /// byte.
+    ///
+    /// See [`preserve`](super::preserve). Parts carry their
+    /// [`framing`](Part::framing), and the reader keeps its
+    /// [`preamble`](Reader::preamble) and [`closing`](Reader::closing); at
+    /// the end of the message the rest of the input is read as the epilogue.
+    /// Use [`Reader::next_raw_part`] so bodies stay as received.
+    pub preserve: bool,
+
+    /// Called with every piece of input skipped while parsing: preamble
+    /// lines, transport padding, and malformations tolerated in lenient
+    /// mode.
+    ///
+    /// Unlike [`Diagnostics::warnings`], which records each kind of problem
+    /// once, the hook sees every occurrence, so a gateway can log or meter
+    /// how dirty its traffic is.
+    pub on_skip: Option<SkipHook>,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        Self {
+            cancellation: None,
+            lenient: false,
+            normalize_header_values: false,
+            max_nesting_depth: MAX_NESTING_DEPTH,
+            max_message_size: None,
+            max_parts: None,
+            max_header_size: None,
+            max_headers: None,
+            max_part_size: None,
+            max_preamble_size: None,
+            max_expansion_ratio: None,
+            max_header_line_length: None,
+            invalid_header_utf8: InvalidUtf8::default(),
+            tee: None,
+            spill_threshold: None,
+            temp_file_prefix: "multipart-".to_string(),
+            expose_temp_paths: false,
+            interner: None,
+            idle_timeout: None,
+            parse_timeout: None,
+            preserve: false,
+            on_skip: None,
+        }
+    }
+}
+
+impl ReaderOptions {
+    /// Applies the limits that are set in `limits`, leaving the other
+    /// options as they are.
+    ///
+    /// `max_total` becomes [`max_message_size`](Self::max_message_size),
+    /// `max_nesting` [`max_nesting_depth`](Self::max_nesting_depth) and
+    /// `max_line_len` [`max_header_line_length`](Self::max_header_line_length);
+    /// the others map to the options of the same name.
+    /// `max_decoded_size` and `max_decoded_word` do not apply to readers.
+    pub fn limits(mut self, limits: &Limits) -> Self {
+        if let Some(limit) = limits.max_header_bytes {
+            self.max_header_size = Some(limit);
+        }
+        if let Some(limit) = limits.max_headers {
+            self.max_headers = Some(limit);
+        }
+        if let Some(limit) = limits.max_part_size {
+            self.max_part_size = Some(limit);
+        }
+        if let Some(limit) = limits.max_parts {
+            self.max_parts = Some(limit);
+        }
+        if let Some(limit) = limits.max_total {
+            self.max_message_size = Some(limit);
+        }
+        if let Some(limit) = limits.max_nesting {
+            self.max_nesting_depth = limit;
+        }
+        if let Some(limit) = limits.max_line_len {
+            self.max_header_line_length = Some(limit);
+        }
+        self
+    }
+
+    /// Copies the raw input into `writer` while parsing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{Reader, ReaderOptions};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let archive = tokio::fs::File::create("message.eml").await?;
+    /// let options = ReaderOptions::default().tee(archive);
+    /// # let _ = options;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tee<W: tokio::io::AsyncWrite + Send + 'static>(mut self, writer: W) -> Self {
+        self.tee = Some(Tee::writer(writer));
+        self
+    }
+}
+
+/// How a [`Reader`] handles header lines that are not valid UTF-8, set in
+/// [`ReaderOptions::invalid_header_utf8`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Fail with an I/O error of kind
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData).
+    #[default]
+    Reject,
+    /// Replace each invalid sequence with U+FFFD and record
+    /// [`Warning::InvalidUtf8`]; the damage stays visible in field names
+    /// and filenames rather than silently altering them.
+    Replace,
+}
+
+/// A malformation tolerated by a [`Reader`] in lenient mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    /// A delimiter line ended in a bare LF while the message uses CRLF.
+    BareLineFeed,
+    /// The boundary was surrounded by whitespace, which was trimmed.
+    PaddedBoundary,
+    /// The boundary is longer than 70 characters or has characters RFC
+    /// 2046 does not allow.
+    InvalidBoundary,
+    /// The input ended without the closing `--boundary--` delimiter.
+    MissingFinalBoundary,
+    /// A header line longer than
+    /// [`ReaderOptions::max_header_line_length`] was truncated.
+    TruncatedHeader,
+    /// Invalid UTF-8 in a header line was replaced, as set by
+    /// [`InvalidUtf8::Replace`].
+    InvalidUtf8,
+}
+
+/// Input skipped by a [`Reader`], reported to [`ReaderOptions::on_skip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Skipped<'a> {
+    /// A line of the preamble, with its line ending.
+    Preamble(&'a [u8]),
+    /// A blank line before a delimiter.
+    BlankLine,
+    /// Whitespace between the boundary and the end of a delimiter line.
+    Padding(&'a [u8]),
+    /// A malformation tolerated in lenient mode.
+    Tolerated(Warning),
+}
+
+/// A callback for [`ReaderOptions::on_skip`].
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use yamime::multipart::{Reader, ReaderOptions, SkipHook, Skipped};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let preamble_bytes = Arc::new(AtomicUsize::new(0));
+/// let counter = preamble_bytes.clone();
+/// let options = ReaderOptions {
+///     on_skip: Some(SkipHook::new(move |skipped| {
+///         if let Skipped::Preamble(line) = skipped {
+///             counter.fetch_add(line.len(), Ordering::Relaxed);
+///         }
+///     })),
+///     ..Default::default()
+/// };
+///
+/// let data = b"junk\r\n--b\r\n\r\nbody\r\n--b--\r\n";
+/// let mut reader = Reader::with_options(&data[..], "b", options);
+/// while reader.next_part().await?.is_some() {}
+/// assert_eq!(preamble_bytes.load(Ordering::Relaxed), 6);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SkipHook(Arc<dyn Fn(Skipped<'_>) + Send + Sync>);
+
+impl SkipHook {
+    /// Creates a hook calling `f`.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Skipped<'_>) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    fn call(&self, skipped: Skipped<'_>) {
+        (self.0)(skipped)
+    }
+}
+
+impl std::fmt::Debug for SkipHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SkipHook")
+    }
+}
+
+/// Information collected by a multipart [`Reader`] while parsing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The closing `--boundary--` delimiter has been seen.
+    pub final_boundary_seen: bool,
+    /// The input ended before the closing delimiter was seen.
+    ///
+    /// Truncated messages are typically interrupted uploads that are worth
+    /// retrying, as opposed to complete but otherwise malformed ones.
+    pub truncated: bool,
+
+    /// Problems tolerated in lenient mode, each recorded once.
+    pub warnings: Vec<Warning>,
+
+    /// Header values truncated in lenient mode, in the order met.
+    pub truncated_headers: Vec<TruncatedHeader>,
+}
+
+/// A header value truncated to [`ReaderOptions::max_header_line_length`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedHeader {
+    /// The index of the part, counting from 0.
+    pub part_index: usize,
+    /// The header name, as received, or the start of the line kept if the
+    /// limit falls within the name.
+    pub name: String,
+    /// The length of the unfolded line before truncation, as far as it was
+    /// read before the next header started.
+    pub length: usize,
+}
+
+impl Diagnostics {
+    fn warn(&mut self, warning: Warning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+}
 
 /// A multipart MIME reader.
 pub struct Reader<R> {
-    buf_reader: BufReader<R>,
-    boundary: Vec<u8>,
-    nl: Vec<u8>,               // "\r\n" or "\n"
-    nl_dash_boundary: Vec<u8>, // nl + "--boundary"
-    dash_boundary_dash: Vec<u8>, // "--boundary--"
-    dash_boundary: Vec<u8>,    // "--boundary"
+    buf_reader: PeekReader<R>,
+    options: Arc<ReaderOptions>,
+    /// Why the boundary cannot be used, reported by the first read.
+    boundary_error: Option<String>,
+    nl: &'static [u8], // "\r\n" or "\n"
+    patterns: BoundaryPatterns,
     parts_read: usize,
+    transforms: Vec<Arc<dyn PartTransform>>,
+    diagnostics: Diagnostics,
+    state: ParseState,
+    /// Number of multipart bodies enclosing this one.
+    depth: usize,
+    /// Framing outside the parts, recorded in preserve mode.
+    preserved: Option<Preserved>,
+}
+
+/// The framing of a message outside its parts, recorded in preserve mode.
+#[derive(Debug, Default)]
+struct Preserved {
+    preamble: Vec<u8>,
+    /// The closing delimiter line and the epilogue.
+    closing: Vec<u8>,
+    /// Where the epilogue starts in `closing`.
+    epilogue_start: usize,
+    /// The epilogue has been read to the end of the input.
+    complete: bool,
+}
+
+/// The role of a line read while looking for a delimiter, in preserve mode.
+#[derive(Debug, Clone, Copy)]
+enum FramingLine {
+    Preamble,
+    Delimiter,
+    Closing,
+}
+
+/// Nesting position of a part, checked by [`Part::into_multipart`].
+#[derive(Debug, Clone)]
+struct Nesting {
+    /// Depth of the reader that produced the part.
+    depth: usize,
+    /// Options of that reader, inherited by nested readers.
+    options: Arc<ReaderOptions>,
+}
+
+/// Where the reader is within the current part.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Stage {
+    /// Looking for the next delimiter line.
+    #[default]
+    Delimiter,
+    /// Reading the part's header block.
+    Headers,
+    /// Reading the part's body up to the next delimiter.
+    Body,
+}
+
+/// Progress of the part being parsed, kept across `next_part` calls.
+struct ParseState {
+    stage: Stage,
+    /// The line being read, possibly incomplete.
+    line: Vec<u8>,
+    /// A blank line after a body was seen; the next line must be a delimiter.
+    expect_new_part: bool,
+    header: HeaderParser,
+    /// The parsed header, and the header as received if normalized.
+    part_header: Option<(MimeHeader, Option<MimeHeader>)>,
+    data: Vec<u8>,
+    at_line_start: bool,
+    /// Once the body has been read, the line break that ended it before the
+    /// delimiter; empty if the input ended first.
+    body_end: Option<&'static [u8]>,
+    /// In preserve mode, the delimiter line and raw header block read so far.
+    raw_delimiter: Vec<u8>,
+    raw_header: Vec<u8>,
+    /// The temporary file the body is being written to, once spilled.
+    spill: Option<Spill>,
+    /// Bytes of preamble skipped before the first delimiter.
+    preamble_size: u64,
+    /// The size past which the body is dropped as it is read rather than
+    /// kept, and the number of bytes dropped.
+    body_cap: Option<u64>,
+    body_dropped: u64,
+}
+
+impl Default for ParseState {
+    fn default() -> Self {
+        Self {
+            stage: Stage::default(),
+            line: Vec::new(),
+            expect_new_part: false,
+            header: HeaderParser::default(),
+            part_header: None,
+            data: Vec::new(),
+            at_line_start: true,
+            body_end: None,
+            raw_delimiter: Vec::new(),
+            raw_header: Vec::new(),
+            spill: None,
+            preamble_size: 0,
+            body_cap: None,
+            body_dropped: 0,
+        }
+    }
 }
 
 impl<R: AsyncRead + Unpin> Reader<R> {
     /// Creates a new multipart reader with the given boundary.
     ///
+    /// The boundary may be any string or byte type, such as `&str`,
+    /// `String`, `Cow<str>` or `&[u8]`, and may be given as taken from a
+    /// Content-Type value: surrounding quotes are removed, along with the
+    /// backslashes of quoted pairs. A boundary RFC 2046 does not allow (empty, over 70 characters
+    /// or with characters outside its `bchars` set) makes the first read
+    /// fail; in [lenient](ReaderOptions::lenient) mode only an empty one
+    /// does, and the others are tolerated with
+    /// [`Warning::InvalidBoundary`].
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -42,34 +568,232 @@ impl<R: AsyncRead + Unpin> Reader<R> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(r: R, boundary: &str) -> Self {
-        let b = format!("\r\n--{}--", boundary).into_bytes();
-        let nl = b[0..2].to_vec();
-        let nl_dash_boundary = b[0..b.len() - 2].to_vec();
-        let dash_boundary_dash = b[2..].to_vec();
-        let dash_boundary = b[2..b.len() - 2].to_vec();
+    pub fn new(r: R, boundary: impl AsRef<[u8]>) -> Self {
+        Self::with_options(r, boundary, ReaderOptions::default())
+    }
+
+    /// Creates a new multipart reader with the given boundary and options.
+    pub fn with_options(r: R, boundary: impl AsRef<[u8]>, mut options: ReaderOptions) -> Self {
+        let mut diagnostics = Diagnostics::default();
+        let mut tolerate = |warning| {
+            if let Some(hook) = &options.on_skip {
+                hook.call(Skipped::Tolerated(warning));
+            }
+            diagnostics.warn(warning);
+        };
+
+        let boundary = unquote(boundary.as_ref());
+        let boundary = match trim_whitespace(&boundary) {
+            trimmed if options.lenient && trimmed.len() != boundary.len() => {
+                tolerate(Warning::PaddedBoundary);
+                trimmed.to_vec()
+            }
+            _ => boundary,
+        };
+
+        let mut boundary_error = None;
+        if boundary.is_empty() {
+            boundary_error = Some("boundary is empty".to_string());
+        } else if let Err(Error::Multipart(err)) = validate_boundary(&boundary) {
+            if options.lenient {
+                tolerate(Warning::InvalidBoundary);
+            } else {
+                boundary_error = Some(err);
+            }
+        }
 
+        let options_preserve = options.preserve;
+        // Parts share the options, and must not keep the tee open
+        let tee = options.tee.take();
         Self {
-            buf_reader: BufReader::with_capacity(PEEK_BUFFER_SIZE, r),
-            boundary: boundary.as_bytes().to_vec(),
-            nl,
-            nl_dash_boundary,
-            dash_boundary_dash,
-            dash_boundary,
+            buf_reader: PeekReader::with_capacity(READ_BUFFER_SIZE, r)
+                .with_tee(tee)
+                .with_timeouts(options.idle_timeout, options.parse_timeout),
+            options: Arc::new(options),
+            boundary_error,
+            nl: b"\r\n",
+            patterns: BoundaryPatterns::new(&boundary),
             parts_read: 0,
+            transforms: Vec::new(),
+            diagnostics,
+            state: ParseState::default(),
+            depth: 0,
+            preserved: options_preserve.then(Preserved::default),
         }
     }
 
+    /// Registers a transformation applied to the bodies of matching parts.
+    ///
+    /// Transforms apply to parts returned by `next_part` (and `read_form`),
+    /// in registration order; `next_raw_part` returns bodies untransformed.
+    pub fn add_transform<T: PartTransform + 'static>(&mut self, transform: T) {
+        self.transforms.push(Arc::new(transform));
+    }
+
+    /// Reports whether the message ended with its closing boundary.
+    ///
+    /// Only meaningful once `next_part` has returned `None` or an error: it
+    /// distinguishes a complete message from one whose input simply stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\n\r\nhello\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// while reader.next_part().await?.is_some() {}
+    /// assert!(reader.finished_cleanly());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn finished_cleanly(&self) -> bool {
+        self.diagnostics.final_boundary_seen
+    }
+
+    /// Reports whether the closing `--boundary--` delimiter has been seen.
+    ///
+    /// A connection that drops right after a part ends looks, to a lenient
+    /// reader, like the end of the message; this tells the two apart. Same
+    /// as [`finished_cleanly`](Self::finished_cleanly).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{Reader, ReaderOptions};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// // The upload stopped after the first part
+    /// let data = b"--b\r\n\r\nhello\r\n--b\r\n";
+    /// let options = ReaderOptions {
+    ///     lenient: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = Reader::with_options(&data[..], "b", options);
+    /// while reader.next_part().await?.is_some() {}
+    /// assert!(!reader.is_complete());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_complete(&self) -> bool {
+        self.diagnostics.final_boundary_seen
+    }
+
+    /// Returns the number of parts found so far.
+    ///
+    /// A part counts once its delimiter has been read, including a part
+    /// whose headers were only peeked.
+    pub fn parts_read(&self) -> usize {
+        self.parts_read
+    }
+
+    /// Returns the number of bytes consumed from the input so far.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.buf_reader.consumed()
+    }
+
+    /// Returns the bytes before the first delimiter line, in preserve mode.
+    ///
+    /// Complete once the first call to `next_part` has returned.
+    pub fn preamble(&self) -> Option<&[u8]> {
+        self.preserved.as_ref().map(|p| &p.preamble[..])
+    }
+
+    /// Returns the closing delimiter line and the epilogue that follows it,
+    /// in preserve mode, once `next_part` has returned `None`.
+    pub fn closing(&self) -> Option<&[u8]> {
+        self.preserved
+            .as_ref()
+            .filter(|p| p.complete)
+            .map(|p| &p.closing[..])
+    }
+
+    /// Returns the epilogue: the bytes after the closing delimiter line, in
+    /// preserve mode, once `next_part` has returned `None`.
+    ///
+    /// Pass it to [`Writer::close_with_epilogue`](super::Writer::close_with_epilogue)
+    /// to write it back after a regenerated closing delimiter.
+    pub fn epilogue(&self) -> Option<&[u8]> {
+        self.preserved
+            .as_ref()
+            .filter(|p| p.complete)
+            .map(|p| &p.closing[p.epilogue_start..])
+    }
+
+    /// Returns the diagnostics collected so far.
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
     /// Returns the next part in the multipart message.
     ///
-    /// Returns `None` when there are no more parts.
+    /// Returns `None` when there are no more parts. Like Go's `NextPart`, a
+    /// body with `Content-Transfer-Encoding: quoted-printable` is decoded and
+    /// that header removed; use [`next_raw_part`](Self::next_raw_part) to
+    /// keep the encoding.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. If the returned future is dropped before
+    /// completing, e.g. as a losing branch of `tokio::select!`, the input
+    /// consumed so far is kept and the next call resumes parsing from there.
     pub async fn next_part(&mut self) -> Result<Option<Part<R>>> {
-        self.next_part_internal(false).await
+        self.next_part_cancellable(false).await
+    }
+
+    /// Reads the header of the next part without reading its body.
+    ///
+    /// Lets routing logic inspect e.g. the Content-Type or filename before
+    /// committing to the body; the following `next_part` or `next_raw_part`
+    /// call returns the peeked part. Returns `None` at the end of the message.
+    ///
+    /// Like [`next_part`](Self::next_part), this method is cancel safe.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Type: image/png\r\n\r\n...\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    ///
+    /// let header = reader.peek_next_headers().await?.unwrap();
+    /// assert_eq!(header.get("content-type"), Some("image/png"));
+    ///
+    /// let part = reader.next_part().await?.unwrap();
+    /// assert_eq!(part.header.get("content-type"), Some("image/png"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn peek_next_headers(&mut self) -> Result<Option<&MimeHeader>> {
+        let found = match self.options.cancellation.clone() {
+            None => self.read_headers().await,
+            Some(token) => {
+                if token.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => return Err(Error::Cancelled),
+                    result = self.read_headers() => result,
+                }
+            }
+        };
+        let found = found.map_err(|e| self.timeout_error(e))?;
+        if !found {
+            return Ok(None);
+        }
+        Ok(self.state.part_header.as_ref().map(|(header, _)| header))
     }
 
-    /// Returns the next part without decoding quoted-printable.
+    /// Returns the next part without decoding quoted-printable or applying
+    /// transforms, like Go's `NextRawPart`.
+    ///
+    /// Like [`next_part`](Self::next_part), this method is cancel safe.
     pub async fn next_raw_part(&mut self) -> Result<Option<Part<R>>> {
-        self.next_part_internal(true).await
+        self.next_part_cancellable(true).await
     }
 
     /// Parses the entire multipart form.
@@ -91,115 +815,451 @@ impl<R: AsyncRead + Unpin> Reader<R> {
     /// # }
     /// ```
     pub async fn read_form(&mut self, max_memory: usize) -> Result<super::formdata::Form> {
+        self.read_form_checked(max_memory, None).await
+    }
+
+    /// Parses the entire multipart form like [`read_form`](Self::read_form),
+    /// checking it against `schema`.
+    ///
+    /// Each part's header is checked before its body is read. The body of a
+    /// part that violates the schema is dropped as it is read, and that of
+    /// a part over its size limit once past the limit, or four times the
+    /// limit for a quoted-printable body. Once the whole form is read,
+    /// any violations fail it with [`Error::InvalidForm`], after removing
+    /// the temporary files created for it; a form with too many parts fails
+    /// right away.
+    ///
+    /// See [`schema`](super::schema) for an example.
+    pub async fn read_form_with_schema(
+        &mut self,
+        max_memory: usize,
+        schema: &super::schema::FormSchema,
+    ) -> Result<super::formdata::Form> {
+        self.read_form_checked(max_memory, Some(schema)).await
+    }
+
+    async fn read_form_checked(
+        &mut self,
+        max_memory: usize,
+        schema: Option<&super::schema::FormSchema>,
+    ) -> Result<super::formdata::Form> {
         use super::formdata::{FileHeader, Form};
+        use super::schema::{FieldKind, FormViolation};
         use tokio::io::AsyncReadExt;
 
         let mut form = Form::new();
+        let mut violations = Vec::new();
         let mut parts_count = 0;
-        const MAX_PARTS: usize = 1000;
+        let max_parts = match schema {
+            Some(schema) => schema.max_part_count(),
+            None => self.options.max_parts.unwrap_or(1000),
+        };
 
-        while let Some(mut part) = self.next_part().await? {
+        loop {
+            if let Some(schema) = schema {
+                let Some(header) = self.peek_next_headers().await? else {
+                    break;
+                };
+                let cap = schema_body_cap(schema, header);
+                self.state.body_cap = cap;
+            }
+            let Some(mut part) = self.next_part().await? else {
+                break;
+            };
             parts_count += 1;
-            if parts_count > MAX_PARTS {
-                return Err(Error::MessageTooLarge);
+            if parts_count > max_parts {
+                return Err(Error::MessageTooLarge(
+                    LimitExceeded::new(LimitKind::PartCount, max_parts as u64, parts_count as u64)
+                        .with_part_index(parts_count - 1),
+                ));
             }
 
             let name = match part.form_name() {
                 Some(n) => n.to_string(),
                 None => continue, // Skip parts without a name
             };
-
             let filename = part.file_name();
 
-            if filename.is_none() {
-                // Regular form field - read into memory
-                let mut value = String::new();
-                part.read_to_string(&mut value).await?;
-                form.value.entry(name).or_insert_with(Vec::new).push(value);
-            } else {
-                // File upload
-                let filename = filename.unwrap();
-                let mut content = Vec::new();
-                part.read_to_end(&mut content).await?;
+            let mut max_size = None;
+            if let Some(schema) = schema {
+                let kind = if filename.is_some() { FieldKind::File } else { FieldKind::Value };
+                let violation = match schema.check_part(&name, kind) {
+                    // Dropped past its cap as it was read
+                    Ok(Some(limit)) if part.body_dropped => {
+                        Some(FormViolation::TooLarge { field: name.clone(), limit })
+                    }
+                    Ok(limit) => {
+                        max_size = limit;
+                        None
+                    }
+                    Err(violation) => Some(violation),
+                };
+                if let Some(violation) = violation {
+                    if !violations.contains(&violation) {
+                        violations.push(violation);
+                    }
+                    continue;
+                }
+            }
+
+            // Read one byte past the limit to tell whether it is exceeded
+            let mut content = Vec::new();
+            (&mut part)
+                .take(max_size.map_or(u64::MAX, |limit| limit.saturating_add(1)))
+                .read_to_end(&mut content)
+                .await?;
+            if let Some(limit) = max_size.filter(|&limit| content.len() as u64 > limit) {
+                let violation = FormViolation::TooLarge { field: name, limit };
+                if !violations.contains(&violation) {
+                    violations.push(violation);
+                }
+                continue;
+            }
 
+            if let Some(filename) = filename {
+                // File upload
                 let file_header = if content.len() <= max_memory {
                     // Keep in memory
-                    FileHeader::new(filename, content, part.header.clone())
+                    FileHeader::new(filename, content, part.take_header())
                 } else {
                     // Write to temporary file
                     use tokio::io::AsyncWriteExt;
 
-                    let tmpfile = format!("/tmp/multipart-{}-{}",
-                        std::process::id(),
-                        uuid::Uuid::new_v4()
+                    let (mut file, path) =
+                        super::spill::create_temp_file(&self.options.temp_file_prefix).await?;
+                    let file_header = FileHeader::from_temp_file(
+                        filename,
+                        content.len() as i64,
+                        path,
+                        part.take_header(),
+                        self.options.expose_temp_paths,
                     );
-
-                    let mut file = tokio::fs::File::create(&tmpfile).await?;
+                    // The file is removed with the header if writing fails
                     file.write_all(&content).await?;
                     file.flush().await?;
-                    drop(file);
-
-                    FileHeader::from_file(
-                        filename,
-                        content.len() as i64,
-                        tmpfile,
-                        part.header.clone(),
-                    )
+                    file_header
                 };
 
-                form.file.entry(name).or_insert_with(Vec::new).push(file_header);
+                form.file.entry(name).or_default().push(file_header);
+            } else {
+                // Regular form field - read into memory
+                let value = String::from_utf8(content).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+                })?;
+                form.value.entry(name).or_default().push(value);
             }
         }
 
+        if let Some(schema) = schema {
+            let missing = schema.missing(|name| {
+                form.value.contains_key(name) || form.file.contains_key(name)
+            });
+            violations.extend(missing.into_iter().map(|name| FormViolation::Missing(name.to_string())));
+        }
+        if !violations.is_empty() {
+            form.remove_all().await?;
+            return Err(Error::InvalidForm(violations));
+        }
+
         Ok(form)
     }
 
-    async fn next_part_internal(&mut self, raw_part: bool) -> Result<Option<Part<R>>> {
-        if self.boundary.is_empty() {
-            return Err(Error::Multipart("boundary is empty".to_string()));
+    async fn next_part_cancellable(&mut self, raw_part: bool) -> Result<Option<Part<R>>> {
+        let token = match self.options.cancellation.clone() {
+            Some(token) => token,
+            None => {
+                let result = self.next_part_internal(raw_part).await;
+                return result.map_err(|e| self.timeout_error(e));
+            }
+        };
+
+        if token.is_cancelled() {
+            return Err(Error::Cancelled);
         }
 
-        let mut expect_new_part = false;
+        let result = tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(Error::Cancelled),
+            result = self.next_part_internal(raw_part) => result,
+        };
+        result.map_err(|e| self.timeout_error(e))
+    }
 
-        loop {
-            let mut line = Vec::new();
-            match self.buf_reader.read_until(b'\n', &mut line).await {
-                Ok(0) => {
-                    // EOF
-                    if self.is_final_boundary(&line) {
-                        return Ok(None);
-                    }
-                    return Err(Error::Io(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "unexpected EOF",
-                    )));
-                }
-                Ok(_) => {}
-                Err(e) => {
-                    if e.kind() == io::ErrorKind::UnexpectedEof && self.is_final_boundary(&line) {
-                        return Ok(None);
-                    }
-                    return Err(Error::Io(e));
-                }
-            }
+    /// Reports a read failed by a timeout as [`Error::Timeout`] rather than
+    /// as the I/O error carrying it.
+    fn timeout_error(&self, err: Error) -> Error {
+        match (&err, self.buf_reader.timed_out()) {
+            (Error::Io(_), Some(kind)) => Error::Timeout(kind),
+            _ => err,
+        }
+    }
 
-            if self.is_boundary_delimiter_line(&line) {
-                self.parts_read += 1;
-                let part = Part::new(
-                    &mut self.buf_reader,
-                    raw_part,
-                    &self.dash_boundary,
-                    &self.nl_dash_boundary,
-                )
-                .await?;
-                return Ok(Some(part));
+    /// Parses the next part.
+    ///
+    /// Every piece of partially parsed input lives in `self.state` rather than
+    /// in locals, and is only acted upon once complete, so dropping this future
+    /// at any await point loses nothing: the next call resumes where it left off.
+    async fn next_part_internal(&mut self, raw_part: bool) -> Result<Option<Part<R>>> {
+        if !self.read_headers().await? {
+            return Ok(None);
+        }
+
+        let part_index = self.parts_read - 1;
+        read_part_data(&mut self.buf_reader, &self.patterns, &mut self.state, &self.options)
+            .await
+            .map_err(|e| with_part_index(e, part_index))?;
+
+        // The body ends where a delimiter starts, or at the end of input
+        let is_final = if self.state.body_end == Some(b"") {
+            false
+        } else {
+            let closing = self.patterns.dash_boundary_dash();
+            self.buf_reader.fill_to(closing.len()).await?.starts_with(closing)
+        };
+
+        let state = std::mem::take(&mut self.state);
+        let (mut header, original_header) = state.part_header.unwrap_or_default();
+        let raw_header = Bytes::from(state.raw_header);
+        let framing = self.options.preserve.then(|| PartFraming {
+            delimiter: Bytes::from(state.raw_delimiter),
+            header: raw_header.clone(),
+            line_break: Bytes::from_static(state.body_end.unwrap_or_default()),
+            received: header.clone(),
+        });
+
+        let encoded_len = state.data.len() as u64 + state.spill.as_ref().map_or(0, Spill::len);
+        let decode_limit = self.options.max_expansion_ratio.map(|ratio| DecodeLimit {
+            limit: encoded_len.saturating_mul(ratio),
+            part_index,
+        });
+        let cancellation = self.options.cancellation.clone();
+        let mut body = match state.spill {
+            Some(spill) => Body::Spilled(spill.into_body(cancellation)),
+            None => Body::Plain(PartReader::new(Bytes::from(state.data), cancellation)),
+        };
+        if !raw_part {
+            const CTE: &str = "content-transfer-encoding";
+            if header
+                .get(CTE)
+                .is_some_and(|cte| cte.eq_ignore_ascii_case("quoted-printable"))
+            {
+                header.remove(CTE);
+                body = Body::Transformed(Box::pin(quotedprintable::Reader::new(body.into_reader())));
+            }
+        }
+        let transforms: &[_] = if raw_part { &[] } else { &self.transforms };
+        let mut part = Part::new(
+            header,
+            original_header,
+            body,
+            transforms,
+            decode_limit,
+            Nesting {
+                depth: self.depth,
+                options: self.options.clone(),
+            },
+        );
+        let blank_line = if raw_header.ends_with(b"\r\n") {
+            2
+        } else {
+            usize::from(raw_header.ends_with(b"\n"))
+        };
+        part.raw_headers = raw_header.slice(..raw_header.len() - blank_line);
+        part.framing = framing;
+        part.is_final = is_final;
+        part.body_dropped = state.body_dropped > 0;
+        Ok(Some(part))
+    }
+
+    /// Parses up to the body of the next part, leaving its header in
+    /// `self.state.part_header`.
+    ///
+    /// Returns `false` at the end of the message. Like `next_part_internal`,
+    /// this is cancel safe, and does nothing if the header was already read.
+    async fn read_headers(&mut self) -> Result<bool> {
+        if let Some(err) = &self.boundary_error {
+            return Err(Error::Multipart(err.clone()));
+        }
+
+        if self.diagnostics.final_boundary_seen {
+            self.read_epilogue().await?;
+            return Ok(false);
+        }
+
+        loop {
+            match self.state.stage {
+                Stage::Delimiter => {
+                    if !self.read_delimiter().await? {
+                        self.read_epilogue().await?;
+                        self.buf_reader.flush_tee().await?;
+                        return Ok(false);
+                    }
+                    if let Some(limit) = self.options.max_parts {
+                        if self.parts_read >= limit {
+                            return Err(Error::MessageTooLarge(
+                                LimitExceeded::new(
+                                    LimitKind::PartCount,
+                                    limit as u64,
+                                    self.parts_read as u64 + 1,
+                                )
+                                .with_part_index(self.parts_read),
+                            ));
+                        }
+                    }
+                    self.parts_read += 1;
+                    self.state.header = HeaderParser::with_options(&self.options);
+                    self.state.stage = Stage::Headers;
+                }
+                Stage::Headers => {
+                    let part_index = self.parts_read - 1;
+                    let state = &mut self.state;
+                    let mut replaced_utf8 = false;
+                    loop {
+                        let max_message_size = self.options.max_message_size;
+                        let buffered = state.line.len();
+                        let parser = &state.header;
+                        let (line, cap) = (&mut state.line, parser.line_cap());
+                        let read = read_line(&mut self.buf_reader, line, cap, |reader, line| {
+                            check_message_size(reader, max_message_size)?;
+                            parser.check_partial_line(line)
+                        })
+                        .await
+                        .map_err(|e| with_part_index(e, part_index))?;
+                        // Bytes past the truncation limit that were never buffered
+                        let dropped = buffered + read - state.line.len();
+                        if dropped > 0 {
+                            trim_partial_char(&mut state.line);
+                        }
+                        state.raw_header.extend_from_slice(&state.line);
+                        let line = match std::str::from_utf8(&state.line) {
+                            Ok(line) => Cow::Borrowed(line),
+                            Err(_) if self.options.invalid_header_utf8 == InvalidUtf8::Replace => {
+                                replaced_utf8 = true;
+                                String::from_utf8_lossy(&state.line)
+                            }
+                            Err(_) => {
+                                return Err(Error::Io(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "stream did not contain valid UTF-8",
+                                )))
+                            }
+                        };
+                        let done = state
+                            .header
+                            .feed_line(&line, dropped, self.options.interner.as_ref())
+                            .map_err(|e| with_part_index(e, part_index))?;
+                        state.line.clear();
+                        if done {
+                            break;
+                        }
+                    }
+
+                    if replaced_utf8 {
+                        self.warn(Warning::InvalidUtf8);
+                    }
+                    let mut parser = std::mem::take(&mut self.state.header);
+                    // The last line may be truncated too
+                    parser.flush_pending(self.options.interner.as_ref());
+                    for (name, length) in std::mem::take(&mut parser.truncated) {
+                        self.diagnostics.truncated_headers.push(TruncatedHeader {
+                            part_index,
+                            name,
+                            length,
+                        });
+                        self.warn(Warning::TruncatedHeader);
+                    }
+
+                    let interner = self.options.interner.as_ref();
+                    let mut header = parser.finish(interner);
+                    let original_header = if self.options.normalize_header_values {
+                        let mut normalized = MimeHeader::new();
+                        for (name, value) in header.iter() {
+                            push_header(&mut normalized, name, &normalize_header_value(value), interner);
+                        }
+                        Some(std::mem::replace(&mut header, normalized))
+                    } else {
+                        None
+                    };
+                    self.state.part_header = Some((header, original_header));
+                    self.state.stage = Stage::Body;
+                }
+                Stage::Body => return Ok(true),
+            }
+        }
+    }
+
+    /// Reads lines up to the next delimiter line.
+    ///
+    /// Returns `false` at the end of the message.
+    async fn read_delimiter(&mut self) -> Result<bool> {
+        loop {
+            let max_message_size = self.options.max_message_size;
+            // Preamble limits apply before the first delimiter
+            let preamble_limit = self.options.max_preamble_size.filter(|_| self.parts_read == 0);
+            let preamble_size = self.state.preamble_size;
+            let patterns = &self.patterns;
+            let line = &mut self.state.line;
+            let read = read_line(&mut self.buf_reader, line, usize::MAX, |reader, line| {
+                check_message_size(reader, max_message_size)?;
+                match preamble_limit {
+                    // Delimiter lines are not preamble, however long their padding
+                    Some(limit)
+                        if preamble_size + line.len() as u64 > limit
+                            && !may_be_delimiter(line, patterns.dash_boundary()) =>
+                    {
+                        Err(boundary_not_found(patterns, preamble_size + line.len() as u64))
+                    }
+                    _ => Ok(()),
+                }
+            })
+            .await;
+            let line = std::mem::take(&mut self.state.line);
+            match read {
+                Ok(0) => {
+                    // EOF
+                    self.preserve_line(&line, FramingLine::Closing);
+                    if self.is_final_boundary(&line) {
+                        self.report_padding(&line, self.patterns.dash_boundary_dash().len());
+                        self.diagnostics.final_boundary_seen = true;
+                        return Ok(false);
+                    }
+                    self.diagnostics.truncated = true;
+                    if self.options.lenient {
+                        self.warn(Warning::MissingFinalBoundary);
+                        return Ok(false);
+                    }
+                    return Err(Error::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "unexpected EOF",
+                    )));
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    if err.is_unexpected_eof() && self.is_final_boundary(&line) {
+                        self.report_padding(&line, self.patterns.dash_boundary_dash().len());
+                        self.preserve_line(&line, FramingLine::Closing);
+                        self.diagnostics.final_boundary_seen = true;
+                        return Ok(false);
+                    }
+                    return Err(err);
+                }
+            }
+
+            if self.is_boundary_delimiter_line(&line) {
+                self.report_padding(&line, self.patterns.dash_boundary().len());
+                self.preserve_line(&line, FramingLine::Delimiter);
+                self.state.expect_new_part = false;
+                return Ok(true);
             }
 
             if self.is_final_boundary(&line) {
-                return Ok(None);
+                self.report_padding(&line, self.patterns.dash_boundary_dash().len());
+                self.preserve_line(&line, FramingLine::Closing);
+                self.diagnostics.final_boundary_seen = true;
+                return Ok(false);
             }
 
-            if expect_new_part {
+            if self.state.expect_new_part {
                 return Err(Error::Multipart(format!(
                     "expecting a new Part; got line {:?}",
                     String::from_utf8_lossy(&line)
@@ -207,12 +1267,17 @@ impl<R: AsyncRead + Unpin> Reader<R> {
             }
 
             if self.parts_read == 0 {
-                // Skip preamble
+                // Skip preamble; read_line has checked its size
+                self.state.preamble_size += line.len() as u64;
+                self.report_skip(Skipped::Preamble(&line));
+                self.preserve_line(&line, FramingLine::Preamble);
                 continue;
             }
 
             if line == self.nl {
-                expect_new_part = true;
+                self.report_skip(Skipped::BlankLine);
+                self.preserve_line(&line, FramingLine::Delimiter);
+                self.state.expect_new_part = true;
                 continue;
             }
 
@@ -223,238 +1288,1033 @@ impl<R: AsyncRead + Unpin> Reader<R> {
         }
     }
 
-    fn is_final_boundary(&self, line: &[u8]) -> bool {
-        if !line.starts_with(&self.dash_boundary_dash) {
+    fn report_skip(&self, skipped: Skipped<'_>) {
+        if let Some(hook) = &self.options.on_skip {
+            hook.call(skipped);
+        }
+    }
+
+    /// Reports the transport padding of a delimiter line, after the first
+    /// `boundary_len` bytes.
+    fn report_padding(&self, line: &[u8], boundary_len: usize) {
+        let rest = &line[boundary_len..];
+        let padding = rest.strip_suffix(b"\n").unwrap_or(rest);
+        let padding = padding.strip_suffix(b"\r").unwrap_or(padding);
+        if !padding.is_empty() {
+            self.report_skip(Skipped::Padding(padding));
+        }
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        self.report_skip(Skipped::Tolerated(warning));
+        self.diagnostics.warn(warning);
+    }
+
+    /// Records a line read by `read_delimiter`, in preserve mode.
+    fn preserve_line(&mut self, line: &[u8], role: FramingLine) {
+        let Some(preserved) = &mut self.preserved else {
+            return;
+        };
+        match role {
+            FramingLine::Preamble => preserved.preamble.extend_from_slice(line),
+            FramingLine::Delimiter => self.state.raw_delimiter.extend_from_slice(line),
+            FramingLine::Closing => {
+                preserved.closing.append(&mut self.state.raw_delimiter);
+                preserved.closing.extend_from_slice(line);
+            }
+        }
+    }
+
+    /// Reads the rest of the input after the end of the message as the
+    /// epilogue, in preserve mode.
+    ///
+    /// The epilogue counts towards `max_message_size`, and no more than is
+    /// left of it is buffered.
+    async fn read_epilogue(&mut self) -> Result<()> {
+        let Some(preserved) = &mut self.preserved else {
+            return Ok(());
+        };
+        if preserved.complete {
+            return Ok(());
+        }
+        preserved.epilogue_start = preserved.closing.len();
+        loop {
+            // One byte past the limit is enough to fail
+            let consumed = self.buf_reader.consumed();
+            let remaining = self
+                .options
+                .max_message_size
+                .map_or(u64::MAX, |limit| (limit + 1).saturating_sub(consumed));
+            let buf = self.buf_reader.fill_buf().await?;
+            if buf.is_empty() {
+                break;
+            }
+            let take = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+            preserved.closing.extend_from_slice(&buf[..take]);
+            self.buf_reader.consume(take);
+            check_message_size(&self.buf_reader, self.options.max_message_size)?;
+        }
+        preserved.complete = true;
+        Ok(())
+    }
+
+    fn is_final_boundary(&mut self, line: &[u8]) -> bool {
+        if !line.starts_with(self.patterns.dash_boundary_dash()) {
             return false;
         }
-        let rest = &line[self.dash_boundary_dash.len()..];
+        let rest = &line[self.patterns.dash_boundary_dash().len()..];
         let rest = skip_lwsp_char(rest);
-        rest.is_empty() || rest == self.nl
+        rest.is_empty() || self.is_line_end(rest)
     }
 
     fn is_boundary_delimiter_line(&mut self, line: &[u8]) -> bool {
-        if !line.starts_with(&self.dash_boundary) {
+        if !line.starts_with(self.patterns.dash_boundary()) {
             return false;
         }
-        let rest = &line[self.dash_boundary.len()..];
+        let rest = &line[self.patterns.dash_boundary().len()..];
         let rest = skip_lwsp_char(rest);
 
         // On the first part, check if lines end in \n instead of \r\n
         if self.parts_read == 0 && rest.len() == 1 && rest[0] == b'\n' {
-            self.nl = vec![b'\n'];
-            self.nl_dash_boundary = [b"\n".as_ref(), &self.dash_boundary].concat();
+            self.nl = b"\n";
+        }
+
+        self.is_line_end(rest)
+    }
+
+    /// Reports whether `rest` is the line ending of a delimiter line.
+    fn is_line_end(&mut self, rest: &[u8]) -> bool {
+        if rest == self.nl {
+            return true;
+        }
+        if self.options.lenient && rest == b"\n" {
+            self.warn(Warning::BareLineFeed);
+            return true;
         }
+        false
+    }
+}
 
-        rest == self.nl
+impl<S, E> Reader<StreamReader<S, Bytes>>
+where
+    S: futures::Stream<Item = std::result::Result<Bytes, E>> + Unpin,
+    E: Into<io::Error>,
+{
+    /// Creates a multipart reader over a stream of byte chunks.
+    ///
+    /// HTTP libraries such as hyper, axum and reqwest expose request and
+    /// response bodies as streams of [`Bytes`]; this parses them directly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let chunks = vec![
+    ///     Ok::<_, std::io::Error>(Bytes::from_static(b"--b\r\n\r\nhel")),
+    ///     Ok(Bytes::from_static(b"lo\r\n--b--\r\n")),
+    /// ];
+    /// let mut reader = Reader::from_stream(futures::stream::iter(chunks), "b");
+    /// let mut part = reader.next_part().await?.unwrap();
+    /// assert_eq!(&part.bytes().await?[..], b"hello");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_stream(stream: S, boundary: impl AsRef<[u8]>) -> Self {
+        Self::new(StreamReader::new(stream), boundary)
     }
 }
 
 /// A single part in a multipart message.
-#[pin_project]
+///
+/// Reading a part yields its body, up to but excluding the line break that
+/// precedes the next delimiter.
 pub struct Part<R> {
     /// The MIME headers of this part.
+    ///
+    /// Empty for a part whose first line is blank, which RFC 2046 allows.
     pub header: MimeHeader,
 
-    #[pin]
-    reader: PartReader<R>,
+    /// The header as received, when `header` holds normalized values.
+    original_header: Option<MimeHeader>,
+
+    body: Body,
+
+    disposition: OnceCell<Option<ContentDisposition>>,
+    content_type: OnceCell<Option<(String, HashMap<String, String>)>>,
+    nesting: Nesting,
+    raw_headers: Bytes,
+    framing: Option<PartFraming>,
+    is_final: bool,
+    /// The body went past the cap set by `read_form_with_schema` and was
+    /// dropped.
+    body_dropped: bool,
+    _reader: std::marker::PhantomData<R>,
+}
+
+/// The body of a part, either read directly or through transforms.
+enum Body {
+    Plain(PartReader),
+    Spilled(SpilledBody),
+    Transformed(PartBody),
+}
 
-    disposition: Option<String>,
-    disposition_params: Option<HashMap<String, String>>,
+impl Body {
+    fn into_reader(self) -> PartBody {
+        match self {
+            Body::Plain(reader) => Box::pin(reader),
+            Body::Spilled(reader) => Box::pin(reader),
+            Body::Transformed(inner) => inner,
+        }
+    }
 }
 
 impl<R: AsyncRead + Unpin> Part<R> {
-    async fn new(
-        buf_reader: &mut BufReader<R>,
-        _raw_part: bool,
-        dash_boundary: &[u8],
-        nl_dash_boundary: &[u8],
-    ) -> Result<Self> {
-        // Read headers
-        let header = read_mime_header(buf_reader).await?;
-
-        // Read part body into memory until boundary
-        let data = read_part_data(buf_reader, dash_boundary, nl_dash_boundary).await?;
-        let reader = PartReader::new(data);
-
-        Ok(Self {
+    fn new(
+        mut header: MimeHeader,
+        original_header: Option<MimeHeader>,
+        mut body: Body,
+        transforms: &[Arc<dyn PartTransform>],
+        decode_limit: Option<DecodeLimit>,
+        nesting: Nesting,
+    ) -> Self {
+        for transform in transforms {
+            if transform.matches(&header) {
+                body = Body::Transformed(transform.transform(&header, body.into_reader()));
+                transform.update_header(&mut header);
+            }
+        }
+        if let (Body::Transformed(_), Some(limit)) = (&body, decode_limit) {
+            body = Body::Transformed(Box::pin(LimitedDecoder {
+                inner: body.into_reader(),
+                decoded: 0,
+                limit,
+            }));
+        }
+
+        Self {
             header,
-            reader,
-            disposition: None,
-            disposition_params: None,
-        })
+            original_header,
+            body,
+            disposition: OnceCell::new(),
+            content_type: OnceCell::new(),
+            nesting,
+            raw_headers: Bytes::new(),
+            framing: None,
+            is_final: false,
+            body_dropped: false,
+            _reader: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the header block exactly as received, without the blank line
+    /// ending it.
+    ///
+    /// Unlike [`header`](Self::header), this keeps the original casing,
+    /// folding, whitespace and order, e.g. for signature verification.
+    /// Lines truncated under [`ReaderOptions::max_header_line_length`] are
+    /// only kept up to the limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nX-Sig: a\r\n  b\r\n\r\nbody\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let part = reader.next_part().await?.unwrap();
+    /// assert_eq!(part.header.get("x-sig"), Some("a  b"));
+    /// assert_eq!(part.raw_headers(), b"X-Sig: a\r\n  b\r\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_headers(&self) -> &[u8] {
+        &self.raw_headers
+    }
+
+    /// Returns the framing the part was received with, in preserve mode.
+    ///
+    /// See [`ReaderOptions::preserve`].
+    pub fn framing(&self) -> Option<&PartFraming> {
+        self.framing.as_ref()
+    }
+
+    /// Reports whether the part was ended by the closing delimiter, making
+    /// it the last part of the message.
+    ///
+    /// The reader reads a body up to the delimiter following it before
+    /// returning the part, so this is known right away, without calling
+    /// [`Reader::next_part`] again. A part cut off by the end of the input
+    /// was not ended by the closing delimiter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\n\r\nfirst\r\n--b\r\n\r\nlast\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// assert!(!reader.next_part().await?.unwrap().was_final());
+    /// assert!(reader.next_part().await?.unwrap().was_final());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn was_final(&self) -> bool {
+        self.is_final
+    }
+
+    /// Returns the parsed Content-Disposition of this part.
+    ///
+    /// The header is parsed on first use and cached. Returns `None` if the
+    /// header is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Disposition: form-data; Name=\"a\"\r\n\r\nhi\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let part = reader.next_part().await?.unwrap();
+    /// let disposition = part.content_disposition().unwrap();
+    /// assert_eq!(disposition.param("name"), Some("a"));
+    /// assert_eq!(disposition.raw_params()[0].0, "Name");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_disposition(&self) -> Option<&ContentDisposition> {
+        self.disposition
+            .get_or_init(|| {
+                self.header
+                    .get("content-disposition")
+                    .map(ContentDisposition::parse)
+            })
+            .as_ref()
     }
 
     /// Returns the form field name if this part has Content-Disposition: form-data.
     pub fn form_name(&mut self) -> Option<&str> {
-        self.parse_content_disposition();
-        if self.disposition.as_deref() != Some("form-data") {
-            return None;
-        }
-        self.disposition_params
-            .as_ref()
-            .and_then(|p| p.get("name"))
-            .map(|s| s.as_str())
+        form_name(self.content_disposition()?)
     }
 
     /// Returns the filename parameter from Content-Disposition header.
+    ///
+    /// The RFC 2231 extended `filename*` parameter is preferred over the plain
+    /// `filename` parameter when it can be decoded, as recommended by RFC 6266.
     pub fn file_name(&mut self) -> Option<String> {
-        self.parse_content_disposition();
-        self.disposition_params
-            .as_ref()
-            .and_then(|p| p.get("filename"))
-            .map(|f| {
-                // Extract just the filename (not path)
-                std::path::Path::new(f)
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or(f)
-                    .to_string()
-            })
+        file_name(self.content_disposition()?)
     }
 
-    fn parse_content_disposition(&mut self) {
-        if self.disposition.is_some() {
-            return;
-        }
+    /// Returns the parsed Content-Type of this part.
+    ///
+    /// The header is parsed with [`parse_media_type`] on first use and cached.
+    /// Returns `None` if the header is missing or malformed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nhi\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let part = reader.next_part().await?.unwrap();
+    /// let (media_type, params) = part.content_type().unwrap();
+    /// assert_eq!(media_type, "text/plain");
+    /// assert_eq!(params.get("charset").map(String::as_str), Some("utf-8"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn content_type(&self) -> Option<(&str, &HashMap<String, String>)> {
+        self.content_type
+            .get_or_init(|| {
+                self.header
+                    .get("content-type")
+                    .and_then(|v| parse_media_type(v).ok())
+            })
+            .as_ref()
+            .map(|(t, params)| (t.as_str(), params))
+    }
 
-        if let Some(values) = self.header.get("content-disposition") {
-            if let Some(v) = values.first() {
-                let (disp, params) = parse_disposition(v);
-                self.disposition = Some(disp);
-                self.disposition_params = Some(params);
-                return;
-            }
-        }
+    /// Returns the parsed Content-Range of a `multipart/byteranges` part.
+    ///
+    /// Returns `None` if the header is missing, or the parse error if it is
+    /// malformed.
+    pub fn content_range(&self) -> Option<Result<ContentRange>> {
+        self.header.get("content-range").map(ContentRange::parse)
+    }
 
-        self.disposition = Some(String::new());
-        self.disposition_params = Some(HashMap::new());
+    /// Returns the header values as received, before normalization.
+    ///
+    /// Identical to [`header`](Self::header) unless the reader was created
+    /// with [`ReaderOptions::normalize_header_values`].
+    pub fn original_header(&self) -> &MimeHeader {
+        self.original_header.as_ref().unwrap_or(&self.header)
     }
-}
 
-impl<R: AsyncRead + Unpin> AsyncRead for Part<R> {
-    fn poll_read(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
-        let this = self.project();
-        this.reader.poll_read(cx, buf)
+    /// Takes the header out of the part, leaving it empty, so it can be
+    /// kept after the part is read and dropped without being cloned.
+    ///
+    /// The Content-Disposition and Content-Type are parsed first and stay
+    /// available through [`content_disposition`](Self::content_disposition),
+    /// [`form_name`](Self::form_name), [`file_name`](Self::file_name) and
+    /// [`content_type`](Self::content_type); other accessors see the empty
+    /// header. Wrap the result in an [`Arc`] to share it between tasks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhi\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let mut part = reader.next_part().await?.unwrap();
+    /// let header = Arc::new(part.take_header());
+    /// let body = part.bytes().await?;
+    /// drop(part);
+    /// assert_eq!(header.get("content-disposition"), Some("form-data; name=\"a\""));
+    /// assert_eq!(&body[..], b"hi");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn take_header(&mut self) -> MimeHeader {
+        self.content_disposition();
+        self.content_type();
+        std::mem::take(&mut self.header)
     }
-}
 
-/// Internal reader for a part's body.
-#[pin_project]
-struct PartReader<R> {
-    data: Vec<u8>,
-    pos: usize,
-    _phantom: std::marker::PhantomData<R>,
-}
+    /// Returns the Content-Description header, with RFC 2047 encoded words
+    /// decoded.
+    pub fn content_description(&self) -> Option<String> {
+        self.header.content_description()
+    }
 
-impl<R> PartReader<R> {
-    fn new(data: Vec<u8>) -> Self {
-        Self {
-            data,
-            pos: 0,
-            _phantom: std::marker::PhantomData,
-        }
+    /// Returns the Content-ID of this part, without its angle brackets.
+    ///
+    /// `multipart/related` parts reference each other through `cid:` URLs
+    /// built from this value (RFC 2392).
+    pub fn content_id(&self) -> Option<String> {
+        self.header.content_id()
     }
-}
 
-impl<R: AsyncRead + Unpin> AsyncRead for PartReader<R> {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
-        let remaining = &self.data[self.pos..];
-        let to_read = remaining.len().min(buf.remaining());
+    /// Returns the Content-Location URI of this part (RFC 2557).
+    pub fn content_location(&self) -> Option<String> {
+        self.header.content_location()
+    }
 
-        if to_read == 0 {
-            return Poll::Ready(Ok(()));
+    /// Returns the temporary file holding the body, if it was spilled to
+    /// disk (see [`ReaderOptions::spill_threshold`]).
+    ///
+    /// Returns `None` once transforms wrap the body.
+    pub fn spill_path(&self) -> Option<&std::path::Path> {
+        match &self.body {
+            Body::Spilled(reader) => Some(reader.path()),
+            _ => None,
         }
-
-        buf.put_slice(&remaining[..to_read]);
-        self.pos += to_read;
-
-        Poll::Ready(Ok(()))
     }
-}
-
-/// Reads MIME headers from a buffered reader.
-async fn read_mime_header<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<MimeHeader> {
-    let mut header = HashMap::new();
-    let mut total_size = 0;
-    let mut header_count = 0;
-
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).await?;
 
-        total_size += line.len();
-        if total_size > MAX_MIME_HEADER_SIZE {
-            return Err(Error::MessageTooLarge);
+    /// Returns a multipart reader over this part's body.
+    ///
+    /// The part's Content-Type must be a `multipart/*` media type carrying a
+    /// `boundary` parameter, e.g. a `multipart/mixed` attachment set nested
+    /// inside `multipart/form-data`.
+    ///
+    /// The nested reader inherits the options of the reader that produced
+    /// the part, limits included, except for [`tee`](ReaderOptions::tee) and
+    /// [`preserve`](ReaderOptions::preserve). Fails with
+    /// [`Error::NestingTooDeep`] if it would exceed
+    /// [`ReaderOptions::max_nesting_depth`].
+    pub fn into_multipart(self) -> Result<Reader<Part<R>>> {
+        let depth = self.nesting.depth + 1;
+        let limit = self.nesting.options.max_nesting_depth;
+        if depth > limit {
+            return Err(Error::NestingTooDeep { depth, limit });
         }
 
-        // Empty line signals end of headers
-        if line == "\r\n" || line == "\n" || line.is_empty() {
-            break;
+        let (media_type, params) = match self.content_type() {
+            Some((media_type, params)) => (media_type.to_string(), params.clone()),
+            None => match self.header.get("content-type") {
+                // Surface the parse error for malformed values
+                Some(v) => parse_media_type(v)?,
+                None => return Err(Error::Multipart("part has no Content-Type".to_string())),
+            },
+        };
+        if !media_type.starts_with("multipart/") {
+            return Err(Error::Multipart(format!(
+                "part is not multipart: {}",
+                media_type
+            )));
         }
 
-        header_count += 1;
-        if header_count > MAX_MIME_HEADERS {
-            return Err(Error::MessageTooLarge);
-        }
+        let boundary = params
+            .get("boundary")
+            .filter(|b| !b.is_empty())
+            .cloned()
+            .ok_or_else(|| Error::Multipart("nested multipart has no boundary".to_string()))?;
 
-        // Parse header line
-        if let Some((key, value)) = parse_header_line(&line) {
-            header
-                .entry(key.to_lowercase())
-                .or_insert_with(Vec::new)
-                .push(value.to_string());
-        }
+        let mut options = ReaderOptions::clone(&self.nesting.options);
+        // The outer reader already preserves the nested body; its tee was
+        // taken out of the options when it was created
+        options.preserve = false;
+        let mut reader = Reader::with_options(self, &boundary, options);
+        reader.depth = depth;
+        Ok(reader)
     }
 
-    Ok(header)
-}
+    /// Reads the rest of the body as [`Bytes`].
+    ///
+    /// Untransformed bodies are already buffered, so this hands out the
+    /// buffer without copying it; transformed bodies are read to the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\n\r\nhello\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let mut part = reader.next_part().await?.unwrap();
+    /// assert_eq!(&part.bytes().await?[..], b"hello");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn bytes(&mut self) -> Result<Bytes> {
+        use tokio::io::AsyncReadExt;
+
+        match &mut self.body {
+            Body::Plain(reader) => {
+                if reader.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                Ok(reader.take_remaining())
+            }
+            Body::Spilled(reader) => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data).await?;
+                Ok(Bytes::from(data))
+            }
+            Body::Transformed(reader) => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data).await?;
+                Ok(Bytes::from(data))
+            }
+        }
+    }
+
+    /// Copies the rest of the body to `writer` without decoding it.
+    ///
+    /// The bytes are written exactly as received, still in their
+    /// Content-Transfer-Encoding, so signatures and digests computed over
+    /// the original body stay valid. Returns the number of bytes copied.
+    ///
+    /// Fails if transforms were applied to the body; use
+    /// [`Reader::next_raw_part`] to get untransformed parts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--b\r\nContent-Transfer-Encoding: base64\r\n\r\naGk=\r\n--b--\r\n";
+    /// let mut reader = Reader::new(&data[..], "b");
+    /// let mut part = reader.next_raw_part().await?.unwrap();
+    ///
+    /// let mut copy = Vec::new();
+    /// part.raw_copy_to(&mut copy).await?;
+    /// assert_eq!(copy, b"aGk=");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn raw_copy_to<W: tokio::io::AsyncWrite + Unpin>(&mut self, mut writer: W) -> Result<u64> {
+        use tokio::io::AsyncWriteExt;
+
+        let copied = match &mut self.body {
+            Body::Plain(reader) => {
+                if reader.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                let data = reader.take_remaining();
+                writer.write_all(&data).await?;
+                data.len() as u64
+            }
+            Body::Spilled(reader) => tokio::io::copy(reader, &mut writer).await?,
+            Body::Transformed(_) => {
+                return Err(Error::Multipart(
+                    "part body is transformed; read it with next_raw_part to copy it raw"
+                        .to_string(),
+                ))
+            }
+        };
+        writer.flush().await?;
+        Ok(copied)
+    }
+
+    /// Returns the next chunk of the body, or `None` at the end.
+    ///
+    /// For untransformed bodies the whole remainder is returned as a single
+    /// chunk without copying.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        use tokio::io::AsyncReadExt;
+
+        let chunk = match &mut self.body {
+            Body::Plain(_) => self.bytes().await?,
+            Body::Spilled(reader) => {
+                let mut buf = vec![0u8; 8192];
+                let n = reader.read(&mut buf).await?;
+                buf.truncate(n);
+                Bytes::from(buf)
+            }
+            Body::Transformed(reader) => {
+                let mut buf = vec![0u8; 8192];
+                let n = reader.read(&mut buf).await?;
+                buf.truncate(n);
+                Bytes::from(buf)
+            }
+        };
+        Ok(if chunk.is_empty() { None } else { Some(chunk) })
+    }
 
-/// Parses a single header line.
-fn parse_header_line(line: &str) -> Option<(&str, &str)> {
-    let line = line.trim_end_matches('\n').trim_end_matches('\r');
-    let colon_pos = line.find(':')?;
-    let key = line[..colon_pos].trim();
-    let value = line[colon_pos + 1..].trim();
-    Some((key, value))
 }
 
-/// Parses Content-Disposition header value.
-/// Format: disposition-type; param1=value1; param2=value2
-fn parse_disposition(value: &str) -> (String, HashMap<String, String>) {
-    let (disposition, rest) = value.split_once(';').unwrap_or((value, ""));
-    let disposition = disposition.trim().to_lowercase();
+impl<R: AsyncRead + Unpin> AsyncRead for Part<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().body {
+            Body::Plain(reader) => Pin::new(reader).poll_read(cx, buf),
+            Body::Spilled(reader) => Pin::new(reader).poll_read(cx, buf),
+            Body::Transformed(reader) => reader.as_mut().poll_read(cx, buf),
+        }
+    }
+}
 
-    let mut params = HashMap::new();
-    for param in rest.split(';') {
-        let param = param.trim();
-        if param.is_empty() {
-            continue;
+/// The most bytes a part body may decode to, see
+/// [`ReaderOptions::max_expansion_ratio`].
+#[derive(Debug, Clone, Copy)]
+struct DecodeLimit {
+    limit: u64,
+    part_index: usize,
+}
+
+/// Fails a decoded body once it grows past its [`DecodeLimit`].
+struct LimitedDecoder {
+    inner: PartBody,
+    decoded: u64,
+    limit: DecodeLimit,
+}
+
+impl AsyncRead for LimitedDecoder {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let before = buf.filled().len();
+        if this.decoded <= this.limit.limit {
+            std::task::ready!(this.inner.as_mut().poll_read(cx, buf))?;
+            this.decoded += (buf.filled().len() - before) as u64;
+            if this.decoded <= this.limit.limit {
+                return Poll::Ready(Ok(()));
+            }
+            // Return the bytes up to the limit, and fail on the next read
+            let over = (this.decoded - this.limit.limit) as usize;
+            buf.set_filled(buf.filled().len() - over);
+            if buf.filled().len() > before {
+                return Poll::Ready(Ok(()));
+            }
         }
+        let limit = LimitExceeded::new(LimitKind::DecodedSize, this.limit.limit, this.decoded)
+            .with_part_index(this.limit.part_index);
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            Error::MessageTooLarge(limit),
+        )))
+    }
+}
 
-        if let Some((key, val)) = param.split_once('=') {
-            let key = key.trim().to_lowercase();
-            let val = val.trim();
+/// Internal reader for a part's body.
+struct PartReader {
+    data: Bytes,
+    pos: usize,
+    cancellation: Option<CancellationToken>,
+}
 
-            // Remove quotes if present
-            let val = if val.starts_with('"') && val.ends_with('"') && val.len() >= 2 {
-                &val[1..val.len() - 1]
-            } else {
-                val
-            };
+impl PartReader {
+    fn new(data: Bytes, cancellation: Option<CancellationToken>) -> Self {
+        Self {
+            data,
+            pos: 0,
+            cancellation,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
+    fn check_cancelled(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, Error::Cancelled));
+        }
+        Ok(())
+    }
+
+    /// Takes the unread remainder of the body without copying it.
+    fn take_remaining(&mut self) -> Bytes {
+        let rest = self.data.slice(self.pos..);
+        self.pos = self.data.len();
+        rest
+    }
+}
+
+impl AsyncRead for PartReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        self.check_cancelled()?;
+
+        let remaining = &self.data[self.pos..];
+        let to_read = remaining.len().min(buf.remaining());
+
+        if to_read == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        buf.put_slice(&remaining[..to_read]);
+        self.pos += to_read;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads a MIME header block, up to and including the blank line ending it.
+#[cfg(any(test, feature = "mhtml"))]
+pub(crate) async fn read_mime_header<R: tokio::io::AsyncBufRead + Unpin>(
+    reader: &mut R,
+    options: &ReaderOptions,
+) -> Result<MimeHeader> {
+    let mut parser = HeaderParser::with_options(options);
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        if parser.feed(&line, None)? {
+            return Ok(parser.finish(None));
+        }
+    }
+}
+
+/// Incremental MIME header parser, fed one line at a time.
+///
+/// Folded header lines (continuation lines starting with a space or tab, per
+/// RFC 5322 section 2.2.3) are unfolded into the value of the preceding header
+/// by removing the line break and keeping the leading whitespace.
+#[derive(Debug, Default)]
+pub(crate) struct HeaderParser {
+    header: MimeHeader,
+    total_size: usize,
+    header_count: usize,
+    /// The logical (unfolded) header line being accumulated
+    pending: Option<String>,
+    /// Maximum length of a logical line, and whether longer lines are
+    /// truncated rather than rejected.
+    line_limit: Option<(usize, bool)>,
+    /// Maximum size of the block, if not the default.
+    max_size: Option<usize>,
+    /// Maximum number of headers, if not the default.
+    max_count: Option<usize>,
+    /// The unfolded length of `pending`, if it was truncated.
+    pending_truncated: Option<usize>,
+    /// Name and unfolded length of each truncated header.
+    pub(crate) truncated: Vec<(String, usize)>,
+}
+
+impl HeaderParser {
+    /// Creates a parser with the header limits of `options`, truncating
+    /// long lines in lenient mode rather than rejecting them.
+    pub(crate) fn with_options(options: &ReaderOptions) -> Self {
+        Self {
+            line_limit: options
+                .max_header_line_length
+                .map(|limit| (limit, options.lenient)),
+            max_size: options.max_header_size,
+            max_count: options.max_headers,
+            ..Self::default()
+        }
+    }
+
+    /// Feeds a complete line, including its line ending.
+    ///
+    /// Returns `true` once the blank line ending the header block (or EOF,
+    /// signalled by an empty line) has been fed.
+    pub(crate) fn feed(&mut self, line: &str, interner: Option<&Interner>) -> Result<bool> {
+        self.feed_line(line, 0, interner)
+    }
+
+    /// Returns how much of a physical line is worth buffering: past the
+    /// limit, a line being truncated only needs counting.
+    pub(crate) fn line_cap(&self) -> usize {
+        match self.line_limit {
+            Some((limit, true)) => limit + 1,
+            _ => usize::MAX,
+        }
+    }
+
+    /// Fails as soon as an incomplete line is known to exceed a line limit
+    /// that is not truncated.
+    pub(crate) fn check_partial_line(&self, line: &[u8]) -> Result<()> {
+        let Some((limit, false)) = self.line_limit else {
+            return Ok(());
+        };
+        if line.ends_with(b"\n") {
+            // Complete lines are checked by feed
+            return Ok(());
+        }
+        let folded = match &self.pending {
+            Some(pending) if line.starts_with(b" ") || line.starts_with(b"\t") => pending.len(),
+            _ => 0,
+        };
+        if folded + line.len() > limit {
+            return Err(Error::MessageTooLarge(LimitExceeded::new(
+                LimitKind::HeaderLineLength,
+                limit as u64,
+                (folded + line.len()) as u64,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Feeds a complete line like [`feed`](Self::feed), from which `dropped`
+    /// bytes past [`line_cap`](Self::line_cap) were left out.
+    pub(crate) fn feed_line(
+        &mut self,
+        line: &str,
+        dropped: usize,
+        interner: Option<&Interner>,
+    ) -> Result<bool> {
+        self.total_size += line.len() + dropped;
+        let max_size = self.max_size.unwrap_or(MAX_MIME_HEADER_SIZE);
+        if self.total_size > max_size {
+            return Err(Error::MessageTooLarge(LimitExceeded::new(
+                LimitKind::HeaderSize,
+                max_size as u64,
+                self.total_size as u64,
+            )));
+        }
+
+        // Empty line signals end of headers
+        if line == "\r\n" || line == "\n" || line.is_empty() {
+            return Ok(true);
+        }
+
+        let content = line.trim_end_matches('\n').trim_end_matches('\r');
+
+        // Continuation of the previous header; a leading continuation line
+        // with nothing to continue is ignored
+        if content.starts_with([' ', '\t']) {
+            if let Some(length) = self.pending_truncated.as_mut() {
+                *length += content.len() + dropped;
+            } else if let Some(pending) = self.pending.as_mut() {
+                pending.push_str(content);
+                self.check_line_length(dropped)?;
+            }
+            return Ok(false);
+        }
+
+        self.flush_pending(interner);
+
+        self.header_count += 1;
+        let max_count = self.max_count.unwrap_or(MAX_MIME_HEADERS);
+        if self.header_count > max_count {
+            return Err(Error::MessageTooLarge(LimitExceeded::new(
+                LimitKind::HeaderCount,
+                max_count as u64,
+                self.header_count as u64,
+            )));
+        }
+
+        self.pending = Some(content.to_string());
+        self.check_line_length(dropped)?;
+        Ok(false)
+    }
+
+    /// Returns the parsed header.
+    pub(crate) fn finish(mut self, interner: Option<&Interner>) -> MimeHeader {
+        self.flush_pending(interner);
+        self.header
+    }
+
+    /// Rejects or truncates the pending line once it exceeds the limit,
+    /// counting `dropped` bytes that were left out of it.
+    fn check_line_length(&mut self, dropped: usize) -> Result<()> {
+        let (Some((limit, truncate)), Some(pending)) = (self.line_limit, self.pending.as_mut()) else {
+            return Ok(());
+        };
+        let length = pending.len() + dropped;
+        if length <= limit {
+            return Ok(());
+        }
+        if !truncate {
+            return Err(Error::MessageTooLarge(LimitExceeded::new(
+                LimitKind::HeaderLineLength,
+                limit as u64,
+                length as u64,
+            )));
+        }
+
+        self.pending_truncated = Some(length);
+        let mut end = limit;
+        while !pending.is_char_boundary(end) {
+            end -= 1;
+        }
+        pending.truncate(end);
+        Ok(())
+    }
+
+    fn flush_pending(&mut self, interner: Option<&Interner>) {
+        let Some(line) = self.pending.take() else {
+            return;
+        };
+        if let Some(length) = self.pending_truncated.take() {
+            // Cut short of its colon, the line is dropped but still recorded
+            let name = line.split_once(':').map_or(&line[..], |(name, _)| name);
+            self.truncated.push((name.to_string(), length));
+        }
+        push_header_line(&mut self.header, &line, interner);
+    }
+}
+
+/// Returns the size past which `read_form_with_schema` drops the body of
+/// the part with `header` as it is read: nothing is kept of parts to be
+/// discarded, and no more than their limit of the others.
+fn schema_body_cap(schema: &super::schema::FormSchema, header: &MimeHeader) -> Option<u64> {
+    use super::schema::FieldKind;
+
+    let disposition = header.get("content-disposition").map(ContentDisposition::parse);
+    let Some(name) = disposition.as_ref().and_then(form_name) else {
+        return Some(0);
+    };
+    let kind = match disposition.as_ref().and_then(file_name) {
+        Some(_) => FieldKind::File,
+        None => FieldKind::Value,
+    };
+    let limit = match schema.check_part(name, kind) {
+        Ok(limit) => limit?,
+        Err(_) => return Some(0),
+    };
+    let quoted_printable = header
+        .get("content-transfer-encoding")
+        .is_some_and(|cte| cte.eq_ignore_ascii_case("quoted-printable"));
+    // The limit is on the decoded body, and quoted-printable takes at most
+    // three bytes and a share of soft line breaks for each decoded one
+    Some(if quoted_printable { limit.saturating_mul(4) } else { limit })
+}
+
+/// Returns the form field name of a `form-data` disposition.
+fn form_name(disposition: &ContentDisposition) -> Option<&str> {
+    if disposition.disposition_type() != "form-data" {
+        return None;
+    }
+    disposition.param("name")
+}
+
+/// Returns the filename of a disposition, without any path.
+fn file_name(disposition: &ContentDisposition) -> Option<String> {
+    disposition
+        .param("filename*")
+        .and_then(decode_rfc2231_value)
+        .or_else(|| disposition.param("filename").map(str::to_string))
+        .map(|f| {
+            // Extract just the filename (not path)
+            std::path::Path::new(&f)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(&f)
+                .to_string()
+        })
+}
 
-            params.insert(key, val.to_string());
+/// Attaches a part index to limit errors.
+fn with_part_index(err: Error, index: usize) -> Error {
+    match err {
+        Error::MessageTooLarge(limit) => Error::MessageTooLarge(limit.with_part_index(index)),
+        err => err,
+    }
+}
+
+/// Parses a logical header line and appends it to the header.
+fn push_header_line(header: &mut MimeHeader, line: &str, interner: Option<&Interner>) {
+    if let Some((key, value)) = parse_header_line(line) {
+        push_header(header, key, value, interner);
+    }
+}
+
+/// Appends a header entry, sharing its strings through `interner` if set.
+fn push_header(header: &mut MimeHeader, name: &str, value: &str, interner: Option<&Interner>) {
+    match interner {
+        Some(interner) => header.append_shared(interner.intern(name), interner.intern(value)),
+        None => header.append(name, value),
+    }
+}
+
+/// Trims a header value and collapses runs of folding whitespace to a single
+/// space, leaving quoted strings untouched.
+fn normalize_header_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut pending_space = false;
+
+    for ch in value.trim_matches([' ', '\t']).chars() {
+        if in_quotes {
+            result.push(ch);
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                in_quotes = false;
+            }
+            continue;
         }
+
+        if ch == ' ' || ch == '\t' {
+            pending_space = true;
+            continue;
+        }
+        if pending_space {
+            result.push(' ');
+            pending_space = false;
+        }
+        if ch == '"' {
+            in_quotes = true;
+        }
+        result.push(ch);
     }
 
-    (disposition, params)
+    result
+}
+
+/// Parses a single header line.
+fn parse_header_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_end_matches('\n').trim_end_matches('\r');
+    let colon_pos = line.find(':')?;
+    let key = line[..colon_pos].trim();
+    let value = line[colon_pos + 1..].trim();
+    Some((key, value))
 }
 
 /// Skips leading whitespace (space and tab).
@@ -466,174 +2326,1076 @@ fn skip_lwsp_char(b: &[u8]) -> &[u8] {
     &b[i..]
 }
 
-/// Reads part data until a boundary is encountered.
-///
-/// This function reads data line by line, checking each line to see if it's a boundary.
-/// When a boundary is found, the boundary line is NOT consumed, so the next call to
-/// next_part() will see it.
-async fn read_part_data<R: AsyncBufRead + Unpin>(
-    reader: &mut R,
-    dash_boundary: &[u8],
-    nl_dash_boundary: &[u8],
-) -> Result<Vec<u8>> {
-    use tokio::io::AsyncBufReadExt;
-
-    let mut data = Vec::new();
-    let mut total_bytes = 0;
-    let mut line_buf = Vec::new();
+/// Trims surrounding ASCII whitespace.
+fn trim_whitespace(mut b: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = b {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        b = rest;
+    }
+    while let [rest @ .., last] = b {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        b = rest;
+    }
+    b
+}
 
-    loop {
-        line_buf.clear();
+/// Removes the quotes around a quoted string, unescaping quoted pairs;
+/// anything else is returned as is.
+fn unquote(b: &[u8]) -> Vec<u8> {
+    let inner = match b {
+        [b'"', inner @ .., b'"'] => inner,
+        _ => return b.to_vec(),
+    };
+    let mut out = Vec::with_capacity(inner.len());
+    let mut escaped = false;
+    for &c in inner {
+        if c == b'\\' && !escaped {
+            escaped = true;
+        } else {
+            out.push(c);
+            escaped = false;
+        }
+    }
+    out
+}
 
-        // Peek at buffered data to check for boundary without consuming
-        let buf = reader.fill_buf().await?;
+/// Reads part data until a boundary is encountered.
+///
+/// The body ends before the line break preceding the first line starting
+/// with `--boundary`; as in RFC 2046, that line break belongs to the
+/// delimiter and is not part of the body. Delimiters
+/// are located with a substring search over whole buffers rather than line by
+/// line; near the end of a buffer, a possible partial delimiter is kept
+/// buffered and completed before deciding. The boundary line is NOT consumed,
+/// so the next call to next_part() will see it.
+///
+/// Data is appended to `data`, and `at_line_start` tracks whether the data so
+/// far ends a line; both start out empty and `true` for a new body, and
+/// `body_end` is set once the end is found. Keeping them in the caller makes
+/// the read resumable if this future is dropped.
+async fn read_part_data<R: AsyncRead + Unpin>(
+    reader: &mut PeekReader<R>,
+    patterns: &BoundaryPatterns,
+    state: &mut ParseState,
+    options: &ReaderOptions,
+) -> Result<()> {
+    let dash_boundary = patterns.dash_boundary();
+    let ParseState {
+        data,
+        at_line_start,
+        body_end,
+        spill,
+        body_cap,
+        body_dropped,
+        ..
+    } = state;
 
+    while body_end.is_none() {
+        let mut buf = reader.fill_buf().await?;
         if buf.is_empty() {
             // EOF
+            *body_end = Some(b"");
             break;
         }
 
-        // Find the next newline
-        let newline_pos = buf.iter().position(|&b| b == b'\n');
-
-        if let Some(pos) = newline_pos {
-            // We have a complete line
-            line_buf.extend_from_slice(&buf[..=pos]);
-
-            // Check if this is a boundary line
-            // Boundaries should be at the start of the line (possibly with leading \r\n or \n)
-            if line_buf.starts_with(dash_boundary)
-                || line_buf.starts_with(nl_dash_boundary)
-                || (line_buf.starts_with(b"\r\n") && line_buf[2..].starts_with(dash_boundary))
-                || (line_buf.starts_with(b"\n") && line_buf[1..].starts_with(dash_boundary))
-            {
-                // Found boundary - don't consume it, return what we have
+        if *at_line_start {
+            if buf.len() < dash_boundary.len() && dash_boundary.starts_with(buf) {
+                // Possibly a delimiter split across reads
+                buf = reader.fill_to(dash_boundary.len()).await?;
+            }
+            if buf.starts_with(dash_boundary) {
+                *body_end = Some(strip_line_break(data));
                 break;
             }
+        }
+
+        // "\n--boundary": a delimiter anywhere past the start of the body
+        let (take, found) = match patterns.find_nl_dash_boundary(buf) {
+            Some(pos) => (pos + 1, true),
+            None => (partial_delimiter_start(buf, patterns.nl_dash_boundary()), false),
+        };
 
-            // Not a boundary, consume the line and add to data
-            reader.consume(pos + 1);
-            data.extend_from_slice(&line_buf);
-            total_bytes += line_buf.len();
+        data.extend_from_slice(&buf[..take]);
+        *at_line_start = buf[take - 1] == b'\n';
+        reader.consume(take);
 
-            // Limit data size to prevent memory exhaustion (32 MB)
-            if total_bytes > 32 * 1024 * 1024 {
-                return Err(Error::MessageTooLarge);
+        check_message_size(reader, options.max_message_size)?;
+
+        if spill.is_none() && options.spill_threshold.is_some_and(|t| data.len() > t) {
+            *spill = Some(Spill::create(&options.temp_file_prefix).await?);
+        }
+        match spill {
+            // Keep the last two bytes: they may be the line break to strip
+            Some(spill) if data.len() >= SPILL_CHUNK_SIZE => spill.write_from(data, 2).await?,
+            Some(_) => {}
+            // Limit data size to prevent memory exhaustion
+            None if options.max_part_size.is_none() && data.len() > MAX_PART_SIZE => {
+                return Err(Error::MessageTooLarge(LimitExceeded::new(
+                    LimitKind::PartSize,
+                    MAX_PART_SIZE as u64,
+                    data.len() as u64,
+                )));
             }
-        } else {
-            // No newline in buffer, consume all buffered data
-            let len = buf.len();
-            data.extend_from_slice(buf);
-            reader.consume(len);
-            total_bytes += len;
+            None => {}
+        }
+
+        if found {
+            *body_end = Some(strip_line_break(data));
+        }
+        // Until the delimiter is found, the last two bytes may be the line
+        // break before it
+        let slack = if found { 0 } else { 2 };
+        if let Some(cap) = *body_cap {
+            if *body_dropped > 0 || data.len() as u64 > cap.saturating_add(slack) {
+                let drop = data.len() - (slack as usize).min(data.len());
+                data.drain(..drop);
+                *body_dropped += drop as u64;
+            }
+        }
+        if let Some(limit) = options.max_part_size {
+            let size = data.len() as u64 + spill.as_ref().map_or(0, Spill::len) + *body_dropped;
+            if size > limit.saturating_add(slack) {
+                return Err(Error::MessageTooLarge(LimitExceeded::new(
+                    LimitKind::PartSize,
+                    limit,
+                    size,
+                )));
+            }
+        }
+    }
 
-            // Limit check
-            if total_bytes > 32 * 1024 * 1024 {
-                return Err(Error::MessageTooLarge);
+    if let Some(spill) = spill {
+        spill.write_from(data, 0).await?;
+        spill.finish().await?;
+    }
+
+    Ok(())
+}
+
+/// Removes the line break ending `data`, if any, and returns it.
+fn strip_line_break(data: &mut Vec<u8>) -> &'static [u8] {
+    if data.last() != Some(&b'\n') {
+        return b"";
+    }
+    data.pop();
+    if data.last() == Some(&b'\r') {
+        data.pop();
+        return b"\r\n";
+    }
+    b"\n"
+}
+
+/// Reads a line into `line` like `read_until(b'\n')`, one buffered chunk at
+/// a time, calling `check` with the line so far after each chunk so limits
+/// fire before an overlong line is buffered whole.
+///
+/// Bytes past the first `max_len` of the line are consumed but dropped,
+/// except for its line ending. Returns the number of bytes read, 0 at EOF.
+async fn read_line<R: AsyncRead + Unpin>(
+    reader: &mut PeekReader<R>,
+    line: &mut Vec<u8>,
+    max_len: usize,
+    mut check: impl FnMut(&PeekReader<R>, &[u8]) -> Result<()>,
+) -> Result<usize> {
+    let mut read = 0;
+    let mut prev = None;
+    loop {
+        let buf = reader.fill_buf().await?;
+        let (take, found) = match memchr::memchr(b'\n', buf) {
+            Some(pos) => (pos + 1, true),
+            None => (buf.len(), false),
+        };
+        let done = found || buf.is_empty();
+        let chunk = &buf[..take];
+        let room = max_len.saturating_sub(line.len());
+        line.extend_from_slice(&chunk[..room.min(take)]);
+        if found && !line.ends_with(b"\n") {
+            // The line ending was dropped: put it back
+            let cr = if take >= 2 { Some(chunk[take - 2]) } else { prev } == Some(b'\r');
+            if cr && !line.ends_with(b"\r") {
+                line.push(b'\r');
             }
+            line.push(b'\n');
+        }
+        prev = chunk.last().copied();
+        reader.consume(take);
+        read += take;
+        check(reader, line)?;
+        if done {
+            return Ok(read);
+        }
+    }
+}
 
-            // Continue to read more data
+/// Removes an incomplete UTF-8 sequence left at the end of a line's content
+/// by [`read_line`] dropping the rest.
+fn trim_partial_char(line: &mut Vec<u8>) {
+    let ending = line.len() - content_end(line);
+    let content = &line[..line.len() - ending];
+    if let Err(err) = std::str::from_utf8(content) {
+        if err.error_len().is_none() {
+            let cut = err.valid_up_to();
+            line.drain(cut..line.len() - ending);
         }
     }
+}
+
+/// Returns the length of `line` without its line ending.
+fn content_end(line: &[u8]) -> usize {
+    let content = line.strip_suffix(b"\n").unwrap_or(line);
+    content.strip_suffix(b"\r").unwrap_or(content).len()
+}
+
+/// Reports whether `line`, possibly incomplete, may still be a delimiter line
+/// or the closing one.
+fn may_be_delimiter(line: &[u8], dash_boundary: &[u8]) -> bool {
+    match line.strip_prefix(dash_boundary) {
+        Some(rest) => {
+            let rest = rest.strip_prefix(b"--").unwrap_or(rest);
+            matches!(skip_lwsp_char(rest), b"" | b"\r" | b"\n" | b"\r\n")
+        }
+        None => dash_boundary.starts_with(line),
+    }
+}
+
+/// The error for a preamble of `bytes_scanned` bytes exceeding
+/// `max_preamble_size`.
+fn boundary_not_found(patterns: &BoundaryPatterns, bytes_scanned: u64) -> Error {
+    let boundary = &patterns.dash_boundary()[2..];
+    Error::BoundaryNotFound {
+        expected: String::from_utf8_lossy(boundary).into_owned(),
+        bytes_scanned,
+    }
+}
+
+/// Fails once more than `limit` bytes have been consumed from `reader`.
+fn check_message_size<R: AsyncRead + Unpin>(reader: &PeekReader<R>, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(limit) if reader.consumed() > limit => Err(Error::MessageTooLarge(
+            LimitExceeded::new(LimitKind::MessageSize, limit, reader.consumed()),
+        )),
+        _ => Ok(()),
+    }
+}
 
-    Ok(data)
+/// Returns the length of `buf` that can safely be consumed as body data:
+/// everything up to and including the newline of a trailing partial match of
+/// `pattern`, or the whole buffer if there is none. This is never 0 for a
+/// non-empty `buf`.
+fn partial_delimiter_start(buf: &[u8], pattern: &[u8]) -> usize {
+    let tail_start = buf.len().saturating_sub(pattern.len() - 1);
+    memchr::memchr_iter(b'\n', &buf[tail_start..])
+        .map(|i| tail_start + i)
+        .find(|&i| pattern.starts_with(&buf[i..]))
+        .map_or(buf.len(), |i| i + 1)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::io::AsyncReadExt;
+    use crate::error::TimeoutKind;
+    use tokio::io::{AsyncReadExt, BufReader};
+
+    #[tokio::test]
+    async fn test_shared_limits() {
+        let read = |limits: Limits, data: &'static [u8]| async move {
+            let options = ReaderOptions {
+                spill_threshold: Some(0),
+                ..Default::default()
+            }
+            .limits(&limits);
+            let mut reader = Reader::with_options(data, "b", options);
+            let mut sizes = Vec::new();
+            while let Some(mut part) = reader.next_part().await? {
+                sizes.push(part.bytes().await?.len());
+            }
+            Ok::<_, Error>(sizes)
+        };
+        let kind = |result: Result<Vec<usize>>| result.unwrap_err().limit_exceeded().map(|l| l.kind);
+
+        let data = b"--b\r\nX-A: 1\r\nX-B: 2\r\n\r\n12345\r\n--b--\r\n";
+        let limits = Limits {
+            max_headers: Some(2),
+            max_header_bytes: Some(18),
+            max_part_size: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(read(limits, data).await.unwrap(), [5]);
+
+        let tight = |limits: Limits| async move { kind(read(limits, data).await) };
+        assert_eq!(
+            tight(Limits { max_headers: Some(1), ..limits }).await,
+            Some(LimitKind::HeaderCount)
+        );
+        assert_eq!(
+            tight(Limits { max_header_bytes: Some(17), ..limits }).await,
+            Some(LimitKind::HeaderSize)
+        );
+        // Applies to spilled parts too
+        assert_eq!(
+            tight(Limits { max_part_size: Some(4), ..limits }).await,
+            Some(LimitKind::PartSize)
+        );
+        assert_eq!(
+            tight(Limits { max_line_len: Some(3), ..limits }).await,
+            Some(LimitKind::HeaderLineLength)
+        );
+
+        let options = ReaderOptions::default().limits(&Limits {
+            max_nesting: Some(2),
+            max_total: Some(100),
+            ..Default::default()
+        });
+        assert_eq!((options.max_nesting_depth, options.max_message_size), (2, Some(100)));
+    }
+
+    #[tokio::test]
+    async fn test_read_form_temp_files() {
+        let data = b"--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"secret-name.txt\"\r\n\r\nbody\r\n--b--\r\n";
+        let form = Reader::new(&data[..], "b").read_form(0).await.unwrap();
+        assert!(form.file["f"][0].temp_path().is_none());
+
+        let options = ReaderOptions {
+            temp_file_prefix: "yamime-form-".to_string(),
+            expose_temp_paths: true,
+            ..Default::default()
+        };
+        let mut form = Reader::with_options(&data[..], "b", options).read_form(0).await.unwrap();
+        let path = form.file["f"][0].temp_path().unwrap().to_path_buf();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("yamime-form-") && !name.contains("secret"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"body");
+        form.remove_all().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_take_header_outlives_part() {
+        let data = b"--b\r\nContent-Disposition: form-data; name=\"f\"; filename=\"a.txt\"\r\n\
+Content-Type: text/plain\r\nX-A: 1\r\n\r\nbody\r\n--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let header = part.take_header();
+        assert!(part.header.is_empty());
+        assert_eq!(part.form_name(), Some("f"));
+        assert_eq!(part.file_name().as_deref(), Some("a.txt"));
+        assert_eq!(part.content_type().map(|(t, _)| t), Some("text/plain"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"body");
+        drop(part);
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert_eq!(header.get("x-a"), Some("1"));
+        assert_eq!(header.len(), 3);
+    }
 
     #[tokio::test]
     async fn test_read_mime_header() {
         let data = b"Content-Type: text/plain\r\nContent-Length: 123\r\n\r\n";
         let mut reader = BufReader::new(&data[..]);
-        let header = read_mime_header(&mut reader).await.unwrap();
+        let header = read_mime_header(&mut reader, &ReaderOptions::default()).await.unwrap();
+
+        assert_eq!(header.get("content-type"), Some("text/plain"));
+        assert_eq!(header.get("content-length"), Some("123"));
+        assert_eq!(
+            header.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            vec!["Content-Type", "Content-Length"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_mime_header_empty() {
+        let mut reader = BufReader::new(&b"\r\nbody"[..]);
+        assert!(read_mime_header(&mut reader, &ReaderOptions::default()).await.unwrap().is_empty());
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "body");
+    }
+
+    #[tokio::test]
+    async fn test_read_mime_header_folded() {
+        let data = b"Content-Disposition: form-data;\r\n\
+\tname=\"field\";\r\n  filename=\"a.txt\"\r\n\
+X-Other: value\r\n\
+\r\n";
+        let mut reader = BufReader::new(&data[..]);
+        let header = read_mime_header(&mut reader, &ReaderOptions::default()).await.unwrap();
+
+        assert_eq!(header.len(), 2);
+        assert_eq!(
+            header.get("content-disposition"),
+            Some("form-data;\tname=\"field\";  filename=\"a.txt\"")
+        );
+        assert_eq!(header.get("x-other"), Some("value"));
+    }
+
+    #[tokio::test]
+    async fn test_folded_header_in_part() {
+        let data = b"--boundary\r\n\
+Content-Disposition: form-data;\r\n name=\"field\"\r\n\
+\r\n\
+value\r\n\
+--boundary--\r\n";
+
+        let mut reader = Reader::new(&data[..], "boundary");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("field"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_header_line() {
+        assert_eq!(
+            parse_header_line("Content-Type: text/plain\r\n"),
+            Some(("Content-Type", "text/plain"))
+        );
+        assert_eq!(
+            parse_header_line("Content-Length:123\n"),
+            Some(("Content-Length", "123"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multipart_reader() {
+        let data = b"--boundary\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Hello World\r\n\
+--boundary\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<html>test</html>\r\n\
+--boundary--\r\n";
+
+        let mut reader = Reader::new(&data[..], "boundary");
+
+        // Read first part
+        let mut part1 = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part1.header.get("content-type"), Some("text/plain"));
+
+        let mut body1 = String::new();
+        part1.read_to_string(&mut body1).await.unwrap();
+        assert_eq!(body1, "Hello World");
+
+        // Read second part
+        let mut part2 = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part2.header.get("content-type"), Some("text/html"));
+
+        let mut body2 = String::new();
+        part2.read_to_string(&mut body2).await.unwrap();
+        assert_eq!(body2, "<html>test</html>");
+
+        // No more parts
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_form_data() {
+        let data = b"--boundary\r\n\
+Content-Disposition: form-data; name=\"field1\"\r\n\
+\r\n\
+value1\r\n\
+--boundary\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"test.txt\"\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+file content\r\n\
+--boundary--\r\n";
+
+        let mut reader = Reader::new(&data[..], "boundary");
+
+        // Read first part (form field)
+        let mut part1 = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part1.form_name(), Some("field1"));
+
+        let mut body1 = String::new();
+        part1.read_to_string(&mut body1).await.unwrap();
+        assert_eq!(body1, "value1");
+
+        // Read second part (file)
+        let mut part2 = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part2.form_name(), Some("file"));
+        assert_eq!(part2.file_name(), Some("test.txt".to_string()));
+
+        let mut body2 = String::new();
+        part2.read_to_string(&mut body2).await.unwrap();
+        assert_eq!(body2, "file content");
+
+        // No more parts
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_nested_multipart() {
+        let data = b"--outer\r\n\
+Content-Disposition: form-data; name=\"field\"\r\n\
+\r\n\
+value\r\n\
+--outer\r\n\
+Content-Disposition: form-data; name=\"files\"\r\n\
+Content-Type: multipart/mixed; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Disposition: file; filename=\"a.txt\"\r\n\
+\r\n\
+aaa\r\n\
+--inner\r\n\
+Content-Disposition: file; filename=\"b.txt\"\r\n\
+\r\n\
+bbb\r\n\
+--inner--\r\n\
+--outer--\r\n";
+
+        let mut reader = Reader::new(&data[..], "outer");
+
+        let part1 = reader.next_part().await.unwrap().unwrap();
+        assert!(part1.into_multipart().is_err());
+
+        let part2 = reader.next_part().await.unwrap().unwrap();
+        let mut inner = part2.into_multipart().unwrap();
+
+        let mut a = inner.next_part().await.unwrap().unwrap();
+        assert_eq!(a.file_name(), Some("a.txt".to_string()));
+        let mut body = String::new();
+        a.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "aaa");
+
+        let mut b = inner.next_part().await.unwrap().unwrap();
+        assert_eq!(b.file_name(), Some("b.txt".to_string()));
+
+        assert!(inner.next_part().await.unwrap().is_none());
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_message_size_limit() {
+        // Many small parts, each well under the per-part cap
+        let mut data = Vec::new();
+        for _ in 0..100 {
+            data.extend_from_slice(b"--b\r\n\r\n");
+            data.extend_from_slice(&[b'x'; 1000]);
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(b"--b--\r\n");
+
+        let options = ReaderOptions {
+            max_message_size: Some(10_000),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let mut parts = 0;
+        let err = loop {
+            match reader.next_part().await {
+                Ok(Some(_)) => parts += 1,
+                Ok(None) => panic!("limit not enforced"),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(parts, 9);
+        match err {
+            Error::MessageTooLarge(limit) => {
+                assert_eq!(limit.kind, LimitKind::MessageSize);
+                assert_eq!(limit.limit, 10_000);
+                assert!(limit.observed > 10_000);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        let mut reader = Reader::new(&data[..], "b");
+        while reader.next_part().await.unwrap().is_some() {}
+        assert_eq!(reader.bytes_consumed(), data.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_message_size_limit_without_newlines() {
+        let options = ReaderOptions {
+            max_message_size: Some(1024),
+            ..Default::default()
+        };
+        // A header line, then a preamble, that never end
+        let mut header = b"--b\r\nX-Long: ".to_vec();
+        header.resize(1 << 20, b'x');
+        let preamble = vec![b'x'; 1 << 20];
+        for data in [header, preamble] {
+            let mut reader = Reader::with_options(&data[..], "b", options.clone());
+            let err = reader.next_part().await.err().unwrap();
+            let limit = err.limit_exceeded().unwrap();
+            assert_eq!(limit.kind, LimitKind::MessageSize);
+            // Fails within a buffer of the limit
+            assert!(limit.observed <= 1024 + READ_BUFFER_SIZE as u64, "{}", limit.observed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spill_large_part_to_disk() {
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut data = b"--b\r\n\r\nsmall\r\n--b\r\n\r\n".to_vec();
+        data.extend_from_slice(&body);
+        data.extend_from_slice(b"\r\n--b--\r\n");
+
+        let options = ReaderOptions {
+            spill_threshold: Some(1024),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+
+        let small = reader.next_part().await.unwrap().unwrap();
+        assert!(small.spill_path().is_none());
+
+        let mut large = reader.next_part().await.unwrap().unwrap();
+        let path = large.spill_path().unwrap().to_path_buf();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), body.len() as u64);
+
+        let mut read = Vec::new();
+        large.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, body);
+
+        drop(large);
+        assert!(!path.exists());
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_raw_copy_to_rejects_transformed_body() {
+        use super::super::transform::FnTransform;
+
+        let data = b"--b\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b--\r\n";
+        let transform = || FnTransform::new(|_: &MimeHeader| true, |_: &MimeHeader, body: PartBody| body);
+
+        let mut reader = Reader::new(&data[..], "b");
+        reader.add_transform(transform());
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert!(matches!(part.raw_copy_to(Vec::new()).await, Err(Error::Multipart(_))));
+
+        let mut reader = Reader::new(&data[..], "b");
+        reader.add_transform(transform());
+        let mut part = reader.next_raw_part().await.unwrap().unwrap();
+        let mut copy = Vec::new();
+        assert_eq!(part.raw_copy_to(&mut copy).await.unwrap(), 5);
+        assert_eq!(copy, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_peek_next_headers() {
+        let data = b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\none\r\n\
+--b\r\nContent-Disposition: form-data; name=\"b\"; filename=\"x.exe\"\r\n\r\ntwo\r\n--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+
+        // Peeking is idempotent
+        for _ in 0..2 {
+            let header = reader.peek_next_headers().await.unwrap().unwrap();
+            assert_eq!(header.get("content-disposition"), Some("form-data; name=\"a\""));
+        }
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("a"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"one");
+
+        // The peeked part is returned by next_raw_part too
+        let header = reader.peek_next_headers().await.unwrap().unwrap();
+        assert!(header.get("content-disposition").unwrap().ends_with("filename=\"x.exe\""));
+        let mut part = reader.next_raw_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name().as_deref(), Some("x.exe"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"two");
+
+        assert!(reader.peek_next_headers().await.unwrap().is_none());
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_interned_headers() {
+        let data = b"--b\r\nContent-Type: text/plain;  charset=utf-8\r\n\r\none\r\n\
+--b\r\nContent-Type: text/plain; charset=utf-8\r\n\r\ntwo\r\n--b--\r\n";
+        let interner = Interner::new();
+        let options = ReaderOptions {
+            interner: Some(interner.clone()),
+            normalize_header_values: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let a = reader.next_part().await.unwrap().unwrap();
+        let b = reader.next_part().await.unwrap().unwrap();
+
+        // Equal strings, normalized or not, share one allocation
+        let value = |part: &Part<&[u8]>| part.header.get("content-type").unwrap().as_ptr();
+        assert_eq!(value(&a), value(&b));
+        let name = |part: &Part<&[u8]>| part.header.iter().next().unwrap().0.as_ptr();
+        assert_eq!(name(&a), name(&b));
+        assert_eq!(
+            a.original_header().get("content-type"),
+            Some("text/plain;  charset=utf-8")
+        );
+        assert_eq!(interner.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_header_less_parts() {
+        // Each part starts with the blank line ending its (empty) header block
+        let data = b"--b\r\n\r\nno headers\r\n\
+--b\r\n\r\n\r\n\
+--b\r\n\r\n\
+--b\r\n\r\nContent-Type: text/html\r\n\
+--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+        let mut bodies = Vec::new();
+        while let Some(mut part) = reader.next_part().await.unwrap() {
+            assert!(part.header.is_empty());
+            assert_eq!(part.content_type(), None);
+            assert_eq!(part.form_name(), None);
+            bodies.push(part.bytes().await.unwrap());
+        }
+        // A header-like first body line stays in the body
+        assert_eq!(
+            bodies,
+            [&b"no headers"[..], b"", b"", b"Content-Type: text/html"]
+        );
+        assert!(reader.finished_cleanly());
+
+        let mut reader = Reader::new(&b"--b\r\n\r\nx\r\n--b--\r\n"[..], "b");
+        assert!(reader.peek_next_headers().await.unwrap().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nesting_depth_limit() {
+        let data = b"--l0\r\n\
+Content-Type: multipart/mixed; boundary=l1\r\n\
+\r\n\
+--l1\r\n\
+Content-Type: multipart/mixed; boundary=l2\r\n\
+\r\n\
+--l2\r\n\
+\r\n\
+deep\r\n\
+--l2--\r\n\
+--l1--\r\n\
+--l0--\r\n";
+
+        let options = ReaderOptions {
+            max_nesting_depth: 1,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "l0", options);
+        let mut l1 = reader.next_part().await.unwrap().unwrap().into_multipart().unwrap();
+        let part = l1.next_part().await.unwrap().unwrap();
+        match part.into_multipart() {
+            Err(Error::NestingTooDeep { depth, limit }) => assert_eq!((depth, limit), (2, 1)),
+            other => panic!("expected NestingTooDeep, got {:?}", other.err()),
+        }
+
+        // The default limit allows it
+        let mut reader = Reader::new(&data[..], "l0");
+        let mut l1 = reader.next_part().await.unwrap().unwrap().into_multipart().unwrap();
+        let mut l2 = l1.next_part().await.unwrap().unwrap().into_multipart().unwrap();
+        assert!(l2.next_part().await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_nested_reader_inherits_limits() {
+        let data = b"--o\r\n\
+Content-Type: multipart/mixed; boundary=i\r\n\
+\r\n\
+--i\r\n\
+A: 1\r\n\
+B: 2\r\n\
+\r\n\
+a\r\n\
+--i\r\n\
+\r\n\
+b\r\n\
+--i--\r\n\
+--o--\r\n";
+
+        let options = ReaderOptions {
+            max_parts: Some(1),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "o", options);
+        let mut inner = reader.next_part().await.unwrap().unwrap().into_multipart().unwrap();
+        inner.next_part().await.unwrap().unwrap();
+        let err = inner.next_part().await.err().unwrap();
+        assert_eq!(err.limit_exceeded().map(|l| l.kind), Some(LimitKind::PartCount));
+
+        let options = ReaderOptions {
+            max_headers: Some(1),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "o", options);
+        let mut inner = reader.next_part().await.unwrap().unwrap().into_multipart().unwrap();
+        let err = inner.next_part().await.err().unwrap();
+        assert_eq!(err.limit_exceeded().map(|l| l.kind), Some(LimitKind::HeaderCount));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_before_next_part() {
+        let data = b"--boundary\r\n\r\nbody\r\n--boundary--\r\n";
+        let token = CancellationToken::new();
+        let options = ReaderOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "boundary", options);
+
+        token.cancel();
+        assert!(matches!(reader.next_part().await, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_while_waiting_for_input() {
+        // The writer half is kept open so the read never completes on its own
+        let (client, _server) = tokio::io::duplex(64);
+        let token = CancellationToken::new();
+        let options = ReaderOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(client, "boundary", options);
+
+        tokio::spawn(async move { token.cancel() });
+        assert!(matches!(reader.next_part().await, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_timeout() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = tokio::io::duplex(64);
+        let options = ReaderOptions {
+            idle_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(client, "b", options);
+
+        // A slow but steady client is fine
+        tokio::spawn(async move {
+            for chunk in [&b"--b\r\n\r\nhel"[..], b"lo\r\n--b\r\n\r\nsta"] {
+                tokio::time::sleep(Duration::from_secs(4)).await;
+                server.write_all(chunk).await.unwrap();
+            }
+            // Then it stalls with the connection open
+            std::future::pending::<()>().await;
+        });
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(&part.bytes().await.unwrap()[..], b"hello");
+
+        let start = tokio::time::Instant::now();
+        assert!(matches!(
+            reader.next_part().await,
+            Err(Error::Timeout(TimeoutKind::Idle))
+        ));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+        assert!(matches!(
+            reader.next_part().await,
+            Err(Error::Timeout(TimeoutKind::Idle))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_parse_timeout() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = tokio::io::duplex(64);
+        let options = ReaderOptions {
+            idle_timeout: Some(Duration::from_secs(5)),
+            parse_timeout: Some(Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(client, "b", options);
+
+        // One byte every second never trips the idle timeout
+        tokio::spawn(async move {
+            server.write_all(b"--b\r\n\r\n").await.unwrap();
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                if server.write_all(b"x").await.is_err() {
+                    break;
+                }
+            }
+        });
+        assert!(matches!(
+            reader.peek_next_headers().await,
+            Ok(Some(_))
+        ));
+        assert!(matches!(
+            reader.next_part().await,
+            Err(Error::Timeout(TimeoutKind::Deadline))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_during_body_read() {
+        let data = b"--boundary\r\n\r\nbody\r\n--boundary--\r\n";
+        let token = CancellationToken::new();
+        let options = ReaderOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "boundary", options);
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        token.cancel();
 
-        assert_eq!(header.get("content-type").unwrap()[0], "text/plain");
-        assert_eq!(header.get("content-length").unwrap()[0], "123");
+        let mut body = Vec::new();
+        let err = part.read_to_end(&mut body).await.unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(Error::Cancelled)
+        ));
     }
 
     #[tokio::test]
-    async fn test_parse_header_line() {
-        assert_eq!(
-            parse_header_line("Content-Type: text/plain\r\n"),
-            Some(("Content-Type", "text/plain"))
-        );
-        assert_eq!(
-            parse_header_line("Content-Length:123\n"),
-            Some(("Content-Length", "123"))
+    async fn test_max_expansion_ratio() {
+        use super::super::transform::FnTransform;
+
+        // Each part expands to 100 bytes per byte received
+        let data = b"--b\r\n\r\nab\r\n--b\r\n\r\nabcd\r\n--b--\r\n";
+        let expand = FnTransform::new(
+            |_: &MimeHeader| true,
+            |_: &MimeHeader, body: PartBody| -> PartBody {
+                Box::pin(body.take(4).chain(tokio::io::repeat(b'x')).take(200))
+            },
         );
+        let options = ReaderOptions {
+            max_expansion_ratio: Some(50),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        reader.add_transform(expand);
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        match part.bytes().await {
+            Err(Error::MessageTooLarge(limit)) => {
+                assert_eq!(limit.kind, LimitKind::DecodedSize);
+                assert_eq!(limit.limit, 100);
+                assert!(limit.observed > 100);
+                assert_eq!(limit.part_index, Some(0));
+            }
+            _ => panic!("expected a decoded size limit error"),
+        }
+
+        // 200 bytes from 4 is within the limit
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.bytes().await.unwrap().len(), 200);
+
+        // Raw parts are not decoded, so not limited
+        let options = ReaderOptions {
+            max_expansion_ratio: Some(0),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let mut part = reader.next_raw_part().await.unwrap().unwrap();
+        assert_eq!(&part.bytes().await.unwrap()[..], b"ab");
     }
 
     #[tokio::test]
-    async fn test_multipart_reader() {
+    async fn test_part_transform() {
+        use super::super::transform::FnTransform;
+        use std::io::Cursor;
+
         let data = b"--boundary\r\n\
-Content-Type: text/plain\r\n\
+X-Quote: yes\r\n\
 \r\n\
-Hello World\r\n\
+quoted\r\n\
 --boundary\r\n\
-Content-Type: text/html\r\n\
 \r\n\
-<html>test</html>\r\n\
+plain\r\n\
 --boundary--\r\n";
 
+        let transform = || {
+            FnTransform::new(
+                |header: &MimeHeader| header.get("x-quote").is_some(),
+                |_: &MimeHeader, body: PartBody| -> PartBody {
+                    Box::pin(Cursor::new(b"> ".to_vec()).chain(body))
+                },
+            )
+        };
+
         let mut reader = Reader::new(&data[..], "boundary");
+        reader.add_transform(transform());
+        reader.add_transform(transform());
 
-        // Read first part
-        let mut part1 = reader.next_part().await.unwrap().unwrap();
-        assert_eq!(part1.header.get("content-type").unwrap()[0], "text/plain");
+        let mut body = String::new();
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        part.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "> > quoted");
 
-        let mut body1 = String::new();
-        part1.read_to_string(&mut body1).await.unwrap();
-        assert_eq!(body1, "Hello World\r\n");
+        body.clear();
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        part.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "plain");
 
-        // Read second part
-        let mut part2 = reader.next_part().await.unwrap().unwrap();
-        assert_eq!(part2.header.get("content-type").unwrap()[0], "text/html");
+        // Raw parts bypass transforms
+        let mut reader = Reader::new(&data[..], "boundary");
+        reader.add_transform(transform());
+        body.clear();
+        let mut part = reader.next_raw_part().await.unwrap().unwrap();
+        part.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, "quoted");
+    }
 
-        let mut body2 = String::new();
-        part2.read_to_string(&mut body2).await.unwrap();
-        assert_eq!(body2, "<html>test</html>\r\n");
+    #[tokio::test]
+    async fn test_part_content_type() {
+        let data = b"--boundary\r\n\
+Content-Type: Text/HTML; Charset=\"UTF-8\"\r\n\
+\r\n\
+<p>hi</p>\r\n\
+--boundary\r\n\
+Content-Type: not a media type\r\n\
+\r\n\
+body\r\n\
+--boundary\r\n\
+\r\n\
+body\r\n\
+--boundary--\r\n";
 
-        // No more parts
-        assert!(reader.next_part().await.unwrap().is_none());
+        let mut reader = Reader::new(&data[..], "boundary");
+
+        let part = reader.next_part().await.unwrap().unwrap();
+        let (media_type, params) = part.content_type().unwrap();
+        assert_eq!(media_type, "text/html");
+        assert_eq!(params.get("charset"), Some(&"UTF-8".to_string()));
+
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.content_type().is_none());
+
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.content_type().is_none());
     }
 
     #[tokio::test]
-    async fn test_form_data() {
+    async fn test_file_name_extended_parameter() {
         let data = b"--boundary\r\n\
-Content-Disposition: form-data; name=\"field1\"\r\n\
+Content-Disposition: form-data; name=\"a\"; filename=\"EUR rates.txt\"; filename*=UTF-8''%E2%82%AC%20rates.txt\r\n\
 \r\n\
-value1\r\n\
+body\r\n\
 --boundary\r\n\
-Content-Disposition: form-data; name=\"file\"; filename=\"test.txt\"\r\n\
-Content-Type: text/plain\r\n\
+Content-Disposition: form-data; name=\"b\"; filename*=utf-8''%E2%82%AC.txt\r\n\
 \r\n\
-file content\r\n\
+body\r\n\
+--boundary\r\n\
+Content-Disposition: form-data; name=\"c\"; filename=\"fallback.txt\"; filename*=x-unknown''%C1\r\n\
+\r\n\
+body\r\n\
+--boundary\r\n\
+Content-Disposition: form-data; name=\"d\"; filename=\"=?us-ascii?q?caf=E9.txt?=\"\r\n\
+\r\n\
+body\r\n\
 --boundary--\r\n";
 
         let mut reader = Reader::new(&data[..], "boundary");
 
-        // Read first part (form field)
-        let mut part1 = reader.next_part().await.unwrap().unwrap();
-        assert_eq!(part1.form_name(), Some("field1"));
-
-        let mut body1 = String::new();
-        part1.read_to_string(&mut body1).await.unwrap();
-        assert_eq!(body1, "value1\r\n");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("€ rates.txt".to_string()));
 
-        // Read second part (file)
-        let mut part2 = reader.next_part().await.unwrap().unwrap();
-        assert_eq!(part2.form_name(), Some("file"));
-        assert_eq!(part2.file_name(), Some("test.txt".to_string()));
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("€.txt".to_string()));
 
-        let mut body2 = String::new();
-        part2.read_to_string(&mut body2).await.unwrap();
-        assert_eq!(body2, "file content\r\n");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("fallback.txt".to_string()));
 
-        // No more parts
-        assert!(reader.next_part().await.unwrap().is_none());
+        // Invalid in its charset, so kept as received rather than with U+FFFD
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name(), Some("=?us-ascii?q?caf=E9.txt?=".to_string()));
     }
 
     #[tokio::test]
@@ -647,6 +3409,47 @@ file content\r\n\
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_boundary_quoting_and_validation() {
+        let data = b"--a:b\r\n\r\nbody\r\n--a:b--\r\n";
+
+        // Quotes from a Content-Type value are stripped, bytes accepted
+        for boundary in [&b"\"a:b\""[..], b"\"a\\:b\"", b"a:b"] {
+            let mut reader = Reader::new(&data[..], boundary);
+            let mut part = reader.next_part().await.unwrap().unwrap();
+            assert_eq!(&part.bytes().await.unwrap()[..], b"body");
+        }
+
+        let long = "x".repeat(71);
+        for boundary in [long.as_bytes(), b"a<b", b"caf\xe9"] {
+            let message = [b"--", boundary, b"\r\n\r\nbody\r\n--", boundary, b"--\r\n"].concat();
+            let mut reader = Reader::new(&message[..], boundary);
+            assert!(matches!(reader.next_part().await, Err(Error::Multipart(_))));
+
+            let options = ReaderOptions {
+                lenient: true,
+                ..Default::default()
+            };
+            let mut reader = Reader::with_options(&message[..], boundary, options);
+            let mut part = reader.next_part().await.unwrap().unwrap();
+            assert_eq!(&part.bytes().await.unwrap()[..], b"body");
+            drop(part);
+            assert_eq!(reader.diagnostics().warnings, vec![Warning::InvalidBoundary]);
+        }
+
+        // Trailing spaces are only trimmed in lenient mode
+        let mut reader = Reader::new(&b"--b \r\n\r\n"[..], "b ");
+        assert!(reader.next_part().await.is_err());
+
+        // An empty boundary fails even in lenient mode
+        let options = ReaderOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "\"\"", options);
+        assert!(reader.next_part().await.is_err());
+    }
+
     #[tokio::test]
     async fn test_multipart_reader_no_parts() {
         // Test with no parts, just final boundary
@@ -669,7 +3472,7 @@ Content-Type: text/plain\r\n\
 
         let mut body = String::new();
         part.read_to_string(&mut body).await.unwrap();
-        assert_eq!(body, "\r\n");
+        assert_eq!(body, "");
     }
 
     #[tokio::test]
@@ -686,6 +3489,551 @@ Hello World\r\n";
         // Trying to read next part should fail with EOF
         let result = reader.next_part().await;
         assert!(result.is_err());
+        assert!(!reader.finished_cleanly());
+        assert!(reader.diagnostics().truncated);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_reader_finished_cleanly() {
+        let data = b"--boundary\r\n\r\nHello\r\n--boundary--\r\nepilogue";
+        let mut reader = Reader::new(&data[..], "boundary");
+        assert!(!reader.finished_cleanly());
+
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert!(reader.finished_cleanly());
+        assert!(!reader.diagnostics().truncated);
+
+        // Further calls keep reporting the end of the message
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_is_complete() {
+        let options = || ReaderOptions {
+            lenient: true,
+            ..Default::default()
+        };
+
+        let data = b"--b\r\n\r\none\r\n--b--\r\n";
+        let mut reader = Reader::with_options(&data[..], "b", options());
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert!(!reader.is_complete());
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert!(reader.is_complete());
+
+        // Input cut off right after a part, or after a delimiter
+        for data in [&b"--b\r\n\r\none\r\n"[..], b"--b\r\n\r\none\r\n--b\r\n"] {
+            let mut reader = Reader::with_options(data, "b", options());
+            while reader.next_part().await.unwrap().is_some() {}
+            assert!(!reader.is_complete());
+            assert!(reader.diagnostics().truncated);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_boundary_split_across_reads() {
+        let data = b"--boundary\r\n\r\nfirst\r\n--bound\r\n--boundary\r\n\r\nsecond\r\n--boundary--\r\n";
+
+        // Feed the input a few bytes at a time so delimiters straddle reads
+        let (mut tx, rx) = tokio::io::duplex(3);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            tx.write_all(data).await.unwrap();
+        });
+
+        let mut reader = Reader::new(rx, "boundary");
+        let mut bodies = Vec::new();
+        while let Some(mut part) = reader.next_part().await.unwrap() {
+            let mut body = String::new();
+            part.read_to_string(&mut body).await.unwrap();
+            bodies.push(body);
+        }
+        assert_eq!(bodies, vec!["first\r\n--bound", "second"]);
+        assert!(reader.finished_cleanly());
+    }
+
+    #[tokio::test]
+    async fn test_large_part_body() {
+        let body = b"0123456789abcdef\n".repeat(20_000);
+        let mut data = b"--b\r\n\r\n".to_vec();
+        data.extend_from_slice(&body);
+        data.extend_from_slice(b"--b--\r\n");
+
+        let mut reader = Reader::new(&data[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let mut read = Vec::new();
+        part.read_to_end(&mut read).await.unwrap();
+        // The last newline precedes the delimiter
+        assert_eq!(read, body[..body.len() - 1]);
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_partial_delimiter_start() {
+        let pattern = b"\n--b";
+        assert_eq!(partial_delimiter_start(b"abc", pattern), 3);
+        assert_eq!(partial_delimiter_start(b"abc\n-", pattern), 4);
+        assert_eq!(partial_delimiter_start(b"abc\n", pattern), 4);
+        assert_eq!(partial_delimiter_start(b"a\nx\n-", pattern), 4);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_mode() {
+        let data = b"--boundary \r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+first\r\n\
+--boundary\n\
+\n\
+second\n";
+        let options = ReaderOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], " boundary ", options);
+
+        let mut bodies = Vec::new();
+        while let Some(mut part) = reader.next_part().await.unwrap() {
+            let mut body = String::new();
+            part.read_to_string(&mut body).await.unwrap();
+            bodies.push(body);
+        }
+        assert_eq!(bodies, vec!["first", "second\n"]);
+
+        assert!(!reader.finished_cleanly());
+        let diagnostics = reader.diagnostics();
+        assert!(diagnostics.truncated);
+        assert_eq!(
+            diagnostics.warnings,
+            vec![
+                Warning::PaddedBoundary,
+                Warning::BareLineFeed,
+                Warning::MissingFinalBoundary
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_hook() {
+        let data = b"preamble line\r\n\
+\r\n\
+--b \t\r\n\
+\r\n\
+first\r\n\
+--b\n\
+\n\
+second\r\n\
+--b\n\
+\n\
+third";
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let options = ReaderOptions {
+            lenient: true,
+            on_skip: Some(SkipHook::new(move |skipped| {
+                let event = match skipped {
+                    Skipped::Preamble(line) => format!("preamble {:?}", String::from_utf8_lossy(line)),
+                    Skipped::BlankLine => "blank".to_string(),
+                    Skipped::Padding(padding) => format!("padding {:?}", String::from_utf8_lossy(padding)),
+                    Skipped::Tolerated(warning) => format!("{:?}", warning),
+                };
+                sink.lock().unwrap().push(event);
+            })),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        while reader.next_part().await.unwrap().is_some() {}
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "preamble \"preamble line\\r\\n\"",
+                "preamble \"\\r\\n\"",
+                "padding \" \\t\"",
+                "BareLineFeed",
+                "BareLineFeed",
+                "MissingFinalBoundary",
+            ]
+        );
+        // Diagnostics record each kind once
+        assert_eq!(
+            reader.diagnostics().warnings,
+            [Warning::BareLineFeed, Warning::MissingFinalBoundary]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_rejects_bare_lf_delimiter() {
+        let data = b"--boundary\r\n\r\nfirst\r\n--boundary\n\nsecond\r\n--boundary--\r\n";
+        let mut reader = Reader::new(&data[..], "boundary");
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert!(reader.next_part().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_header_count_limit_details() {
+        let mut data = b"--b\r\n\r\nfirst\r\n--b\r\n".to_vec();
+        for i in 0..=MAX_MIME_HEADERS {
+            data.extend_from_slice(format!("X-H{}: v\r\n", i).as_bytes());
+        }
+        data.extend_from_slice(b"\r\nbody\r\n--b--\r\n");
+
+        let mut reader = Reader::new(&data[..], "b");
+        assert!(reader.next_part().await.unwrap().is_some());
+        match reader.next_part().await {
+            Err(Error::MessageTooLarge(limit)) => {
+                assert_eq!(limit.kind, LimitKind::HeaderCount);
+                assert_eq!(limit.limit, MAX_MIME_HEADERS as u64);
+                assert_eq!(limit.observed, MAX_MIME_HEADERS as u64 + 1);
+                assert_eq!(limit.part_index, Some(1));
+            }
+            _ => panic!("expected a header count limit error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_long_header_line_salvage() {
+        let data = b"--b\r\nX-Short: ok\r\n\
+X-Long: caf\xc3\xa9-0123456789\r\n  folded-tail\r\n\
+X-After: ok\r\n\r\nbody\r\n--b--\r\n";
+
+        let options = || ReaderOptions {
+            max_header_line_length: Some(12),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options());
+        match reader.next_part().await {
+            Err(Error::MessageTooLarge(limit)) => {
+                assert_eq!(limit.kind, LimitKind::HeaderLineLength);
+                assert_eq!(limit.limit, 12);
+                assert_eq!(limit.part_index, Some(0));
+            }
+            _ => panic!("expected a header line length error"),
+        }
+
+        let options = ReaderOptions {
+            lenient: true,
+            ..options()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        // Cut at a character boundary, before the two-byte "é"
+        assert_eq!(part.header.get("X-Long"), Some("caf"));
+        assert_eq!(part.header.get("X-Short"), Some("ok"));
+        assert_eq!(part.header.get("X-After"), Some("ok"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"body");
+        drop(part);
+        assert!(reader.next_part().await.unwrap().is_none());
+
+        let diagnostics = reader.diagnostics();
+        assert_eq!(diagnostics.warnings, vec![Warning::TruncatedHeader]);
+        assert_eq!(
+            diagnostics.truncated_headers,
+            vec![TruncatedHeader {
+                part_index: 0,
+                name: "X-Long".to_string(),
+                length: 37,
+            }]
+        );
+
+        // The rest of a long line is counted, not buffered
+        let mut data = b"--b\r\nX-Long: ".to_vec();
+        data.resize(1 << 20, b'x');
+        data.extend_from_slice(b"\r\n\r\nbody\r\n--b--\r\n");
+        let lenient = ReaderOptions {
+            lenient: true,
+            max_header_line_length: Some(12),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", lenient);
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("X-Long"), Some("xxxx"));
+        assert_eq!(part.raw_headers(), b"X-Long: xxxxx\r\n");
+        drop(part);
+        assert_eq!(reader.diagnostics().truncated_headers[0].length, (1 << 20) - 5);
+
+        // Or rejected before it ends
+        let strict = ReaderOptions {
+            max_header_line_length: Some(12),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", strict);
+        let err = reader.next_part().await.err().unwrap();
+        let limit = err.limit_exceeded().unwrap();
+        assert_eq!(limit.kind, LimitKind::HeaderLineLength);
+        assert!(limit.observed <= READ_BUFFER_SIZE as u64, "{}", limit.observed);
+
+        // A limit shorter than the name still records the truncation
+        let options = ReaderOptions {
+            lenient: true,
+            max_header_line_length: Some(3),
+            ..Default::default()
+        };
+        let data = b"--b\r\nX-Long: v\r\n\r\n\r\n--b--\r\n";
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.header.get("X-Long").is_none());
+        drop(part);
+        assert_eq!(
+            reader.diagnostics().truncated_headers,
+            vec![TruncatedHeader {
+                part_index: 0,
+                name: "X-L".to_string(),
+                length: 9,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_was_final() {
+        let data = b"--b\r\n\r\none\r\n--b\r\n\r\ntwo\r\n--b--  \r\nepilogue";
+        // Delimiters arrive one byte at a time, so the closing one is split
+        let (mut tx, rx) = tokio::io::duplex(1);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            tx.write_all(data).await.unwrap();
+        });
+        let mut reader = Reader::new(rx, "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert!(!part.was_final());
+        assert_eq!(&part.bytes().await.unwrap()[..], b"one");
+        drop(part);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.was_final());
+        assert_eq!(&part.bytes().await.unwrap()[..], b"two");
+        drop(part);
+        assert!(reader.next_part().await.unwrap().is_none());
+
+        // Cut off before any closing delimiter
+        let mut reader = Reader::new(&b"--b\r\n\r\ntruncated"[..], "b");
+        assert!(!reader.next_part().await.unwrap().unwrap().was_final());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_header_utf8() {
+        let data = b"--b\r\nContent-Disposition: form-data; name=\"f\xffld\"; filename=\"a\xc3.txt\"\r\n\r\nbody\r\n--b--\r\n";
+
+        let mut reader = Reader::new(&data[..], "b");
+        match reader.next_part().await {
+            Err(err) => assert!(err.to_string().contains("valid UTF-8")),
+            Ok(_) => panic!("expected a UTF-8 error"),
+        }
+
+        let options = ReaderOptions {
+            invalid_header_utf8: InvalidUtf8::Replace,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("f\u{fffd}ld"));
+        assert_eq!(part.file_name(), Some("a\u{fffd}.txt".to_string()));
+        drop(part);
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert_eq!(reader.diagnostics().warnings, vec![Warning::InvalidUtf8]);
+    }
+
+    #[tokio::test]
+    async fn test_error_classification() {
+        let mut reader = Reader::new(&b"--b\r\n\r\ntruncated"[..], "b");
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert!(reader.next_part().await.is_err_and(|e| e.is_unexpected_eof()));
+
+        // Errors from a part's body reader keep their meaning
+        let token = CancellationToken::new();
+        let options = ReaderOptions {
+            cancellation: Some(token.clone()),
+            spill_threshold: Some(0),
+            ..Default::default()
+        };
+        let data = b"--b\r\n\r\nspilled body\r\n--b--\r\n";
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        token.cancel();
+        assert!(part.bytes().await.unwrap_err().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_max_parts() {
+        let data = b"--b\r\n\r\n1\r\n--b\r\n\r\n2\r\n--b\r\n\r\n3\r\n--b--\r\n";
+        let options = ReaderOptions {
+            max_parts: Some(2),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        assert_eq!(reader.parts_read(), 0);
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert!(reader.next_part().await.unwrap().is_some());
+        assert_eq!(reader.parts_read(), 2);
+        match reader.next_part().await {
+            Err(Error::MessageTooLarge(limit)) => {
+                assert_eq!(limit.kind, LimitKind::PartCount);
+                assert_eq!(limit.limit, 2);
+                assert_eq!(limit.observed, 3);
+                assert_eq!(limit.part_index, Some(2));
+            }
+            _ => panic!("expected a part count limit error"),
+        }
+
+        // Exactly at the limit is fine
+        let options = ReaderOptions {
+            max_parts: Some(3),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        while reader.next_part().await.unwrap().is_some() {}
+        assert_eq!(reader.parts_read(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_part_bytes_and_chunks() {
+        let data = b"--b\r\n\r\nhello\r\n--b\r\nX-Upper: 1\r\n\r\nworld\r\n--b--\r\n";
+        use super::super::transform::FnTransform;
+
+        let mut reader = Reader::new(&data[..], "b");
+        reader.add_transform(FnTransform::new(
+            |header| header.contains_key("X-Upper"),
+            |_, body: PartBody| -> PartBody {
+                Box::pin(tokio::io::BufReader::new(body))
+            },
+        ));
+
+        // Partially read, then take the rest without copying
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let mut first = [0u8; 2];
+        part.read_exact(&mut first).await.unwrap();
+        assert_eq!(&part.bytes().await.unwrap()[..], b"llo");
+        assert!(part.chunk().await.unwrap().is_none());
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let mut body = Vec::new();
+        while let Some(chunk) = part.chunk().await.unwrap() {
+            body.extend_from_slice(&chunk);
+        }
+        assert_eq!(body, b"world");
+    }
+
+    #[tokio::test]
+    async fn test_from_stream_error() {
+        let chunks = vec![
+            Ok(Bytes::from_static(b"--b\r\n\r\npartial")),
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset")),
+        ];
+        let mut reader = Reader::from_stream(futures::stream::iter(chunks), "b");
+        match reader.next_part().await {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionReset),
+            _ => panic!("expected the stream error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_part_content_description_and_location() {
+        let data = b"--b\r\n\
+Content-Description: =?UTF-8?B?w4ljb2xl?=\r\n\
+Content-Location: http://example.com/\r\n\x20index.html\r\n\
+\r\n\
+<html></html>\r\n\
+--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.content_description().as_deref(), Some("\u{c9}cole"));
+        assert_eq!(
+            part.content_location().as_deref(),
+            Some("http://example.com/index.html")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_next_part_cancel_safe() {
+        use futures::FutureExt;
+        use tokio::io::AsyncWriteExt;
+
+        let (mut tx, rx) = tokio::io::duplex(1024);
+        let mut reader = Reader::new(rx, "b");
+
+        // Each chunk ends mid-line; the pending future is dropped every time
+        let chunks: [&[u8]; 4] = [
+            b"pre",
+            b"amble\r\n--b\r\nContent-Ty",
+            b"pe: text/plain\r\n\r\nhel",
+            b"lo\r\n--",
+        ];
+        for chunk in chunks {
+            tx.write_all(chunk).await.unwrap();
+            assert!(reader.next_part().now_or_never().is_none());
+        }
+
+        tx.write_all(b"b--\r\n").await.unwrap();
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("content-type"), Some("text/plain"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"hello");
+
+        drop(tx);
+        assert!(reader.next_part().await.unwrap().is_none());
+        assert!(reader.finished_cleanly());
+    }
+
+    #[test]
+    fn test_normalize_header_value() {
+        assert_eq!(
+            normalize_header_value("  text/plain;\t\tcharset=utf-8 "),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            normalize_header_value("form-data;  filename=\"a  \\\"b\\\"  c.txt\"   ;x=1"),
+            "form-data; filename=\"a  \\\"b\\\"  c.txt\" ;x=1"
+        );
+        assert_eq!(normalize_header_value(""), "");
+    }
+
+    #[tokio::test]
+    async fn test_normalize_header_values_option() {
+        let data = b"--b\r\n\
+Content-Type: multipart/mixed;\r\n\
+\t\tboundary=inner\r\n\
+\r\n\
+body\r\n\
+--b--\r\n";
+        let options = ReaderOptions {
+            normalize_header_values: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options);
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("content-type"), Some("multipart/mixed; boundary=inner"));
+        assert_eq!(
+            part.original_header().get("content-type"),
+            Some("multipart/mixed;\t\tboundary=inner")
+        );
+
+        // Without the option, both views are the values as received
+        let mut reader = Reader::new(&data[..], "b");
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header, *part.original_header());
+    }
+
+    #[tokio::test]
+    async fn test_raw_headers() {
+        let data = b"--b\r\n\
+x-B: 2\r\n\
+Content-TYPE:text/plain;\r\n\
+\tcharset=utf-8\r\n\
+\r\n\
+body\r\n\
+--b\r\n\
+\r\n\
+no headers\r\n\
+--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(
+            part.raw_headers(),
+            b"x-B: 2\r\nContent-TYPE:text/plain;\r\n\tcharset=utf-8\r\n"
+        );
+        assert_eq!(part.header.get("content-type"), Some("text/plain;\tcharset=utf-8"));
+
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.raw_headers(), b"");
     }
 
     #[tokio::test]
@@ -703,7 +4051,39 @@ Hello World\r\n\
 
         let mut body = String::new();
         part.read_to_string(&mut body).await.unwrap();
-        assert_eq!(body, "Hello World\r\n");
+        assert_eq!(body, "Hello World");
+    }
+
+    #[tokio::test]
+    async fn test_max_preamble_size() {
+        let data = b"0123456789\r\n--b\r\n\r\nbody\r\n--b--\r\n";
+        let options = ReaderOptions {
+            max_preamble_size: Some(12),
+            ..Default::default()
+        };
+        let mut reader = Reader::with_options(&data[..], "b", options.clone());
+        assert!(reader.next_part().await.unwrap().is_some());
+
+        // The wrong boundary fails once the limit is passed, not at EOF
+        let mut body = b"x\r\n".repeat(1000);
+        body.extend_from_slice(data);
+        let mut reader = Reader::with_options(&body[..], "wrong", options.clone());
+        let err = reader.next_part().await.err().unwrap();
+        assert!(err.is_malformed());
+        assert!(matches!(
+            err,
+            Error::BoundaryNotFound { ref expected, bytes_scanned: 15 } if expected == "wrong"
+        ));
+
+        // Also before the end of a preamble line
+        let body = vec![b'x'; 1 << 20];
+        let mut reader = Reader::with_options(&body[..], "b", options);
+        match reader.next_part().await.err().unwrap() {
+            Error::BoundaryNotFound { bytes_scanned, .. } => {
+                assert!(bytes_scanned <= READ_BUFFER_SIZE as u64, "{}", bytes_scanned)
+            }
+            other => panic!("unexpected error: {other}"),
+        }
     }
 
     #[tokio::test]
@@ -720,7 +4100,7 @@ Hello World\n\
 
         let mut body = String::new();
         part.read_to_string(&mut body).await.unwrap();
-        assert_eq!(body, "Hello World\n");
+        assert_eq!(body, "Hello World");
     }
 
     #[tokio::test]
@@ -746,7 +4126,7 @@ Hello World\n\
         // Test with header that has no blank line
         let data = b"Content-Type: text/plain\r\n";
         let mut reader = BufReader::new(&data[..]);
-        let result = read_mime_header(&mut reader).await;
+        let result = read_mime_header(&mut reader, &ReaderOptions::default()).await;
         // Should succeed but return empty header or handle gracefully
         assert!(result.is_ok() || result.is_err());
     }
@@ -0,0 +1,239 @@
+//! `multipart/related` messages (RFC 2387).
+//!
+//! A related message is a compound document: a root part, e.g. an HTML page
+//! or a SOAP envelope, plus the parts it references through `cid:` URLs.
+//! [`RelatedReader`] returns the root first, wherever it appears in the
+//! message, and tracks the Content-ID of every part returned.
+
+use super::reader::{Part, Reader, ReaderOptions};
+use crate::error::{Error, Result};
+use crate::media_type::{parse_media_type, percent_decode};
+use std::collections::{HashMap, VecDeque};
+use tokio::io::AsyncRead;
+
+/// A reader for `multipart/related` messages.
+///
+/// The root part is the one named by the `start` parameter, or the first
+/// part if there is none. It is always returned first; the other parts
+/// follow in message order. Parts before the root are buffered until it is
+/// found.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::RelatedReader;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let content_type = r#"multipart/related; boundary=b; type="text/html"; start="<page>""#;
+/// let data = b"--b\r\nContent-ID: <logo>\r\n\r\nPNG\r\n\
+/// --b\r\nContent-ID: <page>\r\n\r\n<img src=\"cid:logo\">\r\n--b--\r\n";
+///
+/// let mut reader = RelatedReader::new(&data[..], content_type)?;
+/// assert_eq!(reader.root_type(), Some("text/html"));
+///
+/// let root = reader.next_part().await?.unwrap();
+/// assert_eq!(root.content_id().as_deref(), Some("page"));
+///
+/// while reader.next_part().await?.is_some() {}
+/// assert_eq!(reader.resolve("cid:logo"), Some(1));
+/// # Ok(())
+/// # }
+/// ```
+pub struct RelatedReader<R> {
+    reader: Reader<R>,
+    start: Option<String>,
+    root_type: Option<String>,
+    start_info: Option<String>,
+    root_returned: bool,
+    /// Parts read while looking for the root.
+    pending: VecDeque<Part<R>>,
+    content_ids: HashMap<String, usize>,
+    returned: usize,
+}
+
+impl<R: AsyncRead + Unpin> RelatedReader<R> {
+    /// Creates a reader for a message with the given Content-Type value.
+    ///
+    /// Fails unless the media type is `multipart/related` with a boundary.
+    pub fn new(r: R, content_type: &str) -> Result<Self> {
+        Self::with_options(r, content_type, ReaderOptions::default())
+    }
+
+    /// Creates a reader with the given options for the underlying [`Reader`].
+    pub fn with_options(r: R, content_type: &str, options: ReaderOptions) -> Result<Self> {
+        let (media_type, mut params) = parse_media_type(content_type)?;
+        if media_type != "multipart/related" {
+            return Err(Error::Multipart(format!(
+                "not multipart/related: {}",
+                media_type
+            )));
+        }
+        let boundary = params
+            .get("boundary")
+            .filter(|b| !b.is_empty())
+            .ok_or_else(|| Error::Multipart("multipart/related has no boundary".to_string()))?;
+
+        let reader = Reader::with_options(r, boundary, options);
+        let start = params.remove("start").map(|s| strip_angle_brackets(&s).to_string());
+
+        Ok(Self {
+            reader,
+            start,
+            root_type: params.remove("type"),
+            start_info: params.remove("start-info"),
+            root_returned: false,
+            pending: VecDeque::new(),
+            content_ids: HashMap::new(),
+            returned: 0,
+        })
+    }
+
+    /// Returns the media type of the root part (the `type` parameter).
+    pub fn root_type(&self) -> Option<&str> {
+        self.root_type.as_deref()
+    }
+
+    /// Returns the Content-ID of the root part (the `start` parameter),
+    /// without angle brackets.
+    pub fn start(&self) -> Option<&str> {
+        self.start.as_deref()
+    }
+
+    /// Returns the `start-info` parameter, processing information for the
+    /// root part.
+    pub fn start_info(&self) -> Option<&str> {
+        self.start_info.as_deref()
+    }
+
+    /// Returns the underlying multipart reader, e.g. for its diagnostics.
+    pub fn reader(&self) -> &Reader<R> {
+        &self.reader
+    }
+
+    /// Returns the next part, starting with the root.
+    ///
+    /// Fails if a `start` parameter names a part the message does not have.
+    pub async fn next_part(&mut self) -> Result<Option<Part<R>>> {
+        let part = if self.root_returned {
+            match self.pending.pop_front() {
+                Some(part) => Some(part),
+                None => self.reader.next_part().await?,
+            }
+        } else {
+            let root = self.find_root().await?;
+            self.root_returned = true;
+            Some(root)
+        };
+
+        if let Some(part) = &part {
+            if let Some(id) = part.content_id() {
+                self.content_ids.entry(id).or_insert(self.returned);
+            }
+            self.returned += 1;
+        }
+        Ok(part)
+    }
+
+    /// Reads parts until the root, keeping the others for later.
+    async fn find_root(&mut self) -> Result<Part<R>> {
+        loop {
+            let part = match self.reader.next_part().await? {
+                Some(part) => part,
+                None => {
+                    return Err(Error::Multipart(match &self.start {
+                        Some(start) => format!("root part <{}> not found", start),
+                        None => "multipart/related has no parts".to_string(),
+                    }))
+                }
+            };
+            let is_root = match &self.start {
+                Some(start) => part.content_id().as_deref() == Some(start.as_str()),
+                None => true,
+            };
+            if is_root {
+                return Ok(part);
+            }
+            self.pending.push_back(part);
+        }
+    }
+
+    /// Returns the Content-ID of each part returned so far, mapped to the
+    /// part's position in the order returned (the root is 0).
+    pub fn content_ids(&self) -> &HashMap<String, usize> {
+        &self.content_ids
+    }
+
+    /// Resolves a `cid:` URL (RFC 2392) to the position of the part it
+    /// references, among the parts returned so far.
+    pub fn resolve(&self, url: &str) -> Option<usize> {
+        let cid = url
+            .get(..4)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("cid:"))
+            .map(|_| &url[4..])?;
+        let cid = String::from_utf8(percent_decode(cid)?).ok()?;
+        self.content_ids.get(strip_angle_brackets(&cid)).copied()
+    }
+}
+
+fn strip_angle_brackets(id: &str) -> &str {
+    let id = id.trim();
+    id.strip_prefix('<')
+        .and_then(|id| id.strip_suffix('>'))
+        .unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"--b\r\nContent-ID: <a%b@example.com>\r\n\r\nfirst\r\n\
+--b\r\nContent-ID: <root@example.com>\r\n\r\nroot\r\n\
+--b\r\nContent-ID: <c@example.com>\r\n\r\nlast\r\n--b--\r\n";
+
+    async fn read_ids(reader: &mut RelatedReader<&[u8]>) -> Vec<String> {
+        let mut ids = Vec::new();
+        while let Some(part) = reader.next_part().await.unwrap() {
+            ids.push(part.content_id().unwrap());
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_root_from_start_parameter() {
+        let content_type = "multipart/related; boundary=b; start=\"<root@example.com>\"; \
+start-info=\"-o ps\"";
+        let mut reader = RelatedReader::new(DATA, content_type).unwrap();
+        assert_eq!(reader.start(), Some("root@example.com"));
+        assert_eq!(reader.start_info(), Some("-o ps"));
+        assert_eq!(reader.root_type(), None);
+
+        assert_eq!(
+            read_ids(&mut reader).await,
+            ["root@example.com", "a%b@example.com", "c@example.com"]
+        );
+        assert_eq!(reader.resolve("cid:root@example.com"), Some(0));
+        // Content-IDs in URLs are percent-encoded
+        assert_eq!(reader.resolve("CID:a%25b@example.com"), Some(1));
+        assert_eq!(reader.resolve("cid:missing"), None);
+        assert_eq!(reader.resolve("http://example.com/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_root_defaults_to_first_part() {
+        let mut reader = RelatedReader::new(DATA, "multipart/related; boundary=b").unwrap();
+        assert_eq!(
+            read_ids(&mut reader).await,
+            ["a%b@example.com", "root@example.com", "c@example.com"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_root_and_wrong_type() {
+        let content_type = "multipart/related; boundary=b; start=\"<nope>\"";
+        let mut reader = RelatedReader::new(DATA, content_type).unwrap();
+        assert!(matches!(reader.next_part().await, Err(Error::Multipart(_))));
+
+        assert!(RelatedReader::new(DATA, "multipart/mixed; boundary=b").is_err());
+        assert!(RelatedReader::new(DATA, "multipart/related").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Checking HTTP requests before their multipart body is read.
+//!
+//! A [`RequestValidator`] looks only at the Content-Type and Content-Length
+//! of a request, so a server can reject a bad upload with a 400 or 413
+//! before reading any of it. Middleware runs the check up front and stores
+//! the resulting [`ValidatedRequest`] alongside the request; the handler
+//! turns it into a [`Reader`] over the body when it gets there.
+//! With the `tower` feature, `MultipartLayer` does this for tower-based
+//! stacks.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::multipart::RequestValidator;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let validator = RequestValidator::new().max_content_length(1 << 20);
+//!
+//! // In middleware, from the request headers
+//! let validated = validator.check(Some("multipart/form-data; boundary=b"), Some(42))?;
+//!
+//! // In the handler, once the body is available
+//! let body = &b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--b--\r\n"[..];
+//! let mut reader = validated.reader(body);
+//! assert!(reader.next_part().await?.is_some());
+//!
+//! assert!(validator.check(Some("text/plain"), None).is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use super::reader::{Reader, ReaderOptions};
+use super::writer::validate_boundary;
+use crate::error::{Error, LimitExceeded, LimitKind, Result};
+use crate::media_type::parse_media_type;
+use tokio::io::AsyncRead;
+
+/// Checks the headers of requests carrying multipart bodies.
+#[derive(Debug, Clone)]
+pub struct RequestValidator {
+    subtypes: Vec<String>,
+    max_content_length: Option<u64>,
+    options: ReaderOptions,
+}
+
+impl Default for RequestValidator {
+    fn default() -> Self {
+        Self {
+            subtypes: vec!["form-data".to_string()],
+            max_content_length: None,
+            options: ReaderOptions::default(),
+        }
+    }
+}
+
+impl RequestValidator {
+    /// Creates a validator accepting `multipart/form-data` of any size.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts the given multipart subtypes instead of only `form-data`.
+    pub fn subtypes<I, S>(mut self, subtypes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.subtypes = subtypes
+            .into_iter()
+            .map(|s| s.into().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Rejects requests whose Content-Length exceeds `limit`.
+    ///
+    /// Bodies sent without a Content-Length, e.g. chunked, are limited while
+    /// they are read instead: the limit is also set as the readers'
+    /// [`max_message_size`](ReaderOptions::max_message_size), unless that
+    /// is lower.
+    pub fn max_content_length(mut self, limit: u64) -> Self {
+        self.max_content_length = Some(limit);
+        self
+    }
+
+    /// Sets the options of the readers created for validated requests.
+    pub fn reader_options(mut self, options: ReaderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Checks a request's Content-Type and Content-Length header values.
+    ///
+    /// Fails with [`Error::MediaType`] if the Content-Type is missing,
+    /// malformed or not an accepted multipart subtype, with
+    /// [`Error::Multipart`] if its boundary is missing or invalid, and with
+    /// [`Error::MessageTooLarge`] of kind [`LimitKind::MessageSize`] if the
+    /// Content-Length is over the limit.
+    pub fn check(
+        &self,
+        content_type: Option<&str>,
+        content_length: Option<u64>,
+    ) -> Result<ValidatedRequest> {
+        let content_type = content_type
+            .ok_or_else(|| Error::MediaType("missing Content-Type".to_string()))?;
+        let (media_type, mut params) = parse_media_type(content_type)?;
+        let subtype = match media_type.strip_prefix("multipart/") {
+            Some(subtype) if self.subtypes.iter().any(|s| s == subtype) => subtype.to_string(),
+            _ => {
+                return Err(Error::MediaType(format!(
+                    "unexpected media type: {}",
+                    media_type
+                )))
+            }
+        };
+        let boundary = params
+            .remove("boundary")
+            .ok_or_else(|| Error::Multipart("no multipart boundary param in Content-Type".to_string()))?;
+        validate_boundary(boundary.as_bytes())?;
+
+        let mut options = self.options.clone();
+        if let Some(limit) = self.max_content_length {
+            if let Some(length) = content_length.filter(|&length| length > limit) {
+                return Err(Error::MessageTooLarge(LimitExceeded::new(
+                    LimitKind::MessageSize,
+                    limit,
+                    length,
+                )));
+            }
+            options.max_message_size = Some(options.max_message_size.map_or(limit, |max| max.min(limit)));
+        }
+
+        Ok(ValidatedRequest {
+            subtype,
+            boundary,
+            content_length,
+            options,
+        })
+    }
+}
+
+/// A request whose headers passed a [`RequestValidator`], ready to have its
+/// body read.
+#[derive(Debug, Clone)]
+pub struct ValidatedRequest {
+    subtype: String,
+    boundary: String,
+    content_length: Option<u64>,
+    options: ReaderOptions,
+}
+
+impl ValidatedRequest {
+    /// Returns the multipart subtype, e.g. "form-data".
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// Returns the boundary named by the Content-Type.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Returns the Content-Length the request was checked with.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Creates a reader for the request body.
+    pub fn reader<R: AsyncRead + Unpin>(self, body: R) -> Reader<R> {
+        Reader::with_options(body, self.boundary, self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_request_headers() {
+        let validator = RequestValidator::new().max_content_length(100);
+
+        let validated = validator
+            .check(Some("Multipart/Form-Data; boundary=\"a b\""), Some(100))
+            .unwrap();
+        assert_eq!(validated.subtype(), "form-data");
+        assert_eq!(validated.boundary(), "a b");
+
+        assert!(matches!(validator.check(None, None), Err(Error::MediaType(_))));
+        assert!(matches!(
+            validator.check(Some("multipart/mixed; boundary=b"), None),
+            Err(Error::MediaType(_))
+        ));
+        assert!(matches!(
+            validator.check(Some("multipart/form-data"), None),
+            Err(Error::Multipart(_))
+        ));
+        let err = validator
+            .check(Some("multipart/form-data; boundary=b"), Some(101))
+            .err()
+            .unwrap();
+        assert_eq!(err.limit_exceeded().map(|l| l.observed), Some(101));
+
+        // Without a Content-Length the limit applies while reading
+        let validator = validator.subtypes(["mixed"]);
+        let validated = validator.check(Some("multipart/mixed; boundary=b"), None).unwrap();
+        let body = [b"--b\r\n\r\n".as_slice(), &[b'x'; 200], b"\r\n--b--\r\n"].concat();
+        let mut reader = validated.reader(&body[..]);
+        let err = reader.next_part().await.err().unwrap();
+        assert_eq!(err.limit_exceeded().map(|l| l.kind), Some(LimitKind::MessageSize));
+    }
+}
@@ -0,0 +1,351 @@
+//! Validating forms against the fields a handler expects.
+//!
+//! A [`FormSchema`] names the fields of a form, whether each is a plain
+//! value or a file, whether it is required, and how large it may be.
+//! [`Reader::read_form_with_schema`](super::Reader::read_form_with_schema)
+//! checks each part's header before reading its body, so the body of an
+//! unexpected field is dropped as it is read and that of an oversized one
+//! once past its limit, rather than buffered whole. The form then fails with
+//! [`Error::InvalidForm`](crate::Error::InvalidForm) listing every
+//! violation found.
+//!
+//! # Examples
+//!
+//! ```
+//! use yamime::multipart::{FieldRule, FormSchema, FormViolation, Reader};
+//! use yamime::Error;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let schema = FormSchema::new()
+//!     .field("title", FieldRule::value().required().max_size(100))
+//!     .field("avatar", FieldRule::file().max_size(1 << 20));
+//!
+//! let data = b"--b\r\nContent-Disposition: form-data; name=\"avatar\"\r\n\r\nnot a file\r\n--b--\r\n";
+//! let err = Reader::new(&data[..], "b")
+//!     .read_form_with_schema(1 << 20, &schema)
+//!     .await
+//!     .err()
+//!     .unwrap();
+//! let Error::InvalidForm(violations) = err else { panic!() };
+//! assert_eq!(violations.len(), 2);
+//! assert_eq!(violations[1], FormViolation::Missing("title".to_string()));
+//! assert_eq!(
+//!     violations[0].to_string(),
+//!     "field \"avatar\" is not a file"
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Default maximum number of parts, as for [`Reader::read_form`](super::Reader::read_form).
+const MAX_PARTS_DEFAULT: usize = 1000;
+
+/// Whether a field carries a plain value or a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    /// A part without a filename.
+    Value,
+    /// A part with a filename.
+    File,
+}
+
+impl fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FieldKind::Value => "value",
+            FieldKind::File => "file",
+        })
+    }
+}
+
+/// What a [`FormSchema`] expects of one field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRule {
+    /// Whether the field is a value or a file.
+    pub kind: FieldKind,
+    /// The form fails if the field is absent.
+    pub required: bool,
+    /// Maximum size in bytes of each occurrence of the field.
+    pub max_size: Option<u64>,
+}
+
+impl FieldRule {
+    /// An optional plain value of any size.
+    pub fn value() -> Self {
+        Self {
+            kind: FieldKind::Value,
+            required: false,
+            max_size: None,
+        }
+    }
+
+    /// An optional file of any size.
+    pub fn file() -> Self {
+        Self {
+            kind: FieldKind::File,
+            ..Self::value()
+        }
+    }
+
+    /// Makes the field required.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Limits the size of each occurrence of the field.
+    pub fn max_size(mut self, limit: u64) -> Self {
+        self.max_size = Some(limit);
+        self
+    }
+}
+
+/// The fields a form is expected to have.
+#[derive(Debug, Clone)]
+pub struct FormSchema {
+    fields: HashMap<String, FieldRule>,
+    allow_unknown: bool,
+    max_parts: usize,
+}
+
+impl Default for FormSchema {
+    fn default() -> Self {
+        Self {
+            fields: HashMap::new(),
+            allow_unknown: false,
+            max_parts: MAX_PARTS_DEFAULT,
+        }
+    }
+}
+
+impl FormSchema {
+    /// Creates a schema with no fields, rejecting unknown fields and
+    /// allowing up to 1000 parts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a field, replacing any earlier rule for the same name.
+    pub fn field(mut self, name: impl Into<String>, rule: FieldRule) -> Self {
+        self.fields.insert(name.into(), rule);
+        self
+    }
+
+    /// Accepts fields the schema does not declare, instead of reporting
+    /// them as [`FormViolation::Unexpected`].
+    pub fn allow_unknown(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    /// Sets the maximum number of parts.
+    ///
+    /// Exceeding it fails with [`Error::MessageTooLarge`](crate::Error::MessageTooLarge)
+    /// right away, as in [`Reader::read_form`](super::Reader::read_form).
+    pub fn max_parts(mut self, limit: usize) -> Self {
+        self.max_parts = limit;
+        self
+    }
+
+    pub(crate) fn max_part_count(&self) -> usize {
+        self.max_parts
+    }
+
+    /// Checks a part as it starts: returns the size limit to read it with,
+    /// or the violation for which it is to be discarded.
+    pub(crate) fn check_part(
+        &self,
+        name: &str,
+        kind: FieldKind,
+    ) -> std::result::Result<Option<u64>, FormViolation> {
+        match self.fields.get(name) {
+            Some(rule) if rule.kind == kind => Ok(rule.max_size),
+            Some(rule) => Err(FormViolation::WrongKind {
+                field: name.to_string(),
+                expected: rule.kind,
+            }),
+            None if self.allow_unknown => Ok(None),
+            None => Err(FormViolation::Unexpected(name.to_string())),
+        }
+    }
+
+    /// Returns the required fields that are not in `present`, sorted by
+    /// name.
+    pub(crate) fn missing(&self, present: impl Fn(&str) -> bool) -> Vec<&str> {
+        let mut missing: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|(name, rule)| rule.required && !present(name))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        missing.sort_unstable();
+        missing
+    }
+}
+
+/// A way in which a form does not match its [`FormSchema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormViolation {
+    /// A required field is absent.
+    Missing(String),
+    /// A field the schema does not declare.
+    Unexpected(String),
+    /// A value where a file was expected, or the other way around.
+    WrongKind {
+        /// The field name.
+        field: String,
+        /// The kind the schema declares.
+        expected: FieldKind,
+    },
+    /// An occurrence of a field exceeds its size limit.
+    TooLarge {
+        /// The field name.
+        field: String,
+        /// The configured limit in bytes.
+        limit: u64,
+    },
+}
+
+impl fmt::Display for FormViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormViolation::Missing(field) => write!(f, "missing field {:?}", field),
+            FormViolation::Unexpected(field) => write!(f, "unexpected field {:?}", field),
+            FormViolation::WrongKind { field, expected } => {
+                write!(f, "field {:?} is not a {}", field, expected)
+            }
+            FormViolation::TooLarge { field, limit } => {
+                write!(f, "field {:?} exceeds {} bytes", field, limit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Reader;
+    use super::*;
+    use crate::error::Error;
+
+    #[tokio::test]
+    async fn test_read_form_with_schema() {
+        let schema = FormSchema::new()
+            .field("title", FieldRule::value().required().max_size(5))
+            .field("doc", FieldRule::file().max_size(3));
+        let field = |name: &str, extra: &str, body: &str| {
+            format!(
+                "--b\r\nContent-Disposition: form-data; name=\"{}\"{}\r\n\r\n{}\r\n",
+                name, extra, body
+            )
+        };
+
+        let data = field("title", "", "hello") + &field("doc", "; filename=\"a.txt\"", "abc") + "--b--\r\n";
+        let form = Reader::new(data.as_bytes(), "b")
+            .read_form_with_schema(1024, &schema)
+            .await
+            .unwrap();
+        assert_eq!(form.value["title"], ["hello"]);
+        assert_eq!(form.file["doc"][0].size, 3);
+
+        let data = field("title", "; filename=\"t\"", "x")
+            + &field("doc", "; filename=\"a.txt\"", "abcd")
+            + &field("extra", "", "1")
+            + &field("extra", "", "2")
+            + "--b--\r\n";
+        let err = Reader::new(data.as_bytes(), "b")
+            .read_form_with_schema(1024, &schema)
+            .await
+            .err()
+            .unwrap();
+        let Error::InvalidForm(violations) = err else {
+            panic!("unexpected error: {}", err)
+        };
+        assert_eq!(
+            violations,
+            [
+                FormViolation::WrongKind {
+                    field: "title".to_string(),
+                    expected: FieldKind::Value
+                },
+                FormViolation::TooLarge {
+                    field: "doc".to_string(),
+                    limit: 3
+                },
+                FormViolation::Unexpected("extra".to_string()),
+                FormViolation::Missing("title".to_string()),
+            ]
+        );
+
+        let data = field("a", "", "1").repeat(3) + "--b--\r\n";
+        let err = Reader::new(data.as_bytes(), "b")
+            .read_form_with_schema(1024, &FormSchema::new().allow_unknown(true).max_parts(2))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.limit_exceeded().map(|l| l.limit), Some(2));
+
+        // Bodies past the 32 MB buffered for a part are dropped as read
+        let large = "x".repeat((32 << 20) + 1);
+        let data = field("title", "", "hello")
+            + &field("doc", "; filename=\"a.txt\"", &large)
+            + &field("extra", "", &large)
+            + "--b--\r\n";
+        let err = Reader::new(data.as_bytes(), "b")
+            .read_form_with_schema(1024, &schema)
+            .await
+            .err()
+            .unwrap();
+        let Error::InvalidForm(violations) = err else {
+            panic!("unexpected error: {}", err)
+        };
+        assert_eq!(
+            violations,
+            [
+                FormViolation::TooLarge {
+                    field: "doc".to_string(),
+                    limit: 3
+                },
+                FormViolation::Unexpected("extra".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_part() {
+        let schema = FormSchema::new()
+            .field("a", FieldRule::value().required().max_size(10))
+            .field("b", FieldRule::file());
+
+        assert_eq!(schema.check_part("a", FieldKind::Value), Ok(Some(10)));
+        assert_eq!(schema.check_part("b", FieldKind::File), Ok(None));
+        assert_eq!(
+            schema.check_part("a", FieldKind::File),
+            Err(FormViolation::WrongKind {
+                field: "a".to_string(),
+                expected: FieldKind::Value
+            })
+        );
+        assert_eq!(
+            schema.check_part("c", FieldKind::Value),
+            Err(FormViolation::Unexpected("c".to_string()))
+        );
+        assert_eq!(
+            schema.clone().allow_unknown(true).check_part("c", FieldKind::File),
+            Ok(None)
+        );
+
+        assert_eq!(schema.missing(|name| name == "b"), ["a"]);
+        assert!(schema.missing(|name| name == "a").is_empty());
+        assert_eq!(
+            FormViolation::WrongKind {
+                field: "a".to_string(),
+                expected: FieldKind::Value
+            }
+            .to_string(),
+            "field \"a\" is not a value"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Temporary files holding part bodies too large to keep in memory.
+//!
+//! See [`ReaderOptions::spill_threshold`](super::ReaderOptions::spill_threshold).
+//!
+//! Files are created in the system temporary directory with `O_EXCL`
+//! semantics, so two readers can never share one, and on Unix are readable
+//! only by their owner. Names are made of the configured
+//! [prefix](super::ReaderOptions::temp_file_prefix), a Unix timestamp, the
+//! process id and a random UUID; nothing in them comes from the message.
+
+use crate::error::Error;
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncSeekExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio_util::sync::CancellationToken;
+
+/// Number of names tried before giving up on creating a temporary file.
+const CREATE_ATTEMPTS: usize = 4;
+
+/// Creates a new, empty temporary file whose name starts with `prefix`,
+/// failing rather than opening a file that already exists.
+///
+/// A prefix with a path separator or `..` is rejected, so the file is
+/// always created directly in the temporary directory.
+pub(crate) async fn create_temp_file(prefix: &str) -> io::Result<(File, PathBuf)> {
+    if prefix.contains(['/', '\\']) || prefix.contains("..") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid temporary file prefix: {:?}", prefix),
+        ));
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let mut last_err = None;
+    for _ in 0..CREATE_ATTEMPTS {
+        let path = std::env::temp_dir().join(format!(
+            "{}{}-{}-{}",
+            prefix,
+            timestamp,
+            std::process::id(),
+            uuid::Uuid::new_v4().simple()
+        ));
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        match options.open(&path).await {
+            Ok(file) => return Ok((file, path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => last_err = Some(err),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::ErrorKind::AlreadyExists.into()))
+}
+
+/// A temporary file path, removed when dropped.
+#[derive(Debug)]
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// A part body being written to a temporary file.
+#[derive(Debug)]
+pub(crate) struct Spill {
+    // Declared before `path` so the file is closed before it is removed
+    file: File,
+    path: TempPath,
+    len: u64,
+}
+
+impl Spill {
+    /// Creates a new, empty temporary file.
+    pub(crate) async fn create(prefix: &str) -> io::Result<Self> {
+        let (file, path) = create_temp_file(prefix).await?;
+        Ok(Self {
+            file,
+            path: TempPath(path),
+            len: 0,
+        })
+    }
+
+    /// Writes all but the last `keep` bytes of `data` to the file, removing
+    /// what was written.
+    ///
+    /// Bytes leave `data` only once the file has accepted them, so dropping
+    /// the future part way loses nothing.
+    pub(crate) async fn write_from(&mut self, data: &mut Vec<u8>, keep: usize) -> io::Result<()> {
+        while data.len() > keep {
+            let end = data.len() - keep;
+            let n = std::future::poll_fn(|cx| Pin::new(&mut self.file).poll_write(cx, &data[..end]))
+                .await?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            data.drain(..n);
+            self.len += n as u64;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of bytes written to the file.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Flushes the file and rewinds it for reading.
+    ///
+    /// Must be called once everything has been written; calling it again is
+    /// harmless.
+    pub(crate) async fn finish(&mut self) -> io::Result<()> {
+        self.file.flush().await?;
+        self.file.seek(SeekFrom::Start(0)).await?;
+        Ok(())
+    }
+
+    /// Turns the finished file into a part body.
+    pub(crate) fn into_body(self, cancellation: Option<CancellationToken>) -> SpilledBody {
+        SpilledBody {
+            file: self.file,
+            path: self.path,
+            cancellation,
+        }
+    }
+}
+
+/// A part body read back from its temporary file.
+///
+/// The file is deleted when the body is dropped.
+#[derive(Debug)]
+pub(crate) struct SpilledBody {
+    file: File,
+    path: TempPath,
+    cancellation: Option<CancellationToken>,
+}
+
+impl SpilledBody {
+    /// Returns the path of the temporary file.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path.0
+    }
+}
+
+impl AsyncRead for SpilledBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.cancellation.as_ref().is_some_and(|t| t.is_cancelled()) {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, Error::Cancelled)));
+        }
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_temp_files_are_exclusive() {
+        let (_a, path_a) = create_temp_file("yamime-test-").await.unwrap();
+        let (_b, path_b) = create_temp_file("yamime-test-").await.unwrap();
+        let _cleanup = (TempPath(path_a.clone()), TempPath(path_b.clone()));
+        assert_ne!(path_a, path_b);
+        let name = path_a.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("yamime-test-"));
+        assert_eq!(name.split('-').count(), 5);
+
+        // The same name is never opened twice
+        let err = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path_a)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path_a).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_temp_file_prefix_stays_in_temp_dir() {
+        for prefix in ["../escape-", "dir/", "dir\\", "/tmp/abs-", ".."] {
+            let err = create_temp_file(prefix).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", prefix);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Splitting in-memory messages into independently processed parts.
+//!
+//! [`Reader::split_parts`] finds every delimiter of a message held in
+//! [`Bytes`] in one pass and returns [`SharedPart`]s whose bodies are slices
+//! of it: no body is copied, and each part can be moved to its own task.
+
+use super::boundary::BoundaryPatterns;
+use super::codec::{delimiter, Delimiter};
+use super::header::MimeHeader;
+use super::reader::{HeaderParser, Reader};
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use std::io;
+
+/// A part of an in-memory message, sharing the message's buffer.
+///
+/// Cloning is cheap: the body is reference counted, as are the header's
+/// strings. The body is as received, as for
+/// [`Reader::next_raw_part`](super::Reader::next_raw_part).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedPart {
+    /// The part's header.
+    pub header: MimeHeader,
+    body: Bytes,
+}
+
+impl SharedPart {
+    /// Returns the body.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Returns the body, consuming the part.
+    pub fn into_body(self) -> Bytes {
+        self.body
+    }
+}
+
+impl Reader<Bytes> {
+    /// Splits a complete message into its parts, without copying bodies.
+    ///
+    /// The preamble and epilogue are skipped, and bodies exclude the line
+    /// break before the next delimiter. Fails like [`Reader::next_part`] if
+    /// the input ends before the closing delimiter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bytes::Bytes;
+    /// use yamime::multipart::Reader;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = Bytes::from_static(b"--b\r\nX-Id: 1\r\n\r\none\r\n--b\r\nX-Id: 2\r\n\r\ntwo\r\n--b--\r\n");
+    /// let tasks: Vec<_> = Reader::split_parts(data, "b")?
+    ///     .into_iter()
+    ///     .map(|part| tokio::spawn(async move { part.body().len() }))
+    ///     .collect();
+    /// for task in tasks {
+    ///     assert_eq!(task.await?, 3);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn split_parts(bytes: Bytes, boundary: &str) -> Result<Vec<SharedPart>> {
+        if boundary.is_empty() {
+            return Err(Error::Multipart("boundary is empty".to_string()));
+        }
+        let patterns = BoundaryPatterns::new(boundary.as_bytes());
+        let dash_boundary = patterns.dash_boundary();
+        let data = &bytes[..];
+
+        // Skip the preamble, line by line
+        let mut pos = 0;
+        let mut closing = loop {
+            if data[pos..].starts_with(dash_boundary) {
+                match delimiter(&data[pos + dash_boundary.len()..]) {
+                    Delimiter::Part(len) => {
+                        pos += dash_boundary.len() + len;
+                        break false;
+                    }
+                    Delimiter::Closing => break true,
+                    Delimiter::None | Delimiter::Incomplete => {}
+                }
+            }
+            match memchr::memchr(b'\n', &data[pos..]) {
+                Some(end) => pos += end + 1,
+                None => return Err(unexpected_eof()),
+            }
+        };
+
+        let mut parts = Vec::new();
+        while !closing {
+            let mut parser = HeaderParser::default();
+            loop {
+                let end = memchr::memchr(b'\n', &data[pos..]).ok_or_else(unexpected_eof)?;
+                let line = std::str::from_utf8(&data[pos..pos + end + 1]).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+                })?;
+                pos += end + 1;
+                if parser.feed(line, None)? {
+                    break;
+                }
+            }
+            let header = parser.finish(None);
+
+            // The body ends before the next delimiter line's line break,
+            // unless the delimiter directly follows the header block
+            let body_start = pos;
+            let mut search = pos;
+            let (body_end, next) = loop {
+                let (line_start, boundary_start) = if search == body_start
+                    && data[search..].starts_with(dash_boundary)
+                {
+                    (search, search)
+                } else {
+                    let nl = search
+                        + patterns
+                            .find_nl_dash_boundary(&data[search..])
+                            .ok_or_else(unexpected_eof)?;
+                    let line_break = if nl > body_start && data[nl - 1] == b'\r' { nl - 1 } else { nl };
+                    (line_break, nl + 1)
+                };
+                let after = boundary_start + dash_boundary.len();
+                match delimiter(&data[after..]) {
+                    Delimiter::Part(len) => break (line_start, after + len),
+                    Delimiter::Closing => {
+                        closing = true;
+                        break (line_start, after);
+                    }
+                    Delimiter::Incomplete => return Err(unexpected_eof()),
+                    Delimiter::None => search = boundary_start + 1,
+                }
+            };
+            parts.push(SharedPart {
+                header,
+                body: bytes.slice(body_start..body_end),
+            });
+            pos = next;
+        }
+        Ok(parts)
+    }
+}
+
+fn unexpected_eof() -> Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected EOF").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_parts_shares_the_buffer() {
+        let data = Bytes::from_static(
+            b"preamble\r\n--b\r\nA: 1\r\n\r\nfirst\r\n--bX\r\nstill first\r\n\
+--b  \r\n\r\n--b\r\nB: 2\r\n\r\n\r\n\r\n--b--\r\nepilogue",
+        );
+        let parts = Reader::split_parts(data.clone(), "b").unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].header.get("a"), Some("1"));
+        assert_eq!(&parts[0].body()[..], b"first\r\n--bX\r\nstill first");
+        assert!(parts[1].header.is_empty());
+        assert_eq!(&parts[1].body()[..], b"");
+        assert_eq!(parts[2].header.get("b"), Some("2"));
+        assert_eq!(&parts[2].body()[..], b"\r\n");
+
+        // The body points into the original buffer
+        let offset = parts[0].body().as_ptr() as usize - data.as_ptr() as usize;
+        assert_eq!(offset, data.windows(5).position(|w| w == b"first").unwrap());
+    }
+
+    #[test]
+    fn test_split_parts_matches_reader() {
+        let data: &[u8] = b"--b\n\nfirst\n--b\nA: 1\n\nsecond\nline\n--b--";
+        let expected = crate::multipart::parse_multipart_to_vec(data, "b").unwrap();
+        let parts = Reader::split_parts(Bytes::from_static(data), "b").unwrap();
+        let parts: Vec<_> = parts
+            .into_iter()
+            .map(|part| (part.header.clone(), part.into_body().to_vec()))
+            .collect();
+        assert_eq!(parts, expected);
+
+        assert!(Reader::split_parts(Bytes::from_static(b"--b--"), "b").unwrap().is_empty());
+        let parts = Reader::split_parts(Bytes::from_static(b"--b\n\n--bX\n--b--"), "b").unwrap();
+        assert_eq!(&parts[0].body()[..], b"--bX");
+        for truncated in [&b"no delimiter"[..], b"--b\r\nA: 1", b"--b\r\n\r\nbody\r\n--b"] {
+            let err = Reader::split_parts(Bytes::from_static(truncated), "b").unwrap_err();
+            assert!(err.is_unexpected_eof());
+        }
+    }
+}
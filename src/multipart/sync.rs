@@ -0,0 +1,317 @@
+//! Synchronous helpers for non-async consumers.
+//!
+//! Reading from a byte slice never waits, so the async [`Reader`] can be
+//! driven to completion on the calling thread. Tests and small tools can
+//! parse a message without setting up a tokio runtime.
+//!
+//! With the `sync` feature, `Writer` builds multipart bodies over any
+//! [`std::io::Write`], framing them exactly like the async
+//! [`Writer`](super::Writer), for CLIs and build scripts.
+
+use super::header::MimeHeader;
+use super::reader::Reader;
+use crate::error::Result;
+#[cfg(feature = "sync")]
+use super::writer::{
+    content_type_for, form_data_content_type, form_data_disposition, form_file_header,
+    generate_boundary, push_closing, push_part_start, validate_boundary,
+};
+#[cfg(feature = "sync")]
+use crate::error::Error;
+#[cfg(feature = "sync")]
+use std::io::{self, Write};
+
+/// Parses a complete multipart body into its parts' headers and bodies.
+///
+/// Parts are read as by [`Reader::next_part`], so quoted-printable bodies
+/// are decoded. No async runtime is needed.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::parse_multipart_to_vec;
+///
+/// let data = b"--b\r\nContent-Type: text/plain\r\n\r\nhello\r\n--b\r\n\r\nworld\r\n--b--\r\n";
+/// let parts = parse_multipart_to_vec(data, "b")?;
+/// assert_eq!(parts.len(), 2);
+/// assert_eq!(parts[0].0.get("content-type"), Some("text/plain"));
+/// assert_eq!(parts[0].1, b"hello");
+/// assert_eq!(parts[1].1, b"world");
+/// # Ok::<(), yamime::Error>(())
+/// ```
+pub fn parse_multipart_to_vec(data: &[u8], boundary: &str) -> Result<Vec<(MimeHeader, Vec<u8>)>> {
+    futures::executor::block_on(async {
+        let mut reader = Reader::new(data, boundary);
+        let mut parts = Vec::new();
+        while let Some(mut part) = reader.next_part().await? {
+            let body = part.bytes().await?;
+            parts.push((std::mem::take(&mut part.header), body.into()));
+        }
+        Ok(parts)
+    })
+}
+
+/// A multipart writer over [`std::io::Write`], mirroring the async
+/// [`Writer`](super::Writer).
+///
+/// Lines end in CRLF. Each delimiter and header block goes out in a single
+/// write, and the output is flushed on [`close`](Self::close).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use yamime::multipart::sync::Writer;
+///
+/// let mut output = Vec::new();
+/// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+/// writer.write_field("name", "value")?;
+/// writer.create_form_file("upload", "notes.txt")?.write_all(b"hello")?;
+/// writer.close()?;
+///
+/// assert!(output.starts_with(b"--b\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nvalue\r\n"));
+/// assert!(output.ends_with(b"\r\n\r\nhello\r\n--b--\r\n"));
+/// # Ok::<(), yamime::Error>(())
+/// ```
+#[cfg(feature = "sync")]
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: W,
+    boundary: String,
+    parts_written: usize,
+    line_break_pending: bool,
+}
+
+/// Builder configuring a sync [`Writer`] before any part is written.
+///
+/// Only the boundary can be set; the framing options of the async
+/// [`WriterBuilder`](super::WriterBuilder) have no sync counterpart.
+#[cfg(feature = "sync")]
+#[derive(Debug, Clone, Default)]
+pub struct WriterBuilder {
+    boundary: Option<String>,
+}
+
+#[cfg(feature = "sync")]
+impl WriterBuilder {
+    /// Sets a custom boundary instead of a random one.
+    pub fn boundary(mut self, boundary: impl Into<String>) -> Self {
+        self.boundary = Some(boundary.into());
+        self
+    }
+
+    /// Validates the configuration and creates the writer.
+    pub fn build<W: Write>(self, writer: W) -> Result<Writer<W>> {
+        let boundary = match self.boundary {
+            Some(boundary) => {
+                validate_boundary(boundary.as_bytes())?;
+                boundary
+            }
+            None => generate_boundary(),
+        };
+        Ok(Writer {
+            writer,
+            boundary,
+            parts_written: 0,
+            line_break_pending: false,
+        })
+    }
+}
+
+#[cfg(feature = "sync")]
+impl Writer<()> {
+    /// Returns a builder for configuring a writer.
+    pub fn builder() -> WriterBuilder {
+        WriterBuilder::default()
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<W: Write> Writer<W> {
+    /// Creates a new multipart writer with a random boundary.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            boundary: generate_boundary(),
+            parts_written: 0,
+            line_break_pending: false,
+        }
+    }
+
+    /// Returns the writer's boundary string.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Sets a custom boundary.
+    ///
+    /// This must be called before creating any parts. The boundary must be
+    /// 1-70 characters and contain only valid characters.
+    #[deprecated(note = "use `Writer::builder().boundary(..)`, which validates the boundary before any part is written")]
+    pub fn set_boundary(&mut self, boundary: String) -> Result<()> {
+        if self.parts_written > 0 {
+            return Err(Error::Multipart(
+                "cannot set boundary after writing parts".to_string(),
+            ));
+        }
+
+        validate_boundary(boundary.as_bytes())?;
+        self.boundary = boundary;
+        Ok(())
+    }
+
+    /// Returns the Content-Type header value for multipart/form-data.
+    pub fn form_data_content_type(&self) -> String {
+        form_data_content_type(&self.boundary)
+    }
+
+    /// Creates a new part with the given headers, written in order.
+    ///
+    /// Returns a PartWriter that can be used to write the part's body.
+    pub fn create_part(&mut self, header: MimeHeader) -> Result<PartWriter<'_, W>> {
+        let mut framing = Vec::new();
+        if self.line_break_pending {
+            framing.extend_from_slice(b"\r\n");
+        }
+        push_part_start(&mut framing, &self.boundary, &header, "\r\n");
+        self.writer.write_all(&framing)?;
+
+        self.parts_written += 1;
+        self.line_break_pending = true;
+        Ok(PartWriter { parent: self })
+    }
+
+    /// Convenience method to create a form file part.
+    ///
+    /// The Content-Type is detected from `filename` as by the async
+    /// [`create_form_file`](super::Writer::create_form_file).
+    pub fn create_form_file(&mut self, fieldname: &str, filename: &str) -> Result<PartWriter<'_, W>> {
+        self.create_part(form_file_header(fieldname, filename, &content_type_for(filename)))
+    }
+
+    /// Creates a form file part with the given Content-Type rather than
+    /// one detected from the filename.
+    pub fn create_form_file_with_type(
+        &mut self,
+        fieldname: &str,
+        filename: &str,
+        content_type: &str,
+    ) -> Result<PartWriter<'_, W>> {
+        self.create_part(form_file_header(fieldname, filename, content_type))
+    }
+
+    /// Convenience method to create a form field part.
+    pub fn create_form_field(&mut self, fieldname: &str) -> Result<PartWriter<'_, W>> {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Disposition", form_data_disposition(fieldname, None));
+        self.create_part(header)
+    }
+
+    /// Writes a complete form field with value.
+    pub fn write_field(&mut self, fieldname: &str, value: &str) -> Result<()> {
+        let mut part = self.create_form_field(fieldname)?;
+        part.write_all(value.as_bytes())?;
+        Ok(())
+    }
+
+    /// Closes the writer by writing the final boundary, then flushes.
+    pub fn close(mut self) -> Result<()> {
+        let mut framing = Vec::new();
+        if self.line_break_pending {
+            framing.extend_from_slice(b"\r\n");
+        }
+        push_closing(&mut framing, &self.boundary, "\r\n");
+        self.writer.write_all(&framing)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A writer for a single part's body, borrowing its [`Writer`] until
+/// dropped.
+#[cfg(feature = "sync")]
+#[derive(Debug)]
+pub struct PartWriter<'a, W> {
+    parent: &'a mut Writer<W>,
+}
+
+#[cfg(feature = "sync")]
+impl<W: Write> Write for PartWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.parent.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.parent.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_multipart_to_vec() {
+        let data = b"preamble\r\n--b\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n\
+caf=C3=A9\r\n--b\r\n\r\n\r\n--b--\r\n";
+        let parts = parse_multipart_to_vec(data, "b").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].0.is_empty());
+        assert_eq!(parts[0].1, "café".as_bytes());
+        assert_eq!(parts[1].1, b"");
+
+        // Truncated input fails as it does for the async reader
+        assert!(parse_multipart_to_vec(b"--b\r\n\r\nunterminated", "b").is_err());
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    #[allow(deprecated)]
+    fn test_sync_writer_matches_async() {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Type", "text/plain");
+        header.insert("X-Id", "1");
+
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output);
+        writer.set_boundary("b".to_string()).unwrap();
+        assert_eq!(writer.form_data_content_type(), "multipart/form-data; boundary=b");
+        writer.write_field("field", "value").unwrap();
+        writer.create_part(header.clone()).unwrap().write_all(b"body").unwrap();
+        writer.create_form_file("file", "a.txt").unwrap();
+        assert!(writer.set_boundary("c".to_string()).is_err());
+        writer.close().unwrap();
+
+        let mut expected = Vec::new();
+        futures::executor::block_on(async {
+            let mut writer = super::super::Writer::builder()
+                .boundary("b")
+                .build(&mut expected)
+                .unwrap();
+            writer.write_field("field", "value").await.unwrap();
+            let mut part = writer.create_part(header).await.unwrap();
+            tokio::io::AsyncWriteExt::write_all(&mut part, b"body").await.unwrap();
+            writer.create_form_file("file", "a.txt").await.unwrap();
+            writer.close().await.unwrap();
+        });
+        assert_eq!(output, expected);
+
+        let writer = Writer::new(Vec::new());
+        assert_eq!(writer.boundary().len(), 60);
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_sync_writer_builder() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        assert_eq!(writer.boundary(), "b");
+        writer.write_field("field", "value").unwrap();
+        writer.close().unwrap();
+        assert!(output.starts_with(b"--b\r\n"));
+
+        assert!(Writer::builder().boundary("a".repeat(71)).build(Vec::new()).is_err());
+        assert!(Writer::builder().boundary("").build(Vec::new()).is_err());
+        assert_eq!(Writer::builder().build(Vec::new()).unwrap().boundary().len(), 60);
+    }
+}
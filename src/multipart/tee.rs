@@ -0,0 +1,169 @@
+//! Copying the raw input of a multipart reader.
+//!
+//! A [`Tee`] set in [`ReaderOptions::tee`](super::ReaderOptions::tee)
+//! receives every byte the [`Reader`](super::Reader) consumes, exactly as
+//! received, so a message can be archived or hashed in the same pass that
+//! parses it.
+
+use sha2::digest::DynDigest;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use tokio::io::AsyncWrite;
+
+/// A destination for the raw bytes consumed by a multipart reader.
+///
+/// Clones share the same destination, so a clone kept by the caller can be
+/// used to retrieve a digest once parsing is done.
+///
+/// # Examples
+///
+/// ```
+/// use sha2::{Digest, Sha256};
+/// use yamime::multipart::{Reader, ReaderOptions, Tee};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = b"--b\r\n\r\nhello\r\n--b--\r\n";
+/// let tee = Tee::digest(Sha256::new());
+/// let options = ReaderOptions {
+///     tee: Some(tee.clone()),
+///     ..Default::default()
+/// };
+///
+/// let mut reader = Reader::with_options(&data[..], "b", options);
+/// while reader.next_part().await?.is_some() {}
+/// assert_eq!(tee.finalize().unwrap()[..], Sha256::digest(data)[..]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Tee {
+    sink: Arc<Mutex<Sink>>,
+}
+
+enum Sink {
+    Writer(Pin<Box<dyn AsyncWrite + Send>>),
+    Digest(Box<dyn DynDigest + Send>),
+}
+
+impl Tee {
+    /// Copies the consumed bytes into `writer`.
+    ///
+    /// The reader waits for the writer before reading more input, so a slow
+    /// writer slows parsing down rather than buffering without bound.
+    pub fn writer<W: AsyncWrite + Send + 'static>(writer: W) -> Self {
+        Self::new(Sink::Writer(Box::pin(writer)))
+    }
+
+    /// Feeds the consumed bytes into a hasher, e.g. `sha2::Sha256`.
+    pub fn digest<D: DynDigest + Send + 'static>(digest: D) -> Self {
+        Self::new(Sink::Digest(Box::new(digest)))
+    }
+
+    fn new(sink: Sink) -> Self {
+        Self {
+            sink: Arc::new(Mutex::new(sink)),
+        }
+    }
+
+    /// Returns the digest of the bytes seen so far and resets the hasher.
+    ///
+    /// Returns `None` for a writer tee.
+    pub fn finalize(&self) -> Option<Box<[u8]>> {
+        match &mut *self.lock() {
+            Sink::Digest(digest) => Some(digest.finalize_reset()),
+            Sink::Writer(_) => None,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Sink> {
+        self.sink.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records consumed bytes.
+    ///
+    /// Digests are updated immediately. For writers, the bytes are appended
+    /// to `pending` and written by [`poll_write_pending`](Self::poll_write_pending).
+    pub(crate) fn record(&self, data: &[u8], pending: &mut Vec<u8>) {
+        match &mut *self.lock() {
+            Sink::Digest(digest) => digest.update(data),
+            Sink::Writer(_) => pending.extend_from_slice(data),
+        }
+    }
+
+    /// Writes out `pending`, removing what was written.
+    pub(crate) fn poll_write_pending(
+        &self,
+        cx: &mut Context<'_>,
+        pending: &mut Vec<u8>,
+    ) -> Poll<io::Result<()>> {
+        let mut sink = self.lock();
+        let Sink::Writer(writer) = &mut *sink else {
+            return Poll::Ready(Ok(()));
+        };
+        while !pending.is_empty() {
+            let n = ready!(writer.as_mut().poll_write(cx, pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            pending.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Writes out `pending` and flushes the writer.
+    pub(crate) fn poll_flush(
+        &self,
+        cx: &mut Context<'_>,
+        pending: &mut Vec<u8>,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_pending(cx, pending))?;
+        match &mut *self.lock() {
+            Sink::Writer(writer) => writer.as_mut().poll_flush(cx),
+            Sink::Digest(_) => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl fmt::Debug for Tee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &*self.lock() {
+            Sink::Writer(_) => "writer",
+            Sink::Digest(_) => "digest",
+        };
+        f.debug_struct("Tee").field("sink", &kind).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Reader, ReaderOptions};
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_tee_writer_copies_consumed_input() {
+        let mut data = b"preamble\r\n--b\r\nContent-Type: text/plain\r\n\r\n".to_vec();
+        data.extend(std::iter::repeat(b'x').take(200_000));
+        data.extend_from_slice(b"\r\n--b--\r\n");
+        let mut message = data.clone();
+        message.extend_from_slice(b"epilogue");
+
+        // A small pipe forces the reader to wait for the tee
+        let (tx, mut rx) = tokio::io::duplex(64);
+        let collector = tokio::spawn(async move {
+            let mut copy = Vec::new();
+            rx.read_to_end(&mut copy).await.unwrap();
+            copy
+        });
+
+        let mut reader = Reader::with_options(&message[..], "b", ReaderOptions::default().tee(tx));
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.bytes().await.unwrap().len(), 200_000);
+        assert!(reader.next_part().await.unwrap().is_none());
+        drop(reader);
+
+        assert_eq!(collector.await.unwrap(), data);
+    }
+}
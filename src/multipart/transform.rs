@@ -0,0 +1,288 @@
+//! Per-part body transformations.
+//!
+//! A [`PartTransform`] registered on a multipart [`Reader`](super::Reader)
+//! wraps the body of every part whose headers it matches, so that reading the
+//! part yields transformed bytes (decompressed, transcoded, normalized, ...).
+//!
+//! [`Base64Decoding`] and [`QuotedPrintableDecoding`] decode transfer
+//! encodings; [`Decoder`] plugs in any decoding function for parts selected
+//! by Content-Type, Content-Transfer-Encoding or Content-Encoding.
+
+use super::header::MimeHeader;
+use crate::{base64, quotedprintable};
+use std::pin::Pin;
+use tokio::io::AsyncRead;
+
+/// A boxed part body reader.
+pub type PartBody = Pin<Box<dyn AsyncRead + Send>>;
+
+/// A transformation applied to the bodies of matching parts.
+///
+/// Transforms are applied in registration order: the first matching transform
+/// wraps the raw body, the next one wraps the result, and so on.
+pub trait PartTransform: Send + Sync {
+    /// Reports whether this transform applies to a part with the given headers.
+    fn matches(&self, header: &MimeHeader) -> bool;
+
+    /// Wraps a part body into the transformed body.
+    fn transform(&self, header: &MimeHeader, body: PartBody) -> PartBody;
+
+    /// Updates the part's headers to describe the transformed body, e.g. by
+    /// removing the encoding header of a decoded body.
+    ///
+    /// Called after [`transform`](Self::transform); later transforms match
+    /// against the updated headers. Does nothing by default.
+    fn update_header(&self, header: &mut MimeHeader) {
+        let _ = header;
+    }
+}
+
+/// A [`PartTransform`] built from a predicate and a wrapping function.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::transform::{FnTransform, PartBody};
+///
+/// let transform = FnTransform::new(
+///     |header| header.get("X-Passthrough").is_some(),
+///     |_header, body: PartBody| body,
+/// );
+/// # let _ = transform;
+/// ```
+pub struct FnTransform<P, F> {
+    predicate: P,
+    wrap: F,
+}
+
+impl<P, F> FnTransform<P, F>
+where
+    P: Fn(&MimeHeader) -> bool + Send + Sync,
+    F: Fn(&MimeHeader, PartBody) -> PartBody + Send + Sync,
+{
+    /// Creates a transform applying `wrap` to parts matching `predicate`.
+    pub fn new(predicate: P, wrap: F) -> Self {
+        Self { predicate, wrap }
+    }
+}
+
+impl<P, F> PartTransform for FnTransform<P, F>
+where
+    P: Fn(&MimeHeader) -> bool + Send + Sync,
+    F: Fn(&MimeHeader, PartBody) -> PartBody + Send + Sync,
+{
+    fn matches(&self, header: &MimeHeader) -> bool {
+        (self.predicate)(header)
+    }
+
+    fn transform(&self, header: &MimeHeader, body: PartBody) -> PartBody {
+        (self.wrap)(header, body)
+    }
+}
+
+/// A header field selecting the parts a [`Decoder`] applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecoderKey {
+    /// Parts with this media type, compared without parameters. A
+    /// `type/*` pattern matches every subtype.
+    ContentType(String),
+    /// Parts with this Content-Transfer-Encoding.
+    TransferEncoding(String),
+    /// Parts with this Content-Encoding.
+    ContentEncoding(String),
+}
+
+impl DecoderKey {
+    fn matches(&self, header: &MimeHeader) -> bool {
+        let (name, expected) = match self {
+            DecoderKey::ContentType(pattern) => {
+                let Some(value) = header.get("content-type") else {
+                    return false;
+                };
+                let media_type = value.split(';').next().unwrap_or("").trim();
+                return match pattern.strip_suffix("/*") {
+                    Some(major) => media_type
+                        .split_once('/')
+                        .is_some_and(|(m, _)| m.eq_ignore_ascii_case(major)),
+                    None => media_type.eq_ignore_ascii_case(pattern),
+                };
+            }
+            DecoderKey::TransferEncoding(encoding) => ("content-transfer-encoding", encoding),
+            DecoderKey::ContentEncoding(encoding) => ("content-encoding", encoding),
+        };
+        header
+            .get(name)
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case(expected))
+    }
+
+    /// The encoding header describing bytes the decoder removes.
+    fn encoding_header(&self) -> Option<&'static str> {
+        match self {
+            DecoderKey::ContentType(_) => None,
+            DecoderKey::TransferEncoding(_) => Some("content-transfer-encoding"),
+            DecoderKey::ContentEncoding(_) => Some("content-encoding"),
+        }
+    }
+}
+
+/// A [`PartTransform`] decoding the bodies of parts selected by a
+/// [`DecoderKey`].
+///
+/// Decoders keyed by an encoding header remove that header once the body
+/// is decoded, as [`Reader::next_part`](super::Reader::next_part) does for
+/// quoted-printable.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::base64;
+/// use yamime::multipart::Reader;
+/// use yamime::multipart::transform::{Decoder, DecoderKey, PartBody};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = b"--b\r\nContent-Transfer-Encoding: base64\r\n\r\naGk=\r\n--b--\r\n";
+/// let mut reader = Reader::new(&data[..], "b");
+/// reader.add_transform(Decoder::new(
+///     DecoderKey::TransferEncoding("base64".to_string()),
+///     |body: PartBody| -> PartBody { Box::pin(base64::Decoder::new(body)) },
+/// ));
+///
+/// let mut part = reader.next_part().await?.unwrap();
+/// assert_eq!(&part.bytes().await?[..], b"hi");
+/// assert!(part.header.get("content-transfer-encoding").is_none());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Decoder<F> {
+    key: DecoderKey,
+    decode: F,
+}
+
+impl<F> Decoder<F>
+where
+    F: Fn(PartBody) -> PartBody + Send + Sync,
+{
+    /// Creates a decoder applying `decode` to the parts selected by `key`.
+    pub fn new(key: DecoderKey, decode: F) -> Self {
+        Self { key, decode }
+    }
+}
+
+impl<F> PartTransform for Decoder<F>
+where
+    F: Fn(PartBody) -> PartBody + Send + Sync,
+{
+    fn matches(&self, header: &MimeHeader) -> bool {
+        self.key.matches(header)
+    }
+
+    fn transform(&self, _header: &MimeHeader, body: PartBody) -> PartBody {
+        (self.decode)(body)
+    }
+
+    fn update_header(&self, header: &mut MimeHeader) {
+        if let Some(name) = self.key.encoding_header() {
+            header.remove(name);
+        }
+    }
+}
+
+/// A [`PartTransform`] decoding `Content-Transfer-Encoding: base64` bodies
+/// and removing that header.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Base64Decoding;
+
+impl PartTransform for Base64Decoding {
+    fn matches(&self, header: &MimeHeader) -> bool {
+        transfer_encoding_is(header, "base64")
+    }
+
+    fn transform(&self, _header: &MimeHeader, body: PartBody) -> PartBody {
+        Box::pin(base64::Decoder::new(body))
+    }
+
+    fn update_header(&self, header: &mut MimeHeader) {
+        header.remove("content-transfer-encoding");
+    }
+}
+
+/// A [`PartTransform`] decoding `Content-Transfer-Encoding:
+/// quoted-printable` bodies and removing that header.
+///
+/// [`Reader::next_part`](super::Reader::next_part) already decodes
+/// quoted-printable; this adapter is for composing it with other
+/// transforms, or for readers of parts it left encoded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotedPrintableDecoding;
+
+impl PartTransform for QuotedPrintableDecoding {
+    fn matches(&self, header: &MimeHeader) -> bool {
+        transfer_encoding_is(header, "quoted-printable")
+    }
+
+    fn transform(&self, _header: &MimeHeader, body: PartBody) -> PartBody {
+        Box::pin(quotedprintable::Reader::new(body))
+    }
+
+    fn update_header(&self, header: &mut MimeHeader) {
+        header.remove("content-transfer-encoding");
+    }
+}
+
+fn transfer_encoding_is(header: &MimeHeader, encoding: &str) -> bool {
+    header
+        .get("content-transfer-encoding")
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case(encoding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multipart::Reader;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_keyed_decoders() {
+        let data = b"--b\r\nContent-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: BASE64\r\n\r\naGVs\r\nbG8=\r\n\
+--b\r\nContent-Type: image/png\r\nContent-Encoding: x-rot\r\n\r\nraw\r\n--b--\r\n";
+        let mut reader = Reader::new(&data[..], "b");
+        reader.add_transform(Base64Decoding);
+        // Appends a marker, to show which parts each decoder saw
+        let mark = |marker: &'static [u8]| {
+            move |body: PartBody| -> PartBody { Box::pin(body.chain(marker)) }
+        };
+        let content_type = |pattern: &str| DecoderKey::ContentType(pattern.to_string());
+        reader.add_transform(Decoder::new(content_type("TEXT/*"), mark(b"!")));
+        reader.add_transform(Decoder::new(content_type("image/png"), mark(b"?")));
+        reader.add_transform(Decoder::new(
+            DecoderKey::ContentEncoding("X-Rot".to_string()),
+            mark(b"#"),
+        ));
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(&part.bytes().await.unwrap()[..], b"hello!");
+        assert!(!part.header.contains_key("content-transfer-encoding"));
+        assert_eq!(part.header.get("content-type"), Some("text/plain; charset=utf-8"));
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(&part.bytes().await.unwrap()[..], b"raw?#");
+        assert!(!part.header.contains_key("content-encoding"));
+    }
+
+    #[tokio::test]
+    async fn test_quoted_printable_adapter() {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Transfer-Encoding", "Quoted-Printable");
+        assert!(QuotedPrintableDecoding.matches(&header));
+        assert!(!Base64Decoding.matches(&header));
+
+        let mut body = QuotedPrintableDecoding.transform(&header, Box::pin(&b"caf=C3=A9=\r\n!"[..]));
+        let mut decoded = String::new();
+        body.read_to_string(&mut decoded).await.unwrap();
+        assert_eq!(decoded, "café!");
+
+        QuotedPrintableDecoding.update_header(&mut header);
+        assert!(header.is_empty());
+    }
+}
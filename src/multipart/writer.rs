@@ -2,15 +2,396 @@
 //!
 //! Implements RFC 2046 multipart message generation with async I/O.
 
+use crate::encoded_word::WordEncoder;
 use crate::error::{Error, Result};
+use crate::media_type::{encode_rfc2231_value, format_media_type};
+use crate::{base64, quotedprintable};
+use crate::multipart::formdata::Form;
+use crate::multipart::header::MimeHeader;
+use crate::multipart::preserve::PartFraming;
 use std::collections::HashMap;
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use std::fmt;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Size of the buffer used to copy readers into part bodies.
+const COPY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Line ending used by a [`Writer`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// CRLF, as required by RFC 2046.
+    #[default]
+    Crlf,
+    /// Bare LF, for consumers that expect Unix line endings.
+    Lf,
+}
+
+impl LineEnding {
+    /// Returns the line ending characters.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+/// A Content-Transfer-Encoding applied to a part's body as it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferEncoding {
+    /// Base64, for binary data.
+    Base64,
+    /// Quoted-printable, for mostly ASCII text. Line breaks in the body
+    /// are written as the line breaks of the encoded text.
+    QuotedPrintable,
+}
+
+impl TransferEncoding {
+    /// Returns the Content-Transfer-Encoding header value.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferEncoding::Base64 => "base64",
+            TransferEncoding::QuotedPrintable => "quoted-printable",
+        }
+    }
+}
+
+/// The encoder of the part body being written.
+enum BodyEncoder {
+    Base64(base64::EncoderState),
+    QuotedPrintable(quotedprintable::writer::Encoder),
+}
+
+impl BodyEncoder {
+    fn new(encoding: TransferEncoding, line_ending: LineEnding) -> Self {
+        let nl = line_ending.as_str().as_bytes();
+        match encoding {
+            TransferEncoding::Base64 => BodyEncoder::Base64(base64::EncoderState::new(nl)),
+            TransferEncoding::QuotedPrintable => {
+                BodyEncoder::QuotedPrintable(quotedprintable::writer::Encoder::new(nl))
+            }
+        }
+    }
+
+    fn encode(&mut self, buf: &[u8]) {
+        match self {
+            BodyEncoder::Base64(state) => state.encode(buf),
+            BodyEncoder::QuotedPrintable(encoder) => {
+                for &b in buf {
+                    encoder.push(b, false);
+                }
+            }
+        }
+    }
+
+    /// Makes as much of the output available as can be written before the
+    /// body ends.
+    fn release(&mut self) {
+        if let BodyEncoder::QuotedPrintable(encoder) = self {
+            encoder.release_line();
+        }
+    }
+
+    fn finish(&mut self) {
+        match self {
+            BodyEncoder::Base64(state) => state.finish(),
+            BodyEncoder::QuotedPrintable(encoder) => encoder.finish(),
+        }
+    }
+
+    fn poll_drain<W: AsyncWrite>(
+        &mut self,
+        inner: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self {
+            BodyEncoder::Base64(state) => state.poll_drain(inner, cx),
+            BodyEncoder::QuotedPrintable(encoder) => encoder.poll_drain(inner, cx),
+        }
+    }
+}
 
 /// A multipart MIME writer.
+///
+/// Each delimiter line and header block goes out in a single write, and
+/// the output is only flushed by [`close`](Self::close),
+/// [`PartWriter::finish`] and [`flush`](Self::flush). Part bodies are
+/// passed through as written: wrap an unbuffered output in a
+/// [`BufWriter`](tokio::io::BufWriter) when writing bodies in many small
+/// pieces.
 pub struct Writer<W> {
-    writer: W,
+    writer: CountingWriter<W>,
     boundary: String,
-    has_parts: bool,
+    /// The multipart subtype, e.g. "form-data" or "mixed".
+    subtype: String,
+    parts_written: usize,
+    /// The part whose body is being written: index, header and the byte
+    /// count where its body started.
+    open_part: Option<(usize, MimeHeader, u64)>,
+    hooks: WriterHooks,
+    /// Encodes the open part's body, if it has a transfer encoding.
+    body_encoder: Option<BodyEncoder>,
+    /// The last part's body still needs the line break before a delimiter.
+    line_break_pending: bool,
+    /// Framing collected to go out in one write.
+    queued: Vec<u8>,
+    line_ending: LineEnding,
+    strict_7bit: bool,
+    /// Set while the writer from [`create_nested_part`](Writer::create_nested_part)
+    /// for the open part has not been closed.
+    nested_open: Option<Arc<AtomicBool>>,
+    /// Generates the boundaries of nested parts.
+    boundaries: BoundaryGenerator,
+}
+
+/// Builder configuring a [`Writer`] before any part is written.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::multipart::{LineEnding, Writer};
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut output = Vec::new();
+/// let writer = Writer::builder()
+///     .boundary("my-boundary")
+///     .line_ending(LineEnding::Crlf)
+///     .strict_7bit(true)
+///     .build(&mut output)?;
+/// assert_eq!(writer.boundary(), "my-boundary");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WriterBuilder {
+    boundary: Option<String>,
+    subtype: Option<String>,
+    generator: BoundaryOptions,
+    line_ending: LineEnding,
+    strict_7bit: bool,
+    hooks: WriterHooks,
+}
+
+type PartStartHook = Arc<dyn Fn(usize, &MimeHeader) + Send + Sync>;
+type PartEndHook = Arc<dyn Fn(usize, &MimeHeader, u64) + Send + Sync>;
+type CloseHook = Arc<dyn Fn(usize, u64) + Send + Sync>;
+
+/// Callbacks set with the `on_*` methods of [`WriterBuilder`].
+#[derive(Clone, Default)]
+struct WriterHooks {
+    part_start: Option<PartStartHook>,
+    part_end: Option<PartEndHook>,
+    close: Option<CloseHook>,
+}
+
+impl fmt::Debug for WriterHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriterHooks")
+            .field("part_start", &self.part_start.is_some())
+            .field("part_end", &self.part_end.is_some())
+            .field("close", &self.close.is_some())
+            .finish()
+    }
+}
+
+impl WriterBuilder {
+    /// Sets a custom boundary instead of a random one.
+    pub fn boundary(mut self, boundary: impl Into<String>) -> Self {
+        self.boundary = Some(boundary.into());
+        self
+    }
+
+    /// Sets the multipart subtype named by [`Writer::content_type`];
+    /// `form-data` by default.
+    pub fn subtype(mut self, subtype: impl Into<String>) -> Self {
+        self.subtype = Some(subtype.into());
+        self
+    }
+
+    /// Starts generated boundaries with `prefix`, e.g. `----FormBoundary`.
+    ///
+    /// This and the other `boundary_*` options apply to the boundaries of
+    /// [nested parts](Writer::create_nested_part), and to the writer's own
+    /// when no [`boundary`](Self::boundary) is set.
+    pub fn boundary_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.generator.prefix = prefix.into();
+        self
+    }
+
+    /// Sets the number of random characters in generated boundaries,
+    /// after any prefix; 60 by default.
+    pub fn boundary_length(mut self, length: usize) -> Self {
+        self.generator.length = length;
+        self
+    }
+
+    /// Sets the characters random boundary characters are drawn from;
+    /// lowercase hexadecimal digits by default.
+    ///
+    /// They must be boundary characters allowed by RFC 2046 other than
+    /// space.
+    pub fn boundary_alphabet(mut self, alphabet: impl Into<String>) -> Self {
+        self.generator.alphabet = alphabet.into();
+        self
+    }
+
+    /// Generates boundaries from `seed` instead of the operating system's
+    /// random number generator, so every build with the same options gets
+    /// the same boundary, and the same boundaries for its nested parts.
+    ///
+    /// Meant for reproducible output in golden tests: a predictable
+    /// boundary lets part contents forge delimiters, so never use it for
+    /// untrusted data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let builder = Writer::builder()
+    ///     .boundary_prefix("----RustFormBoundary")
+    ///     .boundary_length(16)
+    ///     .boundary_alphabet("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789")
+    ///     .boundary_seed(42);
+    /// let first = builder.clone().build(Vec::new())?;
+    /// let second = builder.build(Vec::new())?;
+    /// assert_eq!(first.boundary(), second.boundary());
+    /// assert_eq!(first.boundary().len(), 36);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn boundary_seed(mut self, seed: u64) -> Self {
+        self.generator.seed = Some(seed);
+        self
+    }
+
+    /// Sets the line ending used for delimiters and headers.
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Rejects header values and body bytes outside 7-bit ASCII.
+    ///
+    /// Useful when the output must survive 7-bit transports such as SMTP
+    /// without `8BITMIME`; bodies should then be quoted-printable or base64
+    /// encoded before being written.
+    pub fn strict_7bit(mut self, strict: bool) -> Self {
+        self.strict_7bit = strict;
+        self
+    }
+
+    /// Calls `hook` with the index and header of each part, once its
+    /// headers are written.
+    ///
+    /// Together with [`on_part_end`](Self::on_part_end) and
+    /// [`on_close`](Self::on_close), this lets metrics or audit logs follow
+    /// what is written without wrapping the writer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let log = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = log.clone();
+    /// let mut writer = Writer::builder()
+    ///     .on_part_end(move |index, header, body_bytes| {
+    ///         let name = header.get("Content-Disposition").unwrap_or("").to_string();
+    ///         sink.lock().unwrap().push((index, name, body_bytes));
+    ///     })
+    ///     .build(Vec::new())?;
+    /// writer.write_field("title", "hello").await?;
+    /// writer.close().await?;
+    /// assert_eq!(
+    ///     *log.lock().unwrap(),
+    ///     [(0, "form-data; name=\"title\"".to_string(), 5)]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_part_start<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(usize, &MimeHeader) + Send + Sync + 'static,
+    {
+        self.hooks.part_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with the index, header and body length in bytes of
+    /// each part, once the part is complete.
+    ///
+    /// A part is complete when the next part is created or the writer is
+    /// closed; body bytes are counted as written, after any encoding.
+    pub fn on_part_end<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(usize, &MimeHeader, u64) + Send + Sync + 'static,
+    {
+        self.hooks.part_end = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with the number of parts and the total bytes written,
+    /// once the writer is closed.
+    pub fn on_close<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(usize, u64) + Send + Sync + 'static,
+    {
+        self.hooks.close = Some(Arc::new(hook));
+        self
+    }
+
+    /// Validates the configuration and creates the writer.
+    pub fn build<W: AsyncWrite + Unpin>(self, writer: W) -> Result<Writer<W>> {
+        let mut boundaries = BoundaryGenerator::new(self.generator);
+        let boundary = match self.boundary {
+            Some(boundary) => {
+                validate_boundary(boundary.as_bytes())?;
+                boundary
+            }
+            None => boundaries.generate()?,
+        };
+        let subtype = match self.subtype {
+            Some(subtype) => {
+                if format_media_type(&format!("multipart/{}", subtype), &HashMap::new()).is_empty() {
+                    return Err(Error::Multipart(format!("invalid multipart subtype: {:?}", subtype)));
+                }
+                subtype.to_lowercase()
+            }
+            None => "form-data".to_string(),
+        };
+
+        Ok(Writer {
+            writer: CountingWriter::new(writer),
+            boundary,
+            subtype,
+            parts_written: 0,
+            open_part: None,
+            hooks: self.hooks,
+            body_encoder: None,
+            line_break_pending: false,
+            queued: Vec::new(),
+            line_ending: self.line_ending,
+            strict_7bit: self.strict_7bit,
+            nested_open: None,
+            boundaries,
+        })
+    }
+}
+
+impl Writer<()> {
+    /// Returns a builder for configuring a writer.
+    pub fn builder() -> WriterBuilder {
+        WriterBuilder::default()
+    }
 }
 
 impl<W: AsyncWrite + Unpin> Writer<W> {
@@ -28,10 +409,55 @@ impl<W: AsyncWrite + Unpin> Writer<W> {
     /// # }
     /// ```
     pub fn new(writer: W) -> Self {
+        Self::with_subtype(writer, "form-data")
+    }
+
+    /// Creates a multipart/mixed writer with a random boundary, for parts
+    /// of independent content such as a message and its attachments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let writer = Writer::new_mixed(Vec::new());
+    /// let content_type = writer.content_type();
+    /// assert_eq!(content_type, format!("multipart/mixed; boundary={}", writer.boundary()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_mixed(writer: W) -> Self {
+        Self::with_subtype(writer, "mixed")
+    }
+
+    /// Creates a multipart/alternative writer with a random boundary, for
+    /// versions of the same content in increasing order of preference.
+    pub fn new_alternative(writer: W) -> Self {
+        Self::with_subtype(writer, "alternative")
+    }
+
+    /// Creates a multipart/related writer with a random boundary, for a
+    /// root part and the resources it refers to (RFC 2387).
+    pub fn new_related(writer: W) -> Self {
+        Self::with_subtype(writer, "related")
+    }
+
+    fn with_subtype(writer: W, subtype: &str) -> Self {
         Self {
-            writer,
+            writer: CountingWriter::new(writer),
             boundary: generate_boundary(),
-            has_parts: false,
+            subtype: subtype.to_string(),
+            parts_written: 0,
+            open_part: None,
+            hooks: WriterHooks::default(),
+            body_encoder: None,
+            line_break_pending: false,
+            queued: Vec::new(),
+            line_ending: LineEnding::default(),
+            strict_7bit: false,
+            nested_open: None,
+            boundaries: BoundaryGenerator::new(BoundaryOptions::default()),
         }
     }
 
@@ -42,188 +468,1248 @@ impl<W: AsyncWrite + Unpin> Writer<W> {
 
     /// Sets a custom boundary.
     ///
-    /// This must be called before creating any parts.
-    /// The boundary must be 1-70 characters and contain only valid characters.
+    /// This must be called before creating any parts. The boundary must be
+    /// 1-70 characters and contain only valid characters.
+    #[deprecated(note = "use `Writer::builder().boundary(..)`, which validates the boundary before any part is written")]
     pub fn set_boundary(&mut self, boundary: String) -> Result<()> {
-        if self.has_parts {
+        if self.parts_written > 0 {
             return Err(Error::Multipart(
                 "cannot set boundary after writing parts".to_string(),
             ));
         }
 
-        // Validate boundary (RFC 2046)
-        if boundary.is_empty() || boundary.len() > 70 {
-            return Err(Error::Multipart("invalid boundary length".to_string()));
-        }
+        validate_boundary(boundary.as_bytes())?;
+        self.boundary = boundary;
+        Ok(())
+    }
 
-        for (i, ch) in boundary.chars().enumerate() {
-            let valid = ch.is_ascii_alphanumeric()
-                || matches!(ch, '\'' | '(' | ')' | '+' | '_' | ',' | '-' | '.' | '/' | ':' | '=' | '?')
-                || (ch == ' ' && i != boundary.len() - 1);
+    /// Returns the multipart subtype, e.g. "form-data" or "mixed".
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
 
-            if !valid {
-                return Err(Error::Multipart(format!(
-                    "invalid boundary character: {}",
-                    ch
-                )));
-            }
+    /// Returns the Content-Type header value for the body, naming the
+    /// writer's subtype and boundary.
+    pub fn content_type(&self) -> String {
+        if self.subtype == "form-data" {
+            return self.form_data_content_type();
         }
-
-        self.boundary = boundary;
-        Ok(())
+        let params = HashMap::from([("boundary".to_string(), self.boundary.clone())]);
+        format_media_type(&format!("multipart/{}", self.subtype), &params)
     }
 
     /// Returns the Content-Type header value for multipart/form-data.
     pub fn form_data_content_type(&self) -> String {
-        let boundary = if self.boundary.contains(|c| matches!(c, '(' | ')' | '<' | '>' | '@' | ',' | ';' | ':' | '"' | '/' | '[' | ']' | '?' | '=' | ' ')) {
-            format!("\"{}\"", self.boundary)
-        } else {
-            self.boundary.clone()
-        };
-
-        format!("multipart/form-data; boundary={}", boundary)
+        form_data_content_type(&self.boundary)
     }
 
     /// Creates a new part with the given headers.
     ///
+    /// Headers are written in order, with the casing they were inserted
+    /// with. A `HashMap<String, Vec<String>>` converts with `.into()`,
+    /// its names sorted for consistent output.
+    ///
     /// Returns a PartWriter that can be used to write the part's body.
-    pub async fn create_part(
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{MimeHeader, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// let mut header = MimeHeader::new();
+    /// header.insert("Content-Type", "text/plain");
+    /// header.append("X-Trace", "first");
+    /// header.append("x-trace", "second");
+    /// writer.create_part(header).await?;
+    /// writer.close().await?;
+    /// assert_eq!(
+    ///     output,
+    ///     &b"--b\r\nContent-Type: text/plain\r\nX-Trace: first\r\nx-trace: second\r\n\r\n\r\n--b--\r\n"[..]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_part(&mut self, header: MimeHeader) -> Result<PartWriter<'_, W>> {
+        self.create_part_with_header(&header).await
+    }
+
+    /// Creates a new part whose body is encoded with `encoding` as it is
+    /// written.
+    ///
+    /// The Content-Transfer-Encoding header is set to match, replacing any
+    /// in `header`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::io::AsyncWriteExt;
+    /// use yamime::multipart::{MimeHeader, TransferEncoding, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// let mut part = writer
+    ///     .create_part_with_encoding(MimeHeader::new(), TransferEncoding::Base64)
+    ///     .await?;
+    /// part.write_all(b"\x00\x01binary").await?;
+    /// writer.close().await?;
+    /// assert_eq!(
+    ///     output,
+    ///     b"--b\r\nContent-Transfer-Encoding: base64\r\n\r\nAAFiaW5hcnk=\r\n--b--\r\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_part_with_encoding(
+        &mut self,
+        header: MimeHeader,
+        encoding: TransferEncoding,
+    ) -> Result<PartWriter<'_, W>> {
+        self.create_encoded_part(header, encoding).await
+    }
+
+    /// Creates a form file part whose body is encoded with `encoding`, with
+    /// a Content-Type detected as in
+    /// [`create_form_file`](Self::create_form_file).
+    pub async fn create_form_file_with_encoding(
+        &mut self,
+        fieldname: &str,
+        filename: &str,
+        encoding: TransferEncoding,
+    ) -> Result<PartWriter<'_, W>> {
+        let header = form_file_header(fieldname, filename, &content_type_for(filename));
+        self.create_encoded_part(header, encoding).await
+    }
+
+    /// Creates a part holding a nested multipart message of type
+    /// `multipart/<subtype>`, such as a `multipart/alternative` body inside
+    /// `multipart/mixed`.
+    ///
+    /// The returned writer has its own boundary, generated with this
+    /// writer's [boundary options](WriterBuilder::boundary_prefix) and set
+    /// in the part's Content-Type header (replacing any in `header`), and
+    /// this writer's line ending and strict 7-bit setting. Closing it writes the nested
+    /// closing delimiter; it must be closed before this writer continues,
+    /// or the next part, or closing this writer, fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{MimeHeader, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("outer").build(&mut output)?;
+    /// let mut alternative = writer.create_nested_part(MimeHeader::new(), "alternative").await?;
+    /// alternative.write_field("text", "Hello").await?;
+    /// alternative.write_field("html", "<p>Hello</p>").await?;
+    /// alternative.close().await?;
+    /// writer.write_field("attachment", "data").await?;
+    /// writer.close().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_nested_part(
+        &mut self,
+        mut header: MimeHeader,
+        subtype: &str,
+    ) -> Result<Writer<PartWriter<'_, W>>> {
+        let boundary = self.boundaries.generate()?;
+        let params = HashMap::from([("boundary".to_string(), boundary.clone())]);
+        let content_type = format_media_type(&format!("multipart/{}", subtype), &params);
+        if content_type.is_empty() {
+            return Err(Error::Multipart(format!("invalid multipart subtype: {:?}", subtype)));
+        }
+
+        header.insert("Content-Type", content_type);
+        let line_ending = self.line_ending;
+        let strict_7bit = self.strict_7bit;
+        let boundaries = self.boundaries.fork();
+        let part = self.create_part_with_header(&header).await?;
+        let open = Arc::new(AtomicBool::new(true));
+        part.parent.nested_open = Some(open.clone());
+        let mut nested = Writer::builder()
+            .boundary(boundary)
+            .subtype(subtype)
+            .line_ending(line_ending)
+            .strict_7bit(strict_7bit)
+            .build(part)?;
+        nested.hooks.close = Some(Arc::new(move |_, _| open.store(false, Ordering::Release)));
+        nested.boundaries = boundaries;
+        Ok(nested)
+    }
+
+    async fn create_encoded_part(
+        &mut self,
+        mut header: MimeHeader,
+        encoding: TransferEncoding,
+    ) -> Result<PartWriter<'_, W>> {
+        header.insert("Content-Transfer-Encoding", encoding.as_str());
+        let line_ending = self.line_ending;
+        let part = self.create_part_with_header(&header).await?;
+        part.parent.body_encoder = Some(BodyEncoder::new(encoding, line_ending));
+        Ok(part)
+    }
+
+    /// Writes the delimiter and headers of a new part, in header order.
+    pub(super) async fn create_part_with_header(&mut self, header: &MimeHeader) -> Result<PartWriter<'_, W>> {
+        self.check_header(header)?;
+
+        self.finish_body().await?;
+        self.end_part();
+        self.queue_pending_line_break();
+        push_part_start(&mut self.queued, &self.boundary, header, self.line_ending.as_str());
+        self.write_queued().await?;
+
+        let index = self.start_part(header);
+        self.open_part = Some((index, header.clone(), self.writer.count));
+        self.line_break_pending = true;
+
+        Ok(PartWriter { parent: self })
+    }
+
+    /// Convenience method to create a form file part.
+    ///
+    /// The Content-Type is looked up from the extension of `filename` with
+    /// [`type_by_extension`](crate::type_by_extension), defaulting to
+    /// `application/octet-stream`.
+    pub async fn create_form_file(
+        &mut self,
+        fieldname: &str,
+        filename: &str,
+    ) -> Result<PartWriter<'_, W>> {
+        let header = form_file_header(fieldname, filename, &content_type_for(filename));
+        self.create_part_with_header(&header).await
+    }
+
+    /// Creates a form file part with the given Content-Type rather than
+    /// one detected from the filename.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut writer = Writer::new(Vec::new());
+    /// // Served as a download whatever the extension says
+    /// writer
+    ///     .create_form_file_with_type("export", "report.html", "application/octet-stream")
+    ///     .await?;
+    /// writer.close().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_form_file_with_type(
+        &mut self,
+        fieldname: &str,
+        filename: &str,
+        content_type: &str,
+    ) -> Result<PartWriter<'_, W>> {
+        let header = form_file_header(fieldname, filename, content_type);
+        self.create_part_with_header(&header).await
+    }
+
+    /// Convenience method to create a form field part.
+    pub async fn create_form_field(&mut self, fieldname: &str) -> Result<PartWriter<'_, W>> {
+        let mut header = MimeHeader::new();
+        header.insert("Content-Disposition", form_data_disposition(fieldname, None));
+
+        self.create_part_with_header(&header).await
+    }
+
+    /// Creates an attachment part (RFC 2183), e.g. a file attached to an
+    /// email.
+    ///
+    /// The `Content-Disposition: attachment` header comes first, with the
+    /// filename escaped as in [`create_form_file`](Self::create_form_file),
+    /// followed by the entries of `header`. Unless `header` sets one, the
+    /// Content-Type is detected from the filename.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{MimeHeader, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// writer.create_attachment("q3 \"final\".pdf", MimeHeader::new()).await?;
+    /// writer.close().await?;
+    /// let expected = "Content-Disposition: attachment; filename=\"q3 \\\"final\\\".pdf\"\r\n\
+    ///                 Content-Type: application/pdf\r\n";
+    /// assert!(String::from_utf8(output)?.contains(expected));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_attachment(
+        &mut self,
+        filename: &str,
+        header: MimeHeader,
+    ) -> Result<PartWriter<'_, W>> {
+        let mut disposition = "attachment".to_string();
+        push_filename_params(&mut disposition, filename);
+        let mut first = MimeHeader::new();
+        first.insert("Content-Disposition", disposition);
+        if !header.contains_key("content-type") {
+            first.insert("Content-Type", content_type_for(filename));
+        }
+        let header = merge_header(first, &header);
+        self.create_part_with_header(&header).await
+    }
+
+    /// Creates an inline part identified by `content_id`, e.g. an image
+    /// referenced as `cid:` from the HTML body of a multipart/related
+    /// message.
+    ///
+    /// `Content-Disposition: inline` and the Content-ID, in angle brackets,
+    /// come first, followed by the entries of `header`.
+    pub async fn create_inline(
         &mut self,
-        headers: HashMap<String, Vec<String>>,
+        content_id: &str,
+        header: MimeHeader,
     ) -> Result<PartWriter<'_, W>> {
-        // Write boundary
-        if self.has_parts {
-            self.writer.write_all(b"\r\n").await?;
-        }
-        self.writer
-            .write_all(format!("--{}\r\n", self.boundary).as_bytes())
-            .await?;
-
-        // Write headers (sorted for consistency)
-        let mut keys: Vec<_> = headers.keys().collect();
-        keys.sort();
-
-        for key in keys {
-            if let Some(values) = headers.get(key) {
-                for value in values {
-                    self.writer
-                        .write_all(format!("{}: {}\r\n", key, value).as_bytes())
-                        .await?;
+        let mut first = MimeHeader::new();
+        first.insert("Content-Disposition", "inline");
+        first.set_content_id(content_id);
+        let header = merge_header(first, &header);
+        self.create_part_with_header(&header).await
+    }
+
+    /// Writes a complete form field with value.
+    pub async fn write_field(&mut self, fieldname: &str, value: &str) -> Result<()> {
+        self.write_field_bytes(fieldname, value.as_bytes()).await
+    }
+
+    /// Writes a complete form field whose value is not necessarily text.
+    pub async fn write_field_bytes(&mut self, fieldname: &str, value: &[u8]) -> Result<()> {
+        let mut part = self.create_form_field(fieldname).await?;
+        part.write_all(value).await?;
+        Ok(())
+    }
+
+    /// Writes a complete form field labelled with `content_type`, e.g. a
+    /// JSON document sent as `application/json`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// writer
+    ///     .write_field_with_content_type("meta", "application/json", br#"{"a":1}"#)
+    ///     .await?;
+    /// writer.close().await?;
+    /// assert_eq!(
+    ///     output,
+    ///     &b"--b\r\nContent-Disposition: form-data; name=\"meta\"\r\n\
+    /// Content-Type: application/json\r\n\r\n{\"a\":1}\r\n--b--\r\n"[..]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn write_field_with_content_type(
+        &mut self,
+        fieldname: &str,
+        content_type: &str,
+        value: &[u8],
+    ) -> Result<()> {
+        let header: MimeHeader = [("Content-Type", content_type)].into_iter().collect();
+        self.write_field_with_header(fieldname, header, value).await
+    }
+
+    /// Writes a complete form field with extra headers.
+    ///
+    /// The Content-Disposition naming the field comes first, followed by
+    /// the entries of `header` in order; a Content-Disposition in `header`
+    /// is replaced.
+    pub async fn write_field_with_header(
+        &mut self,
+        fieldname: &str,
+        header: MimeHeader,
+        value: &[u8],
+    ) -> Result<()> {
+        let mut first = MimeHeader::new();
+        first.insert("Content-Disposition", form_data_disposition(fieldname, None));
+        let field_header = merge_header(first, &header);
+
+        let mut part = self.create_part_with_header(&field_header).await?;
+        part.write_all(value).await?;
+        Ok(())
+    }
+
+    /// Writes a form file part with the contents of the file at `path`.
+    ///
+    /// The filename is the last component of `path`, and the Content-Type
+    /// is detected from it as in [`create_form_file`](Self::create_form_file).
+    /// The file is streamed in chunks rather than read into memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut writer = Writer::new(Vec::new());
+    /// writer.write_file("upload", "photos/cat.png").await?;
+    /// writer.close().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn write_file(&mut self, fieldname: &str, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .ok_or_else(|| {
+                Error::Multipart(format!("path has no file name: {}", path.display()))
+            })?;
+        let mut file = tokio::fs::File::open(path).await?;
+
+        let header = form_file_header(fieldname, &filename, &content_type_for(&filename));
+        self.copy_part(&header, &mut file).await?;
+        Ok(())
+    }
+
+    /// Writes a part with the given headers and the contents of `reader`
+    /// as its body, returning the number of body bytes written.
+    ///
+    /// The body is copied through a fixed-size buffer, waiting for the
+    /// output to accept each chunk before reading the next, so sources of
+    /// any size can be written without holding a [`PartWriter`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{MimeHeader, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut header = MimeHeader::new();
+    /// header.insert("Content-Type", "text/plain");
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// let written = writer.write_part_from_reader(header, &b"streamed"[..]).await?;
+    /// assert_eq!(written, 8);
+    /// writer.close().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn write_part_from_reader<R: AsyncRead + Unpin>(
+        &mut self,
+        header: MimeHeader,
+        mut reader: R,
+    ) -> Result<u64> {
+        self.copy_part(&header, &mut reader).await
+    }
+
+    /// Writes every value and file of a form, e.g. one parsed with
+    /// [`Reader::read_form`](super::Reader::read_form) to be proxied
+    /// downstream.
+    ///
+    /// Fields are written by name in sorted order, values first, each
+    /// repeated value in its own part. Files keep the headers they were
+    /// received with; a file whose header has no Content-Disposition gets a
+    /// form-data one with its Content-Type, or one detected from the
+    /// filename. File content is streamed from [`FileHeader::open`](super::FileHeader::open),
+    /// so files kept on disk are not read into memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::{Reader, Writer};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = b"--a\r\nContent-Disposition: form-data; name=\"x\"\r\n\r\n1\r\n--a--\r\n";
+    /// let form = Reader::new(&data[..], "a").read_form(1 << 20).await?;
+    ///
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// writer.write_form(&form).await?;
+    /// writer.close().await?;
+    /// assert_eq!(
+    ///     output,
+    ///     b"--b\r\nContent-Disposition: form-data; name=\"x\"\r\n\r\n1\r\n--b--\r\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn write_form(&mut self, form: &Form) -> Result<()> {
+        let mut names: Vec<&String> = form.value.keys().collect();
+        names.sort();
+        for name in names {
+            for value in &form.value[name] {
+                self.write_field(name, value).await?;
+            }
+        }
+
+        let mut names: Vec<&String> = form.file.keys().collect();
+        names.sort();
+        for name in names {
+            for file in &form.file[name] {
+                let header = if file.header.contains_key("content-disposition") {
+                    file.header.clone()
+                } else {
+                    let content_type = match file.header.get("content-type") {
+                        Some(content_type) => content_type.to_string(),
+                        None => content_type_for(&file.filename),
+                    };
+                    merge_header(
+                        form_file_header(name, &file.filename, &content_type),
+                        &file.header,
+                    )
+                };
+                let mut content = file.open().await?;
+                self.copy_part(&header, &mut content).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts a part with `header` and copies `reader` into its body.
+    async fn copy_part<R: AsyncRead + Unpin>(
+        &mut self,
+        header: &MimeHeader,
+        reader: &mut R,
+    ) -> Result<u64> {
+        let mut part = self.create_part_with_header(header).await?;
+        let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+        let mut written = 0u64;
+        loop {
+            let n = reader.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(written);
+            }
+            part.write_all(&buf[..n]).await?;
+            written += n as u64;
+        }
+    }
+
+    /// Writes bytes before the first part, such as the
+    /// [`preamble`](super::Reader::preamble) recorded in preserve mode.
+    ///
+    /// The preamble is ignored by readers; it must end with a line break so
+    /// the first delimiter starts a line.
+    pub async fn write_preamble(&mut self, preamble: &[u8]) -> Result<()> {
+        if self.parts_written > 0 {
+            return Err(Error::Multipart(
+                "cannot write a preamble after writing parts".to_string(),
+            ));
+        }
+        self.writer.write_all(preamble).await?;
+        Ok(())
+    }
+
+    /// Writes a complete part with the framing it was received with.
+    ///
+    /// The delimiter line and line break are written as recorded, and so is
+    /// the header block if `header` still [matches](PartFraming::header_matches)
+    /// it; a modified header is written out from `header` instead. The
+    /// recorded delimiter is only used if it is a delimiter line for this
+    /// writer's boundary, not that of a longer one or a closing delimiter.
+    /// See [`preserve`](super::preserve).
+    pub async fn write_preserved_part(
+        &mut self,
+        header: &MimeHeader,
+        framing: &PartFraming,
+        body: &[u8],
+    ) -> Result<()> {
+        self.check_header(header)?;
+        if self.strict_7bit && !body.is_ascii() {
+            return Err(Error::Multipart(
+                "non-ASCII body byte in strict 7-bit mode".to_string(),
+            ));
+        }
+
+        self.finish_body().await?;
+        self.end_part();
+        self.queue_pending_line_break();
+        let dash_boundary = format!("--{}", self.boundary);
+        if is_delimiter_line(&framing.delimiter, dash_boundary.as_bytes()) {
+            self.queued.extend_from_slice(&framing.delimiter);
+        } else {
+            let nl = self.line_ending.as_str();
+            self.queued
+                .extend_from_slice(format!("{}{}", dash_boundary, nl).as_bytes());
+        }
+
+        if framing.header_matches(header) {
+            self.queued.extend_from_slice(&framing.header);
+        } else {
+            self.queue_header(header);
+        }
+        self.write_queued().await?;
+
+        let index = self.start_part(header);
+        self.writer.write_all(body).await?;
+        if let Some(hook) = &self.hooks.part_end {
+            hook(index, header, body.len() as u64);
+        }
+        self.writer.write_all(&framing.line_break).await?;
+        self.line_break_pending = false;
+        Ok(())
+    }
+
+    /// Writes a part given as already formatted bytes: its header block,
+    /// the blank line ending it, and its body, without the line break
+    /// before the next delimiter.
+    ///
+    /// The bytes are written between delimiters as they are, e.g. to splice
+    /// a verified part from one message into another without
+    /// re-serializing it. Fails with [`Error::Multipart`] if they contain a
+    /// line starting with this writer's boundary, which would end the part
+    /// early. The writer hooks see an empty header, and the whole of `raw`
+    /// as the body size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// writer.write_raw_part(b"X-Signed: yes\r\n\r\nbody").await?;
+    /// writer.close().await?;
+    /// assert_eq!(output, b"--b\r\nX-Signed: yes\r\n\r\nbody\r\n--b--\r\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn write_raw_part(&mut self, raw: &[u8]) -> Result<()> {
+        let dash_boundary = format!("--{}", self.boundary);
+        let line_start = format!("\n{}", dash_boundary);
+        if raw.starts_with(dash_boundary.as_bytes())
+            || memchr::memmem::find(raw, line_start.as_bytes()).is_some()
+        {
+            return Err(Error::Multipart("raw part contains the boundary".to_string()));
+        }
+        if self.strict_7bit && !raw.is_ascii() {
+            return Err(Error::Multipart(
+                "non-ASCII byte in raw part in strict 7-bit mode".to_string(),
+            ));
+        }
+
+        self.finish_body().await?;
+        self.end_part();
+        self.queue_pending_line_break();
+        let nl = self.line_ending.as_str();
+        self.queued
+            .extend_from_slice(format!("{}{}", dash_boundary, nl).as_bytes());
+        self.write_queued().await?;
+
+        let header = MimeHeader::new();
+        let index = self.start_part(&header);
+        self.writer.write_all(raw).await?;
+        if let Some(hook) = &self.hooks.part_end {
+            hook(index, &header, raw.len() as u64);
+        }
+        self.line_break_pending = true;
+        Ok(())
+    }
+
+    /// Closes the writer by writing the final boundary.
+    pub async fn close(self) -> Result<()> {
+        self.close_with_epilogue(b"").await
+    }
+
+    /// Closes the writer, writing `epilogue` after the closing delimiter.
+    ///
+    /// RFC 2046 lets anything follow the closing delimiter line; readers
+    /// ignore it, except for [`Reader::epilogue`](super::Reader::epilogue)
+    /// in preserve mode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// writer.write_field("a", "1").await?;
+    /// writer.close_with_epilogue(b"That's all.\r\n").await?;
+    /// assert!(output.ends_with(b"\r\n--b--\r\nThat's all.\r\n"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn close_with_epilogue(mut self, epilogue: &[u8]) -> Result<()> {
+        self.finish_body().await?;
+        self.end_part();
+        self.queue_pending_line_break();
+        push_closing(&mut self.queued, &self.boundary, self.line_ending.as_str());
+        self.queued.extend_from_slice(epilogue);
+        self.write_queued().await?;
+        self.writer.flush().await?;
+        self.report_close();
+        Ok(())
+    }
+
+    /// Closes the writer with a closing delimiter and epilogue as recorded
+    /// in preserve mode by [`Reader::closing`](super::Reader::closing).
+    pub async fn close_preserved(mut self, closing: &[u8]) -> Result<()> {
+        self.finish_body().await?;
+        self.end_part();
+        self.queue_pending_line_break();
+        self.queued.extend_from_slice(closing);
+        self.write_queued().await?;
+        self.writer.flush().await?;
+        self.report_close();
+        Ok(())
+    }
+
+    /// Flushes the output, including the encoded body of a part created
+    /// with a transfer encoding so far.
+    ///
+    /// The writer only flushes in [`close`](Self::close) and
+    /// [`PartWriter::finish`]; this pushes out what was written in between,
+    /// e.g. each frame of a long-lived `multipart/x-mixed-replace` stream.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.resume_part().flush().await?;
+        Ok(())
+    }
+
+    /// Returns a writer for the body of the part being written, continuing
+    /// where a dropped [`PartWriter`] left off.
+    pub(super) fn resume_part(&mut self) -> PartWriter<'_, W> {
+        PartWriter { parent: self }
+    }
+
+    /// Counts a part whose headers are written and reports its start.
+    fn start_part(&mut self, header: &MimeHeader) -> usize {
+        let index = self.parts_written;
+        self.parts_written += 1;
+        if let Some(hook) = &self.hooks.part_start {
+            hook(index, header);
+        }
+        index
+    }
+
+    /// Writes the rest of the open part's encoded body, if it has a
+    /// transfer encoding.
+    ///
+    /// Fails if the part holds a nested writer that was dropped without
+    /// being closed: its body lacks the closing delimiter, so nothing
+    /// written after it could be parsed.
+    async fn finish_body(&mut self) -> Result<()> {
+        if self
+            .nested_open
+            .as_ref()
+            .is_some_and(|open| open.load(Ordering::Acquire))
+        {
+            return Err(Error::Multipart(
+                "nested multipart writer was not closed before the next part".to_string(),
+            ));
+        }
+        if let Some(mut encoder) = self.body_encoder.take() {
+            encoder.finish();
+            futures::future::poll_fn(|cx| encoder.poll_drain(Pin::new(&mut self.writer), cx))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Reports the end of the part being written, if any.
+    fn end_part(&mut self) {
+        if let Some((index, header, start)) = self.open_part.take() {
+            if let Some(hook) = &self.hooks.part_end {
+                hook(index, &header, self.writer.count - start);
+            }
+        }
+    }
+
+    fn report_close(&self) {
+        if let Some(hook) = &self.hooks.close {
+            hook(self.parts_written, self.writer.count);
+        }
+    }
+
+    fn check_header(&self, header: &MimeHeader) -> Result<()> {
+        if self.strict_7bit {
+            if let Some((key, _)) = header.iter().find(|(k, v)| !k.is_ascii() || !v.is_ascii()) {
+                return Err(Error::Multipart(format!(
+                    "non-ASCII header in strict 7-bit mode: {}",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the header lines and the blank line ending them.
+    fn queue_header(&mut self, header: &MimeHeader) {
+        push_header(&mut self.queued, header, self.line_ending.as_str());
+    }
+
+    /// Queues the line break ending the previous part's body before a
+    /// delimiter.
+    fn queue_pending_line_break(&mut self) {
+        if self.line_break_pending {
+            self.queued
+                .extend_from_slice(self.line_ending.as_str().as_bytes());
+            self.line_break_pending = false;
+        }
+    }
+
+    /// Writes the queued framing with a single write where the output
+    /// accepts it whole.
+    async fn write_queued(&mut self) -> Result<()> {
+        if !self.queued.is_empty() {
+            let result = self.writer.write_all(&self.queued).await;
+            self.queued.clear();
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// A writer for a single part's body.
+///
+/// The part borrows its [`Writer`], so only one part is open at a time and
+/// nothing else can be written until it is finished or dropped. Dropping
+/// it leaves the line break ending the body to be written with the next
+/// delimiter; [`finish`](Self::finish) writes it right away.
+pub struct PartWriter<'a, W> {
+    parent: &'a mut Writer<W>,
+}
+
+impl<'a, W: AsyncWrite + Unpin> PartWriter<'a, W> {
+    /// Ends the part: writes the line break terminating its body and
+    /// flushes the output.
+    ///
+    /// The part end is reported to [`WriterBuilder::on_part_end`] now
+    /// rather than when the next part starts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::io::AsyncWriteExt;
+    /// use yamime::multipart::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::builder().boundary("b").build(&mut output)?;
+    /// let mut part = writer.create_form_field("a").await?;
+    /// part.write_all(b"1").await?;
+    /// part.finish().await?;
+    /// writer.close().await?;
+    /// assert!(output.ends_with(b"\r\n\r\n1\r\n--b--\r\n"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn finish(self) -> Result<()> {
+        self.parent.finish_body().await?;
+        self.parent.end_part();
+        self.parent.queue_pending_line_break();
+        self.parent.write_queued().await?;
+        self.parent.writer.flush().await?;
+        Ok(())
+    }
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncWrite for PartWriter<'a, W> {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        let parent = &mut *self.parent;
+        if let Some(encoder) = &mut parent.body_encoder {
+            // Encoded output is ASCII, so strict 7-bit mode accepts any body
+            std::task::ready!(encoder.poll_drain(Pin::new(&mut parent.writer), cx))?;
+            encoder.encode(buf);
+            return std::task::Poll::Ready(Ok(buf.len()));
+        }
+        if self.parent.strict_7bit && !buf.is_ascii() {
+            return std::task::Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "non-ASCII body byte in strict 7-bit mode",
+            )));
+        }
+        std::pin::Pin::new(&mut self.parent.writer).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        let parent = &mut *self.parent;
+        if let Some(encoder) = &mut parent.body_encoder {
+            std::task::ready!(encoder.poll_drain(Pin::new(&mut parent.writer), cx))?;
+            for buf in bufs {
+                encoder.encode(buf);
+            }
+            return std::task::Poll::Ready(Ok(bufs.iter().map(|buf| buf.len()).sum()));
+        }
+        if parent.strict_7bit && !bufs.iter().all(|buf| buf.is_ascii()) {
+            return std::task::Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "non-ASCII body byte in strict 7-bit mode",
+            )));
+        }
+        std::pin::Pin::new(&mut parent.writer).poll_write_vectored(cx, bufs)
+    }
+
+    /// Reports whether the output writes vectors efficiently. Bodies with a
+    /// transfer encoding are copied through the encoder either way.
+    fn is_write_vectored(&self) -> bool {
+        self.parent.body_encoder.is_none() && self.parent.writer.is_write_vectored()
+    }
+
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let parent = &mut *self.parent;
+        if let Some(encoder) = &mut parent.body_encoder {
+            encoder.release();
+            std::task::ready!(encoder.poll_drain(Pin::new(&mut parent.writer), cx))?;
+        }
+        std::pin::Pin::new(&mut parent.writer).poll_flush(cx)
+    }
+
+    /// Writes out the end of an encoded body, then shuts down the output.
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let parent = &mut *self.parent;
+        if let Some(encoder) = &mut parent.body_encoder {
+            encoder.finish();
+            std::task::ready!(encoder.poll_drain(Pin::new(&mut parent.writer), cx))?;
+        }
+        std::pin::Pin::new(&mut parent.writer).poll_shutdown(cx)
+    }
+}
+
+/// Counts the bytes written through it, for the writer hooks.
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CountingWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.count += n as u64;
+        }
+        poll
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.count += n as u64;
+        }
+        poll
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Validates a boundary against RFC 2046: 1-70 characters from the
+/// `bchars` set, not ending with a space.
+pub(crate) fn validate_boundary(boundary: &[u8]) -> Result<()> {
+    if boundary.is_empty() || boundary.len() > 70 {
+        return Err(Error::Multipart("invalid boundary length".to_string()));
+    }
+
+    for (i, &b) in boundary.iter().enumerate() {
+        let valid = is_bchar_nospace(b) || (b == b' ' && i != boundary.len() - 1);
+
+        if !valid {
+            return Err(Error::Multipart(format!(
+                "invalid boundary character: {}",
+                b.escape_ascii()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reports whether `b` is in the RFC 2046 `bcharsnospace` set.
+fn is_bchar_nospace(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || matches!(b, b'\'' | b'(' | b')' | b'+' | b'_' | b',' | b'-' | b'.' | b'/' | b':' | b'=' | b'?')
+}
+
+/// Reports whether `line` is a delimiter line for `dash_boundary`: the
+/// boundary followed only by transport padding and a line ending.
+fn is_delimiter_line(line: &[u8], dash_boundary: &[u8]) -> bool {
+    let Some(rest) = line.strip_prefix(dash_boundary) else {
+        return false;
+    };
+    let padding = rest.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+    matches!(&rest[padding..], b"\r\n" | b"\n")
+}
+
+/// Generates a random boundary string.
+pub(crate) fn generate_boundary() -> String {
+    BoundaryOptions::default()
+        .generate()
+        .expect("default boundary options are valid")
+}
+
+/// How boundaries are generated when none is given.
+#[derive(Debug, Clone)]
+struct BoundaryOptions {
+    prefix: String,
+    length: usize,
+    alphabet: String,
+    /// Seeds a deterministic generator instead of the OS one.
+    seed: Option<u64>,
+}
+
+impl Default for BoundaryOptions {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            length: 60,
+            alphabet: "0123456789abcdef".to_string(),
+            seed: None,
+        }
+    }
+}
+
+impl BoundaryOptions {
+    fn generate(&self) -> Result<String> {
+        self.generate_with(&mut BoundaryRng::new(self.seed))
+    }
+
+    fn generate_with(&self, rng: &mut BoundaryRng) -> Result<String> {
+        let alphabet = self.alphabet.as_bytes();
+        if alphabet.is_empty() || !alphabet.iter().all(|&b| is_bchar_nospace(b)) {
+            return Err(Error::Multipart(format!(
+                "invalid boundary alphabet: {:?}",
+                self.alphabet
+            )));
+        }
+
+        // Accept only random bytes below a multiple of the alphabet size, so
+        // every character is equally likely
+        let limit = 256 - 256 % alphabet.len();
+        let mut boundary = self.prefix.clone();
+        let mut random = [0u8; 32];
+        let mut generated = 0;
+        while generated < self.length {
+            rng.fill(&mut random);
+            for &b in &random {
+                if generated < self.length && (b as usize) < limit {
+                    boundary.push(alphabet[b as usize % alphabet.len()] as char);
+                    generated += 1;
+                }
+            }
+        }
+
+        validate_boundary(boundary.as_bytes())?;
+        Ok(boundary)
+    }
+}
+
+/// Generates a writer's boundaries from its options, drawing each from the
+/// same random stream so seeded writers stay reproducible.
+struct BoundaryGenerator {
+    options: BoundaryOptions,
+    rng: BoundaryRng,
+}
+
+impl BoundaryGenerator {
+    fn new(options: BoundaryOptions) -> Self {
+        let rng = BoundaryRng::new(options.seed);
+        Self { options, rng }
+    }
+
+    fn generate(&mut self) -> Result<String> {
+        self.options.generate_with(&mut self.rng)
+    }
+
+    /// Returns a generator for a nested writer, seeded from this one so
+    /// the two never repeat each other's boundaries.
+    fn fork(&mut self) -> Self {
+        let rng = match self.rng {
+            BoundaryRng::Os => BoundaryRng::Os,
+            BoundaryRng::Seeded(_) => {
+                let mut seed = [0u8; 8];
+                self.rng.fill(&mut seed);
+                BoundaryRng::Seeded(u64::from_le_bytes(seed))
+            }
+        };
+        Self {
+            options: self.options.clone(),
+            rng,
+        }
+    }
+}
+
+/// The source of random boundary bytes.
+enum BoundaryRng {
+    Os,
+    /// SplitMix64 state.
+    Seeded(u64),
+}
+
+impl BoundaryRng {
+    fn new(seed: Option<u64>) -> Self {
+        match seed {
+            Some(seed) => BoundaryRng::Seeded(seed),
+            None => BoundaryRng::Os,
+        }
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        match self {
+            BoundaryRng::Os => {
+                getrandom::getrandom(buf).expect("failed to generate random boundary")
+            }
+            BoundaryRng::Seeded(state) => {
+                for chunk in buf.chunks_mut(8) {
+                    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    let mut z = *state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                    z ^= z >> 31;
+                    chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
                 }
             }
         }
-
-        // Empty line after headers
-        self.writer.write_all(b"\r\n").await?;
-
-        self.has_parts = true;
-
-        Ok(PartWriter {
-            writer: &mut self.writer,
-        })
     }
+}
 
-    /// Convenience method to create a form file part.
-    pub async fn create_form_file(
-        &mut self,
-        fieldname: &str,
-        filename: &str,
-    ) -> Result<PartWriter<'_, W>> {
-        let mut headers = HashMap::new();
-        headers.insert(
-            "Content-Disposition".to_string(),
-            vec![format!(
-                "form-data; name=\"{}\"; filename=\"{}\"",
-                escape_quotes(fieldname),
-                escape_quotes(filename)
-            )],
-        );
-        headers.insert(
-            "Content-Type".to_string(),
-            vec!["application/octet-stream".to_string()],
-        );
+/// Returns the Content-Type of a form-data body with `boundary`, quoting
+/// the boundary if needed.
+pub(super) fn form_data_content_type(boundary: &str) -> String {
+    let boundary = if boundary.contains(['(', ')', '<', '>', '@', ',', ';', ':', '"', '/', '[', ']', '?', '=', ' ']) {
+        format!("\"{}\"", boundary)
+    } else {
+        boundary.to_string()
+    };
 
-        self.create_part(headers).await
-    }
+    format!("multipart/form-data; boundary={}", boundary)
+}
 
-    /// Convenience method to create a form field part.
-    pub async fn create_form_field(&mut self, fieldname: &str) -> Result<PartWriter<'_, W>> {
-        let mut headers = HashMap::new();
-        headers.insert(
-            "Content-Disposition".to_string(),
-            vec![format!(
-                "form-data; name=\"{}\"",
-                escape_quotes(fieldname)
-            )],
-        );
+/// Appends the delimiter line opening a part, then its header block.
+pub(super) fn push_part_start(out: &mut Vec<u8>, boundary: &str, header: &MimeHeader, nl: &str) {
+    out.extend_from_slice(format!("--{}{}", boundary, nl).as_bytes());
+    push_header(out, header, nl);
+}
 
-        self.create_part(headers).await
+/// Appends the header lines and the blank line ending them.
+pub(super) fn push_header(out: &mut Vec<u8>, header: &MimeHeader, nl: &str) {
+    for (key, value) in header.iter() {
+        out.extend_from_slice(format!("{}: {}{}", key, value, nl).as_bytes());
     }
 
-    /// Writes a complete form field with value.
-    pub async fn write_field(&mut self, fieldname: &str, value: &str) -> Result<()> {
-        let mut part = self.create_form_field(fieldname).await?;
-        part.write_all(value.as_bytes()).await?;
-        Ok(())
-    }
+    // Empty line after headers
+    out.extend_from_slice(nl.as_bytes());
+}
 
-    /// Closes the writer by writing the final boundary.
-    pub async fn close(mut self) -> Result<()> {
-        if self.has_parts {
-            self.writer.write_all(b"\r\n").await?;
-        }
-        self.writer
-            .write_all(format!("--{}--\r\n", self.boundary).as_bytes())
-            .await?;
-        self.writer.flush().await?;
-        Ok(())
-    }
+/// Appends the closing delimiter line.
+pub(super) fn push_closing(out: &mut Vec<u8>, boundary: &str, nl: &str) {
+    out.extend_from_slice(format!("--{}--{}", boundary, nl).as_bytes());
 }
 
-/// A writer for a single part's body.
-pub struct PartWriter<'a, W> {
-    writer: &'a mut W,
+/// Builds the header of a form file part.
+pub(super) fn form_file_header(fieldname: &str, filename: &str, content_type: &str) -> MimeHeader {
+    let mut header = MimeHeader::new();
+    header.insert(
+        "Content-Disposition",
+        form_data_disposition(fieldname, Some(filename)),
+    );
+    header.insert("Content-Type", content_type);
+    header
 }
 
-impl<'a, W: AsyncWrite + Unpin> AsyncWrite for PartWriter<'a, W> {
-    fn poll_write(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &[u8],
-    ) -> std::task::Poll<std::io::Result<usize>> {
-        std::pin::Pin::new(&mut self.writer).poll_write(cx, buf)
+/// Formats a form-data Content-Disposition.
+///
+/// Values that are not printable ASCII would be raw UTF-8 or control
+/// characters in the header. Such a name is written as an RFC 2047
+/// encoded-word; such a filename additionally gets an RFC 2231
+/// `filename*` parameter, which readers following RFC 6266 prefer.
+pub(super) fn form_data_disposition(fieldname: &str, filename: Option<&str>) -> String {
+    let mut disposition = format!("form-data; name=\"{}\"", quoted_param_value(fieldname));
+    if let Some(filename) = filename {
+        push_filename_params(&mut disposition, filename);
     }
+    disposition
+}
 
-    fn poll_flush(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        std::pin::Pin::new(&mut self.writer).poll_flush(cx)
+/// Appends the `filename` parameter, and `filename*` if needed, as for
+/// [`form_data_disposition`].
+fn push_filename_params(disposition: &mut String, filename: &str) {
+    disposition.push_str(&format!("; filename=\"{}\"", quoted_param_value(filename)));
+    if needs_word_encoding(filename) {
+        disposition.push_str("; filename*=");
+        disposition.push_str(&encode_rfc2231_value(filename));
     }
+}
 
-    fn poll_shutdown(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        std::pin::Pin::new(&mut self.writer).poll_shutdown(cx)
+/// Returns `first` followed by the entries of `rest` not named in it.
+fn merge_header(first: MimeHeader, rest: &MimeHeader) -> MimeHeader {
+    let mut header = first;
+    for (name, value) in rest.iter() {
+        if !header.contains_key(name) {
+            header.append(name, value);
+        }
     }
+    header
 }
 
-/// Generates a random boundary string.
-fn generate_boundary() -> String {
-    use getrandom::getrandom;
+/// Returns the content of a quoted parameter value: escaped, or as a
+/// base64 encoded-word, which has no characters needing escapes.
+fn quoted_param_value(value: &str) -> String {
+    if needs_word_encoding(value) {
+        WordEncoder::BEncoding.encode("utf-8", value)
+    } else {
+        escape_quotes(value)
+    }
+}
 
-    let mut buf = [0u8; 30];
-    getrandom(&mut buf).expect("failed to generate random boundary");
+fn needs_word_encoding(value: &str) -> bool {
+    value.chars().any(|c| !(' '..='~').contains(&c) && c != '\t')
+}
 
-    // Convert to hex string
-    buf.iter()
-        .map(|b| format!("{:02x}", b))
-        .collect::<String>()
+/// Returns the Content-Type for a file, from its extension.
+pub(super) fn content_type_for(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| crate::type_by_extension(&format!(".{}", ext.to_string_lossy())))
+        .unwrap_or_else(|| "application/octet-stream".to_string())
 }
 
 /// Escapes quotes and backslashes in a string.
-fn escape_quotes(s: &str) -> String {
+pub(crate) fn escape_quotes(s: &str) -> String {
     s.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
@@ -249,6 +1735,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::drop_non_drop)]
     async fn test_form_file() {
         let mut output = Vec::new();
         let mut writer = Writer::new(&mut output);
@@ -265,11 +1752,12 @@ mod tests {
         let result = String::from_utf8(output).unwrap();
         assert!(result.contains("name=\"upload\""));
         assert!(result.contains("filename=\"test.txt\""));
-        assert!(result.contains("Content-Type: application/octet-stream"));
+        assert!(result.contains("Content-Type: text/plain; charset=utf-8"));
         assert!(result.contains("file content"));
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_boundary_validation() {
         let mut output = Vec::new();
         let mut writer = Writer::new(&mut output);
@@ -285,6 +1773,686 @@ mod tests {
         assert!(writer.set_boundary(String::new()).is_err());
     }
 
+    #[test]
+    fn test_builder_validates_boundary() {
+        let output: Vec<u8> = Vec::new();
+        assert!(Writer::builder().boundary("ok-boundary").build(output).is_ok());
+        assert!(Writer::builder().boundary("bad<boundary>").build(Vec::new()).is_err());
+        assert!(Writer::builder().boundary("").build(Vec::new()).is_err());
+
+        let writer = Writer::builder().build(Vec::new()).unwrap();
+        assert_eq!(writer.boundary().len(), 60);
+    }
+
+    #[test]
+    fn test_builder_boundary_options() {
+        let boundary = |builder: WriterBuilder| builder.build(Vec::new()).map(|w| w.boundary().to_string());
+
+        let default = boundary(Writer::builder()).unwrap();
+        assert!(default.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        assert_ne!(default, boundary(Writer::builder()).unwrap());
+
+        let custom = boundary(
+            Writer::builder()
+                .boundary_prefix("----RustFormBoundary")
+                .boundary_length(16)
+                .boundary_alphabet("xyz"),
+        )
+        .unwrap();
+        let random = custom.strip_prefix("----RustFormBoundary").unwrap();
+        assert_eq!(random.len(), 16);
+        assert!(random.bytes().all(|b| b"xyz".contains(&b)));
+
+        // Seeded boundaries are reproducible, and differ between seeds
+        let seeded = |seed| boundary(Writer::builder().boundary_seed(seed)).unwrap();
+        assert_eq!(seeded(7), seeded(7));
+        assert_ne!(seeded(7), seeded(8));
+        assert_eq!(seeded(7).len(), 60);
+
+        // An explicit boundary wins
+        let explicit = Writer::builder().boundary("fixed").boundary_prefix("p-");
+        assert_eq!(boundary(explicit).unwrap(), "fixed");
+
+        assert!(boundary(Writer::builder().boundary_alphabet("")).is_err());
+        assert!(boundary(Writer::builder().boundary_alphabet("ab ")).is_err());
+        assert!(boundary(Writer::builder().boundary_alphabet("a<")).is_err());
+        // Every boundary character but space, longer than a boundary may be
+        let bchars = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz'()+_,-./:=?";
+        let generated = boundary(Writer::builder().boundary_alphabet(bchars)).unwrap();
+        assert!(generated.bytes().all(|b| bchars.as_bytes().contains(&b)));
+        assert!(boundary(Writer::builder().boundary_length(71)).is_err());
+        assert!(boundary(Writer::builder().boundary_prefix("p").boundary_length(70)).is_err());
+        assert!(boundary(Writer::builder().boundary_length(0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_builder_line_ending() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("b")
+            .line_ending(LineEnding::Lf)
+            .build(&mut output)
+            .unwrap();
+        writer.write_field("a", "1").await.unwrap();
+        writer.close().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "--b\nContent-Disposition: form-data; name=\"a\"\n\n1\n--b--\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_strict_7bit() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().strict_7bit(true).build(&mut output).unwrap();
+
+        writer.write_field("ascii", "plain").await.unwrap();
+        assert!(writer.write_field("body", "caf\u{e9}").await.is_err());
+        // Non-ASCII names are encoded, but raw header values are rejected
+        assert!(writer.create_form_field("n\u{e4}me").await.is_ok());
+        let header: MimeHeader = [("X-Name", "n\u{e4}me")].into_iter().collect();
+        assert!(writer.create_part(header).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_hooks() {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (start, end, close) = (events.clone(), events.clone(), events.clone());
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("b")
+            .on_part_start(move |index, header| {
+                let name = header.get("Content-Disposition").unwrap().to_string();
+                start.lock().unwrap().push(format!("start {} {}", index, name));
+            })
+            .on_part_end(move |index, _, body_bytes| {
+                end.lock().unwrap().push(format!("end {} {}", index, body_bytes));
+            })
+            .on_close(move |parts, bytes_written| {
+                close.lock().unwrap().push(format!("close {} {}", parts, bytes_written));
+            })
+            .build(&mut output)
+            .unwrap();
+
+        writer.write_field("a", "one").await.unwrap();
+        let mut part = writer.create_form_file("f", "x.bin").await.unwrap();
+        part.write_all(b"12").await.unwrap();
+        part.write_all(b"345").await.unwrap();
+        writer.close().await.unwrap();
+
+        let total = output.len();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "start 0 form-data; name=\"a\"".to_string(),
+                "end 0 3".to_string(),
+                "start 1 form-data; name=\"f\"; filename=\"x.bin\"".to_string(),
+                "end 1 5".to_string(),
+                format!("close 2 {}", total),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_part_finish() {
+        let body_bytes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = body_bytes.clone();
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("b")
+            .on_part_end(move |_, _, n| sink.lock().unwrap().push(n))
+            .build(&mut output)
+            .unwrap();
+
+        let mut part = writer.create_form_field("a").await.unwrap();
+        part.write_all(b"one").await.unwrap();
+        part.finish().await.unwrap();
+        assert_eq!(*body_bytes.lock().unwrap(), [3]);
+
+        // Finished and unfinished parts produce the same output
+        writer.write_field("b", "two").await.unwrap();
+        let part = writer.create_form_field("c").await.unwrap();
+        part.finish().await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(*body_bytes.lock().unwrap(), [3, 3, 0]);
+
+        let mut expected = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut expected).unwrap();
+        writer.write_field("a", "one").await.unwrap();
+        writer.write_field("b", "two").await.unwrap();
+        writer.write_field("c", "").await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[tokio::test]
+    async fn test_form_file_content_type() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.create_form_file("a", "photo.JPG").await.unwrap();
+        writer.create_form_file("b", "no-extension").await.unwrap();
+        writer.create_form_file("c", "archive.tar.unknownext").await.unwrap();
+        writer
+            .create_form_file_with_type("d", "page.html", "text/x-custom")
+            .await
+            .unwrap();
+        writer.close().await.unwrap();
+
+        let mut reader = crate::multipart::Reader::new(&output[..], "b");
+        let mut types = Vec::new();
+        while let Some(part) = reader.next_part().await.unwrap() {
+            types.push(part.header.get("content-type").unwrap().to_string());
+        }
+        assert_eq!(
+            types,
+            [
+                "image/jpeg",
+                "application/octet-stream",
+                "application/octet-stream",
+                "text/x-custom"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_encoded_parts() {
+        let binary: Vec<u8> = (0..=255).cycle().take(200).collect();
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("b")
+            .strict_7bit(true)
+            .build(&mut output)
+            .unwrap();
+
+        let header: MimeHeader = [("Content-Transfer-Encoding", "8bit")].into_iter().collect();
+        let mut part = writer
+            .create_part_with_encoding(header, TransferEncoding::QuotedPrintable)
+            .await
+            .unwrap();
+        part.write_all("Grüße, \nand a trailing space ".as_bytes()).await.unwrap();
+        part.flush().await.unwrap();
+
+        let mut part = writer
+            .create_form_file_with_encoding("f", "blob.bin", TransferEncoding::Base64)
+            .await
+            .unwrap();
+        for chunk in binary.chunks(7) {
+            part.write_all(chunk).await.unwrap();
+        }
+        part.finish().await.unwrap();
+        writer.close().await.unwrap();
+
+        let text = String::from_utf8(output.clone()).unwrap();
+        assert!(text.starts_with(
+            "--b\r\nContent-Transfer-Encoding: quoted-printable\r\n\r\n\
+             Gr=C3=BC=C3=9Fe,=20\r\nand a trailing space=20\r\n--b\r\n"
+        ));
+        assert!(text.contains("Content-Transfer-Encoding: base64\r\n"));
+        assert!(text.lines().all(|line| line.len() <= 76));
+
+        let mut reader = crate::multipart::Reader::new(&output[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(
+            part.bytes().await.unwrap(),
+            "Grüße, \r\nand a trailing space ".as_bytes()
+        );
+        drop(part);
+        let mut part = reader.next_raw_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name().as_deref(), Some("blob.bin"));
+        let encoded = part.bytes().await.unwrap();
+        let mut decoded = Vec::new();
+        crate::base64::Decoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .await
+            .unwrap();
+        assert_eq!(decoded, binary);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_encoding_line_ending() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("b")
+            .line_ending(LineEnding::Lf)
+            .build(&mut output)
+            .unwrap();
+        let mut part = writer
+            .create_part_with_encoding(MimeHeader::new(), TransferEncoding::Base64)
+            .await
+            .unwrap();
+        part.write_all(&[0xff; 60]).await.unwrap();
+        writer.close().await.unwrap();
+        let expected = format!(
+            "--b\nContent-Transfer-Encoding: base64\n\n{}\n////\n--b--\n",
+            "/".repeat(76)
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_part_shutdown_finishes_encoding() {
+        for (encoding, body) in [
+            (TransferEncoding::Base64, "aGkg"),
+            (TransferEncoding::QuotedPrintable, "hi=20"),
+        ] {
+            let mut output = Vec::new();
+            let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+            let mut part = writer
+                .create_part_with_encoding(MimeHeader::new(), encoding)
+                .await
+                .unwrap();
+            part.write_all(b"hi ").await.unwrap();
+            part.shutdown().await.unwrap();
+            // The encoded body is complete without closing the writer
+            drop(writer);
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.ends_with(&format!("\r\n\r\n{}", body)), "{:?}", output);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_file() {
+        let dir = std::env::temp_dir().join(format!("yamime-write-file-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("report.HTML");
+        let contents = "<p>hi</p>".repeat(2000);
+        tokio::fs::write(&path, &contents).await.unwrap();
+        let unknown = dir.join("data.weird");
+        tokio::fs::write(&unknown, b"\x00\x01").await.unwrap();
+
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.write_file("doc", &path).await.unwrap();
+        writer.write_file("blob", &unknown).await.unwrap();
+        assert!(writer.write_file("gone", dir.join("missing")).await.is_err());
+        writer.close().await.unwrap();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+        let mut reader = crate::multipart::Reader::new(&output[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("doc"));
+        assert_eq!(part.file_name().as_deref(), Some("report.HTML"));
+        assert_eq!(part.header.get("content-type"), Some("text/html; charset=utf-8"));
+        assert_eq!(part.bytes().await.unwrap(), contents.as_bytes());
+        drop(part);
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("content-type"), Some("application/octet-stream"));
+        assert_eq!(&part.bytes().await.unwrap()[..], b"\x00\x01");
+    }
+
+    #[tokio::test]
+    async fn test_write_part_from_reader() {
+        let body: Vec<u8> = (0..COPY_BUFFER_SIZE * 3 + 7).map(|i| i as u8).collect();
+        let header: MimeHeader = [("X-B", "2"), ("X-A", "1")].into_iter().collect();
+
+        // A slow output accepting a few bytes per write
+        let (client, mut server) = tokio::io::duplex(64);
+        let collect = tokio::spawn(async move {
+            let mut output = Vec::new();
+            server.read_to_end(&mut output).await.unwrap();
+            output
+        });
+        let mut writer = Writer::builder().boundary("b").build(client).unwrap();
+        let written = writer
+            .write_part_from_reader(header, &body[..])
+            .await
+            .unwrap();
+        assert_eq!(written, body.len() as u64);
+        writer.write_part_from_reader(MimeHeader::new(), tokio::io::empty()).await.unwrap();
+        writer.close().await.unwrap();
+        let output = collect.await.unwrap();
+
+        let mut expected = b"--b\r\nX-B: 2\r\nX-A: 1\r\n\r\n".to_vec();
+        expected.extend_from_slice(&body);
+        expected.extend_from_slice(b"\r\n--b\r\n\r\n\r\n--b--\r\n");
+        assert_eq!(output, expected);
+    }
+
+    #[tokio::test]
+    async fn test_non_ascii_disposition() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.create_form_file("файл", "résumé.pdf").await.unwrap();
+        writer.create_form_file("evil", "a\r\nX-Injected: 1.txt").await.unwrap();
+        writer.write_field("plain \"quoted\"", "v").await.unwrap();
+        writer.close().await.unwrap();
+
+        let text = String::from_utf8(output.clone()).unwrap();
+        assert!(text.is_ascii());
+        assert!(text.contains(
+            "Content-Disposition: form-data; name=\"=?utf-8?b?0YTQsNC50Ls=?=\"; \
+             filename=\"=?utf-8?b?csOpc3Vtw6kucGRm?=\"; filename*=utf-8''r%C3%A9sum%C3%A9.pdf\r\n"
+        ));
+        assert!(!text.contains("\r\nX-Injected"));
+
+        let mut reader = crate::multipart::Reader::new(&output[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("файл"));
+        assert_eq!(part.file_name().as_deref(), Some("résumé.pdf"));
+        assert_eq!(part.header.get("content-type"), Some("application/pdf"));
+        drop(part);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.file_name().as_deref(), Some("a\r\nX-Injected: 1.txt"));
+        drop(part);
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.header.get("content-disposition").unwrap().contains("name=\"plain \\\"quoted\\\"\""));
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_attachment_and_inline() {
+        let mut output = Vec::new();
+        let mut writer = Writer::new_mixed(&mut output);
+        writer.set_boundary("b".to_string()).unwrap();
+        let header: MimeHeader = [("Content-Type", "text/csv"), ("Content-Disposition", "x")]
+            .into_iter()
+            .collect();
+        writer.create_attachment("résumé.csv", header).await.unwrap();
+        let header: MimeHeader = [("Content-Type", "image/png")].into_iter().collect();
+        writer.create_inline("<logo@x>", header).await.unwrap();
+        writer.close().await.unwrap();
+
+        let mut reader = crate::multipart::Reader::new(&output[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let entries: Vec<_> = part.header.iter().map(|(k, _)| k.to_string()).collect();
+        assert_eq!(entries, ["Content-Disposition", "Content-Type"]);
+        assert_eq!(part.header.get("content-type"), Some("text/csv"));
+        assert_eq!(part.file_name().as_deref(), Some("résumé.csv"));
+        assert_eq!(part.content_disposition().unwrap().disposition_type(), "attachment");
+        drop(part);
+
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("content-disposition"), Some("inline"));
+        assert_eq!(part.header.get("content-id"), Some("<logo@x>"));
+        assert_eq!(part.header.get("content-type"), Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_write_field_variants() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.write_field_bytes("raw", b"\x00\xff").await.unwrap();
+        let header: MimeHeader = [
+            ("content-disposition", "attachment"),
+            ("Content-Type", "application/octet-stream"),
+            ("X-Id", "7"),
+        ]
+        .into_iter()
+        .collect();
+        writer.write_field_with_header("blob", header, b"1").await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(
+            output,
+            &b"--b\r\nContent-Disposition: form-data; name=\"raw\"\r\n\r\n\x00\xff\r\n\
+--b\r\nContent-Disposition: form-data; name=\"blob\"\r\nContent-Type: application/octet-stream\r\n\
+X-Id: 7\r\n\r\n1\r\n--b--\r\n"[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unclosed_nested_part_fails() {
+        let mut writer = Writer::new(Vec::new());
+        let mut nested = writer.create_nested_part(MimeHeader::new(), "mixed").await.unwrap();
+        nested.write_field("a", "1").await.unwrap();
+        drop(nested);
+        assert!(matches!(writer.write_field("b", "2").await, Err(Error::Multipart(_))));
+        assert!(writer.close().await.is_err());
+
+        // Closing the nested writer lets the outer one go on
+        let mut writer = Writer::new(Vec::new());
+        let nested = writer.create_nested_part(MimeHeader::new(), "mixed").await.unwrap();
+        nested.close().await.unwrap();
+        writer.create_nested_part(MimeHeader::new(), "mixed").await.unwrap().close().await.unwrap();
+        writer.write_field("b", "2").await.unwrap();
+        writer.close().await.unwrap();
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_subtype_content_type() {
+        let writer = Writer::new(Vec::new());
+        assert_eq!(writer.subtype(), "form-data");
+        assert_eq!(writer.content_type(), writer.form_data_content_type());
+
+        let mut writer = Writer::new_related(Vec::new());
+        writer.set_boundary("a:b".to_string()).unwrap();
+        assert_eq!(writer.content_type(), "multipart/related; boundary=\"a:b\"");
+        assert_eq!(Writer::new_alternative(Vec::new()).subtype(), "alternative");
+
+        let writer = Writer::builder()
+            .subtype("X-Custom")
+            .boundary("b")
+            .build(Vec::new())
+            .unwrap();
+        assert_eq!(writer.content_type(), "multipart/x-custom; boundary=b");
+        assert!(Writer::builder().subtype("bad type").build(Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_nested_boundaries() {
+        async fn write(seed: u64) -> (Vec<u8>, [String; 3]) {
+            let mut output = Vec::new();
+            let mut writer = Writer::builder()
+                .boundary_prefix("----Golden")
+                .boundary_length(8)
+                .boundary_seed(seed)
+                .build(&mut output)
+                .unwrap();
+            let outer = writer.boundary().to_string();
+            let mut mixed = writer.create_nested_part(MimeHeader::new(), "mixed").await.unwrap();
+            let inner = mixed.boundary().to_string();
+            let mut alternative = mixed.create_nested_part(MimeHeader::new(), "alternative").await.unwrap();
+            let innermost = alternative.boundary().to_string();
+            alternative.write_field("text", "Hello").await.unwrap();
+            alternative.close().await.unwrap();
+            mixed.close().await.unwrap();
+            writer.close().await.unwrap();
+            (output, [outer, inner, innermost])
+        }
+
+        let (output, boundaries) = write(42).await;
+        assert_eq!(write(42).await, (output.clone(), boundaries.clone()));
+        assert_ne!(write(43).await.0, output);
+        for (i, boundary) in boundaries.iter().enumerate() {
+            assert!(boundary.starts_with("----Golden") && boundary.len() == 18);
+            assert!(!boundaries[..i].contains(boundary));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nested_part() {
+        use crate::media_type::parse_media_type;
+        use crate::multipart::Reader;
+
+        let mut output = Vec::new();
+        let mut writer = Writer::builder()
+            .boundary("outer")
+            .line_ending(LineEnding::Lf)
+            .build(&mut output)
+            .unwrap();
+        let header: MimeHeader = [("Content-Type", "text/plain"), ("X-Kind", "body")]
+            .into_iter()
+            .collect();
+        let mut alternative = writer.create_nested_part(header, "alternative").await.unwrap();
+        let inner = alternative.boundary().to_string();
+        assert_ne!(inner, "outer");
+        alternative.write_field("text", "Hello").await.unwrap();
+        alternative.write_field("html", "<p>Hello</p>").await.unwrap();
+        alternative.close().await.unwrap();
+        writer.write_field("attachment", "data").await.unwrap();
+        writer.close().await.unwrap();
+
+        let expected = format!(
+            "--outer\nContent-Type: multipart/alternative; boundary={inner}\nX-Kind: body\n\n--{inner}\n"
+        );
+        assert!(output.starts_with(expected.as_bytes()));
+
+        let mut reader = Reader::new(&output[..], "outer");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        let (media_type, params) = parse_media_type(part.header.get("content-type").unwrap()).unwrap();
+        assert_eq!(media_type, "multipart/alternative");
+        let body = part.bytes().await.unwrap();
+        let mut nested = Reader::new(&body[..], &params["boundary"]);
+        let mut values = Vec::new();
+        while let Some(mut part) = nested.next_part().await.unwrap() {
+            values.push(String::from_utf8(part.bytes().await.unwrap().to_vec()).unwrap());
+        }
+        assert_eq!(values, ["Hello", "<p>Hello</p>"]);
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.form_name(), Some("attachment"));
+        assert!(reader.next_part().await.unwrap().is_none());
+
+        let mut writer = Writer::new(Vec::new());
+        assert!(writer.create_nested_part(MimeHeader::new(), "bad type").await.is_err());
+    }
+
+    /// Records every write and flush it is given.
+    #[derive(Default)]
+    struct RecordingWriter {
+        writes: Vec<Vec<u8>>,
+        flushes: usize,
+    }
+
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.writes.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            self.flushes += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_form_round_trip() {
+        use crate::multipart::{FileHeader, Reader};
+
+        let data = b"--a\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\nx\r\n\
+--a\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\ny\r\n\
+--a\r\nContent-Disposition: form-data; name=\"doc\"; filename=\"d.bin\"\r\nX-Kept: 1\r\n\r\n0123456789\r\n--a--\r\n";
+        // Files larger than a byte are kept on disk
+        let mut form = Reader::new(&data[..], "a").read_form(1).await.unwrap();
+        form.file
+            .entry("notes".to_string())
+            .or_default()
+            .push(FileHeader::new("n.txt".to_string(), b"hi".to_vec(), MimeHeader::new()));
+
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.write_form(&form).await.unwrap();
+        writer.close().await.unwrap();
+
+        let mut reader = Reader::new(&output[..], "b");
+        let mut fields = Vec::new();
+        while let Some(mut part) = reader.next_part().await.unwrap() {
+            let name = part.form_name().unwrap().to_string();
+            let filename = part.file_name();
+            let kept = part.header.get("x-kept").is_some();
+            let body = String::from_utf8(part.bytes().await.unwrap().to_vec()).unwrap();
+            fields.push((name, filename, body, kept));
+        }
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(n, f, b, k)| (n.as_str(), f.as_deref(), b.as_str(), *k))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("tag", None, "x", false),
+                ("tag", None, "y", false),
+                ("doc", Some("d.bin"), "0123456789", true),
+                ("notes", Some("n.txt"), "hi", false)
+            ]
+        );
+        form.remove_all().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_raw_part() {
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer.write_field("a", "1").await.unwrap();
+        writer.write_raw_part(b"X-Raw:  kept  \r\n\r\n--c\r\n").await.unwrap();
+        writer.write_raw_part(b"\r\n").await.unwrap();
+        assert!(matches!(
+            writer.write_raw_part(b"\r\n\r\nbody\r\n--b--").await,
+            Err(Error::Multipart(_))
+        ));
+        assert!(writer.write_raw_part(b"--b\r\n\r\n").await.is_err());
+        writer.close().await.unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n\
+--b\r\nX-Raw:  kept  \r\n\r\n--c\r\n\r\n--b\r\n\r\n\r\n--b--\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_vectored_body_writes() {
+        let bufs = [std::io::IoSlice::new(b"ab"), std::io::IoSlice::new(b"cd")];
+        let mut output = Vec::new();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        let mut part = writer.create_form_field("a").await.unwrap();
+        assert!(part.is_write_vectored());
+        assert_eq!(part.write_vectored(&bufs).await.unwrap(), 4);
+
+        let mut part = writer
+            .create_part_with_encoding(MimeHeader::new(), TransferEncoding::Base64)
+            .await
+            .unwrap();
+        assert!(!part.is_write_vectored());
+        assert_eq!(part.write_vectored(&bufs).await.unwrap(), 4);
+        writer.close().await.unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\r\n\r\nabcd\r\n--b\r\n"));
+        assert!(output.contains("\r\n\r\nYWJjZA==\r\n--b--"));
+
+        let mut writer = Writer::builder().boundary("b").strict_7bit(true).build(Vec::new()).unwrap();
+        let mut part = writer.create_form_field("a").await.unwrap();
+        let bufs = [std::io::IoSlice::new(b"ok"), std::io::IoSlice::new("é".as_bytes())];
+        assert!(part.write_vectored(&bufs).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_framing_written_at_once() {
+        let mut output = RecordingWriter::default();
+        let mut writer = Writer::builder().boundary("b").build(&mut output).unwrap();
+        let header: MimeHeader = [("X-A", "1"), ("X-B", "2"), ("X-C", "3")].into_iter().collect();
+        writer.create_part(header).await.unwrap().write_all(b"one").await.unwrap();
+        writer.write_field("f", "two").await.unwrap();
+        writer.flush().await.unwrap();
+        writer.close().await.unwrap();
+
+        let writes: Vec<&[u8]> = output.writes.iter().map(Vec::as_slice).collect();
+        assert_eq!(
+            writes,
+            [
+                &b"--b\r\nX-A: 1\r\nX-B: 2\r\nX-C: 3\r\n\r\n"[..],
+                b"one",
+                b"\r\n--b\r\nContent-Disposition: form-data; name=\"f\"\r\n\r\n",
+                b"two",
+                b"\r\n--b--\r\n",
+            ]
+        );
+        assert_eq!(output.flushes, 2);
+    }
+
     #[test]
     fn test_escape_quotes() {
         assert_eq!(escape_quotes("hello"), "hello");
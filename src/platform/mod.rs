@@ -7,6 +7,7 @@ mod unix;
 mod windows;
 
 use crate::error::Result;
+use crate::mime_type::set_extension_type_skip_existing;
 
 /// Initialize MIME types from platform-specific sources.
 ///
@@ -16,20 +17,23 @@ use crate::error::Result;
 ///
 /// On Windows, reads from:
 /// - Registry HKEY_CLASSES_ROOT for extension associations
+///
+/// Extensions that already have a type, such as the builtin ones, are kept.
 pub fn init_mime() -> Result<()> {
+    load(&mut |ext, mime| {
+        let _ = set_extension_type_skip_existing(ext, mime);
+    })
+}
+
+/// Reports the platform's mappings to `add`, in priority order.
+pub(crate) fn load(add: &mut dyn FnMut(&str, &str)) -> Result<()> {
     #[cfg(unix)]
     {
-        unix::init_mime_unix()
+        unix::load_mime_unix(add)
     }
 
     #[cfg(windows)]
     {
-        windows::init_mime_windows()
-    }
-
-    #[cfg(not(any(unix, windows)))]
-    {
-        // Unsupported platform, use builtin types only
-        Ok(())
+        windows::load_mime_windows(add)
     }
 }
@@ -3,14 +3,13 @@
 //! Reads file extension associations from the Windows registry.
 
 use crate::error::Result;
-use crate::mime_type::set_extension_type_skip_existing;
 use winreg::enums::*;
 use winreg::RegKey;
 
-/// Initialize MIME types from Windows registry.
+/// Loads MIME types from the Windows registry.
 ///
 /// Reads HKEY_CLASSES_ROOT for extension associations and their Content-Type values.
-pub(super) fn init_mime_windows() -> Result<()> {
+pub(super) fn load_mime_windows(add: &mut dyn FnMut(&str, &str)) -> Result<()> {
     let hkcr = RegKey::predef(HKEY_CLASSES_ROOT);
     
     // Get all subkey names from HKEY_CLASSES_ROOT
@@ -43,8 +42,7 @@ pub(super) fn init_mime_windows() -> Result<()> {
             continue;
         }
         
-        // Add the extension type (skip if already exists to preserve builtins)
-        let _ = set_extension_type_skip_existing(&name, &content_type);
+        add(&name, &content_type);
     }
     
     Ok(())
@@ -55,9 +53,9 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_init_mime_windows() {
+    fn test_load_mime_windows() {
         // Should not panic and complete without error
-        let result = init_mime_windows();
+        let result = load_mime_windows(&mut |_, _| {});
         assert!(result.is_ok());
     }
 }
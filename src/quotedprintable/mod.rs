@@ -3,5 +3,5 @@
 pub mod reader;
 pub mod writer;
 
-pub use reader::Reader;
+pub use reader::{decode_to_string, Reader};
 pub use writer::Writer;
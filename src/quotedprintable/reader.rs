@@ -2,12 +2,13 @@
 //!
 //! Implements RFC 2045 quoted-printable decoding with async I/O.
 
-use crate::error::{Error, Result};
+use crate::error::{Error, LimitExceeded, LimitKind, Result};
+use crate::limits::Limits;
 use pin_project::pin_project;
 use std::io;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, ReadBuf};
 
 /// A quoted-printable decoder.
 ///
@@ -20,6 +21,11 @@ pub struct Reader<R> {
     line_pos: usize,
     eof: bool,
     error: Option<io::Error>,
+    /// Maximum length of an encoded line, excluding its line ending.
+    max_line_len: Option<usize>,
+    /// Maximum number of decoded bytes.
+    max_decoded: Option<u64>,
+    decoded: u64,
 }
 
 impl<R: AsyncRead> Reader<R> {
@@ -47,8 +53,59 @@ impl<R: AsyncRead> Reader<R> {
             line_pos: 0,
             eof: false,
             error: None,
+            max_line_len: None,
+            max_decoded: None,
+            decoded: 0,
         }
     }
+
+    /// Creates a quoted-printable reader enforcing `limits`.
+    ///
+    /// `max_line_len` limits each encoded line, failing with
+    /// [`Error::MessageTooLarge`] of kind [`LimitKind::LineLength`], so a
+    /// body without line breaks is not buffered whole. `max_decoded_size`
+    /// limits the decoded output, failing with kind
+    /// [`LimitKind::DecodedSize`]. The other limits do not apply.
+    /// The errors are returned as I/O errors that convert back to
+    /// [`Error`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::io::AsyncReadExt;
+    /// use yamime::{quotedprintable::Reader, Error, Limits};
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let limits = Limits { max_line_len: Some(76), ..Default::default() };
+    /// let data = vec![b'a'; 100];
+    /// let mut reader = Reader::with_limits(&data[..], &limits);
+    /// let err = Error::from(reader.read_to_end(&mut Vec::new()).await.unwrap_err());
+    /// assert_eq!(err.limit_exceeded().map(|l| l.limit), Some(76));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_limits(inner: R, limits: &Limits) -> Self {
+        Self {
+            max_line_len: limits.max_line_len,
+            max_decoded: limits.max_decoded_size,
+            ..Self::new(inner)
+        }
+    }
+}
+
+/// Returns the length of a line, or the start of one, without its line
+/// ending.
+fn content_len(line: &[u8]) -> usize {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line).len()
+}
+
+/// Wraps an exceeded limit as an I/O error.
+fn limit_error(kind: LimitKind, limit: u64, observed: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        Error::MessageTooLarge(LimitExceeded::new(kind, limit, observed)),
+    )
 }
 
 impl<R: AsyncRead> AsyncRead for Reader<R> {
@@ -95,14 +152,25 @@ impl<R: AsyncRead> AsyncRead for Reader<R> {
                         }
 
                         // Find newline
-                        if let Some(pos) = chunk.iter().position(|&b| b == b'\n') {
-                            line_buf.extend_from_slice(&chunk[..=pos]);
-                            this.inner.as_mut().consume(pos + 1);
+                        let found = chunk.iter().position(|&b| b == b'\n');
+                        let end = found.map_or(chunk.len(), |pos| pos + 1);
+                        line_buf.extend_from_slice(&chunk[..end]);
+                        this.inner.as_mut().consume(end);
+
+                        if let Some(limit) = *this.max_line_len {
+                            let len = content_len(&line_buf);
+                            if len > limit {
+                                let err = limit_error(LimitKind::LineLength, limit as u64, len as u64);
+                                if written == 0 {
+                                    return Poll::Ready(Err(err));
+                                }
+                                *this.error = Some(err);
+                                buf.advance(written);
+                                return Poll::Ready(Ok(()));
+                            }
+                        }
+                        if found.is_some() {
                             break;
-                        } else {
-                            line_buf.extend_from_slice(chunk);
-                            let len = chunk.len();
-                            this.inner.as_mut().consume(len);
                         }
                     }
                     Poll::Ready(Err(e)) => {
@@ -126,6 +194,15 @@ impl<R: AsyncRead> AsyncRead for Reader<R> {
             if !line_buf.is_empty() {
                 match decode_line(&line_buf) {
                     Ok(decoded) => {
+                        *this.decoded += decoded.len() as u64;
+                        if let Some(limit) = this.max_decoded.filter(|&limit| *this.decoded > limit) {
+                            let err = limit_error(LimitKind::DecodedSize, limit, *this.decoded);
+                            if written == 0 {
+                                return Poll::Ready(Err(err));
+                            }
+                            *this.error = Some(err);
+                            break;
+                        }
                         this.line.extend_from_slice(&decoded);
                     }
                     Err(e) => {
@@ -144,6 +221,65 @@ impl<R: AsyncRead> AsyncRead for Reader<R> {
     }
 }
 
+/// Decodes quoted-printable data into a UTF-8 string.
+///
+/// The decoded bytes are validated incrementally, so multi-byte sequences
+/// split across soft line breaks or reads are handled. Invalid sequences are
+/// reported as [`Error::InvalidUtf8`] with their offset in the decoded output.
+///
+/// # Examples
+///
+/// ```
+/// use yamime::quotedprintable::decode_to_string;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let decoded = decode_to_string(&b"caf=C3=\r\n=A9"[..]).await?;
+/// assert_eq!(decoded, "café");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn decode_to_string<R: AsyncRead + Unpin>(reader: R) -> Result<String> {
+    let mut reader = Reader::new(reader);
+    let mut result = String::new();
+    // Bytes of an incomplete sequence carried over to the next chunk
+    let mut pending: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    loop {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        pending.extend_from_slice(&chunk[..n]);
+
+        match std::str::from_utf8(&pending) {
+            Ok(s) => {
+                result.push_str(s);
+                pending.clear();
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                if e.error_len().is_some() {
+                    return Err(Error::InvalidUtf8 {
+                        offset: result.len() + valid,
+                    });
+                }
+                // Incomplete sequence at the end: keep it for the next chunk
+                result.push_str(std::str::from_utf8(&pending[..valid]).unwrap_or_default());
+                pending.drain(..valid);
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        return Err(Error::InvalidUtf8 {
+            offset: result.len(),
+        });
+    }
+
+    Ok(result)
+}
+
 /// Decodes a single line of quoted-printable data.
 fn decode_line(line: &[u8]) -> Result<Vec<u8>> {
     let mut result = Vec::with_capacity(line.len());
@@ -194,7 +330,7 @@ fn decode_line(line: &[u8]) -> Result<Vec<u8>> {
             }
             b => {
                 // Regular byte or whitespace
-                if b == b'\t' || b == b'\r' || (b >= b' ' && b <= b'~') || b >= 0x80 {
+                if b == b'\t' || b == b'\r' || (b' '..=b'~').contains(&b) || b >= 0x80 {
                     result.push(b);
                 } else if b < b' ' && b != b'\t' && b != b'\r' && b != b'\n' {
                     return Err(Error::Encoding(format!(
@@ -244,6 +380,36 @@ mod tests {
     use super::*;
     use tokio::io::AsyncReadExt;
 
+    #[tokio::test]
+    async fn test_limits() {
+        let limits = Limits {
+            max_line_len: Some(5),
+            max_decoded_size: Some(10),
+            // Limits multipart messages only
+            max_total: Some(1),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        Reader::with_limits(&b"ab=3D\r\n12345"[..], &limits)
+            .read_to_end(&mut output)
+            .await
+            .unwrap();
+        assert_eq!(output, b"ab=\r\n12345");
+
+        let read = |data: &'static [u8]| async move {
+            let mut output = Vec::new();
+            let err = Reader::with_limits(data, &limits)
+                .read_to_end(&mut output)
+                .await
+                .unwrap_err();
+            Error::from(err).limit_exceeded().cloned().unwrap()
+        };
+        let limit = read(b"ok\r\n123456\r\n").await;
+        assert_eq!((limit.kind, limit.observed), (LimitKind::LineLength, 6));
+        let limit = read(b"abcd\r\nabcd\r\n").await;
+        assert_eq!((limit.kind, limit.limit), (LimitKind::DecodedSize, 10));
+    }
+
     #[tokio::test]
     async fn test_decode_simple() {
         let data = b"Hello World";
@@ -280,6 +446,25 @@ mod tests {
         assert_eq!(output, "Line1\r\nLine2\r\n");
     }
 
+    #[tokio::test]
+    async fn test_decode_to_string() {
+        // "€" split by a soft line break in the middle of the sequence
+        let data = b"price: =E2=82=\r\n=AC 5\r\n";
+        assert_eq!(decode_to_string(&data[..]).await.unwrap(), "price: € 5\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_decode_to_string_invalid() {
+        let data = b"ok=C3=A9 =FF rest";
+        let err = decode_to_string(&data[..]).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidUtf8 { offset: 5 }));
+
+        // Truncated sequence at the end of input
+        let data = b"abc=E2=82";
+        let err = decode_to_string(&data[..]).await.unwrap_err();
+        assert!(matches!(err, Error::InvalidUtf8 { offset: 3 }));
+    }
+
     #[tokio::test]
     async fn test_decode_hex() {
         let data = b"=48=65=6C=6C=6F"; // "Hello" in hex
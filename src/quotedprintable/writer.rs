@@ -14,15 +14,19 @@ const UPPER_HEX: &[u8] = b"0123456789ABCDEF";
 /// A quoted-printable encoder.
 ///
 /// Implements `AsyncWrite` to encode data to quoted-printable on the fly.
+/// Output lines are kept within 76 characters with soft line breaks.
+///
+/// Encoded output is buffered and reaches the inner writer on the next
+/// write, flush or shutdown. Bytes of an unfinished line are held back
+/// only while they may still need encoding: a trailing space or tab is
+/// encoded if its line ends.
 #[pin_project]
 pub struct Writer<W> {
     #[pin]
     inner: W,
     /// Binary mode treats input as pure binary (doesn't handle line endings specially).
     pub binary: bool,
-    line: [u8; 78], // Buffer for current line (76 + CRLF)
-    line_len: usize,
-    pending_cr: bool,
+    encoder: Encoder,
 }
 
 impl<W: AsyncWrite> Writer<W> {
@@ -46,12 +50,77 @@ impl<W: AsyncWrite> Writer<W> {
         Self {
             inner,
             binary: false,
-            line: [0; 78],
-            line_len: 0,
-            pending_cr: false,
+            encoder: Encoder::new(b"\r\n"),
         }
     }
 
+    /// Returns the column the next encoded byte will be written at,
+    /// counting from 0 at the start of an output line.
+    pub fn current_column(&self) -> usize {
+        self.encoder.column()
+    }
+
+    /// Ends the current output line with a soft line break (`=` CRLF),
+    /// which decoders remove.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::io::AsyncWriteExt;
+    /// use yamime::quotedprintable::Writer;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// let mut writer = Writer::new(&mut output);
+    /// writer.write_all(b"one").await?;
+    /// assert_eq!(writer.current_column(), 3);
+    /// writer.soft_break();
+    /// assert_eq!(writer.current_column(), 0);
+    /// writer.write_all(b"two").await?;
+    /// writer.close().await?;
+    /// assert_eq!(output, b"one=\r\ntwo");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn soft_break(&mut self) {
+        self.encoder.soft_break();
+    }
+
+    /// Forgets the current output line, as if a new line started at
+    /// column 0.
+    ///
+    /// For framers that end lines themselves: call this, flush, then write
+    /// a line break through [`get_mut`](Self::get_mut). Bytes already encoded stay
+    /// in order before anything written next, with a trailing space or tab
+    /// encoded since its line has ended.
+    pub fn reset_line_state(&mut self) {
+        self.set_column(0);
+    }
+
+    /// Like [`reset_line_state`](Self::reset_line_state), but the new line
+    /// starts at `column`, e.g. after a folding prefix or a property name
+    /// written by the framer.
+    ///
+    /// Columns past the last one a soft line break fits after are clamped.
+    pub fn set_column(&mut self, column: usize) {
+        self.encoder.check_last_byte();
+        self.encoder.start_line(column.min(LINE_MAX_LEN - 1));
+        self.encoder.cr = false;
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    ///
+    /// Encoded output is buffered; flush first so it comes before anything
+    /// written here.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
     /// Closes the writer, flushing any buffered data.
     ///
     /// This must be called to ensure all data is written.
@@ -65,178 +134,155 @@ impl<W: AsyncWrite> Writer<W> {
 
 impl<W: AsyncWrite> AsyncWrite for Writer<W> {
     fn poll_write(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        let mut total_written = 0;
-        let mut remaining = buf;
-
-        while !remaining.is_empty() {
-            let mut this = self.as_mut().project();
-
-            // If line buffer is full, flush it first
-            if *this.line_len > 0 && *this.line_len >= LINE_MAX_LEN - 3 {
-                // Try to flush the line buffer
-                match this.inner.as_mut().poll_write(cx, &this.line[..*this.line_len]) {
-                    Poll::Ready(Ok(n)) if n == *this.line_len => {
-                        *this.line_len = 0;
-                    }
-                    Poll::Ready(Ok(_)) => {
-                        return Poll::Ready(Err(io::Error::new(
-                            io::ErrorKind::WriteZero,
-                            "failed to write whole line",
-                        )));
-                    }
-                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                    Poll::Pending => return Poll::Pending,
-                }
-            }
+        let mut this = self.project();
+        // Hand earlier output on first, so the buffer stays bounded by
+        // the size of one write
+        ready!(this.encoder.poll_drain(this.inner.as_mut(), cx))?;
+        for &b in buf {
+            this.encoder.push(b, *this.binary);
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
 
-            let this = self.as_mut().project();
-
-            // Try to write plain bytes
-            let plain_written = {
-                let mut written = 0;
-                for &b in remaining {
-                    // Check if we need a soft line break
-                    if *this.line_len >= LINE_MAX_LEN - 3 {
-                        break;
-                    }
-
-                    // Handle line endings in non-binary mode
-                    if !*this.binary && (b == b'\n' || b == b'\r') {
-                        // Handle CRLF sequence
-                        if *this.pending_cr && b == b'\n' {
-                            *this.pending_cr = false;
-                            written += 1;
-                            continue;
-                        }
-
-                        if b == b'\r' {
-                            *this.pending_cr = true;
-                        }
-
-                        // Add CRLF to buffer
-                        if *this.line_len + 2 <= LINE_MAX_LEN {
-                            this.line[*this.line_len] = b'\r';
-                            this.line[*this.line_len + 1] = b'\n';
-                            *this.line_len += 2;
-                            written += 1;
-                            // Mark that we need to flush
-                            break;
-                        } else {
-                            break;
-                        }
-                    }
-
-                    // Check if byte needs encoding
-                    if b >= b'!' && b <= b'~' && b != b'=' {
-                        // Can write directly
-                        this.line[*this.line_len] = b;
-                        *this.line_len += 1;
-                        *this.pending_cr = false;
-                        written += 1;
-                    } else if is_whitespace(b) {
-                        // Whitespace - write but may need encoding at end of line
-                        this.line[*this.line_len] = b;
-                        *this.line_len += 1;
-                        *this.pending_cr = false;
-                        written += 1;
-                    } else {
-                        // Need to encode
-                        if *this.line_len + 3 <= LINE_MAX_LEN - 1 {
-                            this.line[*this.line_len] = b'=';
-                            this.line[*this.line_len + 1] = UPPER_HEX[(b >> 4) as usize];
-                            this.line[*this.line_len + 2] = UPPER_HEX[(b & 0x0F) as usize];
-                            *this.line_len += 3;
-                            written += 1;
-                        } else {
-                            // Need soft line break first
-                            break;
-                        }
-                    }
-                }
-                written
-            };
-
-            remaining = &remaining[plain_written..];
-            total_written += plain_written;
-
-            if plain_written == 0 && !remaining.is_empty() {
-                // Need to flush and add soft line break
-                let this = self.as_mut().project();
-                if *this.line_len > 0 {
-                    this.line[*this.line_len] = b'=';
-                    this.line[*this.line_len + 1] = b'\r';
-                    this.line[*this.line_len + 2] = b'\n';
-                    *this.line_len += 3;
-                }
-                // Will flush on next iteration
-            }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        this.encoder.release_line();
+        ready!(this.encoder.poll_drain(this.inner.as_mut(), cx))?;
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        this.encoder.finish();
+        ready!(this.encoder.poll_drain(this.inner.as_mut(), cx))?;
+        this.inner.poll_shutdown(cx)
+    }
+}
+
+/// The encoding state of a [`Writer`], independent of its inner writer, so
+/// other writers can encode into their own output.
+#[derive(Debug)]
+pub(crate) struct Encoder {
+    /// Encoded bytes of the current line not yet released to `out`.
+    line: Vec<u8>,
+    /// The column `line` starts at.
+    line_start: usize,
+    /// Whether the previous input byte was a CR, whose line break has
+    /// been written already.
+    cr: bool,
+    /// Encoded output waiting for the inner writer, from `out_pos` on.
+    out: Vec<u8>,
+    out_pos: usize,
+    /// The line break written for hard and soft line breaks.
+    nl: &'static [u8],
+}
+
+impl Encoder {
+    pub(crate) fn new(nl: &'static [u8]) -> Self {
+        Self {
+            line: Vec::new(),
+            line_start: 0,
+            cr: false,
+            out: Vec::new(),
+            out_pos: 0,
+            nl,
         }
+    }
+
+    fn column(&self) -> usize {
+        self.line_start + self.line.len()
+    }
 
-        Poll::Ready(Ok(total_written))
-    }
-
-    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        let mut this = self.as_mut().project();
-
-        // Flush line buffer first
-        if *this.line_len > 0 {
-            match this.inner.as_mut().poll_write(cx, &this.line[..*this.line_len]) {
-                Poll::Ready(Ok(n)) if n == *this.line_len => {
-                    *this.line_len = 0;
-                }
-                Poll::Ready(Ok(_)) => {
-                    return Poll::Ready(Err(io::Error::new(
-                        io::ErrorKind::WriteZero,
-                        "failed to write whole line",
-                    )));
-                }
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                Poll::Pending => return Poll::Pending,
+    /// Encodes one input byte.
+    pub(crate) fn push(&mut self, b: u8, binary: bool) {
+        if !binary && (b == b'\n' || b == b'\r') {
+            // The CRLF for a CR has been written already
+            if self.cr && b == b'\n' {
+                self.cr = false;
+                return;
             }
+            self.cr = b == b'\r';
+            self.check_last_byte();
+            self.hard_break();
+            return;
         }
+        self.cr = false;
+        if (b'!'..=b'~').contains(&b) && b != b'=' || is_whitespace(b) {
+            if self.column() >= LINE_MAX_LEN - 1 {
+                self.soft_break();
+            }
+            self.line.push(b);
+        } else {
+            self.encode(b);
+        }
+    }
 
-        self.project().inner.poll_flush(cx)
-    }
-
-    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        // Check last byte for trailing whitespace
-        {
-            let this = self.as_mut().project();
-            if *this.line_len > 0 {
-                let last_byte = this.line[*this.line_len - 1];
-                if is_whitespace(last_byte) {
-                    *this.line_len -= 1;
-                    // Encode the whitespace
-                    if *this.line_len + 3 <= LINE_MAX_LEN - 1 {
-                        this.line[*this.line_len] = b'=';
-                        this.line[*this.line_len + 1] = UPPER_HEX[(last_byte >> 4) as usize];
-                        this.line[*this.line_len + 2] = UPPER_HEX[(last_byte & 0x0F) as usize];
-                        *this.line_len += 3;
-                    } else {
-                        // Add soft line break
-                        this.line[*this.line_len] = b'=';
-                        this.line[*this.line_len + 1] = b'\r';
-                        this.line[*this.line_len + 2] = b'\n';
-                        *this.line_len += 3;
-                        // Then add encoded byte in a separate line
-                        this.line[*this.line_len] = b'=';
-                        this.line[*this.line_len + 1] = UPPER_HEX[(last_byte >> 4) as usize];
-                        this.line[*this.line_len + 2] = UPPER_HEX[(last_byte & 0x0F) as usize];
-                        *this.line_len += 3;
-                    }
-                }
+    fn encode(&mut self, b: u8) {
+        if self.column() + 3 > LINE_MAX_LEN - 1 {
+            self.soft_break();
+        }
+        self.line
+            .extend_from_slice(&[b'=', UPPER_HEX[(b >> 4) as usize], UPPER_HEX[(b & 0x0F) as usize]]);
+    }
+
+    /// Encodes a trailing space or tab, which a line must not end with.
+    fn check_last_byte(&mut self) {
+        if let Some(&b) = self.line.last() {
+            if is_whitespace(b) {
+                self.line.pop();
+                self.encode(b);
             }
         }
+    }
 
-        // Flush remaining buffer
-        ready!(self.as_mut().poll_flush(cx))?;
+    fn soft_break(&mut self) {
+        self.line.push(b'=');
+        self.hard_break();
+    }
 
-        // Shutdown inner writer
-        self.project().inner.poll_shutdown(cx)
+    fn hard_break(&mut self) {
+        self.line.extend_from_slice(self.nl);
+        self.start_line(0);
+    }
+
+    /// Releases the current line to the output and starts a new one at
+    /// `column`.
+    fn start_line(&mut self, column: usize) {
+        self.out.append(&mut self.line);
+        self.line_start = column;
+    }
+
+    /// Ends the input: releases everything, encoding a trailing space or
+    /// tab.
+    pub(crate) fn finish(&mut self) {
+        self.check_last_byte();
+        self.release_line();
+    }
+
+    /// Releases the current line to the output, except a trailing space or
+    /// tab that may still need encoding.
+    pub(crate) fn release_line(&mut self) {
+        let keep = match self.line.last() {
+            Some(&b) if is_whitespace(b) => Some(b),
+            _ => None,
+        };
+        let release = self.line.len() - keep.is_some() as usize;
+        self.out.extend_from_slice(&self.line[..release]);
+        self.line.drain(..release);
+        self.line_start += release;
+    }
+
+    /// Writes the buffered output to `inner`.
+    pub(crate) fn poll_drain<W: AsyncWrite>(
+        &mut self,
+        inner: Pin<&mut W>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        crate::base64::poll_drain_output(&mut self.out, &mut self.out_pos, inner, cx)
     }
 }
 
@@ -436,4 +482,56 @@ mod tests {
         let output_str = String::from_utf8_lossy(&output);
         assert!(output_str.ends_with("=20"));
     }
+
+    #[tokio::test]
+    async fn test_long_lines_get_soft_breaks() {
+        let input = format!("{}\r\n{} é{}\t", "A".repeat(200), "b".repeat(74), "=".repeat(30));
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output);
+        writer.write_all(input.as_bytes()).await.unwrap();
+        writer.close().await.unwrap();
+
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.starts_with(&format!("{}=\r\n", "A".repeat(75))));
+        assert!(text.split("\r\n").all(|line| line.len() <= LINE_MAX_LEN));
+        assert!(text.ends_with("=09"));
+        let decoded = crate::quotedprintable::decode_to_string(text.as_bytes()).await.unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[tokio::test]
+    async fn test_column_control() {
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output);
+        assert_eq!(writer.current_column(), 0);
+        writer.write_all(b"a=b\r\ncd").await.unwrap();
+        assert_eq!(writer.current_column(), 2);
+
+        // The framer ends the line itself; the trailing space gets encoded
+        writer.write_all(b" ").await.unwrap();
+        writer.reset_line_state();
+        writer.flush().await.unwrap();
+        writer.get_mut().extend_from_slice(b"\r\n PREFIX:");
+
+        // Seeded column: only 76 - 8 - 1 characters fit before the soft break
+        writer.set_column(8);
+        writer.write_all("x".repeat(70).as_bytes()).await.unwrap();
+        assert_eq!(writer.current_column(), 3);
+        writer.close().await.unwrap();
+
+        let expected = format!("a=3Db\r\ncd=20\r\n PREFIX:{}=\r\nxxx", "x".repeat(67));
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_flush_holds_back_trailing_whitespace() {
+        let mut output = Vec::new();
+        let mut writer = Writer::new(&mut output);
+        writer.write_all(b"end ").await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(writer.current_column(), 4);
+        writer.write_all(b"\n").await.unwrap();
+        writer.close().await.unwrap();
+        assert_eq!(output, b"end=20\r\n");
+    }
 }
@@ -0,0 +1,33 @@
+//! Runs the programs in examples/, so the flows they show keep working.
+//!
+//! Each example asserts its own results; a failing assertion fails the
+//! test.
+
+#[path = "../examples/compose_mail.rs"]
+mod compose_mail;
+#[path = "../examples/mjpeg_stream.rs"]
+mod mjpeg_stream;
+#[path = "../examples/qp_mail_body.rs"]
+mod qp_mail_body;
+#[path = "../examples/upload_endpoint.rs"]
+mod upload_endpoint;
+
+#[test]
+fn test_compose_mail() {
+    compose_mail::main().unwrap();
+}
+
+#[test]
+fn test_mjpeg_stream() {
+    mjpeg_stream::main().unwrap();
+}
+
+#[test]
+fn test_qp_mail_body() {
+    qp_mail_body::main().unwrap();
+}
+
+#[test]
+fn test_upload_endpoint() {
+    upload_endpoint::main().unwrap();
+}
@@ -1,5 +1,8 @@
 //! Integration tests for yamime library
 
+// The deprecated Writer::set_boundary stays covered until it is removed
+#![allow(deprecated)]
+
 use yamime::*;
 use std::io::Cursor;
 use tokio::io::AsyncWriteExt;
@@ -409,3 +412,303 @@ async fn test_stress_multipart_writer() {
     // Verify we read a reasonable number of parts (at least most of them)
     assert!(count >= 45, "Expected at least 45 parts, got {}", count);
 }
+
+#[tokio::test]
+async fn test_multipart_diff_across_writers() {
+    // The same logical form written with two random boundaries is identical
+    async fn build(extra: bool) -> (Vec<u8>, String) {
+        let mut buffer = Vec::new();
+        let mut writer = multipart::Writer::new(&mut buffer);
+        let boundary = writer.boundary().to_string();
+        writer.write_field("name", "value").await.unwrap();
+        let mut file = writer.create_form_file("file", "a.txt").await.unwrap();
+        file.write_all(b"file body").await.unwrap();
+        if extra {
+            writer.write_field("extra", "1").await.unwrap();
+        }
+        writer.close().await.unwrap();
+        (buffer, boundary)
+    }
+
+    let (a, boundary_a) = build(false).await;
+    let (b, boundary_b) = build(false).await;
+    assert_ne!(boundary_a, boundary_b);
+
+    let result = multipart::diff(Cursor::new(&a), Cursor::new(&b), &boundary_a, &boundary_b)
+        .await
+        .unwrap();
+    assert!(result.is_empty());
+
+    let (c, boundary_c) = build(true).await;
+    let result = multipart::diff(Cursor::new(&a), Cursor::new(&c), &boundary_a, &boundary_c)
+        .await
+        .unwrap();
+    assert_eq!(result.changes.len(), 1);
+    assert!(matches!(
+        result.changes[0],
+        multipart::PartChange::Added { index: 2, .. }
+    ));
+}
+
+// Ported from Go's mime/multipart tests
+
+fn go_multipart_body(sep: &str, long_line: &str) -> String {
+    let body = "
+This is a multi-part message.  This line is ignored.
+--MyBoundary
+Header1: value1
+HEADER2: value2
+foo-bar: baz
+
+My value
+The end.
+--MyBoundary
+name: bigsection
+
+[longline]
+--MyBoundary
+Header1: value1b
+HEADER2: value2b
+foo-bar: bazb
+
+Line 1
+Line 2
+Line 3 ends in a newline, but just one.
+
+--MyBoundary
+
+never read data
+--MyBoundary--
+
+
+useless trailer
+";
+    body.replace('\n', sep).replacen("[longline]", long_line, 1)
+}
+
+#[tokio::test]
+async fn test_go_multipart() {
+    use tokio::io::AsyncReadExt;
+
+    let long_line = "\n\n\r\r\r\n\r\0".repeat((1 << 20) / 8);
+    for sep in ["\r\n", "\n"] {
+        let data = go_multipart_body(sep, &long_line);
+        let mut reader = multipart::Reader::new(data.as_bytes(), "MyBoundary");
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("Header1"), Some("value1"));
+        assert_eq!(part.header.get("foo-bar"), Some("baz"));
+        let mut body = String::new();
+        part.read_to_string(&mut body).await.unwrap();
+        assert_eq!(body, format!("My value{}The end.", sep));
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("name"), Some("bigsection"));
+        assert_eq!(&part.bytes().await.unwrap()[..], long_line.as_bytes());
+
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("Header2"), Some("value2b"));
+        let mut body = String::new();
+        part.read_to_string(&mut body).await.unwrap();
+        assert_eq!(
+            body,
+            ["Line 1", "Line 2", "Line 3 ends in a newline, but just one.", ""].join(sep)
+        );
+
+        // The last part is not read before moving on
+        let part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.header.is_empty());
+        drop(part);
+
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_go_quoted_printable_encoding() {
+    use tokio::io::AsyncReadExt;
+
+    for cte in ["quoted-printable", "Quoted-PRINTABLE"] {
+        let body = format!(
+            "--0016e68ee29c5d515f04cedf6733\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Disposition: form-data; name=text\r\n\
+Content-Transfer-Encoding: {}\r\n\
+\r\n\
+words words words words words words words words words words words words wor=\r\n\
+ds words words words words words words words words words words words words =\r\n\
+words words words words words words words words words words words words wor=\r\n\
+ds words words words words words words words words words words words words =\r\n\
+words words words words words words words words words\r\n\
+--0016e68ee29c5d515f04cedf6733\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Disposition: form-data; name=submit\r\n\
+\r\n\
+Submit\r\n\
+--0016e68ee29c5d515f04cedf6733--",
+            cte
+        );
+        let mut reader = multipart::Reader::new(body.as_bytes(), "0016e68ee29c5d515f04cedf6733");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert_eq!(part.header.get("Content-Transfer-Encoding"), None);
+
+        let mut got = String::new();
+        part.read_to_string(&mut got).await.unwrap();
+        assert_eq!(got, vec!["words"; 59].join(" "));
+    }
+}
+
+#[tokio::test]
+async fn test_go_raw_part() {
+    use tokio::io::AsyncReadExt;
+
+    let body = "--0016e68ee29c5d515f04cedf6733\r\n\
+Content-Type: text/plain; charset=\"utf-8\"\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<div dir=3D\"ltr\">Hello World.</div>\r\n\
+--0016e68ee29c5d515f04cedf6733\r\n\
+Content-Type: text/html; charset=\"utf-8\"\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<div dir=3D\"ltr\">Hello World.</div>\r\n\
+--0016e68ee29c5d515f04cedf6733--";
+    let mut reader = multipart::Reader::new(body.as_bytes(), "0016e68ee29c5d515f04cedf6733");
+
+    // Raw parts keep their transfer encoding
+    let mut part = reader.next_raw_part().await.unwrap().unwrap();
+    assert!(part.header.contains_key("Content-Transfer-Encoding"));
+    let mut got = String::new();
+    part.read_to_string(&mut got).await.unwrap();
+    assert_eq!(got, "<div dir=3D\"ltr\">Hello World.</div>");
+
+    let mut part = reader.next_part().await.unwrap().unwrap();
+    assert!(!part.header.contains_key("Content-Transfer-Encoding"));
+    let mut got = String::new();
+    part.read_to_string(&mut got).await.unwrap();
+    assert_eq!(got, "<div dir=\"ltr\">Hello World.</div>");
+}
+
+#[tokio::test]
+async fn test_multipart_decoder_framed_read() {
+    use futures::StreamExt;
+    use multipart::{MultipartDecoder, MultipartFrame};
+    use tokio_util::codec::FramedRead;
+
+    // Written by the Writer, read back through a small read buffer
+    let mut buffer = Vec::new();
+    let mut writer = multipart::Writer::new(&mut buffer);
+    writer.set_boundary("frames".to_string()).unwrap();
+    writer.write_field("a", "first value").await.unwrap();
+    writer.write_field("b", &"x".repeat(10_000)).await.unwrap();
+    writer.close().await.unwrap();
+
+    let reader = tokio::io::BufReader::with_capacity(64, &buffer[..]);
+    let mut frames = FramedRead::new(reader, MultipartDecoder::new("frames"));
+    let mut bodies = Vec::new();
+    let mut body = Vec::new();
+    while let Some(frame) = frames.next().await {
+        match frame.unwrap() {
+            MultipartFrame::PartHeaders(header) => {
+                assert!(header.get("content-disposition").is_some());
+            }
+            MultipartFrame::BodyChunk(chunk) => body.extend_from_slice(&chunk),
+            MultipartFrame::PartEnd => bodies.push(String::from_utf8(std::mem::take(&mut body)).unwrap()),
+            MultipartFrame::Finished => {}
+        }
+    }
+    assert_eq!(bodies, ["first value".to_string(), "x".repeat(10_000)]);
+    assert!(frames.decoder().is_finished());
+}
+
+#[tokio::test]
+async fn test_empty_body_round_trip() {
+    use futures::StreamExt;
+    use multipart::{LineEnding, MultipartDecoder, MultipartFrame, TransferEncoding};
+    use tokio_util::codec::FramedRead;
+
+    let cases: [&[&[u8]]; 4] = [
+        &[b"", b"middle", b""],
+        &[b"", b"last"],
+        &[b"first", b""],
+        &[b"", b""],
+    ];
+    for line_ending in [LineEnding::Crlf, LineEnding::Lf] {
+        for bodies in cases {
+            let mut output = Vec::new();
+            let mut writer = multipart::Writer::builder()
+                .boundary("b")
+                .line_ending(line_ending)
+                .build(&mut output)
+                .unwrap();
+            for (i, body) in bodies.iter().enumerate() {
+                // Alternate headerless parts, whose blank line follows the delimiter
+                let mut header = multipart::MimeHeader::new();
+                if i % 2 == 1 {
+                    header.insert("X-Index", i.to_string());
+                }
+                let mut part = writer.create_part(header).await.unwrap();
+                part.write_all(body).await.unwrap();
+            }
+            writer.close().await.unwrap();
+
+            // Exactly one line break ends each body, so no phantom empty lines
+            let nl = line_ending.as_str();
+            let empty_part = format!("--b{nl}{nl}{nl}--b");
+            assert_eq!(output.starts_with(empty_part.as_bytes()), bodies[0].is_empty());
+
+            let mut reader = multipart::Reader::new(&output[..], "b");
+            for body in bodies {
+                let mut part = reader.next_part().await.unwrap().unwrap();
+                assert_eq!(part.bytes().await.unwrap(), body);
+            }
+            assert!(reader.next_part().await.unwrap().is_none());
+
+            let parsed = multipart::parse_multipart_to_vec(&output, "b").unwrap();
+            let parsed: Vec<&[u8]> = parsed.iter().map(|(_, body)| &body[..]).collect();
+            assert_eq!(parsed, bodies);
+
+            let split = multipart::Reader::split_parts(output.clone().into(), "b").unwrap();
+            let split: Vec<&[u8]> = split.iter().map(|part| &part.body()[..]).collect();
+            assert_eq!(split, bodies);
+
+            let frames: Vec<_> = FramedRead::new(&output[..], MultipartDecoder::new("b"))
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            let chunks = frames
+                .iter()
+                .filter(|f| matches!(f, MultipartFrame::BodyChunk(_)))
+                .count();
+            assert_eq!(chunks, bodies.iter().filter(|b| !b.is_empty()).count());
+        }
+    }
+
+    // Transfer-encoded parts without body bytes stay empty too
+    for encoding in [TransferEncoding::Base64, TransferEncoding::QuotedPrintable] {
+        let mut output = Vec::new();
+        let mut writer = multipart::Writer::builder().boundary("b").build(&mut output).unwrap();
+        writer
+            .create_part_with_encoding(Default::default(), encoding)
+            .await
+            .unwrap()
+            .finish()
+            .await
+            .unwrap();
+        writer.close().await.unwrap();
+
+        let mut reader = multipart::Reader::new(&output[..], "b");
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.bytes().await.unwrap().is_empty());
+        assert!(reader.next_part().await.unwrap().is_none());
+    }
+
+    // Senders omitting the blank line after an empty header block
+    let input = b"--b\r\n\r\n--b\r\nX-A: 1\r\n\r\n--b--\r\n";
+    let mut reader = multipart::Reader::new(&input[..], "b");
+    for _ in 0..2 {
+        let mut part = reader.next_part().await.unwrap().unwrap();
+        assert!(part.bytes().await.unwrap().is_empty());
+    }
+    assert!(reader.next_part().await.unwrap().is_none());
+}